@@ -0,0 +1,120 @@
+//! Statistics of chunk data that tell text and image data apart from encrypted or compressed
+//! payloads, which look like random bytes.
+
+use std::fmt::{self, Display};
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::i18n::format_decimal;
+
+/// Statistics of a run of bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteStats {
+    /// Shannon entropy in bits per byte, see [`entropy`].
+    pub entropy: f64,
+    /// The share of bytes that are printable ASCII, see [`printable_ratio`].
+    pub printable: f64,
+    /// Compressed size over original size, see [`compression_ratio`].
+    pub compression: f64,
+}
+
+impl ByteStats {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self { entropy: entropy(bytes), printable: printable_ratio(bytes), compression: compression_ratio(bytes) }
+    }
+}
+
+impl Display for ByteStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entropy {} bits/byte, {}% printable, compresses to {}%",
+            format_decimal(self.entropy, 2),
+            format_decimal(self.printable * 100.0, 0),
+            format_decimal(self.compression * 100.0, 0)
+        )
+    }
+}
+
+/// The Shannon entropy of `bytes` in bits per byte, from 0 for a single repeated byte to 8 for
+/// uniformly random bytes.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+    let total = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// The share of `bytes` that are printable ASCII or whitespace, from 0 to 1. Empty data counts
+/// as all printable.
+pub fn printable_ratio(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 1.0;
+    }
+    let printable = bytes.iter().filter(|&&b| b.is_ascii_graphic() || b.is_ascii_whitespace()).count();
+    printable as f64 / bytes.len() as f64
+}
+
+/// Estimates how well `bytes` compress: their size after fast zlib compression over their size.
+/// Data that is already compressed or encrypted stays at about 1 or grows slightly. Empty data
+/// has a ratio of 1.
+pub fn compression_ratio(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 1.0;
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    // Writing to a Vec can't fail.
+    let compressed = encoder.write_all(bytes).and_then(|_| encoder.finish()).map_or(bytes.len(), |c| c.len());
+    compressed as f64 / bytes.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    /// Bytes spread evenly over every value, the most entropy there can be.
+    fn noise(length: usize) -> Vec<u8> {
+        (0..length).map(|n| (n * 167 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b"").to_string(), "0");
+        assert_eq!(entropy(b"aaaa").to_string(), "0");
+        assert_eq!(entropy(b"abab"), 1.0);
+        assert_eq!(entropy(&noise(4096)), 8.0);
+    }
+
+    #[test]
+    fn test_byte_stats() {
+        let text = b"the quick brown fox jumps over the lazy dog\n".repeat(20);
+        let stats = ByteStats::of(&text);
+        assert_eq!(stats.printable, 1.0);
+        assert!(stats.compression < 0.2);
+        assert_eq!(printable_ratio(&[b'a', 0, 0xff, b' ']), 0.5);
+        assert_eq!(printable_ratio(b""), 1.0);
+        assert_eq!(compression_ratio(b""), 1.0);
+
+        let mut rng = SplitMix64(7);
+        let random: Vec<u8> = (0..4096).map(|_| rng.next() as u8).collect();
+        let stats = ByteStats::of(&random);
+        assert!(stats.entropy > 7.9);
+        assert!(stats.compression > 0.99);
+        assert_eq!(stats.to_string().split(", ").count(), 3);
+    }
+}
@@ -0,0 +1,140 @@
+//! Frames of animated PNGs, which add acTL, fcTL and fdAT chunks to a regular PNG.
+
+use std::ops::Range;
+
+use crate::chunk::Chunk;
+use crate::known_chunks::{self, FrameControl, KnownChunk};
+use crate::png::Png;
+use crate::Result;
+
+/// One frame of an animated PNG.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// Indices of the frame's chunks: its fcTL and the IDAT or fdAT chunks right after it.
+    pub chunks: Range<usize>,
+    /// Whether the frame is drawn from the IDAT chunks, making it the image viewers without
+    /// animation support show too.
+    pub default_image: bool,
+}
+
+/// Whether `png` is animated: an acTL chunk comes before its image data.
+pub fn is_apng(png: &Png) -> bool {
+    png.chunks()
+        .iter()
+        .map(|chunk| chunk.chunk_type().bytes())
+        .take_while(|chunk_type| chunk_type != b"IDAT")
+        .any(|chunk_type| &chunk_type == b"acTL")
+}
+
+/// The frames of `png` in file order, each starting at an fcTL chunk. Empty if `png` isn't
+/// animated; fails if one of its fcTL chunks is malformed.
+pub fn frames(png: &Png) -> Result<Vec<Frame>> {
+    if !is_apng(png) {
+        return Ok(Vec::new());
+    }
+    let chunks = png.chunks();
+    let mut frames = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        if &chunk.chunk_type().bytes() != b"fcTL" {
+            continue;
+        }
+        let Some(KnownChunk::FrameControl(control)) = known_chunks::decode(chunk, None)? else {
+            unreachable!("fcTL chunks decode to a frame control");
+        };
+        let data = chunks[index + 1..]
+            .iter()
+            .take_while(|chunk| matches!(&chunk.chunk_type().bytes(), b"IDAT" | b"fdAT"))
+            .count();
+        let default_image = chunks.get(index + 1).is_some_and(|chunk| &chunk.chunk_type().bytes() == b"IDAT");
+        frames.push(Frame { control, chunks: index..index + 1 + data, default_image });
+    }
+    Ok(frames)
+}
+
+/// Follows the chunks of a PNG as they are read one by one, telling which frame each belongs to
+/// without holding the whole file.
+#[derive(Debug, Default)]
+pub struct FrameTracker {
+    animated: bool,
+    image_seen: bool,
+    frames: usize,
+    in_frame: bool,
+}
+
+impl FrameTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index of the frame `chunk`, the next chunk of the file, belongs to, if any.
+    pub fn track(&mut self, chunk: &Chunk) -> Option<usize> {
+        match &chunk.chunk_type().bytes() {
+            b"acTL" if !self.image_seen => self.animated = true,
+            b"fcTL" if self.animated => {
+                self.frames += 1;
+                self.in_frame = true;
+            }
+            b"IDAT" => self.image_seen = true,
+            b"fdAT" => {}
+            _ => self.in_frame = false,
+        }
+        match &chunk.chunk_type().bytes() {
+            b"IDAT" | b"fdAT" | b"fcTL" if self.in_frame => Some(self.frames - 1),
+            _ => None,
+        }
+    }
+
+    /// Whether the chunk tracked last belongs to a frame.
+    pub fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn frame_control(sequence: u8) -> Chunk {
+        let mut data = vec![0, 0, 0, sequence, 0, 0, 0, 4, 0, 0, 0, 4];
+        data.extend([0; 8]);
+        data.extend([0, 1, 0, 10, 0, 0]);
+        chunk("fcTL", &data)
+    }
+
+    #[test]
+    fn test_frames() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("acTL", &[0, 0, 0, 2, 0, 0, 0, 0]),
+            frame_control(0),
+            chunk("IDAT", b"first"),
+            chunk("IDAT", b"frame"),
+            frame_control(1),
+            chunk("fdAT", &[0, 0, 0, 2, 1]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(is_apng(&png));
+        let frames = frames(&png).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].chunks.clone(), frames[0].default_image), (2..5, true));
+        assert_eq!((frames[1].chunks.clone(), frames[1].default_image), (5..7, false));
+        assert_eq!(frames[1].control.sequence, 1);
+        let mut tracker = FrameTracker::new();
+        let tracked: Vec<Option<usize>> = png.chunks().iter().map(|chunk| tracker.track(chunk)).collect();
+        assert_eq!(tracked, [None, None, Some(0), Some(0), Some(0), Some(1), Some(1), None]);
+    }
+
+    #[test]
+    fn test_still_image_has_no_frames() {
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("IDAT", b"data"), chunk("acTL", &[0, 0, 0, 1, 0, 0, 0, 0])]);
+        assert!(!is_apng(&png));
+        assert!(frames(&png).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+//! Payloads appended after the IEND chunk, for hosting services that strip unknown chunks but
+//! keep the bytes after the image. Each is stored as a record: a marker, then the chunk type,
+//! length, data and CRC like a chunk. The marker keeps records from being read as chunks after
+//! IEND, and lets them share the trailer with data written by other tools.
+
+use std::ops::Range;
+
+use bytes::Bytes;
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::chunk_type::ChunkType;
+use crate::payload::RawChunkData;
+use crate::png::Png;
+use crate::Result;
+
+/// Starts every record.
+pub const MARKER: &[u8; 6] = b"pngme\0";
+
+/// A record found in a trailer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    /// Where the whole record is in the trailer.
+    span: Range<usize>,
+    chunk_type: [u8; 4],
+    /// Where its data is in the trailer.
+    data: Range<usize>,
+}
+
+/// The records in `trailer`, in order. Bytes that don't form a whole record with the right CRC
+/// are skipped.
+fn records(trailer: &[u8]) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut start = 0;
+    while let Some(found) = trailer[start..].windows(MARKER.len()).position(|window| window == MARKER) {
+        let offset = start + found;
+        match parse(trailer, offset) {
+            Some(record) => {
+                start = record.span.end;
+                records.push(record);
+            }
+            None => start = offset + 1,
+        }
+    }
+    records
+}
+
+fn parse(trailer: &[u8], offset: usize) -> Option<Record> {
+    let header = offset + MARKER.len();
+    let chunk_type: [u8; 4] = trailer.get(header..header + 4)?.try_into().ok()?;
+    let length = u32::from_be_bytes(trailer.get(header + 4..header + 8)?.try_into().ok()?) as usize;
+    let data = header + 8..(header + 8).checked_add(length)?;
+    let stored = u32::from_be_bytes(trailer.get(data.end..data.end.checked_add(4)?)?.try_into().ok()?);
+    let computed = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&trailer[header..data.end]);
+    (stored == computed).then_some(Record { span: offset..data.end + 4, chunk_type, data })
+}
+
+/// The data of the first record of type `chunk_type` in `trailer`, if any.
+pub fn find(trailer: &[u8], chunk_type: &ChunkType) -> Option<RawChunkData> {
+    find_all(trailer, chunk_type).into_iter().next()
+}
+
+/// The data of every record of type `chunk_type` in `trailer`, in order.
+pub fn find_all(trailer: &[u8], chunk_type: &ChunkType) -> Vec<RawChunkData> {
+    records(trailer)
+        .into_iter()
+        .filter(|record| record.chunk_type == chunk_type.bytes())
+        .map(|record| RawChunkData::from(trailer[record.data].to_vec()))
+        .collect()
+}
+
+/// The types of the records in `trailer`, each once, in the order they first appear.
+#[cfg(feature = "crypto")]
+pub fn types(trailer: &[u8]) -> Vec<ChunkType> {
+    let mut types: Vec<ChunkType> = Vec::new();
+    for record in records(trailer) {
+        match ChunkType::try_from(record.chunk_type) {
+            Ok(chunk_type) if !types.contains(&chunk_type) => types.push(chunk_type),
+            _ => {}
+        }
+    }
+    types
+}
+
+/// Appends a record of type `chunk_type` holding `data` to the trailer of `png`, after
+/// removing the records of that type already there if `replace` is set.
+pub fn append(png: &mut Png, chunk_type: &ChunkType, data: &[u8], replace: bool) -> Result<()> {
+    let old = png.trailer();
+    let mut trailer = Vec::with_capacity(old.len() + data.len() + MARKER.len() + 12);
+    let mut kept = 0;
+    for record in records(old).into_iter().filter(|record| replace && record.chunk_type == chunk_type.bytes()) {
+        trailer.extend_from_slice(&old[kept..record.span.start]);
+        kept = record.span.end;
+    }
+    trailer.extend_from_slice(&old[kept..]);
+    trailer.extend_from_slice(MARKER);
+    let record_start = trailer.len();
+    trailer.extend_from_slice(&chunk_type.bytes());
+    trailer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    trailer.extend_from_slice(data);
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&trailer[record_start..]);
+    trailer.extend_from_slice(&crc.to_be_bytes());
+    png.set_trailer(Bytes::from(trailer))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    #[test]
+    fn test_append_and_find() {
+        let mut png = Png::from_chunks(vec![Chunk::new(chunk_type("IEND"), Vec::new())]);
+        png.set_trailer(b"other tool pngme\0".to_vec()).unwrap();
+        append(&mut png, &chunk_type("ruSt"), b"first", false).unwrap();
+        append(&mut png, &chunk_type("ruSt"), b"second", false).unwrap();
+        append(&mut png, &chunk_type("apPd"), b"other", false).unwrap();
+        assert!(png.trailer().starts_with(b"other tool pngme\0"));
+        let found = |png: &Png, name: &str| -> Vec<Vec<u8>> {
+            find_all(png.trailer(), &chunk_type(name)).iter().map(|data| data.as_bytes().to_vec()).collect()
+        };
+        assert_eq!(found(&png, "ruSt"), [b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(find(png.trailer(), &chunk_type("apPd")).unwrap().as_bytes(), b"other");
+        #[cfg(feature = "crypto")]
+        assert_eq!(types(png.trailer()), [chunk_type("ruSt"), chunk_type("apPd")]);
+        assert!(find(png.trailer(), &chunk_type("teSt")).is_none());
+
+        append(&mut png, &chunk_type("ruSt"), b"third", true).unwrap();
+        assert_eq!(found(&png, "ruSt"), [b"third".to_vec()]);
+        assert_eq!(found(&png, "apPd"), [b"other".to_vec()]);
+        assert!(png.trailer().starts_with(b"other tool pngme\0"));
+
+        // A damaged record is skipped rather than misread.
+        let mut trailer = png.trailer().to_vec();
+        let last = trailer.len() - 1;
+        trailer[last] ^= 0xff;
+        assert!(find(&trailer, &chunk_type("ruSt")).is_none());
+        assert!(find(&trailer, &chunk_type("apPd")).is_some());
+    }
+}
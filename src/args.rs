@@ -1,21 +1,55 @@
 use clap::{Parser,Subcommand,Args};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
 
+use crate::batch_output::RenameScheme;
 use crate::chunk_type::ChunkType;
+use crate::encode::Position;
+use crate::encoding::Encoding;
+use crate::chunk::{Chunk, CrcPolicy};
+use crate::payload::DEFAULT_PART_SIZE;
+use crate::png::{DuplicatePolicy, ParseOptions, WriteOptions};
+use crate::query::Query;
+use crate::stego::Method;
 
 #[derive(Parser,Debug)]
-#[command(version="1.0", about = "Hide messages in a PNG File", long_about = None)]
+#[command(version="1.0", about = "Hide messages in a PNG File", long_about = None,
+    after_help = "Any file path can be given as - to read from stdin or write to stdout.")]
 pub struct Arg{
     #[clap(subcommand)]
     pub subcommand: SubcommandType,
+
+    /// Plain output: no box drawing, color or animations, and one record per line
+    #[arg(long, global = true)]
+    pub plain: bool,
+
+    /// Don't ask before changing many files at once
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
+
+    /// Give up after this many seconds, exiting with code 7
+    #[arg(long, global = true, value_name = "SECS", value_parser = parse_timeout)]
+    pub timeout: Option<f64>,
+
+    /// Count the commands run and the bytes they read in this local JSON file, e.g.
+    /// ~/.cache/pngme/usage.json. Also taken from PNGME_STATS_FILE; nothing is recorded without it
+    #[arg(long, global = true, value_name = "FILE", value_parser = clap::value_parser!(PathBuf))]
+    pub stats_file: Option<PathBuf>,
+
+    /// Show progress bars for large files and batches even when stdout isn't a terminal
+    #[arg(long, global = true, overrides_with = "no_progress")]
+    pub progress: bool,
+
+    /// Never show progress bars
+    #[arg(long, global = true, overrides_with = "progress")]
+    pub no_progress: bool,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum SubcommandType {
     /// Hide message in a PNG File.   
-    Encode(EncodeArgs),
+    Encode(Box<EncodeArgs>),
 
     /// Decode hidden message from a PNG File.
     Decode(DecodeArgs),
@@ -23,14 +57,110 @@ pub enum SubcommandType {
     /// Remove the hidden message from a PNG File.
     Remove(RemoveArgs),
 
+    /// Write the raw data of a chunk to a file, the counterpart of encode --file.
+    Extract(ExtractArgs),
+
+    /// Remove every ancillary and private chunk and the data after IEND from a PNG File before sharing it.
+    Scrub(ScrubArgs),
+
+    /// Copy chunks of the given types from one PNG File into another, where the PNG spec wants them.
+    CopyChunks(CopyChunksArgs),
+
+    /// Reorder the chunks of a PNG File into the order the PNG spec recommends.
+    Normalize(NormalizeArgs),
+
+    /// Split the image data of a PNG File into IDAT chunks of a fixed size.
+    Rechunk(RechunkArgs),
+
+    /// Rewrite wrong CRCs of a damaged PNG File and remove the bytes after its last chunk.
+    Repair(RepairArgs),
+
+    /// Lock a PNG File so pngme refuses to change it without --unlock, or remove its lock.
+    Lock(LockArgs),
+
     /// Print all chunks in a PNG File.
     Print(PrintArgs),
+
+    /// Time each phase of parsing and writing a PNG File.
+    Benchmark(BenchmarkArgs),
+
+    /// Manage the data stored after the IEND chunk of a PNG File.
+    Trailer(TrailerArgs),
+
+    /// Search PNG Files for chunks matching a filter, or a random sample of them.
+    Scan(ScanArgs),
+
+    /// Extract every complete PNG File embedded in a binary blob such as a network capture or memory dump.
+    CarveStream(CarveStreamArgs),
+
+    /// Count the chunk types found across a directory of PNG Files and how large their chunks are.
+    CorpusStats(CorpusStatsArgs),
+
+    /// Summarize the chunk types of a PNG File, or of every PNG File in a directory or glob.
+    ListTypes(ListTypesArgs),
+
+    /// Explain what a chunk type code says about its chunks, to check it before using it.
+    ChunkType(ChunkTypeArgs),
+
+    /// Compare the chunks of two PNG Files, listing those added, removed or modified.
+    Diff(DiffArgs),
+
+    /// Compare the payloads hidden in two versions of a PNG File, as a text diff or a byte summary.
+    PayloadDiff(PayloadDiffArgs),
+
+    /// Revert the last modification made to a PNG File with --undo.
+    Undo(UndoArgs),
+
+    /// Check the structure and CRCs of PNG Files, or that they carry the payloads recorded in a build manifest.
+    Verify(VerifyArgs),
+
+    /// Flag content of a PNG File that may be hiding data, with a risk score for each finding.
+    Detect(DetectArgs),
+
+    /// Sign or check a provenance claim embedded in a PNG File.
+    #[cfg(feature = "crypto")]
+    Provenance(ProvenanceArgs),
+
+    /// Store an HMAC of every chunk of a PNG File, so verify --fast can tell which chunks changed later.
+    #[cfg(feature = "crypto")]
+    Seal(SealArgs),
+
+    /// Re-encrypt every encrypted payload in PNG Files under a new password, e.g. to rotate one that leaked.
+    #[cfg(feature = "crypto")]
+    Rekey(RekeyArgs),
+
+    /// Check the payloads of every PNG File under the given paths and summarize broken ones.
+    Fsck(FsckArgs),
+
+    /// Checks meant to run from version control hooks.
+    Hook(HookArgs),
+
+    /// Generate deterministic edge-case PNG Files for testing PNG tools.
+    Testvec(TestvecArgs),
+
+    /// Generate demo images hiding payloads in every way pngme can, with a README on revealing them.
+    Examples(ExamplesArgs),
+
+    /// Report which chunk contains a byte offset of a PNG File.
+    WhichChunk(WhichChunkArgs),
+
+    /// Show the usage statistics recorded with --stats-file.
+    Stats(StatsArgs),
+
+    /// Describe the features, formats and limits of this build.
+    Capabilities(CapabilitiesArgs),
+
+    /// Diagnose a file that pngme fails on, and the environment it runs in.
+    Doctor(DoctorArgs),
+
+    /// Describe what the standard chunks of a PNG File say, such as its size and color type.
+    Inspect(InspectArgs),
 }
 
 
 #[derive(Args,Debug,PartialEq)]
 pub struct EncodeArgs {
-    /// Input PNG File path
+    /// Input PNG File path, or a directory or glob to change every PNG File it holds
     #[arg(value_parser=clap::value_parser!(PathBuf))]
     pub input_file_path: PathBuf,
 
@@ -39,47 +169,1178 @@ pub struct EncodeArgs {
     pub chunk_type: ChunkType,
 
     /// Message to hide
-    pub message: String,
+    #[arg(required_unless_present_any = ["from_chunk", "file"], conflicts_with_all = ["from_chunk", "file"])]
+    pub message: Option<String>,
 
-    /// [Optional] Output file path, If not given message will be written to input file 
+    /// [Optional] Output file path, If not given message will be written to input file.
+    /// For a directory or glob, the directory the changed files are written to
     #[arg(value_parser=clap::value_parser!(PathBuf))]
     pub output_file_path: Option<PathBuf>,
+
+    /// Output file path, like the one after the message. Needed with --file and --from-chunk,
+    /// which leave the message out
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), conflicts_with = "output_file_path")]
+    pub out: Option<PathBuf>,
+
+    /// How files written to an output directory are named when their names collide [hash | relative].
+    /// hash appends a hash of the input path to colliding names, relative mirrors the input tree
+    #[arg(long, default_value = "hash")]
+    pub rename: RenameScheme,
+
+    /// How the message spells its bytes [utf8 | base64 | hex], so binary data can be given on the
+    /// command line
+    #[arg(long, default_value = "utf8", requires = "message", conflicts_with_all = ["from_chunk", "file", "text_keyword"])]
+    pub input_encoding: Encoding,
+
+    /// Hide the raw bytes of this file instead of a message
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), conflicts_with = "from_chunk")]
+    pub file: Option<PathBuf>,
+
+    /// Hide the data of another PNG File's chunk instead of a message, e.g. other.png:ruSt or other.png:ruSt@1
+    #[arg(long, value_parser=clap::builder::ValueParser::new(parse_chunk_source))]
+    pub from_chunk: Option<ChunkSource>,
+
+    /// Store the message as the text of a tEXt, zTXt or iTXt chunk under this keyword, e.g. Comment.
+    /// The chunk type picks the format: zTXt compresses, iTXt allows any Unicode text
+    #[arg(long, requires = "message", conflicts_with_all = ["from_chunk", "file"])]
+    pub text_keyword: Option<String>,
+
+    /// Language of an iTXt message, e.g. en-GB
+    #[arg(long, requires = "text_keyword")]
+    pub language: Option<String>,
+
+    /// The keyword translated into --language, for an iTXt message
+    #[arg(long, requires = "text_keyword")]
+    pub translated_keyword: Option<String>,
+
+    /// Compress an iTXt message; zTXt messages always are
+    #[arg(long, requires = "text_keyword")]
+    pub compress: bool,
+
+    /// Abort if the file would grow by more than this percentage, e.g. 5%
+    #[arg(long, value_parser=clap::builder::ValueParser::new(parse_percentage))]
+    pub max_growth: Option<f64>,
+
+    /// Where the new chunk goes [end | before-iend | after-ihdr | N], N being an index in the chunk list
+    #[arg(long, default_value = "end")]
+    pub position: Position,
+
+    /// Where to hide the message [chunk | lsb | trailer]. lsb hides it in the lowest bits of the
+    /// pixels and needs IDAT as the chunk type, trailer appends it after the IEND chunk
+    #[arg(long, default_value = "chunk")]
+    pub method: Method,
+
+    /// Record the embedded payload's digest in this JSON build manifest
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub manifest: Option<PathBuf>,
+
+    /// Encrypt the payload with a key derived from this password. Arguments show up in shell
+    /// history and process lists; PNGME_PASSWORD, PNGME_KEY_FILE or --password-stdin don't
+    #[arg(long)]
+    pub password: Option<String>,
+
+    /// Read the password from this file instead
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), conflicts_with = "password")]
+    pub password_file: Option<PathBuf>,
+
+    /// Read the password from stdin instead, asking for it twice without echo on a terminal
+    #[arg(long, conflicts_with_all = ["password", "password_file"])]
+    pub password_stdin: bool,
+
+    /// Encrypt with a new random passphrase, printed once on stderr
+    #[arg(long, conflicts_with_all = ["password", "password_file", "password_stdin"])]
+    pub generate_pass: bool,
+
+    /// Add an HMAC-SHA256 of the payload under the key in this file, checked by decode --verify-key
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub sign_key: Option<PathBuf>,
+
+    /// Remove existing chunks of the same type before adding the new one
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Show the chunks that would be added or removed and the new file size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Split payloads larger than this many bytes across several chunks
+    #[arg(long, default_value_t = DEFAULT_PART_SIZE, value_parser = clap::value_parser!(usize))]
+    pub split_size: usize,
+
+    /// Descend into subdirectories when the input is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
 }
 
 #[derive(Args,Debug)]
 pub struct DecodeArgs {
-    /// PNG File path
+    /// PNG File path, or a directory or glob to report on every PNG File it holds
     #[arg(value_parser=clap::value_parser!(PathBuf))]
     pub file_path: PathBuf,
 
-    /// Chunk Type [4-Byte value made up of a-z | A-Z]
-    #[arg(value_parser=clap::builder::ValueParser::new(parse_chunk_type))]
-    pub chunk_type: ChunkType,
+    /// Chunk Type [4-Byte value made up of a-z | A-Z], with @N for the N-th chunk of that type from 0, e.g. ruSt@2
+    #[arg(value_parser=clap::builder::ValueParser::new(parse_chunk_selector))]
+    pub chunk: ChunkSelector,
+
+    /// Decrypt a payload written with encode --password. Arguments show up in shell history and
+    /// process lists; PNGME_PASSWORD, PNGME_KEY_FILE or --password-stdin don't
+    #[arg(long)]
+    pub password: Option<String>,
+
+    /// Read the password from this file instead
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), conflicts_with = "password")]
+    pub password_file: Option<PathBuf>,
+
+    /// Read the password from stdin instead, without echo on a terminal
+    #[arg(long, conflicts_with_all = ["password", "password_file"])]
+    pub password_stdin: bool,
+
+    /// Fail unless the payload was signed with encode --sign-key using the key in this file
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub verify_key: Option<PathBuf>,
+
+    /// Write the raw payload to this file instead of printing it
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub out: Option<PathBuf>,
+
+    /// Print the payload as a hex dump, 16 bytes a row, with offsets and the bytes as ASCII
+    #[arg(long, conflicts_with_all = ["out", "output_encoding"])]
+    pub hex: bool,
+
+    /// Show the payload as [utf8 | base64 | hex], so binary data prints safely. --out then
+    /// holds the encoded text
+    #[arg(long, default_value = "utf8")]
+    pub output_encoding: Encoding,
+
+    /// Permissions of the --out file in octal, e.g. 600. Without it new files follow the umask
+    #[arg(long, value_parser=clap::builder::ValueParser::new(parse_mode), requires = "out")]
+    pub mode: Option<u32>,
+
+    /// Decode the N-th chunk of the type, counting from 0, like TYPE@N
+    #[arg(long, conflicts_with = "all")]
+    pub index: Option<usize>,
+
+    /// Decode every chunk of the type, each headed by its TYPE@N selector
+    #[arg(long, conflicts_with = "out")]
+    pub all: bool,
+
+    /// Where the message was hidden [chunk | lsb | trailer]. lsb reads it from the pixels and needs IDAT as the
+    /// chunk type. chunk also finds messages appended after the IEND chunk when the file has no chunk of the type
+    #[arg(long, default_value = "chunk")]
+    pub method: Method,
+
+    /// Descend into subdirectories when the path is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Output format [text | json]. json prints a report of every file, with payloads in base64
+    #[arg(long, default_value = "text")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
 }
 
 
 #[derive(Args,Debug)]
 pub struct RemoveArgs {
+    /// PNG File path, or a directory or glob to change every PNG File it holds
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// Chunk Type [4-Byte value made up of a-z | A-Z], with @N for the N-th chunk of that type from 0, e.g. ruSt@2
+    #[arg(value_parser=clap::builder::ValueParser::new(parse_chunk_selector))]
+    pub chunk: ChunkSelector,
+
+    /// Remove the N-th chunk of the type, counting from 0, like TYPE@N
+    #[arg(long, conflicts_with = "all")]
+    pub index: Option<usize>,
+
+    /// Remove every chunk of the type
+    #[arg(long)]
+    pub all: bool,
+
+    /// Show the chunks that would be removed, with their contents, without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Descend into subdirectories when the path is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ExtractArgs {
     /// PNG File path
     #[arg(value_parser=clap::value_parser!(PathBuf))]
     pub file_path: PathBuf,
 
-    /// Chunk Type [4-Byte value made up of a-z | A-Z]
-    #[arg(value_parser=clap::builder::ValueParser::new(parse_chunk_type))]
-    pub chunk_type: ChunkType,
+    /// Chunk Type [4-Byte value made up of a-z | A-Z], with @N for the N-th chunk of that type from 0, e.g. ruSt@2
+    #[arg(value_parser=clap::builder::ValueParser::new(parse_chunk_selector), required_unless_present = "trailer")]
+    pub chunk: Option<ChunkSelector>,
+
+    /// Extract the N-th chunk of the type, counting from 0, like TYPE@N
+    #[arg(long)]
+    pub index: Option<usize>,
+
+    /// Extract the data after the IEND chunk instead of a chunk
+    #[arg(long, conflicts_with_all = ["chunk", "index"])]
+    pub trailer: bool,
+
+    /// File the chunk data is written to, stdout if not given
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ScrubArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Chunk type to keep, may be repeated. ? stands for any letter, ^ for an uppercase and _ for
+    /// a lowercase one, e.g. ??Xt for the text chunks
+    #[arg(long, value_parser = parse_chunk_pattern)]
+    pub keep: Vec<String>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct CopyChunksArgs {
+    /// PNG File to copy the chunks from
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub source_file_path: PathBuf,
+
+    /// PNG File to copy the chunks into
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the destination file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Chunk types to copy, separated by commas, e.g. tEXt,iCCP. Only ancillary chunks can be copied
+    #[arg(long, required = true, value_delimiter = ',', value_parser=clap::builder::ValueParser::new(parse_chunk_type))]
+    pub types: Vec<ChunkType>,
+
+    /// Remove the chunks of those types the destination already has first. Chunks a file may hold only once, like gAMA or sRGB, are always replaced, and iCCP and sRGB replace each other
+    #[arg(long)]
+    pub replace: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct NormalizeArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Join each run of consecutive IDAT chunks into a single chunk
+    #[arg(long)]
+    pub merge_idat: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct RechunkArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Bytes of image data in each IDAT chunk; the last one holds what is left
+    #[arg(long, value_name = "BYTES", default_value_t = 65536,
+        value_parser = clap::value_parser!(u32).range(1..=crate::capabilities::MAX_CHUNK_LENGTH as i64))]
+    pub idat_size: u32,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct RepairArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Add an IEND chunk if the file has none, e.g. because it was cut off
+    #[arg(long)]
+    pub add_end: bool,
+
+    /// Keep the bytes after the last chunk, which may be data hidden with the trailer command
+    #[arg(long)]
+    pub keep_trailer: bool,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
 }
 
+#[derive(Args,Debug)]
+pub struct LockArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    /// Name recorded as the one who locked the file, the current user if not given
+    #[arg(long)]
+    pub by: Option<String>,
+
+    /// Remove the lock instead
+    #[arg(long, conflicts_with = "by")]
+    pub remove: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
 
 #[derive(Args,Debug)]
 pub struct PrintArgs {
+    /// PNG File path, or a directory or glob to print every PNG File it holds
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// Draw a map of where each chunk lies in the file and how much of it it takes up
+    #[arg(long)]
+    pub graph: bool,
+
+    /// Show the data of each chunk as a hex dump, 16 bytes a row
+    #[arg(long, conflicts_with = "graph")]
+    pub hex: bool,
+
+    /// Group the chunks under headings such as Header, Image Data and Text/Metadata, with the
+    /// size of each group
+    #[arg(long, conflicts_with_all = ["graph", "hex"])]
+    pub tree: bool,
+
+    /// Descend into subdirectories when the path is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+/// Options controlling how input PNG files are parsed.
+#[derive(Args,Debug,PartialEq)]
+pub struct ParseArgs {
+    /// How to handle a repeated IHDR chunk or chunks after IEND [error | keep-first | keep-all]
+    #[arg(long, default_value = "keep-all")]
+    pub duplicates: DuplicatePolicy,
+
+    /// Threads verifying chunk CRCs, 0 to use every core on large files
+    #[arg(long, default_value_t = 0)]
+    pub crc_threads: usize,
+
+    /// Read chunks whose CRC doesn't match instead of failing, so damaged files can be inspected.
+    /// print marks those chunks and verify reports their CRCs without failing the file
+    #[arg(long)]
+    pub ignore_crc: bool,
+
+    /// Refuse compressed text inflating to more than this many times its size, or 1 MiB if
+    /// that is more
+    #[arg(long, value_name = "RATIO", default_value_t = crate::inflate::DEFAULT_MAX_RATIO,
+        value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_inflate_ratio: u64,
+}
+
+impl ParseArgs {
+    pub fn options(&self) -> ParseOptions {
+        ParseOptions { duplicates: self.duplicates, crc_threads: self.crc_threads, ignore_crc: self.ignore_crc }
+    }
+}
+
+/// Options controlling how output PNG files are written.
+#[derive(Args,Debug,PartialEq)]
+pub struct WriteArgs {
+    /// Which CRC to write for each chunk [recompute | preserve | zero]
+    #[arg(long, default_value = "recompute")]
+    pub crc: CrcPolicy,
+
+    /// When overwriting a file, record how to revert the change in <FILE>.undo
+    #[arg(long)]
+    pub undo: bool,
+
+    /// Permissions of the written file in octal, e.g. 600. Without it new files follow the umask
+    #[arg(long, value_parser=clap::builder::ValueParser::new(parse_mode))]
+    pub mode: Option<u32>,
+
+    /// When overwriting a file, first copy it to <FILE><SUFFIX>, <FILE>.bak if no suffix is given
+    #[arg(long, value_name = "SUFFIX", num_args = 0..=1, require_equals = true, default_missing_value = ".bak")]
+    pub backup: Option<String>,
+
+    /// Overwrite a file locked with the lock command anyway, keeping its lock
+    #[arg(long)]
+    pub unlock: bool,
+
+    /// Write over changed files directly instead of through a renamed temporary file, for
+    /// filesystems where that fails
+    #[arg(long)]
+    pub no_atomic: bool,
+}
+
+/// Options controlling how commands working through many files use threads.
+#[derive(Args,Debug,PartialEq)]
+pub struct BatchArgs {
+    /// Files processed at once, 0 for one per core
+    #[arg(short, long, default_value_t = 1)]
+    pub jobs: usize,
+}
+
+impl WriteArgs {
+    pub fn options(&self) -> WriteOptions {
+        WriteOptions { crc: self.crc, mode: self.mode, atomic: !self.no_atomic }
+    }
+}
+
+#[derive(Args,Debug)]
+pub struct BenchmarkArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// Number of times the file is parsed
+    #[arg(short, long, default_value_t = 100)]
+    pub iterations: u32,
+}
+
+#[derive(Args,Debug)]
+pub struct TrailerArgs {
+    #[clap(subcommand)]
+    pub action: TrailerAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TrailerAction {
+    /// Show the size, offset and contents of the trailer.
+    Show(TrailerShowArgs),
+
+    /// Write the trailer to a file.
+    Extract(TrailerExtractArgs),
+
+    /// Remove the trailer.
+    Strip(TrailerStripArgs),
+
+    /// Replace the trailer with the contents of a file.
+    Set(TrailerSetArgs),
+}
+
+#[derive(Args,Debug)]
+pub struct TrailerShowArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct TrailerExtractArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// File the trailer is written to
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: PathBuf,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct TrailerStripArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct TrailerSetArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// File whose contents become the new trailer
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub data_file_path: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ScanArgs {
+    /// PNG File, directory or glob path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub path: PathBuf,
+
+    /// Descend into subdirectories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Only report chunks matching this filter, e.g. 'type !in standard && length > 4096'
+    #[arg(long = "where", value_parser=clap::builder::ValueParser::new(parse_query))]
+    pub filter: Option<Query>,
+
+    /// Index file caching the chunk tables of scanned files between runs
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub index: Option<PathBuf>,
+
+    /// Write the matching chunks to this SQLite database
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output: Option<PathBuf>,
+
+    /// Scan only this many files picked at random, and project the results onto all of them
+    #[arg(long, value_parser=clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub sample: Option<usize>,
+
+    /// Seed picking the --sample files; the same seed always picks the same files
+    #[arg(long, default_value_t = 0, requires = "sample")]
+    pub seed: u64,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct CarveStreamArgs {
+    /// File to search for embedded PNG Files
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub input: PathBuf,
+
+    /// Write each PNG File found to this directory, named after the input and its offset in it
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output_dir: Option<PathBuf>,
+
+    /// List the chunks of each PNG File found, like the scan command
+    #[arg(long)]
+    pub scan: bool,
+
+    /// With --scan, only report chunks matching this filter, e.g. 'type !in standard && length > 4096'
+    #[arg(long = "where", value_parser=clap::builder::ValueParser::new(parse_query), requires = "scan")]
+    pub filter: Option<Query>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct CorpusStatsArgs {
+    /// Directory of PNG Files, searched recursively
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub path: PathBuf,
+
+    /// Index file caching the chunk tables of scanned files between runs
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub index: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ChunkTypeArgs {
+    /// Four character chunk type code, e.g. ruSt
+    #[arg(value_parser = parse_chunk_code)]
+    pub chunk_type: ChunkType,
+}
+
+#[derive(Args,Debug)]
+pub struct ListTypesArgs {
+    /// PNG File path, or a directory or glob to summarize every PNG File it holds
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub path: PathBuf,
+
+    /// Descend into subdirectories when the path is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct DiffArgs {
+    /// Original PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub original_file_path: PathBuf,
+
+    /// Modified PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub modified_file_path: PathBuf,
+
+    /// Print the byte-range edits turning the original file into the modified one
+    #[arg(long)]
+    pub patch: bool,
+
+    /// Output format [text | json]
+    #[arg(long, default_value = "text", conflicts_with = "patch")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct PayloadDiffArgs {
+    /// Original PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub original_file_path: PathBuf,
+
+    /// Modified PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub modified_file_path: PathBuf,
+
+    /// Chunk Type holding the payloads [4-Byte value made up of a-z | A-Z]
+    #[arg(long = "type", value_parser=clap::builder::ValueParser::new(parse_chunk_type))]
+    pub chunk_type: ChunkType,
+
+    /// Decrypt payloads written with encode --password
+    #[arg(long)]
+    pub password: Option<String>,
+
+    /// Read the password from this file instead
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), conflicts_with = "password")]
+    pub password_file: Option<PathBuf>,
+
+    /// Read the password from stdin instead, without echo on a terminal
+    #[arg(long, conflicts_with_all = ["password", "password_file"])]
+    pub password_stdin: bool,
+
+    /// Unchanged lines shown around each change of a text payload
+    #[arg(long, default_value_t = 3)]
+    pub context: usize,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct DetectArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// Only report findings with at least this risk, from 0 to 100
+    #[arg(long, value_name = "RISK", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub min_risk: u8,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct UndoArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+}
+
+#[derive(Args,Debug)]
+pub struct VerifyArgs {
+    /// PNG File or directory paths to check
+    #[arg(value_parser=clap::value_parser!(PathBuf), required_unless_present = "manifest", conflicts_with = "manifest")]
+    pub file_paths: Vec<PathBuf>,
+
+    /// Build manifest written by encode --manifest
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub manifest: Option<PathBuf>,
+
+    /// Report which chunks changed since the files were sealed with the seal command
+    #[arg(long, requires = "key_file", conflicts_with = "manifest")]
+    pub fast: bool,
+
+    /// File holding the key the files were sealed with
+    #[arg(long, value_parser=clap::value_parser!(PathBuf), requires = "fast")]
+    pub key_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ProvenanceArgs {
+    #[clap(subcommand)]
+    pub action: ProvenanceAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProvenanceAction {
+    /// Embed a signed claim of who produced the file and from what.
+    Sign(ProvenanceSignArgs),
+
+    /// Check the embedded claim's signature and that the file is unchanged since signing.
+    Verify(ProvenanceVerifyArgs),
+}
+
+#[derive(Args,Debug)]
+pub struct ProvenanceSignArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// File holding the signing key
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub key_file: PathBuf,
+
+    /// Name of the person or system producing the file
+    #[arg(long)]
+    pub creator: String,
+
+    /// File this image was derived from, recorded by its SHA-256
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub parent: Option<PathBuf>,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct ProvenanceVerifyArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// File holding the signing key
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub key_file: PathBuf,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct SealArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// File holding the key the chunks are signed with
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub key_file: PathBuf,
+
+    /// [Optional] Output file path, If not given the input file is modified
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub output_file_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct RekeyArgs {
+    /// PNG File path, or a directory or glob to rekey every PNG File it holds in place
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub path: PathBuf,
+
+    /// Descend into subdirectories when the path is a directory or glob
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Password the payloads are encrypted with now
+    #[arg(long)]
+    pub old_pass: String,
+
+    /// Password to encrypt them with instead
+    #[arg(long)]
+    pub new_pass: String,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct HookArgs {
+    #[clap(subcommand)]
+    pub action: HookAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HookAction {
+    /// Fail if PNG Files contain EXIF, GPS or private chunks.
+    Check(HookCheckArgs),
+}
+
+#[derive(Args,Debug)]
+pub struct HookCheckArgs {
+    /// PNG File paths
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_paths: Vec<PathBuf>,
+
+    /// Paths are the staged files passed by a pre-commit framework; files that aren't PNGs are skipped
+    #[arg(long)]
+    pub staged: bool,
+
+    /// Private chunk type to accept, may be repeated. ? stands for any letter, ^ for an uppercase
+    /// and _ for a lowercase one, e.g. pr?? for every private chunk starting with pr
+    #[arg(long, value_parser = parse_chunk_pattern)]
+    pub allow: Vec<String>,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct FsckArgs {
+    /// PNG Files or directories, searched recursively
+    #[arg(required = true, value_parser=clap::value_parser!(PathBuf))]
+    pub paths: Vec<PathBuf>,
+
+    /// Key file to check signed provenance claims with
+    #[arg(long, value_parser=clap::value_parser!(PathBuf))]
+    pub key_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct TestvecArgs {
+    /// Seed the files are generated from; the same seed always gives the same files
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Directory the files are written to, created if missing
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output: PathBuf,
+}
+
+#[derive(Args,Debug)]
+pub struct ExamplesArgs {
+    /// Directory the images and README.txt are written to, created if missing
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output: PathBuf,
+}
+
+#[derive(Args,Debug)]
+pub struct WhichChunkArgs {
+    /// PNG File path
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: PathBuf,
+
+    /// Byte offset from the start of the file, decimal or 0x-prefixed hex
+    #[arg(long, value_parser=clap::builder::ValueParser::new(parse_offset))]
+    pub offset: usize,
+}
+
+#[derive(Args,Debug)]
+pub struct DoctorArgs {
+    /// PNG File path, only the environment is checked without it
+    #[arg(value_parser=clap::value_parser!(PathBuf))]
+    pub file_path: Option<PathBuf>,
+
+    /// Where the output would be written, to check it has room for a copy of the file
+    #[arg(short, long, value_parser=clap::value_parser!(PathBuf))]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args,Debug)]
+pub struct InspectArgs {
     /// PNG File path
     #[arg(value_parser=clap::value_parser!(PathBuf))]
     pub file_path: PathBuf,
+
+    /// Also show the entropy, printable share and compressibility of each chunk's data, which
+    /// make encrypted payloads stand out
+    #[arg(long)]
+    pub stats: bool,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+#[derive(Args,Debug)]
+pub struct StatsArgs {
+    /// Show the statistics of this pngme's own use, from the stats file or, without one,
+    /// pngme/usage.json in the cache directory
+    #[arg(long = "self", required = true)]
+    pub own_usage: bool,
+
+    /// Output format [text | json]
+    #[arg(long, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args,Debug)]
+pub struct CapabilitiesArgs {
+    /// Output format [text | json]
+    #[arg(long, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+/// How a command prints its report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown format '{s}', expected one of: text, json")),
+        }
+    }
+}
+
+/// The path standing for stdin when reading and for stdout when writing.
+pub const STDIO_PATH: &str = "-";
+
+/// Whether `path` is [`STDIO_PATH`] rather than a file.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+/// A chunk given by its type, or by `TYPE@N` for the N-th chunk of that type counting from 0,
+/// so scripts can pick one of several chunks of the same type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSelector {
+    pub chunk_type: ChunkType,
+    /// Which chunk of the type, the first one if not given.
+    pub index: Option<usize>,
+}
+
+impl ChunkSelector {
+    /// A predicate to run over a file's chunks in order, true only for the selected chunk.
+    pub fn matcher(&self) -> impl FnMut(&Chunk) -> bool + '_ {
+        let mut remaining = self.index.unwrap_or(0);
+        move |chunk| {
+            if chunk.chunk_type() != &self.chunk_type {
+                return false;
+            }
+            let selected = remaining == 0;
+            remaining = remaining.wrapping_sub(1);
+            selected
+        }
+    }
+
+    /// This selector with the chunk index given separately, e.g. by `--index`. Fails if both
+    /// name an index and they differ.
+    pub fn with_index(self, index: Option<usize>) -> Result<Self, String> {
+        match (self.index, index) {
+            (Some(own), Some(other)) if own != other => {
+                Err(format!("{self} and --index {other} select different chunks"))
+            }
+            (own, other) => Ok(Self { index: own.or(other), ..self }),
+        }
+    }
+
+    /// The position in `chunks` of the selected chunk.
+    pub fn position(&self, chunks: &[Chunk]) -> Option<usize> {
+        chunks.iter().position(self.matcher())
+    }
+}
+
+impl FromStr for ChunkSelector {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (chunk_type, index) = match value.split_once('@') {
+            Some((chunk_type, index)) => {
+                let index = index.parse().map_err(|_| format!("'{index}' in '{value}' is not a chunk index"))?;
+                (chunk_type, Some(index))
+            }
+            None => (value, None),
+        };
+        let chunk_type = ChunkType::from_str(chunk_type).map_err(|e| format!("Invalid chunk type '{chunk_type}': {e}"))?;
+        Ok(Self { chunk_type, index })
+    }
+}
+
+impl std::fmt::Display for ChunkSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}@{index}", self.chunk_type),
+            None => write!(f, "{}", self.chunk_type),
+        }
+    }
+}
+
+fn parse_chunk_selector(value: &str) -> Result<ChunkSelector, String> {
+    ChunkSelector::from_str(value)
+}
+
+/// A chunk of another PNG File, written as `path:TYPE` or `path:TYPE@N`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSource {
+    pub path: PathBuf,
+    pub chunk: ChunkSelector,
+}
+
+fn parse_chunk_source(value: &str) -> Result<ChunkSource, String> {
+    let Some((path, chunk)) = value.rsplit_once(':') else {
+        return Err(format!("'{value}' is not of the form path:TYPE"));
+    };
+    Ok(ChunkSource { path: PathBuf::from(path), chunk: ChunkSelector::from_str(chunk)? })
+}
+
+fn parse_offset(value: &str) -> Result<usize, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("'{value}' is not a valid offset"))
+}
+
+fn parse_mode(value: &str) -> Result<u32, String> {
+    match u32::from_str_radix(value.trim_start_matches("0o"), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("'{value}' is not an octal file mode such as 600")),
+    }
+}
+
+fn parse_chunk_pattern(value: &str) -> Result<String, String> {
+    match ChunkType::is_valid_pattern(value) {
+        true => Ok(value.to_string()),
+        false => Err(format!("'{value}' is not 4 letters or the wildcards ?, ^ and _")),
+    }
+}
+
+/// Any four printable ASCII characters, so invalid codes can still be explained.
+fn parse_chunk_code(value: &str) -> Result<ChunkType, String> {
+    match <[u8; 4]>::try_from(value.as_bytes()) {
+        Ok(code) if code.iter().all(u8::is_ascii_graphic) => ChunkType::try_from(code).map_err(|e| e.to_string()),
+        _ => Err(format!("'{value}' is not four printable ASCII characters")),
+    }
+}
+
+fn parse_timeout(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(format!("'{value}' is not a positive number of seconds")),
+    }
+}
+
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    let number = value.trim_end_matches('%');
+    match number.parse::<f64>() {
+        Ok(percentage) if percentage >= 0.0 => Ok(percentage),
+        _ => Err(format!("'{value}' is not a valid percentage")),
+    }
+}
+
+fn parse_query(query: &str) -> Result<Query, String> {
+    Query::from_str(query).map_err(|e| e.to_string())
 }
 
 fn parse_chunk_type(env: &str)-> Result<ChunkType,std::io::Error>{
     let chunk_type = ChunkType::from_str(env);
-    if let Err(_) = chunk_type{
+    if chunk_type.is_err(){
         eprintln!("Couldnot parse chunk type");
         exit(1);
     }
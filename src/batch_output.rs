@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+/// How a batch writing into an output directory names files whose names would collide, e.g.
+/// `a/cat.png` and `b/cat.png`, or a file that would overwrite another input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenameScheme {
+    /// Append a short hash of the input path to colliding names, e.g. `cat-1a2b3c4d.png`.
+    /// Other files keep their name.
+    #[default]
+    Hash,
+    /// Mirror the input tree: every file keeps its path relative to the batch's directory.
+    Relative,
+}
+
+impl RenameScheme {
+    /// The names accepted by `from_str`.
+    pub const NAMES: [&'static str; 2] = ["hash", "relative"];
+}
+
+impl FromStr for RenameScheme {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(RenameScheme::Hash),
+            "relative" => Ok(RenameScheme::Relative),
+            _ => Err(format!("Unknown scheme '{s}', expected one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// The directory a batch path stands for: the path itself for a directory, or the part of a
+/// glob pattern before its first wildcard, e.g. `reports` for `reports/**/*.png`.
+pub fn batch_root(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Picks the path in `directory` each of `files` is written to, so that no two files share a
+/// path and no file overwrites another input. `root` is the directory the files were found in.
+pub fn plan(files: &[PathBuf], root: &Path, directory: &Path, scheme: RenameScheme) -> Vec<PathBuf> {
+    let mut names: HashMap<&OsStr, usize> = HashMap::new();
+    for file in files {
+        *names.entry(file_name(file)).or_default() += 1;
+    }
+    let inputs: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+    files
+        .iter()
+        .map(|file| {
+            let target = match scheme {
+                RenameScheme::Relative => directory.join(relative(file, root)),
+                RenameScheme::Hash => directory.join(file_name(file)),
+            };
+            let overwrites_input = target != *file && inputs.contains(target.as_path());
+            if (scheme == RenameScheme::Hash && names[file_name(file)] > 1) || overwrites_input {
+                return target.with_file_name(hashed_name(file));
+            }
+            target
+        })
+        .collect()
+}
+
+fn file_name(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
+/// `file` relative to `root`, without `..` or a leading `/` that would leave the output directory.
+fn relative(file: &Path, root: &Path) -> PathBuf {
+    let path = file.strip_prefix(root).unwrap_or(file);
+    path.components().filter(|component| matches!(component, Component::Normal(_))).collect()
+}
+
+/// The name of `file` with a hash of its whole path before the extension.
+fn hashed_name(file: &Path) -> OsString {
+    let hash = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(file.as_os_str().as_encoded_bytes());
+    let mut name = file.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("-{hash:08x}"));
+    if let Some(extension) = file.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_batch_root() {
+        assert_eq!(batch_root(Path::new("reports/**/*.png")), PathBuf::from("reports"));
+        assert_eq!(batch_root(Path::new("images")), PathBuf::from("images"));
+        assert_eq!(batch_root(Path::new("*.png")), PathBuf::new());
+    }
+
+    #[test]
+    fn test_plan() {
+        let files = paths(&["in/a/cat.png", "in/b/cat.png", "in/dog.png"]);
+        let planned = plan(&files, Path::new("in"), Path::new("out"), RenameScheme::Hash);
+        assert_eq!(planned[2], PathBuf::from("out/dog.png"));
+        assert_ne!(planned[0], planned[1]);
+        assert!(planned[0].to_string_lossy().starts_with("out/cat-"));
+
+        let planned = plan(&files, Path::new("in"), Path::new("out"), RenameScheme::Relative);
+        assert_eq!(planned, paths(&["out/a/cat.png", "out/b/cat.png", "out/dog.png"]));
+    }
+
+    #[test]
+    fn test_plan_keeps_inputs() {
+        // Mirroring in/ into in/out/ would write in/a.png over the input in/out/a.png.
+        let files = paths(&["in/out/a.png", "in/a.png"]);
+        let planned = plan(&files, Path::new("in"), Path::new("in/out"), RenameScheme::Relative);
+        assert_eq!(planned[0], PathBuf::from("in/out/out/a.png"));
+        assert!(planned[1].to_string_lossy().starts_with("in/out/a-"));
+    }
+}
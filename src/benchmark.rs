@@ -0,0 +1,105 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use crate::chunk::Chunk;
+use crate::png::{Png, PngError};
+use crate::Result;
+
+/// Time spent in each phase of reading and writing a PNG, summed over all iterations.
+#[derive(Debug, Default)]
+pub struct BenchmarkReport {
+    pub iterations: u32,
+    pub chunk_count: usize,
+    pub signature_check: Duration,
+    pub chunk_parsing: Duration,
+    pub crc_verification: Duration,
+    pub serialization: Duration,
+}
+
+impl BenchmarkReport {
+    /// Total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.signature_check + self.chunk_parsing + self.crc_verification + self.serialization
+    }
+
+    /// Average time of a single iteration of the given phase.
+    pub fn per_iteration(&self, phase: Duration) -> Duration {
+        phase / self.iterations.max(1)
+    }
+}
+
+/// Parses `bytes` `iterations` times, timing each phase separately.
+pub fn run(bytes: &[u8], iterations: u32) -> Result<BenchmarkReport> {
+    let shared = Bytes::copy_from_slice(bytes);
+    let mut report = BenchmarkReport {
+        iterations,
+        ..Default::default()
+    };
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let valid_signature = bytes.len() >= 8 && bytes[..8] == Png::STANDARD_HEADER;
+        report.signature_check += start.elapsed();
+        if !black_box(valid_signature) {
+            return Err(Box::new(PngError::InvalidHeader));
+        }
+
+        let start = Instant::now();
+        let parsed = parse_chunks(shared.slice(8..))?;
+        report.chunk_parsing += start.elapsed();
+
+        let start = Instant::now();
+        let crc_ok = parsed.iter().all(|(chunk, crc)| chunk.crc() == *crc);
+        report.crc_verification += start.elapsed();
+        black_box(crc_ok);
+
+        report.chunk_count = parsed.len();
+        let png = Png::from_chunks(parsed.into_iter().map(|(chunk, _)| chunk).collect());
+        let start = Instant::now();
+        black_box(png.as_bytes());
+        report.serialization += start.elapsed();
+    }
+    Ok(report)
+}
+
+/// Splits the chunk section of a PNG into chunks, keeping the stored CRC of each one.
+fn parse_chunks(mut bytes: Bytes) -> Result<Vec<(Chunk, u32)>> {
+    let mut chunks = Vec::new();
+    while bytes.len() >= 4 {
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let end = (length + 12).min(bytes.len());
+        chunks.push(Chunk::parse_unchecked(bytes.split_to(end))?);
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ];
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    #[test]
+    fn test_benchmark_counts_chunks() {
+        let report = run(&testing_bytes(), 3).unwrap();
+        assert_eq!(report.iterations, 3);
+        assert_eq!(report.chunk_count, 2);
+    }
+
+    #[test]
+    fn test_benchmark_invalid_header() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 13;
+        assert!(run(&bytes, 1).is_err());
+    }
+}
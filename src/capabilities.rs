@@ -0,0 +1,152 @@
+use std::fmt::{self, Display};
+
+use crate::chunk::CrcPolicy;
+use crate::features::Feature;
+use crate::inflate;
+use crate::keyword::MAX_KEYWORD_LENGTH;
+use crate::png::{DuplicatePolicy, PARALLEL_CRC_THRESHOLD};
+
+/// Version of the capabilities document. Bumped whenever a field is removed or changes meaning;
+/// new fields may be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Largest chunk data length the PNG spec allows.
+pub const MAX_CHUNK_LENGTH: u32 = (1 << 31) - 1;
+
+/// What this particular build of pngme can do, for tools that drive it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Capabilities {
+    pub schema: u32,
+    pub version: &'static str,
+    /// Cargo features compiled in.
+    pub features: Vec<&'static str>,
+    pub commands: Vec<String>,
+    pub crypto: Crypto,
+    /// Formats pngme can write besides PNG itself.
+    pub output_formats: Vec<&'static str>,
+    pub strategies: Strategies,
+    /// Payload envelope format versions understood. Payloads are stored raw for now.
+    pub envelope_versions: Vec<u32>,
+    pub server: bool,
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Crypto {
+    pub digests: Vec<&'static str>,
+    pub signatures: Vec<&'static str>,
+    pub ciphers: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Strategies {
+    pub crc: Vec<&'static str>,
+    pub duplicates: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Limits {
+    pub max_chunk_length: u32,
+    pub max_keyword_length: usize,
+    pub parallel_crc_threshold: usize,
+    /// How many times its compressed size zTXt and iTXt text may inflate to by default.
+    pub max_inflate_ratio: u64,
+    /// Bytes compressed data may always inflate to, whatever the ratio.
+    pub min_inflate_limit: u64,
+}
+
+impl Capabilities {
+    /// Describes this build, which offers `commands`.
+    pub fn current(commands: Vec<String>) -> Self {
+        let enabled = |feature: &'static str, on: bool| on.then_some(feature);
+        let crypto = cfg!(feature = "crypto");
+        Self {
+            schema: SCHEMA_VERSION,
+            version: env!("CARGO_PKG_VERSION"),
+            features: Feature::enabled().into_iter().map(Feature::name).collect(),
+            commands,
+            crypto: Crypto {
+                digests: [enabled("sha256", crypto), Some("crc32"), enabled("crc64-xz", cfg!(feature = "json"))].into_iter().flatten().collect(),
+                signatures: [enabled("hmac-sha256", crypto)].into_iter().flatten().collect(),
+                ciphers: [enabled("aes-256-gcm+argon2id", crypto)].into_iter().flatten().collect(),
+            },
+            output_formats: [
+                Some("patch"),
+                enabled("json-index", cfg!(feature = "json")),
+                enabled("json-manifest", crypto),
+                enabled("sqlite", cfg!(feature = "sqlite")),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            strategies: Strategies {
+                crc: CrcPolicy::NAMES.to_vec(),
+                duplicates: DuplicatePolicy::NAMES.to_vec(),
+            },
+            envelope_versions: Vec::new(),
+            server: false,
+            limits: Limits {
+                max_chunk_length: MAX_CHUNK_LENGTH,
+                max_keyword_length: MAX_KEYWORD_LENGTH,
+                parallel_crc_threshold: PARALLEL_CRC_THRESHOLD,
+                max_inflate_ratio: inflate::DEFAULT_MAX_RATIO,
+                min_inflate_limit: inflate::MIN_LIMIT,
+            },
+        }
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pngme {} (capabilities schema {})", self.version, self.schema)?;
+        writeln!(f, "Features          : {}", list(&self.features))?;
+        writeln!(f, "Commands          : {}", list(&self.commands))?;
+        writeln!(f, "Digests           : {}", list(&self.crypto.digests))?;
+        writeln!(f, "Signatures        : {}", list(&self.crypto.signatures))?;
+        writeln!(f, "Ciphers           : {}", list(&self.crypto.ciphers))?;
+        writeln!(f, "Output formats    : {}", list(&self.output_formats))?;
+        writeln!(f, "CRC policies      : {}", list(&self.strategies.crc))?;
+        writeln!(f, "Duplicate policies: {}", list(&self.strategies.duplicates))?;
+        writeln!(f, "Envelope versions : {}", list(&self.envelope_versions))?;
+        writeln!(f, "Server            : {}", if self.server { "yes" } else { "no" })?;
+        writeln!(f, "Max chunk length  : {}", self.limits.max_chunk_length)?;
+        writeln!(f, "Max keyword length: {}", self.limits.max_keyword_length)?;
+        writeln!(f, "Parallel CRC from : {} bytes", self.limits.parallel_crc_threshold)?;
+        write!(f, "Max inflate ratio : {} (at least {} bytes)", self.limits.max_inflate_ratio, self.limits.min_inflate_limit)
+    }
+}
+
+fn list<T: Display>(items: &[T]) -> String {
+    if items.is_empty() {
+        return "none".to_string();
+    }
+    items.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_capabilities() {
+        let capabilities = Capabilities::current(vec!["encode".to_string()]);
+        assert_eq!(capabilities.schema, SCHEMA_VERSION);
+        assert_eq!(capabilities.features.contains(&"crypto"), cfg!(feature = "crypto"));
+        assert_eq!(capabilities.crypto.digests.contains(&"crc64-xz"), cfg!(feature = "json"));
+        assert_eq!(capabilities.strategies.crc, CrcPolicy::NAMES);
+        assert!(capabilities.to_string().contains("Commands          : encode"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_shape() {
+        let json = serde_json::to_value(Capabilities::current(Vec::new())).unwrap();
+        assert_eq!(json["schema"], SCHEMA_VERSION);
+        assert_eq!(json["limits"]["max_keyword_length"], MAX_KEYWORD_LENGTH);
+        assert!(json["features"].is_array());
+    }
+}
@@ -0,0 +1,109 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::png::Png;
+
+/// A complete PNG file found inside a larger blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Carved {
+    /// Where the PNG signature starts in the blob.
+    pub offset: usize,
+    /// Bytes from the signature to the end of IEND.
+    pub length: usize,
+    pub chunks: usize,
+}
+
+impl Carved {
+    pub fn bytes<'a>(&self, blob: &'a [u8]) -> &'a [u8] {
+        &blob[self.offset..self.offset + self.length]
+    }
+}
+
+/// Finds every complete PNG file in `blob`, such as a network capture or a memory dump. A PNG
+/// counts as complete when it starts with IHDR and every chunk up to IEND is there with a
+/// matching CRC. The search goes on after the end of each PNG found, so PNGs embedded in the
+/// chunks of another aren't reported separately.
+pub fn carve(blob: &[u8]) -> Vec<Carved> {
+    let signature = &Png::STANDARD_HEADER;
+    let mut carved = Vec::new();
+    let mut start = 0;
+    while let Some(found) = blob[start..].windows(signature.len()).position(|window| window == signature) {
+        let offset = start + found;
+        match walk(&blob[offset..]) {
+            Some((length, chunks)) => {
+                carved.push(Carved { offset, length, chunks });
+                start = offset + length;
+            }
+            None => start = offset + 1,
+        }
+    }
+    carved
+}
+
+/// The length and chunk count of the PNG at the start of `bytes`, or `None` if it is cut short
+/// or damaged before its IEND.
+fn walk(bytes: &[u8]) -> Option<(usize, usize)> {
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut position = Png::STANDARD_HEADER.len();
+    let mut chunks = 0;
+    loop {
+        let header = bytes.get(position..position + 8)?;
+        let length = u32::from_be_bytes(header[..4].try_into().expect("4 bytes"));
+        let chunk_type = &header[4..];
+        if length > i32::MAX as u32 || !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            return None;
+        }
+        if chunks == 0 && chunk_type != b"IHDR" {
+            return None;
+        }
+        let end = position + 12 + length as usize;
+        let body = bytes.get(position + 4..end - 4)?;
+        let stored = u32::from_be_bytes(bytes.get(end - 4..end)?.try_into().expect("4 bytes"));
+        if crc.checksum(body) != stored {
+            return None;
+        }
+        chunks += 1;
+        position = end;
+        if chunk_type == b"IEND" {
+            return Some((position, chunks));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn png(text: &str) -> Vec<u8> {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec());
+        Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("tEXt", text.as_bytes()), chunk("IEND", &[])]).as_bytes()
+    }
+
+    #[test]
+    fn test_carve() {
+        let (first, second) = (png("Comment\0one"), png("Comment\0two"));
+        let mut blob = b"GET /image.png HTTP/1.1\r\n\r\n".to_vec();
+        blob.extend(&first);
+        blob.extend(b"\x00\x01junk");
+        let second_offset = blob.len();
+        blob.extend(&second);
+        // A PNG cut short by the end of the capture.
+        blob.extend(&first[..first.len() - 5]);
+
+        let carved = carve(&blob);
+        assert_eq!(carved.len(), 2);
+        assert_eq!((carved[0].offset, carved[0].length, carved[0].chunks), (27, first.len(), 3));
+        assert_eq!(carved[1].offset, second_offset);
+        assert_eq!(carved[1].bytes(&blob), second);
+    }
+
+    #[test]
+    fn test_damaged_png_is_skipped() {
+        let mut damaged = png("Comment\0one");
+        damaged[40] ^= 1;
+        assert_eq!(carve(&damaged), Vec::new());
+        assert_eq!(carve(b"no png here"), Vec::new());
+    }
+}
@@ -1,21 +1,84 @@
 use crate::chunk_type::ChunkType;
-use crate::{Result, Error};
+use crate::payload::RawChunkData;
+use crate::error::PngMeError;
 
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{self, ErrorKind, Read, Write};
+use std::str::FromStr;
+use bytes::Bytes;
 use crc::CRC_32_ISO_HDLC;
 
-#[derive(Debug)]
+type Result<T> = std::result::Result<T, PngMeError>;
+
+/// A single PNG chunk. The data is held in a reference-counted `Bytes` buffer, so
+/// chunks parsed from a file share the file's buffer and cloning a chunk never copies its data.
+#[derive(Debug, Clone)]
 pub struct Chunk{
     chunk_type:ChunkType,
-    chunk_data:Vec<u8>,
+    chunk_data:Bytes,
+    stored_crc:Option<u32>,
+}
+
+/// Which CRC is written when a chunk is serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPolicy {
+    /// Compute the CRC from the chunk type and data.
+    #[default]
+    Recompute,
+    /// Write the CRC the chunk was read with, even if it is wrong. New chunks get a computed CRC.
+    Preserve,
+    /// Write a CRC of zero.
+    Zero,
+}
+
+impl CrcPolicy {
+    /// The names accepted by `from_str`.
+    pub const NAMES: [&'static str; 3] = ["recompute", "preserve", "zero"];
+}
+
+impl FromStr for CrcPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "recompute" => Ok(CrcPolicy::Recompute),
+            "preserve" => Ok(CrcPolicy::Preserve),
+            "zero" => Ok(CrcPolicy::Zero),
+            _ => Err(format!("Unknown CRC policy '{s}', expected one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// What a chunk's data looks like when read as a hidden message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum PayloadKind {
+    /// The chunk has no data. The PNG spec allows zero-length chunks.
+    Empty,
+    /// The data is UTF-8 text made only of whitespace.
+    Whitespace,
+    /// The data is UTF-8 text with at least one visible character.
+    Text,
+    /// The data is not valid UTF-8.
+    Binary,
+}
+
+impl PayloadKind {
+    /// Classifies `data` as a message payload.
+    pub fn of(data: &[u8]) -> Self {
+        match std::str::from_utf8(data) {
+            _ if data.is_empty() => PayloadKind::Empty,
+            Ok(text) if text.trim().is_empty() => PayloadKind::Whitespace,
+            Ok(_) => PayloadKind::Text,
+            Err(_) => PayloadKind::Binary,
+        }
+    }
 }
 
 impl Chunk{
 
     /// Creates a new instance of `Chunk`
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        Self {chunk_type,chunk_data:data}
+    pub fn new(chunk_type: ChunkType, data: impl Into<RawChunkData>) -> Self {
+        Self {chunk_type,chunk_data:data.into().into_bytes(),stored_crc:None}
     }
 
      /// The length of the data portion of this chunk.
@@ -34,86 +97,159 @@ impl Chunk{
         &self.chunk_data
     }
 
+    /// A shared handle to the data contained in this chunk. This does not copy the data.
+    #[allow(dead_code)]
+    pub fn data_bytes(&self) -> Bytes {
+        self.chunk_data.clone()
+    }
+
+    /// The data contained in this chunk. This does not copy the data.
+    pub fn raw_data(&self) -> RawChunkData {
+        RawChunkData::from(self.chunk_data.clone())
+    }
+
     /// The CRC of this chunk
     pub fn crc(&self) -> u32 {
-        let bytes:Vec<u8> = self
-                    .chunk_type
-                    .bytes()
-                    .iter()
-                    .chain(self.chunk_data.iter())
-                    .copied()
-                    .collect();
-
         let png_crc = crc::Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let crc = png_crc.checksum(&bytes);
-        crc
+        let mut digest = png_crc.digest();
+        digest.update(&self.chunk_type.bytes());
+        digest.update(&self.chunk_data);
+        digest.finalize()
+    }
+
+    /// The CRC this chunk was read with, if it was parsed from bytes.
+    #[allow(dead_code)]
+    pub fn stored_crc(&self) -> Option<u32> {
+        self.stored_crc
+    }
+
+    /// Whether the CRC this chunk was read with matches its contents. Chunks that weren't parsed
+    /// from bytes always have a matching CRC.
+    pub fn crc_ok(&self) -> bool {
+        self.stored_crc.is_none_or(|stored| stored == self.crc())
     }
 
     /// Returns the data stored in this chunk as a `String`. This function will return an error
     /// if the stored data is not valid UTF-8.
     pub fn data_as_string(&self) -> Result<String> {
-        let data_as_string = std::str::from_utf8(&self.chunk_data)?.to_string();
+        let data_as_string = std::str::from_utf8(&self.chunk_data).map_err(|e| PngMeError::Other(Box::new(e)))?.to_string();
         Ok(data_as_string)
     }
 
+    /// Classifies this chunk's data as a message payload.
+    pub fn payload_kind(&self) -> PayloadKind {
+        PayloadKind::of(&self.chunk_data)
+    }
+
     /// Returns this chunk as a byte sequences described by the PNG spec.
     /// The following data is included in this byte sequence in order:
     /// 1. Length of the data *(4 bytes)*
     /// 2. Chunk type *(4 bytes)*
     /// 3. The data itself *(`length` bytes)*
     /// 4. The CRC of the chunk type and data *(4 bytes)*
+    #[allow(dead_code)]
     pub fn as_bytes(&self) -> Vec<u8> {
-        self
-            .length()
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.chunk_data.iter())
-            .chain(self.crc().to_be_bytes().iter())
-            .copied()
-            .collect()
+        self.as_bytes_with(CrcPolicy::Recompute)
     }
-}
 
-impl TryFrom<&[u8]> for Chunk{
-    type Error = Error;
-    fn try_from(value: &[u8]) -> Result<Self> {
-        if value.len() < 12 {
-            return  Err(Box::new(ChunkError::SmallInput));
+    /// Same as `as_bytes`, but the CRC written is chosen by `policy`.
+    pub fn as_bytes_with(&self, policy: CrcPolicy) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.chunk_data.len() + 12);
+        self.write_to(&mut bytes, policy).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Writes this chunk to `writer` in the layout of `as_bytes`, with the CRC chosen by `policy`.
+    pub fn write_to(&self, writer: &mut impl Write, policy: CrcPolicy) -> io::Result<()> {
+        let crc = match policy {
+            CrcPolicy::Recompute => self.crc(),
+            CrcPolicy::Preserve => self.stored_crc.unwrap_or_else(|| self.crc()),
+            CrcPolicy::Zero => 0,
+        };
+        writer.write_all(&self.length().to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.chunk_data)?;
+        writer.write_all(&crc.to_be_bytes())
+    }
+
+    /// Reads the chunk whose 8-byte length and type fields are `header` from `reader`,
+    /// which is positioned right after them, and verifies its CRC.
+    /// Data is read as it arrives, so a huge declared length in a short file fails without allocating it.
+    pub fn read_from(header: [u8; 8], reader: &mut impl Read) -> Result<Self> {
+        let chunk = Chunk::read_from_lenient(header, reader)?;
+        if !chunk.crc_ok() {
+            return Err(ChunkError::InvalidCrc.into());
+        }
+        Ok(chunk)
+    }
+
+    /// Same as `read_from`, but keeps a chunk whose CRC doesn't match, like `parse_lenient`.
+    pub fn read_from_lenient(header: [u8; 8], reader: &mut impl Read) -> Result<Self> {
+        let data_length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut bytes = header.to_vec();
+        reader.take(data_length + 4).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != 12 + data_length {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
         }
-        let mut reader = BufReader::new(value);
-        let mut buffer:[u8;4] = [0,0,0,0];
+        Chunk::parse_lenient(Bytes::from(bytes))
+    }
 
-        reader.read_exact(&mut buffer)?;
-        let data_length: u32 = u32::from_be_bytes(buffer);
+    /// Parses a `Chunk` from its byte representation without verifying its CRC.
+    /// Returns the chunk together with the CRC stored in the input.
+    /// The returned chunk shares `value`'s buffer instead of copying its data.
+    pub fn parse_unchecked(value: Bytes) -> Result<(Self, u32)> {
+        if value.len() < 12 {
+            return  Err(ChunkError::SmallInput.into());
+        }
+        let data_length = u32::from_be_bytes([value[0], value[1], value[2], value[3]]) as usize;
+        let chunk_type = ChunkType::try_from([value[4], value[5], value[6], value[7]])?;
 
-        reader.read_exact(&mut buffer)?;
-        let chunk_type = ChunkType::try_from(buffer)?;
-        
         if !chunk_type.is_valid(){
-            return Err(Box::new(ChunkError::InvalidChunkType));
+            return Err(ChunkError::InvalidChunkType.into());
         }
 
-       let mut data_buffer = vec![0;data_length as usize];
-        reader.read_exact(&mut data_buffer)?;
-        let chunk_data = data_buffer;
-
-        reader.read_exact(&mut buffer)?;
-        let crc_bytes = u32::from_be_bytes(buffer);
+        let data_end = 8 + data_length;
+        if value.len() < data_end + 4 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        let chunk_data = value.slice(8..data_end);
+        let crc_bytes = u32::from_be_bytes([value[data_end], value[data_end + 1], value[data_end + 2], value[data_end + 3]]);
 
         let new_chunk = Self{
             chunk_type,
             chunk_data,
+            stored_crc: Some(crc_bytes),
         };
+        Ok((new_chunk, crc_bytes))
+    }
+}
+
+impl Chunk {
+    /// Parses a `Chunk` like `try_from`, but keeps a chunk whose CRC doesn't match instead of
+    /// failing. The CRC read is kept in `stored_crc`, and `crc_ok` tells whether it matched.
+    pub fn parse_lenient(value: Bytes) -> Result<Self> {
+        Chunk::parse_unchecked(value).map(|(chunk, _)| chunk)
+    }
+}
+
+impl TryFrom<Bytes> for Chunk{
+    type Error = PngMeError;
+    fn try_from(value: Bytes) -> Result<Self> {
+        let (new_chunk, given_crc) = Chunk::parse_unchecked(value)?;
         let crc = new_chunk.crc();
-        let given_crc = crc_bytes;
 
         if crc!=given_crc {
-             return Err(Box::new(ChunkError::InvalidCrc));
+             return Err(ChunkError::InvalidCrc.into());
         }
 
         Ok(new_chunk)
+    }
+}
 
+impl TryFrom<&[u8]> for Chunk{
+    type Error = PngMeError;
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Chunk::try_from(Bytes::copy_from_slice(value))
     }
 }
 
@@ -122,33 +258,30 @@ impl Display for Chunk{
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "  Length: {}", self.length())?;
         writeln!(f, "  Type: {}", self.chunk_type())?;
-        writeln!(f, "  Data: {} ",self.data().len())?;
-        writeln!(f, "  Crc: {}", self.crc())?;
+        match self.payload_kind() {
+            PayloadKind::Empty => writeln!(f, "  Data: 0 (empty payload)")?,
+            _ => writeln!(f, "  Data: {} ",self.data().len())?,
+        }
+        match self.stored_crc.filter(|_| !self.crc_ok()) {
+            Some(stored) => writeln!(f, "  Crc: {} (stored {stored}, mismatch)", self.crc())?,
+            None => writeln!(f, "  Crc: {}", self.crc())?,
+        }
         writeln!(f, "}}",)?;
         Ok(())
     }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum ChunkError {
+    #[error("At least 12 bytes needeed to create a Chunk")]
     SmallInput,
+    #[error("CRC of chunk doesnot match with calculated CRC")]
     InvalidCrc,
+    #[error("Invalid chunk type")]
     InvalidChunkType,
 }
 
-impl std::error::Error for ChunkError {}
-
-impl std::fmt::Display for ChunkError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            ChunkError::SmallInput=> write!(f, "At least 12 bytes needeed to create a Chunk"),
-            ChunkError::InvalidCrc => write!(f,"CRC of chunk doesnot match with calculated CRC"),
-            ChunkError::InvalidChunkType => write!(f, "Invalid chunk type"),
-        }
-    }
-}
-
 
 
 #[cfg(test)]
@@ -258,6 +391,67 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_as_bytes_with_crc_policy() {
+        let chunk = testing_chunk();
+        let crc_of = |bytes: Vec<u8>| u32::from_be_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+
+        assert_eq!(crc_of(chunk.as_bytes_with(CrcPolicy::Recompute)), 2882656334);
+        assert_eq!(crc_of(chunk.as_bytes_with(CrcPolicy::Zero)), 0);
+
+        let mut bytes = chunk.as_bytes();
+        let length = bytes.len();
+        bytes[length - 1] ^= 0xff;
+        let (wrong_crc_chunk, stored_crc) = Chunk::parse_unchecked(Bytes::from(bytes.clone())).unwrap();
+        assert_eq!(wrong_crc_chunk.stored_crc(), Some(stored_crc));
+        assert_eq!(wrong_crc_chunk.as_bytes_with(CrcPolicy::Preserve), bytes);
+        assert_eq!(crc_of(wrong_crc_chunk.as_bytes_with(CrcPolicy::Recompute)), 2882656334);
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let mut bytes = testing_chunk().as_bytes();
+        assert!(Chunk::parse_lenient(Bytes::from(bytes.clone())).unwrap().crc_ok());
+        let length = bytes.len();
+        bytes[length - 1] ^= 0xff;
+        assert!(Chunk::try_from(bytes.as_slice()).is_err());
+        let chunk = Chunk::parse_lenient(Bytes::from(bytes)).unwrap();
+        assert!(!chunk.crc_ok());
+        assert!(chunk.data_as_string().is_ok());
+        assert!(testing_chunk().crc_ok());
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        chunk_data.truncate(20);
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(chunk.is_err());
+    }
+
+    #[test]
+    fn test_zero_length_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), Vec::new());
+        let bytes = chunk.as_bytes();
+        assert_eq!(bytes.len(), 12);
+
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(parsed.length(), 0);
+        assert_eq!(parsed.payload_kind(), PayloadKind::Empty);
+        assert!(parsed.to_string().contains("empty payload"));
+    }
+
+    #[test]
+    fn test_payload_kind() {
+        let kind = |data: &[u8]| Chunk::new(ChunkType::from_str("ruSt").unwrap(), data.to_vec()).payload_kind();
+        assert_eq!(kind(b""), PayloadKind::Empty);
+        assert_eq!(kind(b" \t\n"), PayloadKind::Whitespace);
+        assert_eq!(kind(b" hi "), PayloadKind::Text);
+        assert_eq!(kind(&[0xff, 0xfe]), PayloadKind::Binary);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
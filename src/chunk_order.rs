@@ -0,0 +1,125 @@
+use std::fmt::{self, Display};
+
+use crate::chunk_type::ChunkType;
+
+/// Chunks the spec wants before PLTE, and so also before IDAT.
+pub(crate) const BEFORE_PLTE: [&str; 8] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV", "cLLI"];
+
+/// Chunks the spec wants before the first IDAT.
+pub(crate) const BEFORE_IDAT: [&str; 10] = ["PLTE", "tRNS", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "oFFs", "pCAL", "sCAL"];
+
+/// Chunks the spec wants after PLTE, when there is one.
+pub(crate) const AFTER_PLTE: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+
+/// Ancillary chunks the spec allows at most once in a file.
+pub(crate) const SINGLE: [&str; 18] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV", "cLLI", "tRNS", "bKGD", "hIST", "pHYs", "eXIf", "oFFs",
+    "pCAL", "sCAL", "tIME", "acTL",
+];
+
+/// Whether a file may not hold a chunk of type `added` next to one of type `existing`: both are
+/// the same chunk the spec allows only once, or they are iCCP and sRGB, which exclude each other.
+pub fn replaces(added: &ChunkType, existing: &ChunkType) -> bool {
+    let (added, existing) = (added.to_string(), existing.to_string());
+    match (added.as_str(), existing.as_str()) {
+        ("iCCP", "sRGB") | ("sRGB", "iCCP") => true,
+        (added, existing) => added == existing && SINGLE.contains(&added),
+    }
+}
+
+/// Why a chunk is out of the order the PNG spec requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OrderIssue {
+    IhdrNotFirst,
+    AfterIend,
+    AfterPlte,
+    BeforePlte,
+    AfterIdat,
+    IdatNotConsecutive,
+}
+
+impl Display for OrderIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderIssue::IhdrNotFirst => write!(f, "not the first chunk"),
+            OrderIssue::AfterIend => write!(f, "after IEND"),
+            OrderIssue::AfterPlte => write!(f, "after PLTE"),
+            OrderIssue::BeforePlte => write!(f, "before PLTE"),
+            OrderIssue::AfterIdat => write!(f, "after IDAT"),
+            OrderIssue::IdatNotConsecutive => write!(f, "not next to the other IDAT chunks"),
+        }
+    }
+}
+
+/// Finds the chunks in `types`, the chunk types of a file in order, that are out of place,
+/// with the index of each. A chunk gets at most one issue, the first that applies.
+pub fn check(types: &[ChunkType]) -> Vec<(usize, OrderIssue)> {
+    let names: Vec<String> = types.iter().map(ChunkType::to_string).collect();
+    let first = |name: &str| names.iter().position(|other| other == name);
+    let (plte, idat, iend) = (first("PLTE"), first("IDAT"), first("IEND"));
+    let after = |index: usize, first: Option<usize>| first.is_some_and(|first| index > first);
+    names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let name = name.as_str();
+            let issue = if name == "IHDR" && index != 0 {
+                OrderIssue::IhdrNotFirst
+            } else if after(index, iend) {
+                OrderIssue::AfterIend
+            } else if BEFORE_PLTE.contains(&name) && after(index, plte) {
+                OrderIssue::AfterPlte
+            } else if AFTER_PLTE.contains(&name) && plte.is_some_and(|plte| index < plte) {
+                OrderIssue::BeforePlte
+            } else if (BEFORE_PLTE.contains(&name) || BEFORE_IDAT.contains(&name)) && after(index, idat) {
+                OrderIssue::AfterIdat
+            } else if name == "IDAT" && after(index, idat) && names[index - 1] != "IDAT" {
+                OrderIssue::IdatNotConsecutive
+            } else {
+                return None;
+            };
+            Some((index, issue))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn types(names: &[&str]) -> Vec<ChunkType> {
+        names.iter().map(|name| ChunkType::from_str(name).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_valid_order() {
+        let types = types(&["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(check(&types), Vec::new());
+    }
+
+    #[test]
+    fn test_replaces() {
+        let [gama, srgb, iccp, text] = <[ChunkType; 4]>::try_from(types(&["gAMA", "sRGB", "iCCP", "tEXt"])).unwrap();
+        assert!(replaces(&gama, &gama));
+        assert!(replaces(&iccp, &srgb) && replaces(&srgb, &iccp));
+        assert!(!replaces(&text, &text));
+        assert!(!replaces(&gama, &srgb));
+    }
+
+    #[test]
+    fn test_order_issues() {
+        let types = types(&["tRNS", "IHDR", "PLTE", "gAMA", "IDAT", "tEXt", "IDAT", "pHYs", "IEND", "ruSt"]);
+        assert_eq!(
+            check(&types),
+            [
+                (0, OrderIssue::BeforePlte),
+                (1, OrderIssue::IhdrNotFirst),
+                (3, OrderIssue::AfterPlte),
+                (6, OrderIssue::IdatNotConsecutive),
+                (7, OrderIssue::AfterIdat),
+                (9, OrderIssue::AfterIend),
+            ]
+        );
+    }
+}
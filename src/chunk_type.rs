@@ -1,8 +1,19 @@
-use crate::{Result,Error};
+use crate::error::PngMeError;
 use std::convert::TryFrom;
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
+type Result<T> = std::result::Result<T, PngMeError>;
+
+/// Chunk types defined by the PNG spec and its registered extensions.
+pub const STANDARD_TYPES: [&str; 25] = [
+    "IHDR", "PLTE", "IDAT", "IEND",
+    "cHRM", "cICP", "gAMA", "iCCP", "mDCV", "cLLI", "sBIT", "sRGB",
+    "bKGD", "hIST", "tRNS", "eXIf", "pHYs", "sPLT", "tIME",
+    "iTXt", "tEXt", "zTXt",
+    "acTL", "fcTL", "fdAT",
+];
+
 #[derive(PartialEq,Eq, PartialOrd, Ord,Debug,Clone)]
 /// A validated PNG chunk type. See the PNG spec for more details.
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
@@ -16,26 +27,23 @@ impl ChunkType{
         self.code
     }
 
-    #[allow(dead_code)]
     /// Returns the property state of the first byte as described in the PNG spec
-    fn is_critical(&self)->bool{
+    pub fn is_critical(&self)->bool{
         (self.code[0] & 0b00100000) != 0b00100000
     }
 
-    #[allow(dead_code)]
     /// Returns the property state of the second byte as described in the PNG spec
-    fn is_public(&self)->bool{
+    pub fn is_public(&self)->bool{
         (self.code[1] & 0b00100000) != 0b00100000
     }
 
     /// Returns the property state of the third byte as described in the PNG spec
-    fn is_reserved_bit_valid(&self)->bool{
+    pub fn is_reserved_bit_valid(&self)->bool{
         (self.code[2] & 0b00100000) != 0b00100000
     }
     
-    #[allow(dead_code)]
     /// Returns the property state of the fourth byte as described in the PNG spec
-    fn is_safe_to_copy(&self)->bool{
+    pub fn is_safe_to_copy(&self)->bool{
         (self.code[3] & 0b00100000) == 0b00100000
     }
 
@@ -48,6 +56,29 @@ impl ChunkType{
         .all(|&e| ChunkType::is_valid_byte(e)) 
     }
 
+    /// Returns true if this chunk type is defined by the PNG spec or one of its registered extensions
+    pub fn is_standard(&self) -> bool {
+        STANDARD_TYPES.iter().any(|t| t.as_bytes() == self.code)
+    }
+
+    /// Returns true if this chunk type fits `pattern`, four characters standing for one byte each:
+    /// a letter matches itself, `?` any letter, `^` an uppercase and `_` a lowercase letter. As the
+    /// case of each byte is a chunk property, `_???` matches every ancillary chunk and `??Xt` the
+    /// text chunks.
+    pub fn matches(&self, pattern: &str) -> bool {
+        pattern.len() == 4 && pattern.bytes().zip(self.code).all(|(p, b)| match p {
+            b'?' => true,
+            b'^' => b.is_ascii_uppercase(),
+            b'_' => b.is_ascii_lowercase(),
+            _ => p == b,
+        })
+    }
+
+    /// Returns true if `pattern` is four letters or wildcards understood by `matches`
+    pub fn is_valid_pattern(pattern: &str) -> bool {
+        pattern.len() == 4 && pattern.bytes().all(|b| ChunkType::is_valid_byte(b) || matches!(b, b'?' | b'^' | b'_'))
+    }
+
     #[allow(dead_code)]
     /// Valid bytes are represented by the characters A-Z or a-z
     pub fn is_valid_byte(byte: u8) -> bool {
@@ -58,23 +89,23 @@ impl ChunkType{
 }
 
 impl TryFrom<[u8;4]> for ChunkType{
-    type Error = Error;
+    type Error = PngMeError;
     fn try_from(value: [u8;4]) -> Result<Self> {
         Ok(Self{ code: value })
     }
 }
 
 impl FromStr for ChunkType{
-    type Err = Error;
+    type Err = PngMeError;
     fn from_str(str: &str) -> Result<Self> {
         let str_bytes = str.as_bytes();
         if str_bytes.len() != 4{
-            return Err(Box::new(ChunkTypeError::LengthError(str_bytes.len())));
+            return Err(ChunkTypeError::LengthError(str_bytes.len()).into());
         }
         if !str_bytes
             .iter()
             .all(|&b| ChunkType::is_valid_byte(b)){
-                return Err(Box::new(ChunkTypeError::IllegalCharacter));
+                return Err(ChunkTypeError::IllegalCharacter.into());
             }
 
         let code = [str_bytes[0],str_bytes[1],str_bytes[2],str_bytes[3]];
@@ -89,23 +120,30 @@ impl Display for ChunkType{
     }
 }
 
+#[cfg(feature = "json")]
+impl serde::Serialize for ChunkType{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 
-#[derive(Debug)]
+#[cfg(feature = "json")]
+impl<'de> serde::Deserialize<'de> for ChunkType{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+
+#[derive(Debug, thiserror::Error)]
 pub enum ChunkTypeError {
+    #[error("Expected 4 bytes but found {0} ")]
     LengthError(usize),
+    #[error("Contains non alphabetic characters")]
     IllegalCharacter,
 }
 
-impl std::error::Error for ChunkTypeError {}
-impl Display for ChunkTypeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ChunkTypeError::LengthError(length) => write!(f,"Expected 4 bytes but found {length} "),
-            ChunkTypeError::IllegalCharacter => write!(f, "Contains non alphabetic characters")
-        }
-    }
-}
-
 
 #[cfg(test)]
 mod tests {
@@ -113,6 +151,19 @@ mod tests {
     use std::convert::TryFrom;
     use std::str::FromStr;
 
+    #[test]
+    pub fn test_chunk_type_matches() {
+        let text = ChunkType::from_str("tEXt").unwrap();
+        assert!(text.matches("tEXt"));
+        assert!(text.matches("??Xt"));
+        assert!(text.matches("_^^_"));
+        assert!(!text.matches("^???"));
+        assert!(!text.matches("tEX"));
+        assert!(ChunkType::is_valid_pattern("_??t"));
+        assert!(!ChunkType::is_valid_pattern("t*"));
+        assert!(!ChunkType::is_valid_pattern("tEX1"));
+    }
+
     #[test]
     pub fn test_chunk_type_from_bytes() {
         let expected = [82, 117, 83, 116];
@@ -191,6 +242,13 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("IHDR").unwrap().is_standard());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_standard());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -1,49 +1,1935 @@
-use std::convert::TryFrom;
-use std::fs;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+#[cfg(feature = "json")]
+use std::sync::Mutex;
 
-use crate::{Result};
+use bytes::Bytes;
+use clap::CommandFactory;
+
+use crate::error::PngMeError;
+use crate::Error;
 use crate::args::*;
-use crate::chunk::Chunk;
-use crate::png::Png;
+use crate::capabilities::Capabilities;
+use crate::chunk::{Chunk, PayloadKind};
+use crate::chunk_type::ChunkType;
+use crate::chunk_order;
+use crate::corpus::CorpusStats;
+use crate::diff::{ChunkChange, DiffOp, PngDiff};
+use crate::display::hexdump;
+use crate::i18n::{describe_error, format_date, format_decimal, format_number, tr};
+use crate::apng::FrameTracker;
+use crate::graph::Category;
+use crate::repair::RepairOptions;
+use crate::known_chunks::KnownChunk;
+#[cfg(feature = "json")]
+use crate::index::ScanIndex;
+use crate::keyword::{Keyword, TEXT_CHUNK_TYPES};
+use crate::locate::{locate, ChunkPart, Location};
+use crate::lock::Lock;
+#[cfg(feature = "crypto")]
+use crate::manifest::{EntryStatus, Manifest};
+use crate::output::{self, Color};
+#[cfg(feature = "crypto")]
+use crate::passphrase::Strength;
+use crate::patch::Patch;
+use crate::decode::{CredentialProvider, DecodeOptions, DecodedFile, Environment, KeyFile, Prompt};
+use crate::events::{Event, EventSink};
+use crate::encode::{encode_into, EncodeOptions, ReplacePolicy};
+use crate::encoding::Encoding;
+use crate::payload::{Envelope, Part, Payload};
+use crate::png::{replace_file, ChunkReader, Png, WriteOptions};
+use crate::progress::{Progress, ProgressReader, Unit, LARGE_FILE};
+#[cfg(feature = "crypto")]
+use crate::provenance::{self, Claim};
+use crate::scan::ChunkRecord;
+use crate::stego::{Method, StegoError};
+use crate::inflate::InflateError;
+use crate::text_chunk::{TextChunk, TextChunkError, TextKind};
+use crate::usage::BytesRead;
+#[cfg(feature = "crypto")]
+use crate::seal;
+#[cfg(feature = "crypto")]
+use crate::sign;
+
+type Result<T> = std::result::Result<T, PngMeError>;
+
+/// Reads the file at `path`, or stdin for `-`, counting its bytes in `read`.
+fn read_input(path: &Path, read: &BytesRead) -> Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut input = Vec::new();
+        io::stdin().lock().read_to_end(&mut input)?;
+        read.add(input.len() as u64);
+        return Ok(input);
+    }
+    let file = File::open(path)?;
+    let length = file.metadata()?.len();
+    let mut input = Vec::with_capacity(length as usize);
+    match length >= LARGE_FILE {
+        true => ProgressReader::new(file, Progress::new(path.display(), Unit::Bytes, length)).read_to_end(&mut input)?,
+        false => (&file).read_to_end(&mut input)?,
+    };
+    read.add(input.len() as u64);
+    Ok(input)
+}
+
+/// Opens the file at `path` for reading, or stdin for `-`, counting its bytes in `read`.
+fn open_input(path: &Path, read: &BytesRead) -> Result<Box<dyn Read>> {
+    if is_stdio(path) {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    let file = File::open(path)?;
+    let length = file.metadata()?.len();
+    read.add(length);
+    if length >= LARGE_FILE {
+        return Ok(Box::new(ProgressReader::new(file, Progress::new(path.display(), Unit::Bytes, length))));
+    }
+    Ok(Box::new(file))
+}
+
+/// Writes `bytes` to stdout for `-`, or else replaces the file at `path` with them, giving a
+/// new file the permissions `mode`.
+fn write_output(path: &Path, mode: Option<u32>, bytes: &[u8]) -> Result<()> {
+    if is_stdio(path) {
+        output::reserve_stdout();
+        io::stdout().lock().write_all(bytes)?;
+        return Ok(());
+    }
+    replace_file(path, &WriteOptions { mode, ..WriteOptions::default() }, |writer| writer.write_all(bytes))?;
+    Ok(())
+}
+
+/// Reads and parses the PNG file at `path`.
+fn read_png(path: &Path, parse: &ParseArgs, read: &BytesRead) -> Result<Png> {
+    let input = read_input(path, read)?;
+    Png::parse(Bytes::from(input), &parse.options())
+}
+
+/// Opens the PNG file at `path` for reading one chunk at a time.
+fn read_chunks(path: &Path, parse: &ParseArgs, read: &BytesRead) -> Result<ChunkReader<BufReader<Box<dyn Read>>>> {
+    ChunkReader::new(BufReader::new(open_input(path, read)?), parse.options())
+}
+
+/// Serializes `png` according to `write` and stores it at `path`, refusing to replace a locked
+/// file unless `write` unlocks it.
+fn write_png(path: &Path, png: &Png, write: &WriteArgs) -> Result<()> {
+    if !write.unlock && !is_stdio(path) && path.exists() {
+        crate::lock::check_unlocked(path, &fs::read(path)?)?;
+    }
+    if let Some(suffix) = write.backup.as_deref().filter(|_| !is_stdio(path) && path.exists()) {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        fs::copy(path, backup)?;
+    }
+    if write.undo && !is_stdio(path) && path.exists() {
+        let bytes = png.to_bytes(&write.options());
+        crate::undo::record(path, &fs::read(path)?, &bytes)?;
+        replace_file(path, &write.options(), |writer| writer.write_all(&bytes))?;
+        return Ok(());
+    }
+    if is_stdio(path) {
+        return write_output(path, None, &png.to_bytes(&write.options()));
+    }
+    png.save(path, &write.options())
+}
+
+pub fn encode(args: EncodeArgs, read: &BytesRead) -> Result<()> {
+    let output = args.out.clone().or_else(|| args.output_file_path.clone());
+    let payload = match (&args.from_chunk, &args.file, args.message.clone()) {
+        (Some(source), _, _) => {
+            let source_png = read_png(&source.path, &args.parse, read)?;
+            let position = source.chunk.position(source_png.chunks()).ok_or_else(|| {
+                CommandError::ChunkNotFound { path: source.path.clone(), chunk_type: source.chunk.chunk_type.clone() }
+            })?;
+            Payload::new(source_png.chunks()[position].data().to_vec())
+        }
+        (None, Some(file), _) => Payload::new(read_input(file, read)?),
+        (None, None, Some(message)) if args.text_keyword.is_some() => Payload::new(text_chunk_payload(&args, &message)?),
+        (None, None, Some(message)) if args.input_encoding != Encoding::Utf8 => {
+            Payload::new(args.input_encoding.decode(&message).map_err(Error::from)?)
+        }
+        (None, None, message) => {
+            let message = message.unwrap_or_default();
+            let payload = text_payload(&args.chunk_type, &message)?;
+            Payload::new(payload.unwrap_or_else(|| message.into_bytes()))
+        }
+    };
+    match payload.kind() {
+        PayloadKind::Empty => output::status(tr("encode-note-empty", &[])),
+        PayloadKind::Whitespace => output::status(tr("encode-note-whitespace", &[])),
+        PayloadKind::Text | PayloadKind::Binary => {}
+    }
+    #[cfg(not(feature = "crypto"))]
+    if args.password.is_some() || args.password_file.is_some() || args.password_stdin || args.generate_pass {
+        return Err(crate::features::Feature::Crypto.missing("--password").into());
+    }
+    #[cfg(not(feature = "crypto"))]
+    if args.sign_key.is_some() {
+        return Err(crate::features::Feature::Crypto.missing("--sign-key").into());
+    }
+    let mut options = EncodeOptions::new(args.chunk_type.clone());
+    #[cfg(feature = "crypto")]
+    if let Some(password) = encode_password(&args)? {
+        options = options.password(&password);
+    }
+    #[cfg(feature = "crypto")]
+    if let Some(path) = &args.sign_key {
+        options = options.sign_key(sign::load_key(path)?);
+    }
+    if args.replace {
+        options = options.replace(ReplacePolicy::Replace);
+    }
+    options = options.part_size(args.split_size).method(args.method).position(args.position);
+
+    if crate::scan::is_batch_path(&args.input_file_path) {
+        let files = crate::scan::expand_path(&args.input_file_path, args.recursive)?;
+        let Some(directory) = output else {
+            if !args.dry_run {
+                confirm_batch(&tr("encode-plan", &[("chunk_type", &args.chunk_type), ("count", &files.len())]), &files)?;
+            }
+            return run_batch(&files, &args.batch, Some("encode"), |file| encode_file(&args, payload.clone(), &options, file, file, read));
+        };
+        if !directory.is_dir() {
+            return Err(CommandError::OutputForBatch.into());
+        }
+        let root = crate::batch_output::batch_root(&args.input_file_path);
+        let targets = crate::batch_output::plan(&files, &root, &directory, args.rename);
+        let targets: HashMap<&Path, &PathBuf> = files.iter().map(PathBuf::as_path).zip(&targets).collect();
+        return run_batch(&files, &args.batch, Some("encode"), |file| {
+            let target = targets[file];
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            encode_file(&args, payload.clone(), &options, file, target, read)
+        });
+    }
+    let output = output.unwrap_or_else(|| args.input_file_path.clone());
+    if is_stdio(&output) {
+        output::reserve_stdout();
+    }
+    encode_file(&args, payload, &options, &args.input_file_path, &output, read)
+}
+
+/// The password to encrypt with: a generated one, shown once on stderr so it stays out of
+/// piped output, or the one given by an argument, stdin or the environment, with a warning
+/// when it is easy to guess.
+#[cfg(feature = "crypto")]
+fn encode_password(args: &EncodeArgs) -> Result<Option<String>> {
+    if args.generate_pass {
+        let passphrase = crate::passphrase::generate()?;
+        eprintln!("{}", tr("encode-generated-pass", &[("passphrase", &passphrase)]));
+        return Ok(Some(passphrase));
+    }
+    let password = match (&args.password, &args.password_file) {
+        (Some(password), _) => password.clone(),
+        (None, Some(path)) => KeyFile(path.clone()).password()?,
+        _ if args.password_stdin => read_password(&[&args.input_file_path], true)?,
+        _ => match Environment::source() {
+            Some(variable) => {
+                output::status(tr("encode-env-password", &[("variable", &variable)]));
+                Environment.password()?
+            }
+            None => return Ok(None),
+        },
+    };
+    let bits = crate::passphrase::estimate_bits(&password);
+    if Strength::of(&password) == Strength::Weak {
+        output::status(tr("encode-weak-password", &[("bits", &(bits as u32))]));
+    }
+    Ok(Some(password))
+}
+
+/// Reads the password for `--password-stdin`, prompting without echo when stdin is a terminal
+/// and asking a second time to catch typos if `confirm` is set. Fails if any of the PNG Files
+/// at `inputs` is read from stdin too.
+fn read_password(inputs: &[&Path], confirm: bool) -> Result<String> {
+    if inputs.iter().any(|input| is_stdio(input)) {
+        return Err(CommandError::PasswordStdinConflict.into());
+    }
+    let terminal = io::stdin().is_terminal();
+    let prompt = |id| Prompt { message: if terminal { format!("{} ", tr(id, &[])) } else { String::new() } };
+    let password = prompt("password-prompt").password()?;
+    if confirm && terminal && prompt("password-confirm").password()? != password {
+        return Err(CommandError::PasswordMismatch.into());
+    }
+    Ok(password)
+}
+
+/// Hides `payload` in the file at `input` and writes the result to `output`.
+fn encode_file(args: &EncodeArgs, payload: Payload, options: &EncodeOptions, input: &Path, output: &Path, read: &BytesRead) -> Result<()> {
+    let mut png = read_png(input, &args.parse, read)?;
+    let original_size = png.byte_len();
+    let original_chunks = args.dry_run.then(|| png.chunks().to_vec());
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    let stored = encode_into(&mut png, payload, options)?;
+
+    let new_size = png.byte_len();
+    let growth = (new_size as f64 - original_size as f64) / original_size as f64 * 100.0;
+    let delta = new_size as i64 - original_size as i64;
+    let sign = |negative: bool| if negative { "" } else { "+" };
+    let size_args: [(&str, &dyn std::fmt::Display); 4] = [
+        ("before", &format_number(original_size as i64)),
+        ("after", &format_number(new_size as i64)),
+        ("delta", &format!("{}{}", sign(delta < 0), format_number(delta))),
+        ("percent", &format!("{}{}", sign(growth < 0.0), format_decimal(growth, 2))),
+    ];
+    output::status(tr("encode-size", &size_args));
+    if let Some(limit) = args.max_growth {
+        if growth > limit {
+            return Err(CommandError::GrowthExceeded { growth, limit }.into());
+        }
+    }
+
+    if let Some(original_chunks) = original_chunks {
+        for op in crate::diff::align(&original_chunks, png.chunks()) {
+            let (id, index, chunk) = match op {
+                DiffOp::Added(index) => ("dry-run-add", index, &png.chunks()[index]),
+                DiffOp::Removed(index) => ("dry-run-remove", index, &original_chunks[index]),
+                DiffOp::Same(..) => continue,
+            };
+            let args: [(&str, &dyn std::fmt::Display); 3] =
+                [("chunk_type", chunk.chunk_type()), ("index", &index), ("length", &format_number(chunk.length() as i64))];
+            output::status(tr(id, &args));
+        }
+        output::status(tr("dry-run-done", &[("path", &output.display())]));
+        return Ok(());
+    }
+    #[cfg(not(feature = "crypto"))]
+    if args.manifest.is_some() {
+        return Err(crate::features::Feature::Crypto.missing("--manifest").into());
+    }
+    write_png(output, &png, &args.write)?;
+    #[cfg(feature = "crypto")]
+    if let Some(manifest_path) = &args.manifest {
+        let mut manifest = Manifest::load(manifest_path)?;
+        manifest.record(output, &args.chunk_type, stored.as_bytes());
+        manifest.save(manifest_path)?;
+    }
+    match args.method {
+        Method::Trailer => output::status(tr("encode-done-trailer", &[])),
+        _ => output::status(tr("encode-done", &[])),
+    }
+    Ok(())
+}
+
+/// For textual chunk types, validates the keyword before the first null byte of `message`
+/// and returns the payload with the keyword stored as Latin-1.
+fn text_payload(chunk_type: &ChunkType, message: &str) -> Result<Option<Vec<u8>>> {
+    if !TEXT_CHUNK_TYPES.contains(&chunk_type.to_string().as_str()) {
+        return Ok(None);
+    }
+    let (keyword, rest) = message.split_once('\0').map_or((message, None), |(k, r)| (k, Some(r)));
+    let mut payload = Keyword::from_str(keyword)?.as_bytes().to_vec();
+    if let Some(rest) = rest {
+        payload.push(0);
+        payload.extend_from_slice(rest.as_bytes());
+    }
+    Ok(Some(payload))
+}
+
+/// Builds the data of a text chunk holding `message` under `--text-keyword`, in the format of the
+/// chunk type given to `encode`.
+fn text_chunk_payload(args: &EncodeArgs, message: &str) -> Result<Vec<u8>> {
+    let keyword = Keyword::from_str(args.text_keyword.as_deref().unwrap_or_default())?;
+    let kind = match &args.chunk_type.bytes() {
+        b"iTXt" => TextKind::International {
+            compressed: args.compress,
+            language: args.language.clone().unwrap_or_default(),
+            translated_keyword: args.translated_keyword.clone().unwrap_or_default(),
+        },
+        _ if args.language.is_some() || args.translated_keyword.is_some() || args.compress => {
+            return Err(CommandError::NotInternational(args.chunk_type.clone()).into());
+        }
+        b"tEXt" => TextKind::Plain,
+        b"zTXt" => TextKind::Compressed,
+        _ => return Err(Error::from(TextChunkError::NotTextChunk(args.chunk_type.clone())).into()),
+    };
+    Ok(TextChunk::new(keyword, message).kind(kind).to_data()?)
+}
+
+pub fn decode(mut args: DecodeArgs, read: &BytesRead) -> Result<()> {
+    // Read once, so a batch or --all doesn't ask for it again for every payload.
+    if args.password_stdin {
+        args.password = Some(read_password(&[&args.file_path], false)?);
+    }
+    if args.out.as_deref().is_some_and(is_stdio) {
+        output::reserve_stdout();
+    }
+    if crate::scan::is_batch_path(&args.file_path) || args.format == OutputFormat::Json {
+        return decode_report(&args, read);
+    }
+    let selector = args.chunk.clone().with_index(args.index)?;
+    let path = args.file_path.clone();
+    let not_found = || match selector.index {
+        Some(index) if index > 0 && !args.all => CommandError::ChunkIndexNotFound { path, chunk: selector.clone() },
+        _ => CommandError::ChunkNotFound { path, chunk_type: args.chunk.chunk_type.clone() },
+    };
+    if args.method == Method::Lsb {
+        // The pixels hold at most one payload, so --all and indexes have nothing to choose from.
+        let png = read_png(&args.file_path, &args.parse, read)?;
+        if args.chunk.chunk_type.to_string() != "IDAT" {
+            return Err(Error::from(StegoError::NotIdat(args.chunk.chunk_type.clone())).into());
+        }
+        let Some(data) = crate::stego::extract(&png)? else {
+            return Err(CommandError::NothingHidden(args.file_path.clone()).into());
+        };
+        return show_payload(&args, Envelope::from_raw(data.into()));
+    }
+    let mut chunks = read_chunks(&args.file_path, &args.parse, read)?;
+    if args.method == Method::Trailer {
+        // Skip the chunks to reach the trailer.
+        for chunk in &mut chunks {
+            chunk?;
+        }
+        return match show_appended(&args, &selector, chunks.trailer())? {
+            true => Ok(()),
+            false => Err(not_found().into()),
+        };
+    }
+    if args.all {
+        let mut found = 0;
+        for chunk in &mut chunks {
+            let chunk = chunk?;
+            if chunk.chunk_type() != &args.chunk.chunk_type {
+                continue;
+            }
+            println!("{}@{found}:", chunk.chunk_type());
+            output::status(tr("decode-chunk", &[("chunk", &output::chunk(&chunk))]));
+            show_payload(&args, Envelope::from_raw(chunk.raw_data()))?;
+            found += 1;
+        }
+        for data in crate::appended::find_all(chunks.trailer(), &args.chunk.chunk_type) {
+            println!("{}@{found}:", args.chunk.chunk_type);
+            output::status(tr("decode-trailer", &[("chunk_type", &args.chunk.chunk_type)]));
+            show_payload(&args, Envelope::from_raw(data))?;
+            found += 1;
+        }
+        if found == 0 {
+            return Err(not_found().into());
+        }
+        return Ok(());
+    }
+    // Stop at the selected chunk, or at the first error.
+    let mut selected = selector.matcher();
+    let mut seen = false;
+    let found = chunks.find(|chunk| {
+        chunk.as_ref().map_or(true, |c| {
+            seen |= c.chunk_type() == &args.chunk.chunk_type;
+            selected(c)
+        })
+    });
+    let Some(c) = found.transpose()? else {
+        // Without a chunk of the type, the message may have been appended after IEND.
+        if !seen && show_appended(&args, &selector, chunks.trailer())? {
+            return Ok(());
+        }
+        return Err(not_found().into());
+    };
+    output::status(tr("decode-chunk", &[("chunk", &output::chunk(&c))]));
+    let mut envelope = Envelope::from_raw(c.raw_data());
+    if Part::parse(&c.raw_data()).is_some() {
+        let rest = chunks
+            .filter(|chunk| chunk.as_ref().map_or(true, |c| c.chunk_type() == &args.chunk.chunk_type))
+            .map(|chunk| chunk.map(|c| c.raw_data()))
+            .collect::<Result<Vec<_>>>()?;
+        envelope = Envelope::join(std::iter::once(c.raw_data()).chain(rest))?;
+    }
+    show_payload(&args, envelope)
+}
+
+/// Decodes every file `decode` was given into one report, carrying on past unreadable files.
+/// Fails at the end if any file couldn't be read or its payload opened.
+fn decode_report(args: &DecodeArgs, read: &BytesRead) -> Result<()> {
+    if args.out.is_some() || args.all || args.index.is_some() || args.chunk.index.is_some() {
+        return Err(CommandError::SingleFileOptions.into());
+    }
+    #[cfg(not(feature = "json"))]
+    if args.format == OutputFormat::Json {
+        return Err(crate::features::Feature::Json.missing("--format json").into());
+    }
+    let files = crate::scan::expand_path(&args.file_path, args.recursive)?;
+    // Fail on a bad --verify-key before any file. Options are built per job, since credential
+    // providers can't be shared between threads.
+    decode_options(args)?;
+    let decode_one = |file: &PathBuf| match read_png(file, &args.parse, read).and_then(|png| Ok((png, decode_options(args)?))) {
+        Ok((png, options)) => crate::decode::decode_file(file, &png, &options),
+        Err(e) => DecodedFile::failed(file, describe_error(&e)),
+    };
+    let mut report = Vec::with_capacity(files.len());
+    let mut progress = Progress::new("decode", Unit::Files, files.len() as u64);
+    crate::jobs::run(&files, args.batch.jobs, decode_one, |_, decoded| {
+        progress.advance(1);
+        if args.format == OutputFormat::Text {
+            progress.println(&decoded);
+        }
+        report.push(decoded);
+        Ok(())
+    })?;
+    drop(progress);
+    #[cfg(feature = "json")]
+    if args.format == OutputFormat::Json {
+        report.sort_by(|a, b| a.file.cmp(&b.file));
+        println!("{}", serde_json::to_string_pretty(&report).map_err(Error::from)?);
+    }
+    let failed = report.iter().filter(|decoded| decoded.error.is_some()).count();
+    if failed > 0 {
+        return Err(CommandError::BatchFailed(failed).into());
+    }
+    Ok(())
+}
+
+/// The options reading the chunk type and password given to `decode`, and checking the
+/// signature against `--verify-key`.
+fn decode_options(args: &DecodeArgs) -> Result<DecodeOptions> {
+    #[cfg_attr(not(feature = "crypto"), allow(unused_mut))]
+    let mut options = DecodeOptions::new(args.chunk.chunk_type.clone()).method(args.method);
+    #[cfg(not(feature = "crypto"))]
+    if args.verify_key.is_some() {
+        return Err(crate::features::Feature::Crypto.missing("--verify-key").into());
+    }
+    #[cfg(feature = "crypto")]
+    if let Some(path) = &args.verify_key {
+        options = options.verify_key(sign::load_key(path)?);
+    }
+    Ok(with_password(options, &args.password, &args.password_file))
+}
+
+/// Adds the password given by `password`, `password_file` or the environment to `options`.
+fn with_password(options: DecodeOptions, password: &Option<String>, password_file: &Option<PathBuf>) -> DecodeOptions {
+    if let Some(password) = password {
+        options.password(password)
+    } else if let Some(path) = password_file {
+        options.credentials(KeyFile(path.clone()))
+    } else if Environment::is_set() {
+        options.credentials(Environment)
+    } else {
+        options
+    }
+}
+
+/// Shows the payload `selector` picks among those appended after IEND in `trailer`, returning
+/// whether there was one.
+fn show_appended(args: &DecodeArgs, selector: &ChunkSelector, trailer: &[u8]) -> Result<bool> {
+    let records = crate::appended::find_all(trailer, &selector.chunk_type);
+    let Some(data) = records.into_iter().nth(selector.index.unwrap_or(0)) else {
+        return Ok(false);
+    };
+    output::status(tr("decode-trailer", &[("chunk_type", &selector.chunk_type)]));
+    show_payload(args, Envelope::from_raw(data))?;
+    Ok(true)
+}
+
+/// Opens `envelope` with the password given to `decode` and prints the payload or writes it to `--out`.
+fn show_payload(args: &DecodeArgs, envelope: Envelope) -> Result<()> {
+    let options = decode_options(args)?;
+    let signed = envelope.is_signed();
+    let envelope = options.verify(envelope)?;
+    match (signed, args.verify_key.is_some()) {
+        (true, true) => output::status(tr("decode-signature-valid", &[])),
+        (true, false) => output::status(tr("decode-signed", &[])),
+        (false, _) => {}
+    }
+    #[cfg(feature = "crypto")]
+    if args.password.is_none() && args.password_file.is_none() && !Environment::is_set() && envelope.is_encrypted() {
+        output::status(tr("decode-encrypted", &[]));
+        return Ok(());
+    }
+    let payload = options.decrypt(envelope)?;
+    if args.hex {
+        print!("{}", hexdump(payload.as_bytes()));
+        return Ok(());
+    }
+    if args.output_encoding != Encoding::Utf8 {
+        let encoded = args.output_encoding.encode(payload.as_bytes());
+        if let Some(out) = &args.out {
+            write_output(out, args.mode, encoded.as_bytes())?;
+            output::status(tr("decode-written", &[("length", &encoded.len()), ("path", &out.display())]));
+        } else {
+            println!("{}", tr("decode-data", &[("data", &encoded)]));
+        }
+        return Ok(());
+    }
+    if let Some(out) = &args.out {
+        write_output(out, args.mode, payload.as_bytes())?;
+        output::status(tr("decode-written", &[("length", &payload.as_bytes().len()), ("path", &out.display())]));
+        return Ok(());
+    }
+    if TEXT_CHUNK_TYPES.contains(&args.chunk.chunk_type.to_string().as_str()) {
+        // Text chunks that don't follow the spec are shown like any other payload, but not ones
+        // that inflate past the limit.
+        match TextChunk::parse_limited(&args.chunk.chunk_type, payload.as_bytes(), args.parse.max_inflate_ratio) {
+            Ok(text) => {
+                println!("{}", tr("decode-text", &[("text", &text)]));
+                return Ok(());
+            }
+            Err(e) if e.is::<InflateError>() => return Err(e.into()),
+            Err(_) => {}
+        }
+    }
+    let text = || std::str::from_utf8(payload.as_bytes()).map_err(Error::from);
+    match payload.kind() {
+        PayloadKind::Empty => println!("{}", tr("decode-empty", &[])),
+        PayloadKind::Whitespace => {
+            let data = format!("{:?}", text()?);
+            println!("{}", tr("decode-whitespace", &[("data", &data)]))
+        }
+        PayloadKind::Text => println!("{}", tr("decode-data", &[("data", &text()?)])),
+        PayloadKind::Binary => println!("{}", tr("decode-binary", &[])),
+    }
+    Ok(())
+}
+
+/// Bytes of binary data shown by `preview`.
+const PREVIEW_LENGTH: usize = 32;
+
+/// `data` as text if it is UTF-8, otherwise its first bytes in hex.
+fn preview(data: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(data) {
+        return tr("decode-data", &[("data", &text)]);
+    }
+    let hex: Vec<String> = data.iter().take(PREVIEW_LENGTH).map(|byte| format!("{byte:02x}")).collect();
+    let more = if data.len() > PREVIEW_LENGTH { " ..." } else { "" };
+    tr("decode-data", &[("data", &format!("{}{more}", hex.join(" ")))])
+}
+
+/// The error for a `selector` that picks no chunk in the file at `path`.
+fn missing_chunk(path: &Path, selector: &ChunkSelector) -> CommandError {
+    let path = path.to_path_buf();
+    match selector.index {
+        Some(index) if index > 0 => CommandError::ChunkIndexNotFound { path, chunk: selector.clone() },
+        _ => CommandError::ChunkNotFound { path, chunk_type: selector.chunk_type.clone() },
+    }
+}
+
+pub fn remove(args: RemoveArgs, read: &BytesRead) -> Result<()> {
+    let selector = args.chunk.clone().with_index(args.index)?;
+    let remove_from = |file: &Path| {
+        let mut png = read_png(file, &args.parse, read)?;
+        let removed = if args.all {
+            if !png.chunks().iter().any(|chunk| *chunk.chunk_type() == selector.chunk_type) {
+                return Err(CommandError::ChunkNotFound { path: file.to_path_buf(), chunk_type: selector.chunk_type.clone() }.into());
+            }
+            png.remove_all_chunks(&selector.chunk_type.to_string())?
+        } else {
+            let position = selector.position(png.chunks()).ok_or_else(|| missing_chunk(file, &selector))?;
+            vec![png.remove_chunk_at(position)?]
+        };
+        if args.dry_run {
+            for chunk in &removed {
+                output::status(tr("remove-dry-run", &[("chunk", &output::chunk(chunk))]));
+                output::status(preview(chunk.data()));
+            }
+            output::status(tr("dry-run-size", &[("after", &format_number(png.byte_len() as i64))]));
+            output::status(tr("dry-run-done", &[("path", &file.display())]));
+            return Ok(());
+        }
+        write_png(file, &png, &args.write)?;
+        for chunk in &removed {
+            output::status(tr("remove-done", &[("chunk", &output::chunk(chunk))]));
+        }
+        Ok(())
+    };
+    if crate::scan::is_batch_path(&args.file_path) {
+        let files = crate::scan::expand_path(&args.file_path, args.recursive)?;
+        let plan = match args.all {
+            true => tr("remove-plan-all", &[("chunk_type", &selector.chunk_type), ("count", &files.len())]),
+            false => tr("remove-plan", &[("chunk", &selector), ("count", &files.len())]),
+        };
+        if !args.dry_run {
+            confirm_batch(&plan, &files)?;
+        }
+        return run_batch(&files, &args.batch, Some("remove"), remove_from);
+    }
+    remove_from(&args.file_path)
+}
+
+pub fn extract(args: ExtractArgs, read: &BytesRead) -> Result<()> {
+    let output = args.output.unwrap_or_else(|| PathBuf::from("-"));
+    if is_stdio(&output) {
+        output::reserve_stdout();
+    }
+    let png = read_png(&args.file_path, &args.parse, read)?;
+    let Some(selector) = args.chunk else {
+        let trailer = png.trailer();
+        if trailer.is_empty() {
+            return Err(CommandError::NoTrailer(args.file_path).into());
+        }
+        write_output(&output, None, trailer)?;
+        if !is_stdio(&output) {
+            output::status(tr("extract-trailer-written", &[("length", &trailer.len()), ("path", &output.display())]));
+        }
+        return Ok(());
+    };
+    let selector = selector.with_index(args.index)?;
+    let position = selector.position(png.chunks()).ok_or_else(|| missing_chunk(&args.file_path, &selector))?;
+    let data = png.chunks()[position].data();
+    write_output(&output, None, data)?;
+    if !is_stdio(&output) {
+        output::status(tr("extract-written", &[("length", &data.len()), ("chunk", &selector), ("path", &output.display())]));
+    }
+    Ok(())
+}
+
+pub fn scrub(args: ScrubArgs, read: &BytesRead) -> Result<()> {
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    let removed = png.retain_chunks(|chunk| {
+        let chunk_type = chunk.chunk_type();
+        (chunk_type.is_critical() && chunk_type.is_public()) || args.keep.iter().any(|pattern| chunk_type.matches(pattern))
+    })?;
+    let trailer = png.strip_trailer()?;
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    for chunk in &removed {
+        output::status(tr("scrub-removed", &[("chunk_type", chunk.chunk_type()), ("length", &chunk.length())]));
+    }
+    if !trailer.is_empty() {
+        output::status(tr("scrub-trailer", &[("length", &trailer.len())]));
+    }
+    output::status(tr("scrub-done", &[("count", &removed.len())]));
+    Ok(())
+}
+
+pub fn copy_chunks(args: CopyChunksArgs, read: &BytesRead) -> Result<()> {
+    if let Some(critical) = args.types.iter().find(|chunk_type| chunk_type.is_critical()) {
+        return Err(CommandError::CriticalChunk(critical.clone()).into());
+    }
+    let source = read_png(&args.source_file_path, &args.parse, read)?;
+    let mut copied: Vec<&Chunk> = Vec::new();
+    for chunk in source.chunks().iter().filter(|chunk| args.types.contains(chunk.chunk_type())) {
+        // Of chunks the destination may hold only one of, the first in the source wins.
+        if !copied.iter().any(|earlier| chunk_order::replaces(chunk.chunk_type(), earlier.chunk_type())) {
+            copied.push(chunk);
+        }
+    }
+    if copied.is_empty() {
+        let path = args.source_file_path;
+        return Err(match <[ChunkType; 1]>::try_from(args.types) {
+            Ok([chunk_type]) => CommandError::ChunkNotFound { path, chunk_type },
+            Err(types) => CommandError::ChunkTypesNotFound { path, types: types.iter().map(ChunkType::to_string).collect::<Vec<_>>().join(", ") },
+        }
+        .into());
+    }
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    let mut chunks = png.retain_chunks(|_| false)?;
+    let count = chunks.len();
+    chunks.retain(|chunk| {
+        let replaced = args.replace && args.types.contains(chunk.chunk_type());
+        !replaced && !copied.iter().any(|copy| chunk_order::replaces(copy.chunk_type(), chunk.chunk_type()))
+    });
+    let replaced = count - chunks.len();
+    let mut placed = Vec::with_capacity(copied.len());
+    for chunk in copied {
+        let index = crate::layout::insert(&mut chunks, chunk.clone());
+        // Chunks copied before move down when one lands in front of them.
+        for (_, earlier) in placed.iter_mut().filter(|(_, earlier)| *earlier >= index) {
+            *earlier += 1;
+        }
+        placed.push((chunk, index));
+    }
+    for chunk in chunks {
+        png.append_chunk(chunk)?;
+    }
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    for (chunk, index) in &placed {
+        output::status(tr("copy-chunks-copied", &[("chunk_type", chunk.chunk_type()), ("length", &chunk.length()), ("index", index)]));
+    }
+    if replaced > 0 {
+        output::status(tr("copy-chunks-replaced", &[("count", &replaced)]));
+    }
+    output::status(tr("copy-chunks-done", &[("count", &placed.len())]));
+    Ok(())
+}
+
+pub fn normalize(args: NormalizeArgs, read: &BytesRead) -> Result<()> {
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    let chunks = png.retain_chunks(|_| false)?;
+    let before: Vec<ChunkType> = chunks.iter().map(|chunk| chunk.chunk_type().clone()).collect();
+    let mut chunks = crate::layout::normalize(chunks);
+    let moved = chunks.iter().zip(&before).filter(|(chunk, chunk_type)| chunk.chunk_type() != *chunk_type).count();
+    if args.merge_idat {
+        let count = chunks.len();
+        chunks = crate::layout::merge_idat(chunks);
+        output::status(tr("normalize-merged", &[("count", &(count - chunks.len()))]));
+    }
+    for chunk in chunks {
+        png.append_chunk(chunk)?;
+    }
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    output::status(tr("normalize-done", &[("count", &moved)]));
+    Ok(())
+}
+
+pub fn rechunk(args: RechunkArgs, read: &BytesRead) -> Result<()> {
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    let before = png.chunks_by_type("IDAT").len();
+    let chunks = png.retain_chunks(|_| false)?;
+    for chunk in crate::layout::split_idat(chunks, args.idat_size as usize) {
+        png.append_chunk(chunk)?;
+    }
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    output::status(tr("rechunk-done", &[("before", &before), ("after", &png.chunks_by_type("IDAT").len())]));
+    Ok(())
+}
+
+pub fn repair(args: RepairArgs, read: &BytesRead) -> Result<()> {
+    let options = RepairOptions { add_end: args.add_end, keep_trailer: args.keep_trailer };
+    let (png, fixes) = crate::repair::repair(&read_input(&args.file_path, read)?, &options).map_err(PngMeError::from)?;
+    for (offset, fix) in &fixes {
+        output::status(tr("repair-fixed", &[("offset", offset), ("fix", fix)]));
+    }
+    if fixes.is_empty() {
+        output::status(tr("repair-nothing", &[]));
+        // Leave an intact file alone, but still write the copy asked for.
+        if args.output_file_path.is_none() {
+            return Ok(());
+        }
+    }
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    if !fixes.is_empty() {
+        output::status(tr("repair-done", &[("count", &fixes.len())]));
+    }
+    Ok(())
+}
+
+pub fn lock(args: LockArgs, read: &BytesRead) -> Result<()> {
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    let mut write = args.write;
+    if args.remove {
+        let Some(lock) = crate::lock::unlock(&mut png)? else {
+            output::status(tr("lock-none", &[]));
+            return Ok(());
+        };
+        write.unlock = true;
+        write_png(&output, &png, &write)?;
+        output::status(tr("lock-removed", &[("by", &lock.by), ("date", &format_date(lock.at))]));
+        return Ok(());
+    }
+    let by = args.by.or_else(|| env::var("USER").or_else(|_| env::var("USERNAME")).ok()).unwrap_or_else(|| "unknown".to_string());
+    let lock = Lock::new(&by);
+    crate::lock::lock(&mut png, &lock)?;
+    write_png(&output, &png, &write)?;
+    output::status(tr("lock-done", &[("by", &lock.by), ("date", &format_date(lock.at))]));
+    Ok(())
+}
+
+pub fn print(args: PrintArgs, read: &BytesRead) -> Result<()> {
+    if crate::scan::is_batch_path(&args.file_path) {
+        let files = crate::scan::expand_path(&args.file_path, args.recursive)?;
+        return run_batch(&files, &args.batch, None, |file| {
+            // Print each file at once, so files printed by parallel jobs don't interleave.
+            let mut listing = format!("{}:\n", file.display());
+            if args.graph {
+                listing.push_str(&graph(&read_png(file, &args.parse, read)?));
+            } else if args.tree {
+                listing.push_str(&tree(&read_png(file, &args.parse, read)?));
+            } else {
+                print_chunks(file, &args.parse, read, args.hex, |shown| listing.push_str(&shown))?;
+            }
+            print!("{listing}");
+            Ok(())
+        });
+    }
+    if args.graph {
+        print!("{}", graph(&read_png(&args.file_path, &args.parse, read)?));
+        return Ok(());
+    }
+    if args.tree {
+        print!("{}", tree(&read_png(&args.file_path, &args.parse, read)?));
+        return Ok(());
+    }
+    print_chunks(&args.file_path, &args.parse, read, args.hex, |shown| print!("{shown}"))
+}
+
+/// Passes each chunk of the file at `path` to `show` as `print_chunk` shows it, then the size
+/// and offset of the data after the last chunk, if any.
+fn print_chunks(path: &Path, parse: &ParseArgs, read: &BytesRead, hex: bool, mut show: impl FnMut(String)) -> Result<()> {
+    let mut frames = FrameTracker::new();
+    let mut reader = read_chunks(path, parse, read)?;
+    let mut offset = Png::STANDARD_HEADER.len();
+    for chunk in &mut reader {
+        let chunk = chunk?;
+        offset += chunk.length() as usize + 12;
+        show(print_chunk(&chunk, &mut frames, hex));
+    }
+    let trailer = reader.trailer();
+    if !trailer.is_empty() {
+        let length = format_number(trailer.len() as i64);
+        let mut shown = format!("{}\n", tr("print-trailer", &[("length", &length), ("offset", &format_number(offset as i64))]));
+        if hex {
+            shown.push_str(&hexdump(trailer));
+        }
+        show(shown);
+    }
+    Ok(())
+}
+
+/// A chunk as `print` shows it, followed by a hex dump of its data with `hex`. The fcTL chunk
+/// starting each frame of an animated PNG is preceded by a heading for the frame.
+fn print_chunk(chunk: &Chunk, frames: &mut FrameTracker, hex: bool) -> String {
+    let mut shown = frame_heading(chunk, frames);
+    shown.push_str(&format!("{}\n", output::chunk(chunk)));
+    if hex {
+        shown.push_str(&hexdump(chunk.data()));
+    }
+    shown
+}
+
+/// The chunks of `png` under a heading for each category, with the number and total size of
+/// the chunks in it. Sizes include the length, type and CRC of each chunk.
+fn tree(png: &Png) -> String {
+    let mut tree = String::new();
+    for (category, spans) in crate::graph::groups(png) {
+        let bytes = format_number(spans.iter().map(|span| span.length).sum::<usize>() as i64);
+        let heading = match category {
+            Category::Trailer => tr("print-tree-trailer", &[("category", &category), ("bytes", &bytes)]),
+            _ => tr("print-tree-group", &[("category", &category), ("count", &spans.len()), ("bytes", &bytes)]),
+        };
+        tree.push_str(&format!("{heading}\n"));
+        if category == Category::Trailer {
+            continue;
+        }
+        for (position, span) in spans.iter().enumerate() {
+            let branch = match (output::is_plain(), position + 1 == spans.len()) {
+                (true, _) => "  ",
+                (false, false) => "├─ ",
+                (false, true) => "└─ ",
+            };
+            tree.push_str(&format!("{branch}{:<6} {:>10}  @ {}\n", span.label, format_number(span.length as i64), format_number(span.offset as i64)));
+        }
+    }
+    tree
+}
+
+/// One row per chunk of `png` with its offset, size and share of the file, and a bar showing
+/// where it lies, then how much of the file decoders can skip.
+fn graph(png: &Png) -> String {
+    let total = png.byte_len();
+    let spans = crate::graph::spans(png);
+    let mut graph = String::new();
+    for span in &spans {
+        let share = span.length as f64 / total as f64 * 100.0;
+        graph.push_str(&format!(
+            "{:>10}  {:<7} {:>10}  {:>6}%  {}\n",
+            format_number(span.offset as i64),
+            span.label,
+            format_number(span.length as i64),
+            format_decimal(share, 1),
+            crate::graph::bar(span, total, crate::graph::WIDTH, output::is_plain())
+        ));
+    }
+    let skippable: usize = spans.iter().filter(|span| span.skippable).map(|span| span.length).sum();
+    let percent = format_decimal(skippable as f64 / total as f64 * 100.0, 1);
+    graph.push_str(&format!("{}\n", tr("print-graph-skippable", &[("percent", &percent), ("total", &format_number(total as i64))])));
+    graph
+}
+
+/// Files listed by name when asking to change a batch; the rest are counted.
+const CONFIRM_LISTED_FILES: usize = 10;
+
+/// Asks before a batch changes `files` in place, showing `plan` and the files.
+fn confirm_batch(plan: &str, files: &[PathBuf]) -> Result<()> {
+    let mut listing = format!("{plan}\n");
+    for file in files.iter().take(CONFIRM_LISTED_FILES) {
+        listing.push_str(&format!("  {}\n", file.display()));
+    }
+    if files.len() > CONFIRM_LISTED_FILES {
+        listing.push_str(&format!("  {}\n", tr("confirm-more", &[("count", &(files.len() - CONFIRM_LISTED_FILES))])));
+    }
+    if !output::confirm(&listing)? {
+        return Err(CommandError::Cancelled.into());
+    }
+    Ok(())
+}
+
+/// Runs `run` on every file in `files`, carrying on past failures, then prints a table of
+/// which files succeeded and which failed. Shows a progress bar named `label` meanwhile, if
+/// given; commands that print as they go have none, so the bar doesn't cut into their output.
+fn run_batch(files: &[PathBuf], batch: &BatchArgs, label: Option<&str>, run: impl Fn(&Path) -> Result<()> + Sync) -> Result<()> {
+    let mut results = vec![Ok(()); files.len()];
+    let mut progress = label.map(|label| Progress::new(label, Unit::Files, files.len() as u64));
+    crate::jobs::run(files, batch.jobs, |file| run(file).map_err(|e| describe_error(&e)), |index, result| {
+        results[index] = result;
+        if let Some(progress) = progress.as_mut() {
+            progress.advance(1);
+        }
+        Ok(())
+    })?;
+    drop(progress);
+    println!();
+    for (file, result) in files.iter().zip(&results) {
+        match result {
+            Ok(()) => println!("OK       {}", file.display()),
+            Err(e) => println!("FAILED   {}: {e}", file.display()),
+        }
+    }
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    println!("{}", tr("batch-summary", &[("succeeded", &(files.len() - failed)), ("failed", &failed)]));
+    if failed > 0 {
+        return Err(CommandError::BatchFailed(failed).into());
+    }
+    Ok(())
+}
+
+pub fn benchmark(args: BenchmarkArgs, read: &BytesRead) -> Result<()> {
+    let input = read_input(&args.file_path, read)?;
+    let report = crate::benchmark::run(&input, args.iterations)?;
+    println!(
+        "Benchmark: {} bytes, {} chunks, {} iterations (average per iteration)",
+        input.len(),
+        report.chunk_count,
+        report.iterations
+    );
+    println!("  Signature check  : {:?}", report.per_iteration(report.signature_check));
+    println!("  Chunk parsing    : {:?}", report.per_iteration(report.chunk_parsing));
+    println!("  CRC verification : {:?}", report.per_iteration(report.crc_verification));
+    println!("  Serialization    : {:?}", report.per_iteration(report.serialization));
+    println!("  Total            : {:?}", report.per_iteration(report.total()));
+    Ok(())
+}
+
+pub fn trailer(args: TrailerArgs, read: &BytesRead) -> Result<()> {
+    match args.action {
+        TrailerAction::Show(args) => {
+            let png = read_png(&args.file_path, &args.parse, read)?;
+            let trailer = png.trailer();
+            if trailer.is_empty() {
+                println!("{}", tr("trailer-none", &[]));
+                return Ok(());
+            }
+            println!("{}", tr("trailer-show", &[("length", &trailer.len()), ("offset", &png.trailer_offset())]));
+            match std::str::from_utf8(trailer) {
+                Ok(text) => println!("{}", tr("trailer-data", &[("data", &text)])),
+                Err(_) => println!("{}", tr("trailer-binary", &[])),
+            }
+        }
+        TrailerAction::Extract(args) => {
+            let png = read_png(&args.file_path, &args.parse, read)?;
+            write_output(&args.output_file_path, None, png.trailer())?;
+            output::status(tr("trailer-written", &[("length", &png.trailer().len()), ("path", &args.output_file_path.display())]));
+        }
+        TrailerAction::Strip(args) => {
+            let mut png = read_png(&args.file_path, &args.parse, read)?;
+            let removed = png.strip_trailer()?;
+            let output = args.output_file_path.unwrap_or(args.file_path);
+            write_png(&output, &png, &args.write)?;
+            output::status(tr("trailer-removed", &[("length", &removed.len())]));
+        }
+        TrailerAction::Set(args) => {
+            let mut png = read_png(&args.file_path, &args.parse, read)?;
+            let data = read_input(&args.data_file_path, read)?;
+            let length = data.len();
+            png.set_trailer(data)?;
+            let output = args.output_file_path.unwrap_or(args.file_path);
+            write_png(&output, &png, &args.write)?;
+            output::status(tr("trailer-set", &[("length", &length)]));
+        }
+    }
+    Ok(())
+}
+
+pub fn scan(args: ScanArgs, read: &BytesRead) -> Result<()> {
+    scan_with(args, &mut output::EventLog::new(), read)
+}
+
+/// Runs `scan`, reporting its progress to `events`.
+pub fn scan_with(args: ScanArgs, events: &mut dyn EventSink, read: &BytesRead) -> Result<()> {
+    let mut files = crate::scan::expand_path(&args.path, args.recursive)?;
+    let corpus_size = files.len();
+    if let Some(count) = args.sample {
+        files = crate::scan::sample(files, count, args.seed);
+    }
+    #[cfg(feature = "json")]
+    let index = match &args.index {
+        Some(path) => Some(Mutex::new(ScanIndex::load(path)?)),
+        None => None,
+    };
+    #[cfg(not(feature = "json"))]
+    if args.index.is_some() {
+        return Err(crate::features::Feature::Json.missing("--index").into());
+    }
+    #[cfg(feature = "sqlite")]
+    let mut export = match &args.output {
+        Some(path) => Some(crate::sqlite::SqliteExport::create(path)?),
+        None => None,
+    };
+    #[cfg(not(feature = "sqlite"))]
+    if args.output.is_some() {
+        return Err(crate::features::Feature::Sqlite.missing("--output").into());
+    }
+    let mut match_count = 0;
+    let mut error_count = 0;
+    let mut stats = CorpusStats::default();
+    #[cfg(feature = "json")]
+    let read_table = |file: &PathBuf| scan_file(file, &args.parse, index.as_ref(), read).map_err(|e| Failure::new(&e));
+    #[cfg(not(feature = "json"))]
+    let read_table = |file: &PathBuf| {
+        read_png(file, &args.parse, read).map(|png| crate::scan::chunk_table(&png)).map_err(|e| Failure::new(&e))
+    };
+    crate::jobs::run(&files, args.batch.jobs, read_table, |position, table| {
+        let file = &files[position];
+        events.emit(Event::FileStarted { path: file.clone() });
+        let table = match table {
+            Ok(table) => table,
+            Err(failure) => {
+                events.emit(Event::Warning { path: file.clone(), message: failure.described });
+                events.emit(Event::FileFinished { path: file.clone(), payloads: 0 });
+                #[cfg(feature = "sqlite")]
+                if let Some(export) = export.as_mut() {
+                    export.add_error(file, &failure.message)?;
+                }
+                error_count += 1;
+                return Ok(());
+            }
+        };
+        emit_chunks(events, file, &table);
+        if args.sample.is_some() {
+            stats.add(&table);
+        }
+        let matches = crate::scan::matching_chunks(&table, args.filter.as_ref());
+        for (index, record) in &matches {
+            events.emit(Event::PayloadFound { path: file.clone(), index: *index, record: (*record).clone() });
+        }
+        events.emit(Event::FileFinished { path: file.clone(), payloads: matches.len() });
+        match_count += matches.len();
+        #[cfg(feature = "sqlite")]
+        if let Some(export) = export.as_mut() {
+            export.add_file(file, &matches)?;
+        }
+        Ok(())
+    })?;
+    #[cfg(feature = "sqlite")]
+    if let Some(export) = export {
+        export.finish()?;
+    }
+    #[cfg(feature = "json")]
+    if let (Some(index), Some(path)) = (index, &args.index) {
+        index.into_inner().expect("a scan job panicked").save(path)?;
+    }
+    eprintln!("{}", tr("scan-summary", &[("count", &files.len()), ("matches", &match_count), ("unreadable", &error_count)]));
+    if args.sample.is_some() {
+        stats.unreadable = error_count;
+        print_projection(&stats, match_count, corpus_size, args.seed);
+    }
+    Ok(())
+}
+
+/// Prints what scanning all `corpus_size` files would likely find, scaling up the sample `stats`
+/// was gathered from.
+fn print_projection(stats: &CorpusStats, match_count: usize, corpus_size: usize, seed: u64) {
+    let scale = stats.scale(corpus_size);
+    let projected = |count: f64| format_number((count * scale).round() as i64);
+    let projection = tr(
+        "scan-sample",
+        &[
+            ("sampled", &format_number((stats.files + stats.unreadable) as i64)),
+            ("total", &format_number(corpus_size as i64)),
+            ("seed", &seed),
+            ("matches", &projected(match_count as f64)),
+            ("unreadable", &projected(stats.unreadable as f64)),
+        ],
+    );
+    eprintln!("{projection}");
+    let [kind, share, files, chunks, bytes] = ["column-type", "column-files-share", "column-files", "column-chunks", "column-bytes"].map(|id| tr(id, &[]));
+    eprintln!("{kind:<6} {share:>7} {files:>12} {chunks:>12} {bytes:>16}");
+    for (chunk_type, type_stats) in stats.by_prevalence() {
+        eprintln!(
+            "{:<6} {:>7} {:>12} {:>12} {:>16}",
+            chunk_type.to_string(),
+            format_decimal(type_stats.files as f64 / stats.files as f64 * 100.0, 1),
+            projected(type_stats.files as f64),
+            projected(type_stats.lengths.len() as f64),
+            projected(type_stats.total_length() as f64),
+        );
+    }
+}
+
+fn emit_chunks(events: &mut dyn EventSink, file: &Path, table: &[ChunkRecord]) {
+    for (index, record) in table.iter().enumerate() {
+        events.emit(Event::ChunkParsed { path: file.to_path_buf(), index, record: record.clone() });
+    }
+}
+
+/// Returns the chunk table of `file`, served from and recorded in `index` when one is given.
+/// The index is only locked to look the file up and to record it, not while parsing.
+#[cfg(feature = "json")]
+fn scan_file(file: &Path, parse: &ParseArgs, index: Option<&Mutex<ScanIndex>>, read: &BytesRead) -> Result<Vec<ChunkRecord>> {
+    let Some(index) = index.filter(|_| !is_stdio(file)) else {
+        return Ok(crate::scan::chunk_table(&read_png(file, parse, read)?));
+    };
+    let metadata = fs::metadata(file)?;
+    if let Some(table) = index.lock().expect("a scan job panicked").lookup(file, &metadata) {
+        return Ok(table.to_vec());
+    }
+    let input = Bytes::from(fs::read(file)?);
+    let table = crate::scan::chunk_table(&Png::parse(input.clone(), &parse.options())?);
+    index.lock().expect("a scan job panicked").insert(file, &metadata, &input, table.clone());
+    Ok(table)
+}
+
+/// An error described on the thread it happened on, since errors can't be sent between threads.
+struct Failure {
+    /// The error with its code, as `describe_error` prints it.
+    described: String,
+    #[cfg(feature = "sqlite")]
+    message: String,
+}
+
+impl Failure {
+    fn new(error: &(dyn std::error::Error + 'static)) -> Self {
+        Self {
+            described: describe_error(error),
+            #[cfg(feature = "sqlite")]
+            message: error.to_string(),
+        }
+    }
+}
+
+pub fn carve_stream(args: CarveStreamArgs, read: &BytesRead) -> Result<()> {
+    let blob = read_input(&args.input, read)?;
+    let carved = crate::carve::carve(&blob);
+    if let Some(directory) = &args.output_dir {
+        fs::create_dir_all(directory)?;
+    }
+    let stem = match is_stdio(&args.input) {
+        true => OsStr::new("stdin"),
+        false => args.input.file_stem().unwrap_or_default(),
+    };
+    let mut events = output::EventLog::new();
+    for found in &carved {
+        let bytes = found.bytes(&blob);
+        let path = match &args.output_dir {
+            Some(directory) => {
+                let mut name = stem.to_owned();
+                name.push(format!("-{:08x}.png", found.offset));
+                let path = directory.join(name);
+                replace_file(&path, &WriteOptions::default(), |writer| writer.write_all(bytes))?;
+                path
+            }
+            None => {
+                let mut label = args.input.clone().into_os_string();
+                label.push(format!("@{}", found.offset));
+                PathBuf::from(label)
+            }
+        };
+        println!("{:>12}  {:>12}  {:>5} chunks  {}", found.offset, format_number(found.length as i64), found.chunks, path.display());
+        if !args.scan {
+            continue;
+        }
+        match Png::parse(Bytes::copy_from_slice(bytes), &args.parse.options()) {
+            Ok(png) => {
+                let table = crate::scan::chunk_table(&png);
+                for (index, record) in crate::scan::matching_chunks(&table, args.filter.as_ref()) {
+                    events.emit(Event::PayloadFound { path: path.clone(), index, record: record.clone() });
+                }
+            }
+            Err(e) => events.emit(Event::Warning { path, message: describe_error(&e) }),
+        }
+    }
+    output::status(tr("carve-summary", &[("count", &carved.len()), ("length", &format_number(blob.len() as i64))]));
+    Ok(())
+}
+
+pub fn corpus_stats(args: CorpusStatsArgs, read: &BytesRead) -> Result<()> {
+    corpus_stats_with(args, &mut output::EventLog::new(), read)
+}
+
+/// Runs `corpus-stats`, reporting its progress to `events`.
+pub fn corpus_stats_with(args: CorpusStatsArgs, events: &mut dyn EventSink, read: &BytesRead) -> Result<()> {
+    let files = crate::scan::collect_files(&args.path, true)?;
+    #[cfg(feature = "json")]
+    let index = match &args.index {
+        Some(path) => Some(Mutex::new(ScanIndex::load(path)?)),
+        None => None,
+    };
+    #[cfg(not(feature = "json"))]
+    if args.index.is_some() {
+        return Err(crate::features::Feature::Json.missing("--index").into());
+    }
+    let mut stats = CorpusStats::default();
+    #[cfg(feature = "json")]
+    let read_table = |file: &PathBuf| scan_file(file, &args.parse, index.as_ref(), read).map_err(|e| describe_error(&e));
+    #[cfg(not(feature = "json"))]
+    let read_table = |file: &PathBuf| {
+        read_png(file, &args.parse, read).map(|png| crate::scan::chunk_table(&png)).map_err(|e| describe_error(&e))
+    };
+    crate::jobs::run(&files, args.batch.jobs, read_table, |position, table| {
+        let file = &files[position];
+        events.emit(Event::FileStarted { path: file.clone() });
+        match table {
+            Ok(table) => {
+                emit_chunks(events, file, &table);
+                stats.add(&table);
+            }
+            Err(message) => {
+                events.emit(Event::Warning { path: file.clone(), message });
+                stats.unreadable += 1;
+            }
+        }
+        events.emit(Event::FileFinished { path: file.clone(), payloads: 0 });
+        Ok(())
+    })?;
+    stats.finish();
+    #[cfg(feature = "json")]
+    if let (Some(index), Some(path)) = (index, &args.index) {
+        index.into_inner().expect("a scan job panicked").save(path)?;
+    }
 
-pub fn encode(args: EncodeArgs) -> Result<()> {
-    let input = fs::read(&args.input_file_path)?;
-    let output = args.output_file_path.unwrap_or(args.input_file_path);
-    
-    let mut png = Png::try_from(input.as_slice())?;
-    let chunk = Chunk::new(args.chunk_type, args.message.as_bytes().to_vec());
-    png.append_chunk(chunk);
+    println!("{} readable files, {} unreadable", format_number(stats.files as i64), format_number(stats.unreadable as i64));
+    println!(
+        "{:<6} {:>8} {:>7} {:>8} {:>10} {:>10} {:>10} {:>10} {:>14}",
+        "Type", "Files", "Files%", "Chunks", "Min", "Median", "P95", "Max", "Total"
+    );
+    for (chunk_type, type_stats) in stats.by_prevalence() {
+        let share = type_stats.files as f64 / stats.files as f64 * 100.0;
+        println!(
+            "{:<6} {:>8} {:>7} {:>8} {:>10} {:>10} {:>10} {:>10} {:>14}",
+            chunk_type.to_string(),
+            format_number(type_stats.files as i64),
+            format_decimal(share, 1),
+            format_number(type_stats.lengths.len() as i64),
+            format_number(type_stats.percentile(0.0) as i64),
+            format_number(type_stats.percentile(50.0) as i64),
+            format_number(type_stats.percentile(95.0) as i64),
+            format_number(type_stats.percentile(100.0) as i64),
+            format_number(type_stats.total_length() as i64),
+        );
+    }
+    Ok(())
+}
 
-    fs::write(output, png.as_bytes())?;
-    println!("Chunk written successfully.");
+pub fn chunk_type(args: ChunkTypeArgs) -> Result<()> {
+    let chunk_type = &args.chunk_type;
+    let pick = |value: bool, yes: &str, no: &str| tr(if value { yes } else { no }, &[]);
+    let yes_no = |value: bool| pick(value, "yes", "no");
+    println!("{}", tr("chunk-type-type", &[("chunk_type", chunk_type)]));
+    println!("{}", pick(chunk_type.is_critical(), "chunk-type-critical", "chunk-type-ancillary"));
+    println!("{}", pick(chunk_type.is_public(), "chunk-type-public", "chunk-type-private"));
+    println!("{}", pick(chunk_type.is_reserved_bit_valid(), "chunk-type-reserved-valid", "chunk-type-reserved-invalid"));
+    println!("{}", pick(chunk_type.is_safe_to_copy(), "chunk-type-safe-to-copy", "chunk-type-unsafe-to-copy"));
+    println!("{}", tr("chunk-type-valid", &[("answer", &yes_no(chunk_type.is_valid()))]));
+    println!("{}", tr("chunk-type-standard", &[("answer", &yes_no(chunk_type.is_standard()))]));
     Ok(())
 }
 
-pub fn decode(args: DecodeArgs) -> Result<()> {
-    let input = fs::read(&args.file_path)?;
-    let png = Png::try_from(input.as_slice())?;
-    let chunk = png.chunk_by_type(args.chunk_type.to_string().as_str());
-    if let Some(c) = chunk {
-        println!("Chunk : {}", c);
-        println!("Chunk data : {}", c.data_as_string().unwrap_or("{Non UTF-8 data}".to_string()));
+pub fn list_types(args: ListTypesArgs, read: &BytesRead) -> Result<()> {
+    let files = crate::scan::expand_path(&args.path, args.recursive)?;
+    let read_table = |file: &PathBuf| {
+        read_png(file, &args.parse, read).map(|png| crate::scan::chunk_table(&png)).map_err(|e| describe_error(&e))
+    };
+    let mut stats = CorpusStats::default();
+    crate::jobs::run(&files, args.batch.jobs, read_table, |position, table| {
+        match table {
+            Ok(table) => stats.add(&table),
+            Err(message) => {
+                eprintln!("pngme: {}: {message}", files[position].display());
+                stats.unreadable += 1;
+            }
+        }
+        Ok(())
+    })?;
+    stats.finish();
+
+    if files.len() > 1 {
+        let (readable, unreadable) = (format_number(stats.files as i64), format_number(stats.unreadable as i64));
+        println!("{}", tr("list-types-summary", &[("readable", &readable), ("unreadable", &unreadable)]));
+    }
+    let [name, chunks, files, bytes, kind, misplaced] =
+        ["column-type", "column-chunks", "column-files", "column-bytes", "column-kind", "column-misplaced"].map(|id| tr(id, &[]));
+    println!("{name:<6} {chunks:>8} {files:>8} {bytes:>14}  {kind:<10} {misplaced:>10}");
+    for (chunk_type, type_stats) in stats.by_prevalence() {
+        let kind = tr(if chunk_type.is_critical() { "kind-critical" } else { "kind-ancillary" }, &[]);
+        println!(
+            "{:<6} {:>8} {:>8} {:>14}  {:<10} {:>10}",
+            chunk_type.to_string(),
+            format_number(type_stats.lengths.len() as i64),
+            format_number(type_stats.files as i64),
+            format_number(type_stats.total_length() as i64),
+            kind,
+            format_number(type_stats.misplaced.values().sum::<usize>() as i64),
+        );
+    }
+    for (chunk_type, type_stats) in stats.by_prevalence() {
+        for (issue, count) in &type_stats.misplaced {
+            println!("{}", tr("list-types-misplaced", &[("chunk_type", chunk_type), ("issue", issue), ("count", count)]));
+        }
+    }
+    if stats.unreadable > 0 {
+        return Err(CommandError::BatchFailed(stats.unreadable).into());
     }
     Ok(())
 }
 
-pub fn remove(args: RemoveArgs) -> crate::Result<()> {
-    let input = fs::read(&args.file_path)?;
-    let mut png = Png::try_from(input.as_slice())?;
-    let chunk = png.remove_chunk(args.chunk_type.to_string().as_str())?;
-    fs::write(&args.file_path, png.as_bytes())?;
-    println!("Removed chunk: {chunk}");
+pub fn diff(args: DiffArgs, read: &BytesRead) -> Result<()> {
+    let original = read_png(&args.original_file_path, &args.parse, read)?;
+    let modified = read_png(&args.modified_file_path, &args.parse, read)?;
+    if args.patch {
+        print!("{}", Patch::between(&original, &modified));
+        return Ok(());
+    }
+    let diff = PngDiff::between(&original, &modified);
+    #[cfg(feature = "json")]
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&diff).map_err(Error::from)?);
+        return Ok(());
+    }
+    #[cfg(not(feature = "json"))]
+    if args.format == OutputFormat::Json {
+        return Err(crate::features::Feature::Json.missing("--format json").into());
+    }
+    if diff.is_same() {
+        output::status(tr("diff-same", &[("count", &diff.chunks.len())]));
+        return Ok(());
+    }
+    for change in &diff.chunks {
+        match change {
+            ChunkChange::Same { .. } => println!("{change}"),
+            ChunkChange::Removed { .. } => println!("{}", output::paint(change, Color::Red)),
+            ChunkChange::Added { .. } => println!("{}", output::paint(change, Color::Green)),
+            ChunkChange::Modified { .. } => println!("{}", output::paint(change, Color::Yellow)),
+        }
+    }
+    if diff.trailer_changed {
+        let line = format!("~ trailer {} -> {} bytes", format_number(diff.original_trailer as i64), format_number(diff.modified_trailer as i64));
+        println!("{}", output::paint(line, Color::Yellow));
+    }
     Ok(())
 }
 
-pub fn print(args: PrintArgs) -> crate::Result<()> {
-    let input = fs::read(&args.file_path)?;
-    let png = Png::try_from(input.as_slice())?;
-    for chunk in png.chunks() {
-        println!("{chunk}");
+pub fn payload_diff(mut args: PayloadDiffArgs, read: &BytesRead) -> Result<()> {
+    if args.password_stdin {
+        args.password = Some(read_password(&[&args.original_file_path, &args.modified_file_path], false)?);
+    }
+    let options = with_password(DecodeOptions::new(args.chunk_type.clone()), &args.password, &args.password_file);
+    let payload = |path: &Path| -> Result<Option<Payload>> { Ok(crate::decode::decode_from(&read_png(path, &args.parse, read)?, &options)?) };
+    let (original, modified) = (payload(&args.original_file_path)?, payload(&args.modified_file_path)?);
+    if original.is_none() && modified.is_none() {
+        return Err(CommandError::ChunkNotFound { path: args.modified_file_path, chunk_type: args.chunk_type }.into());
     }
+    let bytes = |payload: &Option<Payload>| payload.as_ref().map(|payload| payload.as_bytes().to_vec()).unwrap_or_default();
+    let (old, new) = (bytes(&original), bytes(&modified));
+    if old == new {
+        output::status(tr("payload-diff-same", &[]));
+        return Ok(());
+    }
+    let header = |path: &Path, payload: &Option<Payload>| match payload {
+        Some(_) => format!("{}:{}", path.display(), args.chunk_type),
+        None => format!("{}:{} ({})", path.display(), args.chunk_type, tr("payload-diff-none", &[])),
+    };
+    println!("--- {}", header(&args.original_file_path, &original));
+    println!("+++ {}", header(&args.modified_file_path, &modified));
+    match (std::str::from_utf8(&old), std::str::from_utf8(&new)) {
+        (Ok(old), Ok(new)) => print!("{}", crate::diff::unified(old, new, args.context)),
+        _ => println!("{}", crate::diff::ByteSummary::between(&old, &new)),
+    }
+    Ok(())
+}
+
+pub fn undo(args: UndoArgs) -> Result<()> {
+    crate::undo::revert(&args.file_path)?;
+    println!("{}", tr("undo-done", &[("path", &args.file_path.display())]));
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn verify(args: VerifyArgs, read: &BytesRead) -> Result<()> {
+    if args.fast {
+        #[cfg(feature = "crypto")]
+        return verify_seals(&args, read);
+        #[cfg(not(feature = "crypto"))]
+        return Err(crate::features::Feature::Crypto.missing("--fast").into());
+    }
+    match &args.manifest {
+        None => verify_structure(&args.file_paths, &args.batch, args.parse.ignore_crc, read),
+        #[cfg(feature = "crypto")]
+        Some(manifest) => verify_manifest(manifest, &args.parse, read),
+        #[cfg(not(feature = "crypto"))]
+        Some(_) => Err(crate::features::Feature::Crypto.missing("--manifest").into()),
+    }
+}
+
+pub fn detect(args: DetectArgs, read: &BytesRead) -> Result<()> {
+    let png = read_png(&args.file_path, &args.parse, read)?;
+    let findings: Vec<_> =
+        crate::detect::detect(&png).into_iter().filter(|(_, suspicion)| suspicion.risk() >= args.min_risk).collect();
+    let Some((_, highest)) = findings.first() else {
+        output::status(tr("detect-none", &[]));
+        return Ok(());
+    };
+    let highest = highest.risk();
+    println!("{:>4}  {:<7}  FINDING", "RISK", "CHUNK");
+    for (index, suspicion) in &findings {
+        let chunk = index.map_or_else(|| "-".to_string(), |index| index.to_string());
+        println!("{:>4}  {chunk:<7}  {suspicion}", suspicion.risk());
+    }
+    output::status(tr("detect-summary", &[("findings", &findings.len()), ("risk", &highest)]));
+    Ok(())
+}
+
+/// Checks the structure and CRCs of every file, exiting with status 1 if any is invalid.
+fn verify_structure(paths: &[PathBuf], batch: &BatchArgs, ignore_crc: bool, read: &BytesRead) -> Result<()> {
+    let mut files = Vec::new();
+    for path in paths {
+        files.extend(crate::scan::collect_files(path, true)?);
+    }
+    let check_file = |file: &PathBuf| {
+        let bytes = read_input(file, read).map_err(|e| describe_error(&e))?;
+        // With --ignore-crc, CRC mismatches are reported but leave the file valid.
+        let report = crate::structure::validate(&bytes);
+        let (warnings, issues): (Vec<_>, Vec<_>) = report
+            .issues
+            .into_iter()
+            .partition(|(_, issue)| ignore_crc && matches!(issue, crate::structure::Issue::CrcMismatch { .. }));
+        let describe = |found: Vec<(u64, crate::structure::Issue)>| {
+            found.iter().map(|(offset, issue)| format!("{issue} (offset {offset})")).collect::<Vec<_>>()
+        };
+        Ok((describe(issues), describe(warnings), report.trailer_length))
+    };
+    let mut invalid = 0;
+    let mut progress = Progress::new("verify", Unit::Files, files.len() as u64);
+    crate::jobs::run(&files, batch.jobs, check_file, |position, found: std::result::Result<_, String>| {
+        let file = &files[position];
+        let (issues, warnings, trailer_length) = found.unwrap_or_else(|message| (vec![message], Vec::new(), 0));
+        progress.advance(1);
+        for issue in &issues {
+            progress.println(format_args!("INVALID  {}: {issue}", file.display()));
+        }
+        for warning in &warnings {
+            progress.println(format_args!("BADCRC   {}: {warning}", file.display()));
+        }
+        if trailer_length > 0 {
+            let length = format_number(trailer_length as i64);
+            progress.println(format_args!("TRAILER  {}: {}", file.display(), tr("verify-trailer", &[("length", &length)])));
+        }
+        if issues.is_empty() {
+            progress.println(format_args!("OK       {}", file.display()));
+        } else {
+            invalid += 1;
+        }
+        Ok(())
+    })?;
+    drop(progress);
+    let valid = files.len() - invalid;
+    println!("{}", tr("verify-files-summary", &[("valid", &valid), ("total", &files.len())]));
+    if invalid > 0 {
+        return Err(CommandError::InvalidFiles(invalid).into());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "crypto")]
+fn verify_manifest(path: &Path, parse: &ParseArgs, read: &BytesRead) -> Result<()> {
+    let manifest = Manifest::load(path)?;
+    let mut failures = 0;
+    for entry in &manifest.assets {
+        let status = match read_png(&entry.path, parse, read) {
+            Ok(png) => entry.check(&png),
+            Err(e) => {
+                println!("ERROR    {} ({}): {}", entry.path.display(), entry.chunk_type, describe_error(&e));
+                failures += 1;
+                continue;
+            }
+        };
+        let label = match status {
+            EntryStatus::Valid => "OK",
+            EntryStatus::MissingChunk => "MISSING",
+            EntryStatus::DigestMismatch => "MISMATCH",
+        };
+        println!("{label:<8} {} ({})", entry.path.display(), entry.chunk_type);
+        if status != EntryStatus::Valid {
+            failures += 1;
+        }
+    }
+    let (valid, total) = (manifest.assets.len() - failures, manifest.assets.len());
+    println!("{}", tr("verify-summary", &[("valid", &valid), ("total", &total)]));
+    if failures > 0 {
+        return Err(CommandError::VerificationFailed(failures).into());
+    }
+    Ok(())
+}
+
+/// Compares every file with its seal, listing the chunks that changed since it was sealed.
+#[cfg(feature = "crypto")]
+fn verify_seals(args: &VerifyArgs, read: &BytesRead) -> Result<()> {
+    let key = sign::load_key(args.key_file.as_deref().expect("--fast requires --key-file"))?;
+    let mut files = Vec::new();
+    for path in &args.file_paths {
+        files.extend(crate::scan::collect_files(path, true)?);
+    }
+    let mut failures = 0;
+    for file in &files {
+        let changes = read_png(file, &args.parse, read).and_then(|png| Ok(seal::check(&png, &key)?));
+        match changes {
+            Ok(changes) if changes.is_empty() => println!("OK       {}", file.display()),
+            Ok(changes) => {
+                for change in &changes {
+                    println!("TAMPERED {}: {change}", file.display());
+                }
+                failures += 1;
+            }
+            Err(e) => {
+                println!("ERROR    {}: {}", file.display(), describe_error(&e));
+                failures += 1;
+            }
+        }
+    }
+    let valid = files.len() - failures;
+    println!("{}", tr("verify-summary", &[("valid", &valid), ("total", &files.len())]));
+    if failures > 0 {
+        return Err(CommandError::VerificationFailed(failures).into());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "crypto")]
+pub fn seal(args: SealArgs, read: &BytesRead) -> Result<()> {
+    let key = sign::load_key(&args.key_file)?;
+    let mut png = read_png(&args.file_path, &args.parse, read)?;
+    seal::seal(&mut png, &key)?;
+    let output = args.output_file_path.unwrap_or(args.file_path);
+    write_png(&output, &png, &args.write)?;
+    output::status(tr("seal-written", &[("path", &output.display()), ("chunks", &(png.chunks().len() - 1))]));
+    Ok(())
+}
+
+/// Re-encrypts the payloads of one file, or of every file in a directory or glob, reporting
+/// the files whose payloads the old password doesn't open.
+#[cfg(feature = "crypto")]
+pub fn rekey(args: RekeyArgs, read: &BytesRead) -> Result<()> {
+    let rekey_file = |file: &Path| {
+        let mut png = read_png(file, &args.parse, read)?;
+        let rekeyed = crate::rekey::rekey(&mut png, &args.old_pass, &args.new_pass)?;
+        if rekeyed.is_empty() {
+            output::status(tr("rekey-none", &[("path", &file.display())]));
+            return Ok(());
+        }
+        write_png(file, &png, &args.write)?;
+        let locations = rekeyed.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        output::status(tr("rekey-done", &[("path", &file.display()), ("locations", &locations)]));
+        Ok(())
+    };
+    if crate::scan::is_batch_path(&args.path) {
+        let files = crate::scan::expand_path(&args.path, args.recursive)?;
+        return run_batch(&files, &args.batch, None, rekey_file);
+    }
+    rekey_file(&args.path)
+}
+
+#[cfg(feature = "crypto")]
+pub fn provenance(args: ProvenanceArgs, read: &BytesRead) -> Result<()> {
+    match args.action {
+        ProvenanceAction::Sign(args) => {
+            let key = sign::load_key(&args.key_file)?;
+            let mut png = read_png(&args.file_path, &args.parse, read)?;
+            let parent = args.parent.as_deref().map(|path| read_input(path, read)).transpose()?;
+            let claim = Claim::new(&png, &args.creator, parent.as_deref());
+            provenance::embed(&mut png, claim, &key)?;
+            let output = args.output_file_path.unwrap_or(args.file_path);
+            write_png(&output, &png, &args.write)?;
+            output::status(tr("provenance-signed", &[("path", &output.display())]));
+        }
+        ProvenanceAction::Verify(args) => {
+            let key = sign::load_key(&args.key_file)?;
+            let png = read_png(&args.file_path, &args.parse, read)?;
+            let claim = provenance::verify(&png, &key)?;
+            println!("Creator   : {}", claim.creator);
+            println!("Toolchain : {}", claim.toolchain);
+            println!("{}", tr("provenance-timestamp", &[("date", &crate::i18n::format_date(claim.timestamp))]));
+            println!("Content   : {}", claim.content_sha256);
+            if let Some(parent) = &claim.parent_sha256 {
+                println!("Parent    : {parent}");
+            }
+            println!("{}", tr("provenance-verified", &[]));
+        }
+    }
+    Ok(())
+}
+
+pub fn fsck(args: FsckArgs, read: &BytesRead) -> Result<()> {
+    fsck_with(args, &mut output::EventLog::report("BROKEN   "), read)
+}
+
+/// Runs `fsck`, reporting every problem it finds to `events` as a warning.
+pub fn fsck_with(args: FsckArgs, events: &mut dyn EventSink, read: &BytesRead) -> Result<()> {
+    #[cfg(not(feature = "crypto"))]
+    if args.key_file.is_some() {
+        return Err(crate::features::Feature::Crypto.missing("--key-file").into());
+    }
+    #[cfg(feature = "crypto")]
+    let key = args.key_file.as_deref().map(sign::load_key).transpose()?;
+    let mut files = Vec::new();
+    for path in &args.paths {
+        files.extend(crate::scan::collect_files(path, true)?);
+    }
+    let check_file = |file: &PathBuf| {
+        let bytes = read_input(file, read).map_err(|e| describe_error(&e))?;
+        #[allow(unused_mut)]
+        let mut problems = crate::fsck::check(&bytes);
+        #[cfg(feature = "crypto")]
+        if let Some(key) = &key {
+            if let Ok(png) = Png::try_from(bytes.as_slice()) {
+                let claim_offset = png
+                    .chunks()
+                    .iter()
+                    .zip(png.chunk_offsets())
+                    .find(|(chunk, _)| chunk.chunk_type().to_string() == provenance::CLAIM_CHUNK_TYPE)
+                    .map(|(_, offset)| offset);
+                if let (Some(offset), Err(e)) = (claim_offset, provenance::verify(&png, key)) {
+                    problems.push((offset, crate::fsck::Problem::Claim(e.to_string())));
+                }
+            }
+        }
+        let problems: Vec<String> =
+            problems.iter().map(|(offset, problem)| format!("{problem} (offset {offset})")).collect();
+        Ok(problems)
+    };
+    let mut broken = 0;
+    crate::jobs::run(&files, args.batch.jobs, check_file, |position, problems: std::result::Result<_, String>| {
+        let file = &files[position];
+        events.emit(Event::FileStarted { path: file.clone() });
+        let problems = problems.unwrap_or_else(|message| vec![message]);
+        for message in &problems {
+            events.emit(Event::Warning { path: file.clone(), message: message.clone() });
+        }
+        events.emit(Event::FileFinished { path: file.clone(), payloads: 0 });
+        if !problems.is_empty() {
+            broken += 1;
+        }
+        Ok(())
+    })?;
+    println!("{}", tr("fsck-summary", &[("broken", &broken), ("total", &files.len())]));
+    if broken > 0 {
+        return Err(CommandError::BrokenPayloads(broken).into());
+    }
+    Ok(())
+}
+
+pub fn hook(args: HookArgs, read: &BytesRead) -> Result<()> {
+    match args.action {
+        HookAction::Check(args) => {
+            let mut failures = 0;
+            for path in &args.file_paths {
+                let is_png = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("png"));
+                if args.staged && !is_png {
+                    continue;
+                }
+                let png = match read_png(path, &args.parse, read) {
+                    Ok(png) => png,
+                    Err(e) => {
+                        println!("{}: {}", path.display(), describe_error(&e));
+                        failures += 1;
+                        continue;
+                    }
+                };
+                let violations = crate::hook::check(&png, &args.allow);
+                for (offset, violation) in &violations {
+                    println!("{}: {violation} (offset {offset})", path.display());
+                }
+                if !violations.is_empty() {
+                    failures += 1;
+                }
+            }
+            if failures > 0 {
+                // Hooks are judged by exit status alone.
+                return Err(CommandError::HookFailed(failures).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn testvec(args: TestvecArgs) -> Result<()> {
+    fs::create_dir_all(&args.output)?;
+    for vector in crate::testvec::generate(args.seed) {
+        replace_file(&args.output.join(vector.name), &WriteOptions::default(), |writer| writer.write_all(&vector.bytes))?;
+        println!("{:<28} {}", vector.name, vector.description);
+    }
+    Ok(())
+}
+
+pub fn examples(args: ExamplesArgs) -> Result<()> {
+    fs::create_dir_all(&args.output)?;
+    let examples = crate::examples::generate()?;
+    for example in &examples {
+        replace_file(&args.output.join(example.name), &WriteOptions::default(), |writer| writer.write_all(&example.bytes))?;
+        println!("{:<16} {}", example.name, example.description);
+    }
+    let readme = crate::examples::readme(&examples);
+    replace_file(&args.output.join("README.txt"), &WriteOptions::default(), |writer| writer.write_all(readme.as_bytes()))?;
+    Ok(())
+}
+
+pub fn which_chunk(args: WhichChunkArgs, read: &BytesRead) -> Result<()> {
+    let bytes = read_input(&args.file_path, read)?;
+    let offset = args.offset;
+    match locate(&bytes, offset) {
+        Location::Signature => println!("{}", tr("which-chunk-signature", &[("offset", &offset)])),
+        Location::Chunk(span, part) => {
+            let chunk_type = String::from_utf8_lossy(&span.chunk_type);
+            let args: [(&str, &dyn std::fmt::Display); 4] =
+                [("offset", &offset), ("index", &span.index), ("chunk_type", &chunk_type), ("start", &span.offset)];
+            println!("{}", tr("which-chunk-chunk", &args));
+            let field = match part {
+                ChunkPart::Length => tr("which-chunk-field-length", &[]),
+                ChunkPart::Type => tr("which-chunk-field-type", &[]),
+                ChunkPart::Data(position) => tr("which-chunk-field-data", &[("position", &position), ("length", &span.length)]),
+                ChunkPart::Crc => tr("which-chunk-field-crc", &[]),
+            };
+            println!("{field}");
+            println!("{}", tr("which-chunk-status", &[("status", &span.status)]));
+        }
+        Location::Trailer(position) => {
+            println!("{}", tr("which-chunk-trailer", &[("offset", &offset), ("position", &position)]))
+        }
+        Location::PastEnd => println!("{}", tr("which-chunk-past-end", &[("offset", &offset), ("length", &bytes.len())])),
+    }
+    Ok(())
+}
+
+/// Adds this run of `operation` to the usage statistics kept in `stats_file`.
+pub fn record_usage(stats_file: &Path, operation: &str, read: &BytesRead) -> Result<()> {
+    #[cfg(feature = "json")]
+    return crate::usage::record(stats_file, operation, read.total()).map_err(PngMeError::from);
+    #[cfg(not(feature = "json"))]
+    {
+        let _ = (stats_file, operation, read);
+        Err(crate::features::Feature::Json.missing("--stats-file").into())
+    }
+}
+
+/// Shows the usage statistics kept in `stats_file`, or in the default one.
+pub fn stats(args: StatsArgs, stats_file: Option<PathBuf>) -> Result<()> {
+    #[cfg(feature = "json")]
+    {
+        let Some(path) = stats_file.or_else(crate::usage::default_path) else {
+            return Err("No --stats-file given and no home directory to look for one in".into());
+        };
+        let stats = crate::usage::UsageStats::load(&path)?;
+        match args.format {
+            OutputFormat::Text => {
+                println!("{}", tr("stats-file", &[("path", &path.display())]));
+                print!("{stats}");
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats).map_err(Error::from)?),
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "json"))]
+    {
+        let _ = (args, stats_file);
+        Err(crate::features::Feature::Json.missing("stats").into())
+    }
+}
+
+pub fn capabilities(args: CapabilitiesArgs) -> Result<()> {
+    let commands = Arg::command().get_subcommands().map(|c| c.get_name().to_string()).collect();
+    let capabilities = Capabilities::current(commands);
+    match args.format {
+        OutputFormat::Text => println!("{capabilities}"),
+        #[cfg(feature = "json")]
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&capabilities).map_err(Error::from)?),
+        #[cfg(not(feature = "json"))]
+        OutputFormat::Json => return Err(crate::features::Feature::Json.missing("--format json").into()),
+    }
+    Ok(())
+}
+
+pub fn doctor(args: DoctorArgs) -> Result<()> {
+    let mut findings = crate::doctor::check_environment();
+    if let Some(path) = &args.file_path {
+        findings.extend(crate::doctor::check_file(path));
+        let size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        let output = args.output.as_deref().unwrap_or(path);
+        findings.push(crate::doctor::check_output(output, size));
+    }
+    for finding in &findings {
+        println!("{finding}");
+    }
+    let problems = findings.iter().filter(|finding| finding.status == crate::doctor::Status::Problem).count();
+    println!("{}", tr("doctor-summary", &[("problems", &problems)]));
+    Ok(())
+}
+
+/// The heading shown before `chunk` if it starts a frame, as `frames` follows the file.
+fn frame_heading(chunk: &Chunk, frames: &mut FrameTracker) -> String {
+    match frames.track(chunk) {
+        Some(frame) if &chunk.chunk_type().bytes() == b"fcTL" => format!("{}\n", tr("print-frame", &[("number", &(frame + 1))])),
+        _ => String::new(),
+    }
+}
+
+pub fn inspect(args: InspectArgs, read: &BytesRead) -> Result<()> {
+    let mut color_type = None;
+    let mut frames = FrameTracker::new();
+    for chunk in read_chunks(&args.file_path, &args.parse, read)? {
+        let chunk = chunk?;
+        let heading = frame_heading(&chunk, &mut frames);
+        let indent = if frames.in_frame() { "  " } else { "" };
+        let summary = match crate::known_chunks::decode_limited(&chunk, color_type, args.parse.max_inflate_ratio) {
+            Ok(Some(known)) => {
+                if let KnownChunk::Header(header) = &known {
+                    color_type = Some(header.color_type);
+                }
+                known.to_string()
+            }
+            Ok(None) => format!("{} bytes", chunk.length()),
+            Err(e) => format!("invalid: {}", describe_error(&*e)),
+        };
+        print!("{heading}");
+        println!("{indent}{}  {summary}", chunk.chunk_type());
+        if args.stats {
+            println!("{indent}      {}", crate::analysis::ByteStats::of(chunk.data()));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("No {chunk_type} chunk found in {}", path.display())]
+    ChunkNotFound { path: PathBuf, chunk_type: ChunkType },
+    #[error("None of the chunk types {types} found in {}", path.display())]
+    ChunkTypesNotFound { path: PathBuf, types: String },
+    #[error("No chunk {chunk} in {}, it has fewer {} chunks", path.display(), chunk.chunk_type)]
+    ChunkIndexNotFound { path: PathBuf, chunk: ChunkSelector },
+    #[error("Nothing is hidden in the pixels of {}", .0.display())]
+    NothingHidden(PathBuf),
+    #[error("No data after the IEND chunk of {}", .0.display())]
+    NoTrailer(PathBuf),
+    #[error("--language, --translated-keyword and --compress need the iTXt chunk type, not {0}")]
+    NotInternational(ChunkType),
+    #[error("{0} is a critical chunk; only ancillary chunks can be copied")]
+    CriticalChunk(ChunkType),
+    #[error("File would grow by {growth:.2}%, more than the allowed {limit}%")]
+    GrowthExceeded { growth: f64, limit: f64 },
+    #[cfg(feature = "crypto")]
+    #[error("{0} assets failed verification")]
+    VerificationFailed(usize),
+    #[error("{0} files are not valid PNG files")]
+    InvalidFiles(usize),
+    #[error("{0} files break the hook rules")]
+    HookFailed(usize),
+    #[error("{0} files have broken payloads")]
+    BrokenPayloads(usize),
+    #[error("{0} files failed")]
+    BatchFailed(usize),
+    #[error("--out, --all and chunk indexes can't be used with a directory, glob or --format json")]
+    SingleFileOptions,
+    #[error("The output of a directory or glob must be an existing directory, or left out to change files in place")]
+    OutputForBatch,
+    #[error("Cancelled, no files were changed")]
+    Cancelled,
+    #[error("Gave up after the {0} seconds allowed by --timeout")]
+    TimedOut(f64),
+    #[error("The passwords don't match")]
+    PasswordMismatch,
+    #[error("--password-stdin can't be used when the PNG File is read from stdin")]
+    PasswordStdinConflict,
+}
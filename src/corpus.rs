@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use crate::chunk_order::OrderIssue;
+use crate::chunk_type::ChunkType;
+use crate::scan::ChunkRecord;
+
+/// How often chunks of one type appear across a corpus and how large they are.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TypeStats {
+    /// Number of files with at least one chunk of this type.
+    pub files: usize,
+    /// Length of every chunk of this type, sorted once the corpus is complete.
+    pub lengths: Vec<u32>,
+    /// Number of chunks of this type out of the order the spec requires, by issue.
+    pub misplaced: BTreeMap<OrderIssue, usize>,
+}
+
+impl TypeStats {
+    pub fn total_length(&self) -> u64 {
+        self.lengths.iter().map(|&length| length as u64).sum()
+    }
+
+    /// The length below which `percent` of the chunks fall, by the nearest-rank method.
+    /// Only meaningful after [`CorpusStats::finish`].
+    pub fn percentile(&self, percent: f64) -> u32 {
+        if self.lengths.is_empty() {
+            return 0;
+        }
+        let rank = (percent / 100.0 * self.lengths.len() as f64).ceil() as usize;
+        self.lengths[rank.clamp(1, self.lengths.len()) - 1]
+    }
+}
+
+/// Chunk type statistics gathered from the chunk tables of many files.
+#[derive(Debug, Default)]
+pub struct CorpusStats {
+    pub files: usize,
+    pub unreadable: usize,
+    pub types: BTreeMap<ChunkType, TypeStats>,
+}
+
+impl CorpusStats {
+    /// Adds the chunk table of one file.
+    pub fn add(&mut self, table: &[ChunkRecord]) {
+        self.files += 1;
+        for (index, record) in table.iter().enumerate() {
+            let stats = self.types.entry(record.chunk_type.clone()).or_default();
+            if !table[..index].iter().any(|earlier| earlier.chunk_type == record.chunk_type) {
+                stats.files += 1;
+            }
+            stats.lengths.push(record.length);
+        }
+        let types: Vec<ChunkType> = table.iter().map(|record| record.chunk_type.clone()).collect();
+        for (index, issue) in crate::chunk_order::check(&types) {
+            *self.types.get_mut(&types[index]).expect("an added type").misplaced.entry(issue).or_default() += 1;
+        }
+    }
+
+    /// Sorts the recorded lengths so percentiles can be read.
+    pub fn finish(&mut self) {
+        for stats in self.types.values_mut() {
+            stats.lengths.sort_unstable();
+        }
+    }
+
+    /// How many times more files a corpus of `corpus_size` files has than were added, readable
+    /// or not, to project the statistics of a sample onto the whole corpus.
+    pub fn scale(&self, corpus_size: usize) -> f64 {
+        match self.files + self.unreadable {
+            0 => 0.0,
+            sampled => corpus_size as f64 / sampled as f64,
+        }
+    }
+
+    /// The chunk types by descending number of files they appear in, then by type.
+    pub fn by_prevalence(&self) -> Vec<(&ChunkType, &TypeStats)> {
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by(|a, b| b.1.files.cmp(&a.1.files).then_with(|| a.0.cmp(b.0)));
+        types
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn table(chunks: &[(&str, u32)]) -> Vec<ChunkRecord> {
+        chunks
+            .iter()
+            .map(|&(chunk_type, length)| ChunkRecord { chunk_type: ChunkType::from_str(chunk_type).unwrap(), length })
+            .collect()
+    }
+
+    #[test]
+    fn test_corpus_stats() {
+        let mut stats = CorpusStats::default();
+        stats.add(&table(&[("IHDR", 13), ("IDAT", 100), ("IDAT", 50), ("IEND", 0)]));
+        stats.add(&table(&[("IHDR", 13), ("ruSt", 7), ("IDAT", 10), ("IEND", 0)]));
+        stats.finish();
+
+        assert_eq!(stats.files, 2);
+        let idat = &stats.types[&ChunkType::from_str("IDAT").unwrap()];
+        assert_eq!((idat.files, idat.lengths.len(), idat.total_length()), (2, 3, 160));
+        assert_eq!((idat.percentile(0.0), idat.percentile(50.0), idat.percentile(100.0)), (10, 50, 100));
+
+        assert_eq!(stats.scale(200), 100.0);
+
+        let order: Vec<String> = stats.by_prevalence().iter().map(|(t, _)| t.to_string()).collect();
+        assert_eq!(order, ["IDAT", "IEND", "IHDR", "ruSt"]);
+    }
+
+    #[test]
+    fn test_misplaced_chunks() {
+        let mut stats = CorpusStats::default();
+        stats.add(&table(&[("IHDR", 13), ("IDAT", 10), ("IEND", 0), ("ruSt", 7)]));
+        stats.add(&table(&[("IHDR", 13), ("ruSt", 7), ("IDAT", 10), ("IEND", 0)]));
+        stats.finish();
+
+        let rust = &stats.types[&ChunkType::from_str("ruSt").unwrap()];
+        assert_eq!(rust.misplaced, BTreeMap::from([(OrderIssue::AfterIend, 1)]));
+        assert!(stats.types[&ChunkType::from_str("IDAT").unwrap()].misplaced.is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use aes_gcm::aead::{Aead, Key, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+
+use crate::Result;
+
+/// Marks a payload as encrypted by pngme.
+pub const MAGIC: &[u8; 4] = b"PMEC";
+
+/// Version of the framing written by [`encrypt`]: AES-256-GCM with a key derived by
+/// Argon2id using its default parameters.
+pub const VERSION: u8 = 1;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Encrypts `plaintext` under `password`. The result holds the magic, the version byte,
+/// the salt and the nonce, followed by the ciphertext and its authentication tag.
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::fill(&mut salt).map_err(|_| CryptoError::Random)?;
+    getrandom::fill(&mut nonce).map_err(|_| CryptoError::Random)?;
+
+    let cipher = cipher(password, &salt)?;
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce), plaintext)
+        .map_err(|_| CryptoError::Encryption)?;
+
+    let mut framed = Vec::with_capacity(HEADER_LENGTH + ciphertext.len());
+    framed.extend_from_slice(MAGIC);
+    framed.push(VERSION);
+    framed.extend_from_slice(&salt);
+    framed.extend_from_slice(&nonce);
+    framed.extend_from_slice(&ciphertext);
+    Ok(framed)
+}
+
+/// Decrypts a payload written by [`encrypt`].
+pub fn decrypt(framed: &[u8], password: &str) -> Result<Vec<u8>> {
+    if !is_encrypted(framed) {
+        return Err(Box::new(CryptoError::NotEncrypted));
+    }
+    let version = framed[MAGIC.len()];
+    if version != VERSION {
+        return Err(Box::new(CryptoError::UnsupportedVersion(version)));
+    }
+    if framed.len() < HEADER_LENGTH {
+        return Err(Box::new(CryptoError::Truncated));
+    }
+    let salt = &framed[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LENGTH];
+    let nonce: [u8; NONCE_LENGTH] = framed[HEADER_LENGTH - NONCE_LENGTH..HEADER_LENGTH].try_into()?;
+    let cipher = cipher(password, salt)?;
+    // A wrong password and tampered data both fail authentication and can't be told apart.
+    let plaintext = cipher
+        .decrypt(&Nonce::from(nonce), &framed[HEADER_LENGTH..])
+        .map_err(|_| CryptoError::WrongPassword)?;
+    Ok(plaintext)
+}
+
+/// Whether `payload` starts like a payload written by [`encrypt`].
+pub fn is_encrypted(payload: &[u8]) -> bool {
+    payload.len() > MAGIC.len() && payload.starts_with(MAGIC)
+}
+
+fn cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|_| CryptoError::KeyDerivation)?;
+    Ok(Aes256Gcm::new(&Key::<Aes256Gcm>::from(key)))
+}
+
+#[derive(Debug)]
+pub enum CryptoError {
+    NotEncrypted,
+    UnsupportedVersion(u8),
+    Truncated,
+    WrongPassword,
+    KeyDerivation,
+    Encryption,
+    Random,
+}
+
+impl std::error::Error for CryptoError {}
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::NotEncrypted => write!(f, "Payload is not encrypted"),
+            CryptoError::UnsupportedVersion(version) => write!(f, "Unsupported encryption version {version}"),
+            CryptoError::Truncated => write!(f, "Encrypted payload is truncated"),
+            CryptoError::WrongPassword => write!(f, "Wrong password, or the payload was modified"),
+            CryptoError::KeyDerivation => write!(f, "Could not derive a key from the password"),
+            CryptoError::Encryption => write!(f, "Could not encrypt the payload"),
+            CryptoError::Random => write!(f, "Could not get random bytes from the operating system"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let framed = encrypt(b"secret message", "hunter2").unwrap();
+        assert!(is_encrypted(&framed));
+        assert_eq!(framed.len(), HEADER_LENGTH + b"secret message".len() + 16);
+        assert_eq!(decrypt(&framed, "hunter2").unwrap(), b"secret message");
+    }
+
+    #[test]
+    fn test_wrong_password_and_tampering() {
+        let mut framed = encrypt(b"secret message", "hunter2").unwrap();
+        let error = decrypt(&framed, "hunter3").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(CryptoError::WrongPassword)));
+
+        let last = framed.len() - 1;
+        framed[last] ^= 1;
+        assert!(decrypt(&framed, "hunter2").is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_payloads() {
+        assert!(!is_encrypted(b"plain text"));
+        assert!(matches!(decrypt(b"plain text", "x").unwrap_err().downcast_ref(), Some(CryptoError::NotEncrypted)));
+        let error = decrypt(b"PMEC\x09rest", "x").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(CryptoError::UnsupportedVersion(9))));
+        assert!(matches!(decrypt(b"PMEC\x01short", "x").unwrap_err().downcast_ref(), Some(CryptoError::Truncated)));
+    }
+}
@@ -0,0 +1,510 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::chunk::PayloadKind;
+use crate::chunk_type::ChunkType;
+use crate::payload::{Envelope, Payload, RawChunkData};
+use crate::png::Png;
+use crate::stego::{Method, StegoError};
+use crate::Result;
+
+/// Supplies the password for an encrypted payload. Embedders implement it to plug in their
+/// own secret stores; it is only asked once a payload turns out to be encrypted.
+pub trait CredentialProvider {
+    fn password(&self) -> Result<String>;
+}
+
+/// A password known up front, e.g. from a command line argument.
+pub struct StaticPassword(pub String);
+
+impl CredentialProvider for StaticPassword {
+    fn password(&self) -> Result<String> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Asks for the password on the terminal, with echo turned off where `stty` is available.
+pub struct Prompt {
+    pub message: String,
+}
+
+impl CredentialProvider for Prompt {
+    fn password(&self) -> Result<String> {
+        eprint!("{}", self.message);
+        io::stderr().flush()?;
+        let echo_off = stty("-echo");
+        let mut line = String::new();
+        let read = io::stdin().lock().read_line(&mut line);
+        if echo_off {
+            stty("echo");
+            eprintln!();
+        }
+        read?;
+        Ok(trim_line_ending(&line).to_string())
+    }
+}
+
+fn stty(setting: &str) -> bool {
+    Command::new("stty")
+        .arg(setting)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Looks the password up in the system keyring: the login keychain on macOS, the Secret
+/// Service (through `secret-tool`) elsewhere.
+pub struct Keyring {
+    pub service: String,
+    pub account: String,
+}
+
+impl CredentialProvider for Keyring {
+    fn password(&self) -> Result<String> {
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("security");
+            command.args(["find-generic-password", "-w", "-s", &self.service, "-a", &self.account]);
+            command
+        } else {
+            let mut command = Command::new("secret-tool");
+            command.args(["lookup", "service", &self.service, "account", &self.account]);
+            command
+        };
+        let output = command.stderr(Stdio::null()).output().map_err(|_| DecodeError::KeyringUnavailable)?;
+        if !output.status.success() {
+            return Err(Box::new(DecodeError::NoKeyringEntry { service: self.service.clone(), account: self.account.clone() }));
+        }
+        Ok(trim_line_ending(&String::from_utf8(output.stdout)?).to_string())
+    }
+}
+
+/// Reads the password from a file. A single trailing line ending is not part of it.
+pub struct KeyFile(pub PathBuf);
+
+impl CredentialProvider for KeyFile {
+    fn password(&self) -> Result<String> {
+        Ok(trim_line_ending(&fs::read_to_string(&self.0)?).to_string())
+    }
+}
+
+/// Environment variable holding the password, which unlike an argument stays out of shell
+/// history and process lists.
+pub const PASSWORD_VARIABLE: &str = "PNGME_PASSWORD";
+/// Environment variable naming a file holding the password, read like a [`KeyFile`].
+pub const KEY_FILE_VARIABLE: &str = "PNGME_KEY_FILE";
+
+/// Takes the password from `PNGME_PASSWORD`, or else from the file `PNGME_KEY_FILE` names.
+pub struct Environment;
+
+impl Environment {
+    /// Whether either variable is set to something, so there is a password to take.
+    pub fn is_set() -> bool {
+        Self::source().is_some()
+    }
+
+    /// The variable the password is taken from, or `None` if neither is set to something.
+    pub fn source() -> Option<&'static str> {
+        [PASSWORD_VARIABLE, KEY_FILE_VARIABLE].into_iter().find(|name| variable(name).is_some())
+    }
+}
+
+impl CredentialProvider for Environment {
+    fn password(&self) -> Result<String> {
+        if let Some(password) = variable(PASSWORD_VARIABLE) {
+            return Ok(password.into_string().map_err(|_| format!("{PASSWORD_VARIABLE} is not valid UTF-8"))?);
+        }
+        match variable(KEY_FILE_VARIABLE) {
+            Some(path) => KeyFile(PathBuf::from(path)).password(),
+            None => Err(Box::new(DecodeError::MissingCredentials)),
+        }
+    }
+}
+
+fn variable(name: &str) -> Option<std::ffi::OsString> {
+    env::var_os(name).filter(|value| !value.is_empty())
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// How [`decode_from`] finds and opens a payload, built up from [`DecodeOptions::new`].
+pub struct DecodeOptions {
+    chunk_type: ChunkType,
+    credentials: Option<Box<dyn CredentialProvider>>,
+    verify_key: Option<Vec<u8>>,
+    method: Method,
+}
+
+impl DecodeOptions {
+    /// Options reading the payload of the first chunk of type `chunk_type`, without decrypting it.
+    pub fn new(chunk_type: ChunkType) -> Self {
+        Self { chunk_type, credentials: None, verify_key: None, method: Method::default() }
+    }
+
+    /// Reads the payload from where `method` hid it. [`Method::Lsb`] needs the chunk type IDAT.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Decrypts encrypted payloads with the password `credentials` supplies.
+    pub fn credentials(mut self, credentials: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Some(Box::new(credentials));
+        self
+    }
+
+    /// Shorthand for decrypting with a [`StaticPassword`].
+    pub fn password(self, password: impl Into<String>) -> Self {
+        self.credentials(StaticPassword(password.into()))
+    }
+
+    /// Requires payloads to carry a valid tag under `key`, as added by
+    /// [`EncodeOptions::sign_key`](crate::EncodeOptions::sign_key). Without it tags are ignored.
+    pub fn verify_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.verify_key = Some(key.into());
+        self
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    /// Checks the tag of `envelope` if a verify key is set, and returns the envelope without it.
+    pub fn verify(&self, envelope: Envelope) -> Result<Envelope> {
+        match &self.verify_key {
+            #[cfg(feature = "crypto")]
+            Some(key) => envelope.verify(key),
+            #[cfg(not(feature = "crypto"))]
+            Some(_) => Err(crate::features::Feature::Crypto.missing("verifying signatures").into()),
+            None => Ok(envelope.unsigned()),
+        }
+    }
+
+    /// Returns the payload in `envelope`, asking for a password only if it is encrypted.
+    /// Fails if a verify key is set and the envelope's tag doesn't match it.
+    pub fn open(&self, envelope: Envelope) -> Result<Payload> {
+        self.decrypt(self.verify(envelope)?)
+    }
+
+    /// Returns the payload in an envelope [`DecodeOptions::verify`] already checked.
+    pub(crate) fn decrypt(&self, envelope: Envelope) -> Result<Payload> {
+        #[cfg(feature = "crypto")]
+        if !envelope.is_encrypted() {
+            return envelope.open(None);
+        }
+        match &self.credentials {
+            Some(credentials) => envelope.open(Some(&credentials.password()?)),
+            #[cfg(feature = "crypto")]
+            None => Err(Box::new(DecodeError::MissingCredentials)),
+            #[cfg(not(feature = "crypto"))]
+            None => envelope.open(None),
+        }
+    }
+}
+
+impl fmt::Debug for DecodeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecodeOptions")
+            .field("chunk_type", &self.chunk_type)
+            .field("credentials", &self.credentials.is_some())
+            .field("verify_key", &self.verify_key.is_some())
+            .finish()
+    }
+}
+
+/// Reads the payload hidden in `png` according to `options`, or `None` if there is no chunk
+/// of the type. Payloads split across several chunks are reassembled first.
+pub fn decode_from(png: &Png, options: &DecodeOptions) -> Result<Option<Payload>> {
+    match find_envelope(png, options)? {
+        Some((envelope, _)) => options.open(envelope).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The envelope hidden in `png` and the number of chunks it was read from, or `None` if there
+/// is no chunk of the type or, for [`Method::Lsb`], nothing in the pixels. Without a chunk of
+/// the type, [`Method::Chunk`] looks for a record of it after IEND, as [`Method::Trailer`] does.
+fn find_envelope(png: &Png, options: &DecodeOptions) -> Result<Option<(Envelope, usize)>> {
+    let chunks: Vec<_> = png.chunks().iter().filter(|chunk| chunk.chunk_type() == &options.chunk_type).collect();
+    if options.method == Method::Trailer || (options.method == Method::Chunk && chunks.is_empty()) {
+        let found = crate::appended::find(png.trailer(), &options.chunk_type);
+        return Ok(found.map(|data| (Envelope::from_raw(data), 1)));
+    }
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+    let envelope = match options.method {
+        Method::Chunk | Method::Trailer => Envelope::join(chunks.iter().map(|chunk| chunk.raw_data()))?,
+        Method::Lsb if options.chunk_type.to_string() != "IDAT" => {
+            return Err(Box::new(StegoError::NotIdat(options.chunk_type.clone())));
+        }
+        Method::Lsb => match crate::stego::extract(png)? {
+            Some(data) => Envelope::from_raw(RawChunkData::from(data)),
+            None => return Ok(None),
+        },
+    };
+    Ok(Some((envelope, chunks.len())))
+}
+
+/// What decoding one file of a batch found, for a report over many files.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct DecodedFile {
+    /// JSON holds a path that isn't valid Unicode as NUL and the base64 of its bytes.
+    #[cfg_attr(feature = "json", serde(serialize_with = "crate::os_path::serialize"))]
+    pub file: PathBuf,
+    pub found: bool,
+    pub payload_meta: Option<PayloadMeta>,
+    /// The payload, unless it is encrypted and no password was given. JSON holds it in base64.
+    #[cfg_attr(
+        feature = "json",
+        serde(rename = "payload_b64", serialize_with = "base64", skip_serializing_if = "Option::is_none")
+    )]
+    pub payload: Option<Payload>,
+    /// Why the file couldn't be read or its payload opened.
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "Option::is_none"))]
+    pub error: Option<String>,
+}
+
+/// How a payload is stored. `kind` and `length` are only known once it is opened.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct PayloadMeta {
+    pub chunks: usize,
+    pub encrypted: bool,
+    pub signed: bool,
+    pub kind: Option<PayloadKind>,
+    pub length: Option<usize>,
+}
+
+impl DecodedFile {
+    /// A file that couldn't be read at all.
+    pub fn failed(file: &Path, error: String) -> Self {
+        Self { file: file.to_path_buf(), found: false, payload_meta: None, payload: None, error: Some(error) }
+    }
+}
+
+impl fmt::Display for DecodedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.file.display())?;
+        let Some(meta) = &self.payload_meta else {
+            return match &self.error {
+                Some(error) => write!(f, "error: {error}"),
+                None => write!(f, "not found"),
+            };
+        };
+        match (&self.payload, &self.error) {
+            (_, Some(error)) => write!(f, "error: {error}"),
+            (Some(payload), None) => match std::str::from_utf8(payload.as_bytes()) {
+                Ok(text) => write!(f, "{text}"),
+                Err(_) => write!(f, "{} bytes of binary data", payload.as_bytes().len()),
+            },
+            (None, None) => write!(f, "encrypted payload in {} chunks", meta.chunks),
+        }
+    }
+}
+
+/// Reads the payload of `file` like [`decode_from`], recording what was found instead of failing.
+/// Encrypted payloads are only opened if `options` has credentials.
+pub fn decode_file(file: &Path, png: &Png, options: &DecodeOptions) -> DecodedFile {
+    let mut report = DecodedFile { file: file.to_path_buf(), found: false, payload_meta: None, payload: None, error: None };
+    let (envelope, chunks) = match find_envelope(png, options) {
+        Ok(Some(found)) => found,
+        Ok(None) => return report,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    report.found = true;
+    let mut meta = PayloadMeta { chunks, encrypted: false, signed: envelope.is_signed(), kind: None, length: None };
+    #[cfg(feature = "crypto")]
+    {
+        meta.encrypted = envelope.is_encrypted();
+    }
+    let opened = match meta.encrypted && options.credentials.is_none() {
+        // The tag covers the encrypted payload, so it is checked without the password.
+        true => options.verify(envelope).map(|_| None),
+        false => options.open(envelope).map(Some),
+    };
+    match opened {
+        Ok(payload) => {
+            meta.kind = payload.as_ref().map(Payload::kind);
+            meta.length = payload.as_ref().map(|payload| payload.as_bytes().len());
+            report.payload = payload;
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    report.payload_meta = Some(meta);
+    report
+}
+
+#[cfg(feature = "json")]
+fn base64<S: serde::Serializer>(payload: &Option<Payload>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    use base64ct::Encoding;
+    match payload {
+        Some(payload) => serializer.serialize_str(&base64ct::Base64::encode_string(payload.as_bytes())),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    MissingCredentials,
+    KeyringUnavailable,
+    NoKeyringEntry { service: String, account: String },
+}
+
+impl std::error::Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::MissingCredentials => write!(f, "Payload is encrypted but no password was given"),
+            DecodeError::KeyringUnavailable => write!(f, "No system keyring is available"),
+            DecodeError::NoKeyringEntry { service, account } => {
+                write!(f, "The keyring has no password for service {service}, account {account}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::encode::{encode_into, EncodeOptions};
+    use std::str::FromStr;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_decode_from() {
+        let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13])]);
+        let options = DecodeOptions::new(chunk_type());
+        assert_eq!(decode_from(&png, &options).unwrap(), None);
+
+        let encode = EncodeOptions::new(chunk_type()).part_size(3);
+        encode_into(&mut png, Payload::from("split message".to_string()), &encode).unwrap();
+        // Plain payloads never ask for a password.
+        #[cfg(feature = "crypto")]
+        let options = options.credentials(KeyFile(PathBuf::from("/nonexistent")));
+        assert_eq!(decode_from(&png, &options).unwrap().unwrap().as_bytes(), b"split message");
+    }
+
+    #[test]
+    fn test_decode_trailer() {
+        let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())]);
+        let encode = EncodeOptions::new(chunk_type()).method(Method::Trailer);
+        encode_into(&mut png, Payload::from("appended".to_string()), &encode).unwrap();
+        assert_eq!(png.chunks().len(), 1);
+        // Found without asking for the trailer, since no ruSt chunk is left to read.
+        let payload = decode_from(&png, &DecodeOptions::new(chunk_type())).unwrap().unwrap();
+        assert_eq!(payload.as_bytes(), b"appended");
+        let options = DecodeOptions::new(chunk_type()).method(Method::Trailer);
+        assert_eq!(decode_from(&png, &options).unwrap().unwrap().as_bytes(), b"appended");
+
+        encode_into(&mut png, Payload::from("in a chunk".to_string()), &EncodeOptions::new(chunk_type())).unwrap();
+        let payload = decode_from(&png, &DecodeOptions::new(chunk_type())).unwrap().unwrap();
+        assert_eq!(payload.as_bytes(), b"in a chunk");
+    }
+
+    #[test]
+    fn test_decode_file() {
+        let path = Path::new("image.png");
+        let mut png = Png::from_chunks(Vec::new());
+        let decoded = decode_file(path, &png, &DecodeOptions::new(chunk_type()));
+        assert!(!decoded.found && decoded.payload_meta.is_none() && decoded.error.is_none());
+
+        encode_into(&mut png, Payload::from("hello".to_string()), &EncodeOptions::new(chunk_type())).unwrap();
+        let decoded = decode_file(path, &png, &DecodeOptions::new(chunk_type()));
+        let meta = PayloadMeta { chunks: 1, encrypted: false, signed: false, kind: Some(PayloadKind::Text), length: Some(5) };
+        assert_eq!(decoded.payload_meta, Some(meta));
+        assert_eq!(decoded.to_string(), "image.png: hello");
+        #[cfg(feature = "json")]
+        assert!(serde_json::to_string(&decoded).unwrap().contains(r#""payload_b64":"aGVsbG8=""#));
+    }
+
+    #[test]
+    fn test_key_file() {
+        let path = std::env::temp_dir().join(format!("pngme-decode-key-{}", std::process::id()));
+        fs::write(&path, "hunter2\r\n").unwrap();
+        let password = KeyFile(path.clone()).password();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(password.unwrap(), "hunter2");
+        assert_eq!(trim_line_ending("two\n\n"), "two\n");
+    }
+
+    // The only test that sets the variables, so tests running in parallel don't see them change.
+    #[test]
+    fn test_environment() {
+        let path = std::env::temp_dir().join(format!("pngme-decode-env-key-{}", std::process::id()));
+        fs::write(&path, "from file\n").unwrap();
+        env::set_var(PASSWORD_VARIABLE, "");
+        env::set_var(KEY_FILE_VARIABLE, "");
+        // Empty variables count as unset.
+        assert!(!Environment::is_set());
+        assert!(matches!(Environment.password().unwrap_err().downcast_ref(), Some(DecodeError::MissingCredentials)));
+
+        env::set_var(KEY_FILE_VARIABLE, &path);
+        assert_eq!(Environment::source(), Some(KEY_FILE_VARIABLE));
+        assert_eq!(Environment.password().unwrap(), "from file");
+        // The password itself wins over the file.
+        env::set_var(PASSWORD_VARIABLE, "hunter2");
+        assert_eq!(Environment::source(), Some(PASSWORD_VARIABLE));
+        assert_eq!(Environment.password().unwrap(), "hunter2");
+
+        env::remove_var(PASSWORD_VARIABLE);
+        env::remove_var(KEY_FILE_VARIABLE);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_encrypted_decode() {
+        let mut png = Png::from_chunks(Vec::new());
+        encode_into(&mut png, Payload::from("secret".to_string()), &EncodeOptions::new(chunk_type()).password("hunter2"))
+            .unwrap();
+
+        let error = decode_from(&png, &DecodeOptions::new(chunk_type())).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(DecodeError::MissingCredentials)));
+        let payload = decode_from(&png, &DecodeOptions::new(chunk_type()).password("hunter2")).unwrap();
+        assert_eq!(payload.unwrap().as_bytes(), b"secret");
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_decode_encrypted_file() {
+        let mut png = Png::from_chunks(Vec::new());
+        encode_into(&mut png, Payload::from("secret".to_string()), &EncodeOptions::new(chunk_type()).password("hunter2"))
+            .unwrap();
+        let decoded = decode_file(Path::new("a.png"), &png, &DecodeOptions::new(chunk_type()));
+        assert!(decoded.payload_meta.unwrap().encrypted && decoded.payload.is_none() && decoded.error.is_none());
+        let decoded = decode_file(Path::new("a.png"), &png, &DecodeOptions::new(chunk_type()).password("wrong"));
+        assert!(decoded.error.is_some());
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_signed_decode() {
+        let mut png = Png::from_chunks(Vec::new());
+        let encode = EncodeOptions::new(chunk_type()).part_size(8).sign_key(b"key".to_vec());
+        encode_into(&mut png, Payload::from("watermark".to_string()), &encode).unwrap();
+
+        // Without a verify key the tag is dropped unchecked.
+        assert_eq!(decode_from(&png, &DecodeOptions::new(chunk_type())).unwrap().unwrap().as_bytes(), b"watermark");
+        let options = DecodeOptions::new(chunk_type()).verify_key(b"key".to_vec());
+        assert_eq!(decode_from(&png, &options).unwrap().unwrap().as_bytes(), b"watermark");
+        let decoded = decode_file(Path::new("a.png"), &png, &DecodeOptions::new(chunk_type()).verify_key(b"other".to_vec()));
+        assert!(decoded.payload_meta.unwrap().signed && decoded.error.is_some());
+    }
+}
@@ -0,0 +1,138 @@
+//! Looking for content hidden in a PNG file, for the defensive side of what pngme does.
+
+use std::fmt::{self, Display};
+
+use crate::analysis::entropy;
+use crate::chunk_type::ChunkType;
+use crate::i18n::{format_decimal, format_number};
+use crate::png::Png;
+use crate::text_chunk::TextChunk;
+
+/// Standard ancillary chunks longer than this are reported. ICC profiles and Exif data, the
+/// largest legitimate ones, rarely come close.
+pub const LARGE_ANCILLARY: u32 = 256 * 1024;
+/// Text with more bits of entropy per byte than this reads like encoded data rather than words,
+/// which stay below 5.
+pub const TEXT_ENTROPY: f64 = 5.0;
+/// Data with more bits of entropy per byte than this is likely encrypted or compressed.
+pub const DATA_ENTROPY: f64 = 7.5;
+/// Texts shorter than this are too short for their entropy to say anything.
+const MIN_TEXT_LENGTH: usize = 64;
+
+/// Something in a PNG that may be hiding data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suspicion {
+    /// A chunk type the PNG spec doesn't define, which viewers skip.
+    UnknownChunk { chunk_type: ChunkType, length: u32, entropy: f64 },
+    /// A standard ancillary chunk far larger than its purpose needs.
+    LargeAncillary { chunk_type: ChunkType, length: u32 },
+    /// Bytes after the IEND chunk, which decoders never read.
+    Trailer { length: usize, entropy: f64 },
+    /// Text that reads like encoded data rather than words.
+    EncodedText { chunk_type: ChunkType, keyword: String, entropy: f64 },
+}
+
+impl Suspicion {
+    /// How likely this hides data, from 0 to 100.
+    pub fn risk(&self) -> u8 {
+        match self {
+            Suspicion::UnknownChunk { entropy, .. } if *entropy > DATA_ENTROPY => 80,
+            Suspicion::UnknownChunk { .. } => 50,
+            Suspicion::LargeAncillary { .. } => 30,
+            Suspicion::Trailer { entropy, .. } if *entropy > DATA_ENTROPY => 90,
+            Suspicion::Trailer { .. } => 60,
+            Suspicion::EncodedText { .. } => 70,
+        }
+    }
+}
+
+impl Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = |entropy: f64| format_decimal(entropy, 2);
+        match self {
+            Suspicion::UnknownChunk { chunk_type, length, entropy } => {
+                let scope = if chunk_type.is_public() { "unregistered" } else { "private" };
+                write!(f, "{scope} chunk {chunk_type} of {} bytes, {} bits of entropy per byte", format_number(*length as i64), bits(*entropy))
+            }
+            Suspicion::LargeAncillary { chunk_type, length } => {
+                write!(f, "{chunk_type} chunk of {} bytes, far larger than usual", format_number(*length as i64))
+            }
+            Suspicion::Trailer { length, entropy } => {
+                write!(f, "{} bytes after IEND, {} bits of entropy per byte", format_number(*length as i64), bits(*entropy))
+            }
+            Suspicion::EncodedText { chunk_type, keyword, entropy } => {
+                write!(f, "{chunk_type} text '{keyword}' looks encoded, {} bits of entropy per byte", bits(*entropy))
+            }
+        }
+    }
+}
+
+/// Lists what in `png` may be hiding data, with the index of the chunk each was found in, if
+/// any, by descending risk.
+pub fn detect(png: &Png) -> Vec<(Option<usize>, Suspicion)> {
+    let mut found = Vec::new();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let suspicion = if !chunk_type.is_standard() {
+            Suspicion::UnknownChunk { chunk_type: chunk_type.clone(), length: chunk.length(), entropy: entropy(chunk.data()) }
+        } else if chunk_type.is_critical() {
+            continue;
+        } else if let Ok(text) = TextChunk::from_chunk(chunk) {
+            let entropy = entropy(text.text.as_bytes());
+            if text.text.len() < MIN_TEXT_LENGTH || entropy <= TEXT_ENTROPY {
+                continue;
+            }
+            Suspicion::EncodedText { chunk_type: chunk_type.clone(), keyword: text.keyword.to_string(), entropy }
+        } else if chunk.length() > LARGE_ANCILLARY {
+            Suspicion::LargeAncillary { chunk_type: chunk_type.clone(), length: chunk.length() }
+        } else {
+            continue;
+        };
+        found.push((Some(index), suspicion));
+    }
+    if !png.trailer().is_empty() {
+        found.push((None, Suspicion::Trailer { length: png.trailer().len(), entropy: entropy(png.trailer()) }));
+    }
+    found.sort_by_key(|(_, suspicion)| std::cmp::Reverse(suspicion.risk()));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::keyword::Keyword;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    /// Bytes spread evenly over every value, the most entropy there can be.
+    fn noise(length: usize) -> Vec<u8> {
+        (0..length).map(|n| (n * 167 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let keyword = |k: &str| Keyword::from_str(k).unwrap();
+        let prose = TextChunk::new(keyword("Comment"), "a plain sentence about the picture, long enough to be measured at all").to_chunk().unwrap();
+        let encoded: String = noise(300).iter().map(|b| (b'!' + b % 90) as char).collect();
+        let hidden = TextChunk::new(keyword("Note"), encoded).to_chunk().unwrap();
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            prose,
+            hidden,
+            chunk("iCCP", vec![0; LARGE_ANCILLARY as usize + 1]),
+            chunk("ruSt", noise(1024)),
+            chunk("IDAT", vec![0; 100]),
+            chunk("IEND", Vec::new()),
+        ]);
+        png.set_trailer(b"appended".to_vec()).unwrap();
+
+        let found = detect(&png);
+        let summary: Vec<(Option<usize>, u8)> = found.iter().map(|(index, s)| (*index, s.risk())).collect();
+        assert_eq!(summary, [(Some(4), 80), (Some(2), 70), (None, 60), (Some(3), 30)]);
+        assert!(found[0].1.to_string().starts_with("private chunk ruSt of 1,024 bytes, 8"));
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::i18n::format_number;
+use crate::png::Png;
+
+/// One step of the alignment between the chunks of two PNGs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    /// The chunk at the given index of the first PNG is identical to the one at the given index of the second.
+    Same(usize, usize),
+    /// The chunk at the given index of the first PNG is not in the second.
+    Removed(usize),
+    /// The chunk at the given index of the second PNG is not in the first.
+    Added(usize),
+}
+
+/// Aligns two chunk lists by their longest common subsequence of identical chunks.
+/// Removals are listed before additions wherever chunks were replaced.
+pub fn align(a: &[Chunk], b: &[Chunk]) -> Vec<DiffOp> {
+    align_by(a, b, identical)
+}
+
+/// Aligns two lists like [`align`], with `same` telling which items match.
+pub fn align_by<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> Vec<DiffOp> {
+    // lengths[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if same(&a[i], &b[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && same(&a[i], &b[j]) {
+            ops.push(DiffOp::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            ops.push(DiffOp::Removed(i));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Added(j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// Two chunks are identical when they serialize to the same bytes, including the CRC they were read with.
+fn identical(a: &Chunk, b: &Chunk) -> bool {
+    a.chunk_type() == b.chunk_type()
+        && a.data() == b.data()
+        && a.stored_crc().unwrap_or_else(|| a.crc()) == b.stored_crc().unwrap_or_else(|| b.crc())
+}
+
+/// Where a chunk is in one of the compared PNGs and what it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct ChunkSummary {
+    pub index: usize,
+    pub length: u32,
+    /// The CRC the chunk was read with.
+    pub crc: u32,
+}
+
+impl ChunkSummary {
+    fn of(chunks: &[Chunk], index: usize) -> Self {
+        let chunk = &chunks[index];
+        Self { index, length: chunk.length(), crc: chunk.stored_crc().unwrap_or_else(|| chunk.crc()) }
+    }
+}
+
+impl Display for ChunkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {} bytes crc {:08x}", self.index, format_number(self.length as i64), self.crc)
+    }
+}
+
+/// What happened to a chunk between two PNGs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize), serde(tag = "change", rename_all = "lowercase"))]
+pub enum ChunkChange {
+    Same { chunk_type: ChunkType, original: ChunkSummary, modified: ChunkSummary },
+    Removed { chunk_type: ChunkType, original: ChunkSummary },
+    Added { chunk_type: ChunkType, modified: ChunkSummary },
+    /// A chunk replaced by one of the same type in its place, with other data.
+    Modified { chunk_type: ChunkType, original: ChunkSummary, modified: ChunkSummary },
+}
+
+impl Display for ChunkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkChange::Same { chunk_type, modified, .. } => write!(f, "  {chunk_type} {modified}"),
+            ChunkChange::Removed { chunk_type, original } => write!(f, "- {chunk_type} {original}"),
+            ChunkChange::Added { chunk_type, modified } => write!(f, "+ {chunk_type} {modified}"),
+            ChunkChange::Modified { chunk_type, original, modified } => write!(
+                f,
+                "~ {chunk_type} #{} -> #{}, {} -> {} bytes, crc {:08x} -> {:08x}",
+                original.index,
+                modified.index,
+                format_number(original.length as i64),
+                format_number(modified.length as i64),
+                original.crc,
+                modified.crc
+            ),
+        }
+    }
+}
+
+/// The chunk-level differences between two PNGs, and the lengths of their trailers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct PngDiff {
+    /// Every chunk of both PNGs, in the order of the alignment.
+    pub chunks: Vec<ChunkChange>,
+    pub original_trailer: usize,
+    pub modified_trailer: usize,
+    /// Whether the bytes after the last chunk differ, even if their lengths don't.
+    pub trailer_changed: bool,
+}
+
+impl PngDiff {
+    /// Aligns the chunks of `a` and `b` like [`align`]. Within each run of removed and added
+    /// chunks, the n-th removed and n-th added chunk of a type are paired up as modified.
+    pub fn between(a: &Png, b: &Png) -> Self {
+        let (old, new) = (a.chunks(), b.chunks());
+        let mut chunks = Vec::new();
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        let flush = |chunks: &mut Vec<ChunkChange>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+            for i in removed.drain(..) {
+                let chunk_type = old[i].chunk_type().clone();
+                let original = ChunkSummary::of(old, i);
+                match added.iter().position(|&j| new[j].chunk_type() == &chunk_type) {
+                    Some(position) => {
+                        let modified = ChunkSummary::of(new, added.remove(position));
+                        chunks.push(ChunkChange::Modified { chunk_type, original, modified });
+                    }
+                    None => chunks.push(ChunkChange::Removed { chunk_type, original }),
+                }
+            }
+            for j in added.drain(..) {
+                chunks.push(ChunkChange::Added { chunk_type: new[j].chunk_type().clone(), modified: ChunkSummary::of(new, j) });
+            }
+        };
+        for op in align(old, new) {
+            match op {
+                DiffOp::Removed(i) => removed.push(i),
+                DiffOp::Added(j) => added.push(j),
+                DiffOp::Same(i, j) => {
+                    flush(&mut chunks, &mut removed, &mut added);
+                    let (original, modified) = (ChunkSummary::of(old, i), ChunkSummary::of(new, j));
+                    chunks.push(ChunkChange::Same { chunk_type: old[i].chunk_type().clone(), original, modified });
+                }
+            }
+        }
+        flush(&mut chunks, &mut removed, &mut added);
+        Self {
+            chunks,
+            original_trailer: a.trailer().len(),
+            modified_trailer: b.trailer().len(),
+            trailer_changed: a.trailer() != b.trailer(),
+        }
+    }
+
+    /// Whether the PNGs have the same chunks and trailer.
+    pub fn is_same(&self) -> bool {
+        !self.trailer_changed
+            && self.chunks.iter().all(|change| matches!(change, ChunkChange::Same { .. }))
+    }
+}
+
+/// The lines of `old` and `new` in unified diff format, each change with up to `context`
+/// unchanged lines around it. Only the hunks are included, not the `---`/`+++` header. Empty
+/// if the texts have the same lines.
+pub fn unified(old: &str, new: &str, context: usize) -> String {
+    let (a, b): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    let ops = align_by(&a, &b, |x, y| x == y);
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| !matches!(ops[k], DiffOp::Same(..))).collect();
+    // Lines of each text before every op, to number the hunks.
+    let mut before = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        before.push((i, j));
+        match op {
+            DiffOp::Same(..) => (i, j) = (i + 1, j + 1),
+            DiffOp::Removed(_) => i += 1,
+            DiffOp::Added(_) => j += 1,
+        }
+    }
+    before.push((i, j));
+
+    let mut diff = String::new();
+    let mut next = 0;
+    while next < changes.len() {
+        // A hunk runs until the unchanged lines between two changes are too many to show.
+        let mut last = next;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * context + 1 {
+            last += 1;
+        }
+        let start = changes[next].saturating_sub(context);
+        let end = (changes[last] + context + 1).min(ops.len());
+        let ((old_start, new_start), (old_end, new_end)) = (before[start], before[end]);
+        let range = |start: usize, count: usize| match count {
+            0 => format!("{start},0"),
+            _ => format!("{},{count}", start + 1),
+        };
+        diff.push_str(&format!("@@ -{} +{} @@\n", range(old_start, old_end - old_start), range(new_start, new_end - new_start)));
+        for op in &ops[start..end] {
+            let line = match *op {
+                DiffOp::Same(i, _) => format!(" {}", a[i]),
+                DiffOp::Removed(i) => format!("-{}", a[i]),
+                DiffOp::Added(j) => format!("+{}", b[j]),
+            };
+            diff.push_str(&line);
+            diff.push('\n');
+        }
+        next = last + 1;
+    }
+    diff
+}
+
+/// Where two byte strings differ, for payloads that aren't text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSummary {
+    pub old_length: usize,
+    pub new_length: usize,
+    /// Length of the bytes both start with.
+    pub common_prefix: usize,
+    /// Length of the bytes both end with, not overlapping the common prefix.
+    pub common_suffix: usize,
+}
+
+impl ByteSummary {
+    pub fn between(old: &[u8], new: &[u8]) -> Self {
+        let common_prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let common_suffix = old[common_prefix..]
+            .iter()
+            .rev()
+            .zip(new[common_prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        Self { old_length: old.len(), new_length: new.len(), common_prefix, common_suffix }
+    }
+}
+
+impl Display for ByteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changed = |length: usize| format_number((length - self.common_prefix - self.common_suffix) as i64);
+        write!(
+            f,
+            "{} -> {} bytes: the first {} are the same, then {} bytes became {}, then the last {} are the same",
+            format_number(self.old_length as i64),
+            format_number(self.new_length as i64),
+            format_number(self.common_prefix as i64),
+            changed(self.old_length),
+            changed(self.new_length),
+            format_number(self.common_suffix as i64),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunks(types: &[&str]) -> Vec<Chunk> {
+        types
+            .iter()
+            .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), t.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_align_identical() {
+        let a = chunks(&["IHDR", "IDAT", "IEND"]);
+        let ops = align(&a, &a);
+        assert_eq!(ops, vec![DiffOp::Same(0, 0), DiffOp::Same(1, 1), DiffOp::Same(2, 2)]);
+    }
+
+    #[test]
+    fn test_align_changes() {
+        let a = chunks(&["IHDR", "tEXt", "IDAT", "IEND"]);
+        let b = chunks(&["IHDR", "IDAT", "ruSt", "IEND"]);
+        let ops = align(&a, &b);
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Same(0, 0),
+                DiffOp::Removed(1),
+                DiffOp::Same(2, 1),
+                DiffOp::Added(2),
+                DiffOp::Same(3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_png_diff() {
+        let mut a = chunks(&["IHDR", "tEXt", "IDAT", "IEND"]);
+        let mut b = chunks(&["IHDR", "tEXt", "ruSt", "IEND"]);
+        b[1] = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"changed".to_vec());
+        a.insert(3, Chunk::new(ChunkType::from_str("zTXt").unwrap(), Vec::new()));
+        let diff = PngDiff::between(&Png::from_chunks(a), &Png::from_chunks(b));
+        let shown: Vec<String> = diff.chunks.iter().map(|change| change.to_string()).collect();
+        assert_eq!(shown[0], "  IHDR #0 4 bytes crc 7beb9876");
+        assert!(shown[1].starts_with("~ tEXt #1 -> #1, 4 -> 7 bytes, crc "));
+        assert!(shown[2].starts_with("- IDAT #2 4 bytes"));
+        assert!(shown[3].starts_with("- zTXt #3 0 bytes"));
+        assert!(shown[4].starts_with("+ ruSt #2 4 bytes"));
+        assert!(matches!(diff.chunks[5], ChunkChange::Same { original: ChunkSummary { index: 4, .. }, .. }));
+        assert!(!diff.is_same());
+        let png = Png::from_chunks(chunks(&["IHDR", "IEND"]));
+        assert!(PngDiff::between(&png, &png).is_same());
+    }
+
+    #[test]
+    fn test_unified() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(unified(old, new, 1), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -10,1 +10,2 @@\n j\n+k\n");
+        // Eight unchanged lines between two changes are shown in full with four lines of context.
+        assert!(unified(old, new, 4).starts_with("@@ -1,10 +1,11 @@\n"));
+        assert_eq!(unified(old, old, 3), "");
+        assert_eq!(unified("", "x", 3), "@@ -0,0 +1,1 @@\n+x\n");
+    }
+
+    #[test]
+    fn test_byte_summary() {
+        let summary = ByteSummary::between(b"header-old-footer", b"header-brand-new-footer");
+        assert_eq!((summary.common_prefix, summary.common_suffix), (7, 7));
+        assert_eq!(summary.to_string(), "17 -> 23 bytes: the first 7 are the same, then 3 bytes became 9, then the last 7 are the same");
+        assert_eq!(ByteSummary::between(b"aaa", b"aa").common_suffix, 0);
+    }
+}
@@ -0,0 +1,42 @@
+/// Bytes shown on each row of a `hexdump`.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// Renders `data` as a classic hex dump: each row holds the offset of its first byte, up to
+/// `BYTES_PER_ROW` bytes in hex, split in two groups of eight, and the same bytes as ASCII with
+/// `.` for anything unprintable. Every row ends with a newline; empty data gives no rows.
+pub fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (row, bytes) in data.chunks(BYTES_PER_ROW).enumerate() {
+        let mut hex = String::new();
+        for column in 0..BYTES_PER_ROW {
+            if column == BYTES_PER_ROW / 2 {
+                hex.push(' ');
+            }
+            match bytes.get(column) {
+                Some(byte) => hex.push_str(&format!(" {byte:02x}")),
+                None => hex.push_str("   "),
+            }
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        dump.push_str(&format!("{:08x} {hex}  |{ascii}|\n", row * BYTES_PER_ROW));
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let dump = hexdump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR and more");
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(rows[0], "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|");
+        assert_eq!(rows[1], "00000010  20 61 6e 64 20 6d 6f 72  65                       | and more|");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(hexdump(b""), "");
+    }
+}
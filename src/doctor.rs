@@ -0,0 +1,235 @@
+use std::env;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+use bytes::Bytes;
+
+use crate::i18n::{language, Catalog};
+use crate::png::{ParseOptions, Png};
+use crate::structure::{self, Issue};
+
+/// Signatures of formats often mistaken for PNG, e.g. after a file was renamed.
+const OTHER_FORMATS: [(&[u8], &str); 6] = [
+    (b"\xff\xd8\xff", "JPEG"),
+    (b"GIF8", "GIF"),
+    (b"BM", "BMP"),
+    (b"%PDF", "PDF"),
+    (b"\x8aMNG", "MNG"),
+    (b"PK\x03\x04", "ZIP"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Problem,
+}
+
+/// The outcome of one check made by `doctor`, with what to do about it if anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self { check, status: Status::Ok, detail: detail.into(), suggestion: None }
+    }
+
+    fn warning(check: &'static str, detail: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self { check, status: Status::Warning, detail: detail.into(), suggestion: Some(suggestion.into()) }
+    }
+
+    fn problem(check: &'static str, detail: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self { check, status: Status::Problem, detail: detail.into(), suggestion: Some(suggestion.into()) }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.status {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Problem => "PROBLEM",
+        };
+        write!(f, "{status:<8} {:<11} {}", self.check, self.detail)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n         {:<11} -> {suggestion}", "")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the file at `path` from the outside in: can it be read, is it a PNG, which chunks are
+/// damaged, and does it parse. Stops at the first check the others depend on.
+pub fn check_file(path: &Path) -> Vec<Finding> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let suggestion = match e.kind() {
+                std::io::ErrorKind::NotFound => "check the path, it is relative to the current directory",
+                std::io::ErrorKind::PermissionDenied => "check the file's permissions, or run as its owner",
+                _ => "check that the file is a regular file and the disk is healthy",
+            };
+            return vec![Finding::problem("readable", e.to_string(), suggestion)];
+        }
+    };
+    let mut findings = vec![Finding::ok("readable", format!("{} bytes", bytes.len()))];
+
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        let suggestion = match OTHER_FORMATS.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+            Some((_, format)) => format!("this is a {format} file, convert it to PNG first"),
+            None if bytes.is_empty() => "the file is empty, it may not have been written completely".to_string(),
+            None => "this is not a PNG file, or its first bytes were overwritten".to_string(),
+        };
+        findings.push(Finding::problem("signature", "no PNG signature", suggestion));
+        return findings;
+    }
+    findings.push(Finding::ok("signature", "PNG signature present"));
+
+    let issues = structure::check(&bytes);
+    if issues.is_empty() {
+        findings.push(Finding::ok("structure", "all chunks and CRCs are intact"));
+    }
+    for (offset, issue) in issues {
+        let suggestion = match issue {
+            Issue::CrcMismatch { .. } => "the chunk changed after it was written, restore the file from a copy",
+            Issue::Truncated { .. } | Issue::MissingEnd => {
+                "the file was cut off, e.g. by an interrupted download or copy; fetch it again"
+            }
+            Issue::InvalidType { .. } => "the file is damaged here, use which-chunk with this offset to see where",
+            Issue::DuplicateIhdr | Issue::ChunkAfterEnd { .. } => {
+                "read it with --duplicates keep-first to ignore the extra chunks"
+            }
+            _ => "other PNG tools may reject this file; re-save it with an image editor",
+        };
+        findings.push(Finding::problem("structure", format!("offset {offset}: {issue}"), suggestion));
+    }
+
+    match Png::parse(Bytes::from(bytes), &ParseOptions::default()) {
+        Ok(png) => findings.push(Finding::ok("parse", format!("{} chunks", png.chunks().len()))),
+        Err(e) => findings.push(Finding::problem("parse", e.to_string(), "pngme commands will fail on this file until it is fixed")),
+    }
+    findings
+}
+
+/// Checks that the directory of `output` exists and has room for a file of about `size` bytes.
+pub fn check_output(output: &Path, size: u64) -> Finding {
+    let directory = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !directory.is_dir() {
+        return Finding::problem("output", format!("{} is not a directory", directory.display()), "create it first");
+    }
+    match free_space(directory) {
+        Some(free) if free < size => Finding::problem(
+            "output",
+            format!("{free} bytes free in {}, about {size} needed", directory.display()),
+            "free some space or write the output to another disk",
+        ),
+        Some(free) => Finding::ok("output", format!("{free} bytes free in {}", directory.display())),
+        None => Finding::warning(
+            "output",
+            format!("couldn't tell the free space in {}", directory.display()),
+            "make sure the disk has room for a copy of the file",
+        ),
+    }
+}
+
+/// Free bytes on the file system holding `directory`, as reported by `df`.
+fn free_space(directory: &Path) -> Option<u64> {
+    let output = Command::new("df").arg("-Pk").arg(directory).output().ok()?;
+    let report = String::from_utf8(output.stdout).ok()?;
+    let available: u64 = report.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available * 1024)
+}
+
+/// Checks what affects how pngme talks to the user: language, terminal and compiled features.
+pub fn check_environment() -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let language = language();
+    // The C and POSIX locales ask for untranslated messages.
+    if ["", "c", "posix"].contains(&language.as_str()) {
+        findings.push(Finding::ok("language", "messages in en"));
+    } else if Catalog::builtin(&language).is_some() {
+        findings.push(Finding::ok("language", format!("messages in {language}")));
+    } else {
+        findings.push(Finding::warning(
+            "language",
+            format!("no translation for {language:?}, messages are in English"),
+            "set PNGME_LANG to en or de",
+        ));
+    }
+
+    match env::var("TERM") {
+        Ok(term) if term == "dumb" => {
+            findings.push(Finding::ok("terminal", "TERM=dumb, output is plain"));
+        }
+        Ok(term) => findings.push(Finding::ok("terminal", format!("TERM={term}"))),
+        Err(_) if std::io::stdout().is_terminal() => findings.push(Finding::warning(
+            "terminal",
+            "TERM is not set",
+            "pass --plain if chunks are not shown correctly",
+        )),
+        Err(_) => findings.push(Finding::ok("terminal", "output is not a terminal")),
+    }
+
+    if cfg!(feature = "crypto") {
+        findings.push(Finding::ok("features", "built with encryption and signatures"));
+    } else {
+        findings.push(Finding::warning(
+            "features",
+            "built without the crypto feature",
+            "rebuild with default features to use --password, manifests and provenance",
+        ));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn write_file(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("pngme-doctor-{}-{name}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_check_file() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        let bytes = png.as_bytes();
+        let path = write_file("valid.png", &bytes);
+        assert!(check_file(&path).iter().all(|finding| finding.status == Status::Ok));
+        fs::remove_file(path).unwrap();
+
+        let path = write_file("cut.png", &bytes[..bytes.len() - 6]);
+        let findings = check_file(&path);
+        assert!(findings.iter().any(|finding| finding.check == "structure" && finding.status == Status::Problem));
+        assert_eq!(findings.last().unwrap().status, Status::Problem);
+        fs::remove_file(path).unwrap();
+
+        let path = write_file("photo.png", b"\xff\xd8\xff\xe0 jpeg data");
+        let findings = check_file(&path);
+        assert!(findings.last().unwrap().suggestion.as_ref().unwrap().contains("JPEG"));
+        fs::remove_file(path).unwrap();
+
+        let findings = check_file(Path::new("/nonexistent/pngme-doctor.png"));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].status, Status::Problem);
+    }
+}
@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::keyword::TEXT_CHUNK_TYPES;
+use crate::payload::{Envelope, Payload, RawChunkData, DEFAULT_PART_SIZE};
+use crate::png::Png;
+use crate::stego::{Method, StegoError};
+use crate::Result;
+
+/// What happens to chunks of the target type already in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplacePolicy {
+    /// Leave them in place and add the new chunk after them.
+    #[default]
+    Keep,
+    /// Remove them before adding the new chunk.
+    Replace,
+}
+
+/// Where [`encode_into`] puts the new chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Position {
+    /// After the last chunk, even IEND. Most decoders read such chunks, some complain.
+    #[default]
+    End,
+    /// Just before IEND, where the spec wants ancillary chunks.
+    BeforeIend,
+    /// Just after IHDR, where decoders that stop early still find them.
+    AfterIhdr,
+    /// At this index in the chunk list, counting from 0.
+    Index(usize),
+}
+
+impl Position {
+    /// The index in `chunks` new chunks go to. Falls back to the end without an IEND, and to
+    /// the start without an IHDR.
+    pub fn index(self, chunks: &[Chunk]) -> usize {
+        let find = |name: &str| chunks.iter().position(|chunk| chunk.chunk_type().to_string() == name);
+        match self {
+            Position::End => chunks.len(),
+            Position::BeforeIend => find("IEND").unwrap_or(chunks.len()),
+            Position::AfterIhdr => find("IHDR").map_or(0, |index| index + 1),
+            Position::Index(index) => index,
+        }
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "end" => Ok(Position::End),
+            "before-iend" => Ok(Position::BeforeIend),
+            "after-ihdr" => Ok(Position::AfterIhdr),
+            _ => s.parse().map(Position::Index).map_err(|_| {
+                format!("Unknown position '{s}', expected one of: end, before-iend, after-ihdr, or a chunk index")
+            }),
+        }
+    }
+}
+
+/// How [`encode_into`] hides a payload, built up from [`EncodeOptions::new`]:
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use pngme_rs::{ChunkType, EncodeOptions, ReplacePolicy};
+/// let options = EncodeOptions::new(ChunkType::from_str("ruSt").unwrap()).replace(ReplacePolicy::Replace);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeOptions {
+    chunk_type: ChunkType,
+    password: Option<String>,
+    sign_key: Option<Vec<u8>>,
+    replace: ReplacePolicy,
+    part_size: usize,
+    method: Method,
+    position: Position,
+}
+
+impl EncodeOptions {
+    /// Options storing the payload as is in a new chunk of type `chunk_type`.
+    pub fn new(chunk_type: ChunkType) -> Self {
+        Self {
+            chunk_type,
+            password: None,
+            sign_key: None,
+            replace: ReplacePolicy::default(),
+            part_size: DEFAULT_PART_SIZE,
+            method: Method::default(),
+            position: Position::default(),
+        }
+    }
+
+    /// Encrypts the payload with a key derived from `password`.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Adds an HMAC-SHA256 tag of the stored payload under `key`, checked by
+    /// [`DecodeOptions::verify_key`](crate::DecodeOptions::verify_key).
+    pub fn sign_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.sign_key = Some(key.into());
+        self
+    }
+
+    pub fn replace(mut self, replace: ReplacePolicy) -> Self {
+        self.replace = replace;
+        self
+    }
+
+    /// Splits payloads larger than `part_size` bytes across several chunks of the type.
+    /// Defaults to [`DEFAULT_PART_SIZE`].
+    pub fn part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size;
+        self
+    }
+
+    /// Hides the payload where `method` says. [`Method::Lsb`] needs the chunk type IDAT and
+    /// ignores the replace policy and part size. [`Method::Trailer`] ignores the part size and
+    /// position.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+}
+
+/// Hides `payload` in `png` according to `options` and returns the envelope it was stored as,
+/// before it was split into chunks.
+pub fn encode_into(png: &mut Png, payload: Payload, options: &EncodeOptions) -> Result<RawChunkData> {
+    let is_text_chunk = TEXT_CHUNK_TYPES.contains(&options.chunk_type.to_string().as_str());
+    let envelope = match &options.password {
+        // Text chunks must stay readable by other PNG tools.
+        Some(_) if is_text_chunk => {
+            return Err(Box::new(EncodeError::EncryptedTextChunk(options.chunk_type.clone())));
+        }
+        #[cfg(feature = "crypto")]
+        Some(password) => Envelope::encrypt(&payload, password)?,
+        #[cfg(not(feature = "crypto"))]
+        Some(_) => return Err(crate::features::Feature::Crypto.missing("encryption").into()),
+        None => Envelope::plain(payload),
+    };
+    let envelope = match &options.sign_key {
+        Some(_) if is_text_chunk => return Err(Box::new(EncodeError::SignedTextChunk(options.chunk_type.clone()))),
+        #[cfg(feature = "crypto")]
+        Some(key) => envelope.sign(key),
+        #[cfg(not(feature = "crypto"))]
+        Some(_) => return Err(crate::features::Feature::Crypto.missing("signing").into()),
+        None => envelope,
+    };
+    if options.method == Method::Lsb {
+        if options.chunk_type.to_string() != "IDAT" {
+            return Err(Box::new(StegoError::NotIdat(options.chunk_type.clone())));
+        }
+        let stored = envelope.into_raw();
+        crate::stego::embed(png, stored.as_bytes())?;
+        return Ok(stored);
+    }
+    if options.method == Method::Trailer {
+        let stored = envelope.into_raw();
+        crate::appended::append(png, &options.chunk_type, stored.as_bytes(), options.replace == ReplacePolicy::Replace)?;
+        return Ok(stored);
+    }
+    if options.replace == ReplacePolicy::Replace {
+        png.retain_chunks(|chunk| chunk.chunk_type() != &options.chunk_type)?;
+    }
+    let stored = envelope.clone().into_raw();
+    let index = options.position.index(png.chunks());
+    for (offset, part) in envelope.split(options.part_size).into_iter().enumerate() {
+        png.insert_chunk(index + offset, Chunk::new(options.chunk_type.clone(), part))?;
+    }
+    Ok(stored)
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    EncryptedTextChunk(ChunkType),
+    SignedTextChunk(ChunkType),
+}
+
+impl std::error::Error for EncodeError {}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::EncryptedTextChunk(chunk_type) => {
+                write!(f, "{chunk_type} chunks hold text for other PNG tools and can't be encrypted")
+            }
+            EncodeError::SignedTextChunk(chunk_type) => {
+                write!(f, "{chunk_type} chunks hold text for other PNG tools and can't be signed")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let mut png = Png::from_chunks(vec![Chunk::new(chunk_type("IHDR"), vec![0; 13])]);
+        let options = EncodeOptions::new(chunk_type("ruSt"));
+        encode_into(&mut png, Payload::from("first".to_string()), &options).unwrap();
+        let stored = encode_into(&mut png, Payload::from("second".to_string()), &options).unwrap();
+        assert_eq!(stored.as_bytes(), b"second");
+        assert_eq!(png.chunks().len(), 3);
+
+        let options = options.replace(ReplacePolicy::Replace);
+        encode_into(&mut png, Payload::from("third".to_string()), &options).unwrap();
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"third");
+    }
+
+    #[test]
+    fn test_split_encode() {
+        let mut png = Png::from_chunks(Vec::new());
+        let options = EncodeOptions::new(chunk_type("ruSt")).part_size(4);
+        let stored = encode_into(&mut png, Payload::from("split payload".to_string()), &options).unwrap();
+        assert_eq!(stored.as_bytes(), b"split payload");
+        assert_eq!(png.chunks().len(), 4);
+        let parts = png.chunks().iter().map(|chunk| chunk.raw_data());
+        assert_eq!(Envelope::join(parts).unwrap().into_raw(), stored);
+    }
+
+    #[test]
+    fn test_position() {
+        let types = |png: &Png| png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect::<Vec<_>>();
+        let mut png = Png::from_chunks(vec![Chunk::new(chunk_type("IHDR"), vec![0; 13]), Chunk::new(chunk_type("IEND"), vec![])]);
+        let options = EncodeOptions::new(chunk_type("ruSt")).part_size(4).position(Position::BeforeIend);
+        encode_into(&mut png, Payload::from("two parts".to_string()), &options).unwrap();
+        assert_eq!(types(&png), ["IHDR", "ruSt", "ruSt", "ruSt", "IEND"]);
+
+        let options = EncodeOptions::new(chunk_type("tEXt")).position(Position::from_str("after-ihdr").unwrap());
+        encode_into(&mut png, Payload::from("Comment".to_string()), &options).unwrap();
+        assert_eq!(types(&png)[..2], ["IHDR", "tEXt"]);
+        assert_eq!(Position::from_str("3"), Ok(Position::Index(3)));
+        let options = EncodeOptions::new(chunk_type("ruSt")).position(Position::Index(9));
+        assert!(encode_into(&mut png, Payload::from("x".to_string()), &options).is_err());
+    }
+
+    #[test]
+    fn test_text_chunks_are_not_encrypted() {
+        let mut png = Png::from_chunks(Vec::new());
+        let options = EncodeOptions::new(chunk_type("tEXt")).password("hunter2");
+        let error = encode_into(&mut png, Payload::from("Comment\0hi".to_string()), &options).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EncodeError::EncryptedTextChunk(_))));
+        let options = EncodeOptions::new(chunk_type("tEXt")).sign_key(b"key".to_vec());
+        let error = encode_into(&mut png, Payload::from("Comment\0hi".to_string()), &options).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EncodeError::SignedTextChunk(_))));
+        assert!(png.chunks().is_empty());
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_encrypted_encode() {
+        let mut png = Png::from_chunks(Vec::new());
+        let options = EncodeOptions::new(chunk_type("ruSt")).password("hunter2");
+        let stored = encode_into(&mut png, Payload::from("secret".to_string()), &options).unwrap();
+        let payload = Envelope::from_raw(stored).open(Some("hunter2")).unwrap();
+        assert_eq!(payload.as_bytes(), b"secret");
+    }
+}
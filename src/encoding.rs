@@ -0,0 +1,105 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use base64ct::{Base64, Encoding as _};
+
+/// How binary data is spelled as text on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// The text is the data.
+    #[default]
+    Utf8,
+    /// Standard base64 with padding.
+    Base64,
+    /// Two hex digits per byte, in either case.
+    Hex,
+}
+
+impl Encoding {
+    /// The names accepted by `from_str`.
+    pub const NAMES: [&'static str; 3] = ["utf8", "base64", "hex"];
+
+    /// The bytes `text` stands for. Whitespace between base64 or hex digits is ignored, so
+    /// wrapped output can be pasted back.
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>, EncodingError> {
+        let digits = || text.chars().filter(|c| !c.is_ascii_whitespace()).collect::<String>();
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Base64 => Base64::decode_vec(&digits()).map_err(|_| EncodingError::InvalidBase64),
+            Encoding::Hex => {
+                let digits = digits();
+                if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+                    return Err(EncodingError::InvalidHex);
+                }
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| EncodingError::InvalidHex))
+                    .collect()
+            }
+        }
+    }
+
+    /// `bytes` as text. Bytes that aren't UTF-8 are replaced for `Utf8`.
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Base64 => Base64::encode_string(bytes),
+            Encoding::Hex => bytes.iter().map(|byte| format!("{byte:02x}")).collect(),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(Encoding::Utf8),
+            "base64" => Ok(Encoding::Base64),
+            "hex" => Ok(Encoding::Hex),
+            _ => Err(format!("Unknown encoding '{s}', expected one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    InvalidBase64,
+    InvalidHex,
+}
+
+impl std::error::Error for EncodingError {}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidBase64 => write!(f, "Message is not valid base64"),
+            EncodingError::InvalidHex => write!(f, "Message is not valid hex, which needs two digits per byte"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let bytes = [0, 1, 0xfe, 0xff, b'a'];
+        for encoding in [Encoding::Base64, Encoding::Hex] {
+            assert_eq!(encoding.decode(&encoding.encode(&bytes)).unwrap(), bytes);
+        }
+        assert_eq!(Encoding::Hex.encode(&bytes), "0001feff61");
+        assert_eq!(Encoding::Base64.encode(b"hi"), "aGk=");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Encoding::Hex.decode("DE ad\nbe ef").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(Encoding::Base64.decode("aGVs\nbG8=").unwrap(), b"hello");
+        assert_eq!(Encoding::Utf8.decode("héllo").unwrap(), "héllo".as_bytes());
+        assert!(matches!(Encoding::Hex.decode("abc"), Err(EncodingError::InvalidHex)));
+        assert!(matches!(Encoding::Hex.decode("zz"), Err(EncodingError::InvalidHex)));
+        assert!(matches!(Encoding::Base64.decode("a!=="), Err(EncodingError::InvalidBase64)));
+        assert!(Encoding::from_str("rot13").is_err());
+    }
+}
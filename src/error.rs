@@ -0,0 +1,298 @@
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+use crate::commands::CommandError;
+#[cfg(feature = "crypto")]
+use crate::crypto::CryptoError;
+use crate::decode::DecodeError;
+use crate::encode::EncodeError;
+use crate::keyword::KeywordError;
+use crate::known_chunks::KnownChunkError;
+use crate::patch::PatchError;
+use crate::payload::PayloadError;
+use crate::png::PngError;
+#[cfg(feature = "crypto")]
+use crate::provenance::ProvenanceError;
+use crate::query::QueryError;
+use crate::stego::StegoError;
+use crate::inflate::InflateError;
+use crate::encoding::EncodingError;
+use crate::features::FeatureError;
+use crate::lock::LockError;
+use crate::text_chunk::TextChunkError;
+use crate::Error;
+#[cfg(feature = "crypto")]
+use crate::rekey::RekeyError;
+#[cfg(feature = "crypto")]
+use crate::seal::SealError;
+#[cfg(feature = "crypto")]
+use crate::sign::SignError;
+
+/// The errors of the PNG core and the commands, for callers that want to match on what went
+/// wrong instead of printing it. Errors of the other modules, such as encryption or the JSON
+/// index, are kept as they are in [`PngMeError::Other`].
+#[derive(Debug, thiserror::Error)]
+pub enum PngMeError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A chunk can't be read, e.g. its CRC doesn't match.
+    #[error(transparent)]
+    Chunk(#[from] ChunkError),
+    #[error(transparent)]
+    ChunkType(#[from] ChunkTypeError),
+    /// The file isn't a valid PNG, e.g. its header is wrong.
+    #[error(transparent)]
+    Png(#[from] PngError),
+    /// A command failed, e.g. the requested chunk is missing.
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("{0}")]
+    Other(Error),
+}
+
+impl PngMeError {
+    /// The error this one wraps, for looking at its type.
+    pub fn inner(&self) -> &(dyn std::error::Error + 'static) {
+        match self {
+            PngMeError::Io(error) => error,
+            PngMeError::Chunk(error) => error,
+            PngMeError::ChunkType(error) => error,
+            PngMeError::Png(error) => error,
+            PngMeError::Command(error) => error,
+            PngMeError::Other(error) => &**error,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::of(self)
+    }
+}
+
+/// Keeps the type of boxed errors from the other modules, unwrapping those that have a variant.
+impl From<Error> for PngMeError {
+    fn from(error: Error) -> Self {
+        let error = match error.downcast::<PngMeError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(error) => return PngMeError::Io(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ChunkError>() {
+            Ok(error) => return PngMeError::Chunk(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ChunkTypeError>() {
+            Ok(error) => return PngMeError::ChunkType(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<PngError>() {
+            Ok(error) => return PngMeError::Png(*error),
+            Err(error) => error,
+        };
+        match error.downcast::<CommandError>() {
+            Ok(error) => PngMeError::Command(*error),
+            Err(error) => PngMeError::Other(error),
+        }
+    }
+}
+
+impl From<FeatureError> for PngMeError {
+    fn from(error: FeatureError) -> Self {
+        PngMeError::Other(Box::new(error))
+    }
+}
+
+impl From<&str> for PngMeError {
+    fn from(message: &str) -> Self {
+        PngMeError::Other(message.into())
+    }
+}
+
+impl From<String> for PngMeError {
+    fn from(message: String) -> Self {
+        PngMeError::Other(message.into())
+    }
+}
+
+/// What kind of failure an error is, which decides the exit code of `pngme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Anything not covered below, such as a batch with failed files.
+    Other,
+    /// The arguments can't be used, e.g. a malformed keyword or query.
+    Usage,
+    /// A file couldn't be read or written.
+    Io,
+    /// The input isn't a well-formed PNG, chunk or payload.
+    InvalidData,
+    /// The file has no chunk of the requested type.
+    NotFound,
+    /// A password is missing or wrong, or a signature doesn't match.
+    Credentials,
+    /// The command ran longer than `--timeout` allowed.
+    Timeout,
+}
+
+impl ErrorKind {
+    /// Classifies `error` by its type and, where the variants differ, by its variant.
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<PngMeError>() {
+            return Self::of(error.inner());
+        }
+        if error.is::<std::io::Error>() {
+            return ErrorKind::Io;
+        }
+        if let Some(PngError::IndexOutOfRange { .. }) = error.downcast_ref() {
+            return ErrorKind::Usage;
+        }
+        if error.is::<PngError>()
+        || error.is::<ChunkError>()
+        || error.is::<ChunkTypeError>()
+        || error.is::<PayloadError>()
+        || error.is::<KnownChunkError>()
+        || error.is::<InflateError>()
+    {
+            return ErrorKind::InvalidData;
+        }
+        if error.is::<KeywordError>()
+            || error.is::<QueryError>()
+            || error.is::<EncodeError>()
+            || error.is::<EncodingError>()
+            || error.is::<FeatureError>()
+        {
+            return ErrorKind::Usage;
+        }
+        if let Some(error) = error.downcast_ref::<CommandError>() {
+            return match error {
+                CommandError::ChunkNotFound { .. }
+                | CommandError::ChunkTypesNotFound { .. }
+                | CommandError::ChunkIndexNotFound { .. }
+                | CommandError::NothingHidden(_)
+                | CommandError::NoTrailer(_) => ErrorKind::NotFound,
+                CommandError::OutputForBatch
+                | CommandError::SingleFileOptions
+                | CommandError::PasswordMismatch
+                | CommandError::PasswordStdinConflict
+                | CommandError::NotInternational(_)
+                | CommandError::CriticalChunk(_) => ErrorKind::Usage,
+                CommandError::TimedOut(_) => ErrorKind::Timeout,
+                _ => ErrorKind::Other,
+            };
+        }
+        if let Some(error) = error.downcast_ref::<LockError>() {
+            return match error {
+                LockError::Locked { .. } => ErrorKind::Usage,
+                LockError::Malformed => ErrorKind::InvalidData,
+            };
+        }
+        if let Some(error) = error.downcast_ref::<PatchError>() {
+            return match error {
+                PatchError::Mismatch(_) => ErrorKind::InvalidData,
+                PatchError::InvalidLine(_) => ErrorKind::Usage,
+            };
+        }
+        if let Some(error) = error.downcast_ref::<TextChunkError>() {
+            return match error {
+                TextChunkError::NotLatin1 { .. } => ErrorKind::Usage,
+                _ => ErrorKind::InvalidData,
+            };
+        }
+        if let Some(error) = error.downcast_ref::<StegoError>() {
+            return match error {
+                StegoError::MissingHeader | StegoError::CorruptImage => ErrorKind::InvalidData,
+                StegoError::NotIdat(_) | StegoError::Unsupported(_) | StegoError::TooLarge { .. } => ErrorKind::Usage,
+            };
+        }
+        if error.is::<DecodeError>() {
+            return ErrorKind::Credentials;
+        }
+        #[cfg(feature = "crypto")]
+        if let Some(kind) = Self::of_crypto(error) {
+            return kind;
+        }
+        ErrorKind::Other
+    }
+
+    #[cfg(feature = "crypto")]
+    fn of_crypto(error: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if error.is::<ProvenanceError>() {
+            return Some(ErrorKind::Credentials);
+        }
+        if let Some(error) = error.downcast_ref::<SignError>() {
+            return Some(match error {
+                SignError::EmptyKey => ErrorKind::Usage,
+                SignError::Unsigned => ErrorKind::InvalidData,
+                SignError::Mismatch => ErrorKind::Credentials,
+            });
+        }
+        if let Some(error) = error.downcast_ref::<SealError>() {
+            return Some(match error {
+                SealError::Missing => ErrorKind::NotFound,
+                SealError::Invalid => ErrorKind::Credentials,
+            });
+        }
+        if let Some(RekeyError::Signed(_)) = error.downcast_ref::<RekeyError>() {
+            return Some(ErrorKind::Usage);
+        }
+        Some(match error.downcast_ref::<CryptoError>()? {
+            CryptoError::WrongPassword => ErrorKind::Credentials,
+            CryptoError::NotEncrypted | CryptoError::UnsupportedVersion(_) | CryptoError::Truncated => {
+                ErrorKind::InvalidData
+            }
+            CryptoError::KeyDerivation | CryptoError::Encryption | CryptoError::Random => ErrorKind::Other,
+        })
+    }
+
+    /// The process exit code for this kind. 2 matches what clap uses for bad arguments.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Io => 3,
+            ErrorKind::InvalidData => 4,
+            ErrorKind::NotFound => 5,
+            ErrorKind::Credentials => 6,
+            ErrorKind::Timeout => 7,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use crate::chunk_type::ChunkType;
+
+    #[test]
+    fn test_kinds() {
+        let kind = |error: Error| ErrorKind::of(&*error);
+        assert_eq!(kind(Box::new(std::io::Error::other("disk full"))), ErrorKind::Io);
+        assert_eq!(kind(Box::new(PngError::InvalidHeader)), ErrorKind::InvalidData);
+        assert_eq!(kind(Box::new(KeywordError::Empty)), ErrorKind::Usage);
+        let not_found = CommandError::ChunkNotFound { path: PathBuf::from("a.png"), chunk_type: ChunkType::from_str("ruSt").unwrap() };
+        assert_eq!(kind(Box::new(not_found)), ErrorKind::NotFound);
+        assert_eq!(kind(Box::new(DecodeError::MissingCredentials)), ErrorKind::Credentials);
+        assert_eq!(kind("built without a feature".into()), ErrorKind::Other);
+        assert_eq!(kind(Box::new(crate::features::Feature::Sqlite.missing("--output"))), ErrorKind::Usage);
+        assert_eq!(kind(Box::new(CommandError::TimedOut(1.5))), ErrorKind::Timeout);
+        assert_eq!(kind(Box::new(CommandError::InvalidFiles(2))).exit_code(), 1);
+        assert_eq!(kind(Box::new(CommandError::PasswordStdinConflict)), ErrorKind::Usage);
+        assert_eq!(ErrorKind::Usage.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_from_boxed_error() {
+        let error = PngMeError::from(Box::new(ChunkError::InvalidCrc) as Error);
+        assert!(matches!(error, PngMeError::Chunk(ChunkError::InvalidCrc)));
+        let wrapped: Error = Box::new(PngMeError::Png(PngError::InvalidHeader));
+        assert!(matches!(PngMeError::from(wrapped), PngMeError::Png(PngError::InvalidHeader)));
+        assert!(matches!(PngMeError::from(Box::new(KeywordError::Empty) as Error), PngMeError::Other(_)));
+
+        let error = PngMeError::from(PngError::InvalidHeader);
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(crate::i18n::error_code(&error), "png-invalid-header");
+        assert_eq!(error.to_string(), PngError::InvalidHeader.to_string());
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use crate::scan::ChunkRecord;
+
+/// Progress of a command working through many files, such as `scan`, `fsck` or `corpus-stats`.
+/// The CLI prints its log from these events; front-ends can pass their own [`EventSink`] to the
+/// `*_with` functions in [`crate::commands`] to follow the same stream.
+/// With several `--jobs`, each file's events arrive together once it is done, in the order files finish.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    FileStarted { path: PathBuf },
+    /// A chunk was read from the file. `index` is its position in the file.
+    ChunkParsed { path: PathBuf, index: usize, record: ChunkRecord },
+    /// A chunk the command is looking for, e.g. one matching the `scan` filter.
+    PayloadFound { path: PathBuf, index: usize, record: ChunkRecord },
+    /// The file is done, with the number of `PayloadFound` events it produced.
+    FileFinished { path: PathBuf, payloads: usize },
+    /// Something is wrong with the file, e.g. it is unreadable or a payload is damaged.
+    Warning { path: PathBuf, message: String },
+}
+
+/// Receives the events of a command as they happen.
+pub trait EventSink {
+    fn emit(&mut self, event: Event);
+}
+
+impl<F: FnMut(Event)> EventSink for F {
+    fn emit(&mut self, event: Event) {
+        self(event)
+    }
+}
+
+/// Forwards events to another thread. Events are dropped once the receiver is gone.
+pub struct ChannelSink(pub Sender<Event>);
+
+impl EventSink for ChannelSink {
+    fn emit(&mut self, event: Event) {
+        let _ = self.0.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_sinks() {
+        let path = PathBuf::from("image.png");
+        let mut seen = Vec::new();
+        let mut collect = |event| seen.push(event);
+        collect.emit(Event::FileStarted { path: path.clone() });
+        assert_eq!(seen, vec![Event::FileStarted { path: path.clone() }]);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut sink = ChannelSink(sender);
+        sink.emit(Event::FileFinished { path: path.clone(), payloads: 2 });
+        drop(receiver);
+        sink.emit(Event::FileStarted { path });
+    }
+}
@@ -0,0 +1,147 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::encode::{encode_into, EncodeOptions};
+use crate::keyword::Keyword;
+use crate::payload::Payload;
+use crate::png::Png;
+use crate::stego::Method;
+use crate::text_chunk::{TextChunk, TextKind};
+use crate::Result;
+
+/// Width and height of the image every example hides its payload in.
+const SIZE: u32 = 48;
+
+/// Password of the encrypted example.
+#[cfg(feature = "crypto")]
+pub const PASSWORD: &str = "workshop";
+
+/// A demo image and how to get its hidden payload back out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The hidden payload, as text.
+    pub payload: String,
+    /// The `pngme` arguments that reveal the payload, after the file name.
+    pub decode_args: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Builds the example gallery, hiding a payload in the same image once for every way pngme can
+/// store one. The encrypted example needs the `crypto` feature.
+pub fn generate() -> Result<Vec<Example>> {
+    let mut examples = Vec::new();
+    let mut add = |name, description, payload: &str, decode_args: &str, options: EncodeOptions, data: Vec<u8>| -> Result<()> {
+        let mut png = image()?;
+        encode_into(&mut png, Payload::new(data), &options)?;
+        let (payload, decode_args) = (payload.to_string(), decode_args.to_string());
+        examples.push(Example { name, description, payload, decode_args, bytes: png.as_bytes() });
+        Ok(())
+    };
+    let private = chunk_type("ruSt");
+
+    let plain = "Hello from pngme! This message sits in a private ruSt chunk.";
+    add("plain.png", "A message in a chunk of its own", plain, "ruSt", EncodeOptions::new(private.clone()), plain.into())?;
+
+    let compressed = format!("Compressed text: {}", ["pngme"; 20].join(" "));
+    let text = TextChunk::new(Keyword::from_str("Comment")?, compressed.as_str()).kind(TextKind::Compressed);
+    add("compressed.png", "A message deflated into a standard zTXt chunk", &compressed, "zTXt", EncodeOptions::new(chunk_type("zTXt")), text.to_data()?)?;
+
+    #[cfg(feature = "crypto")]
+    {
+        let secret = "Only people with the password can read this.";
+        let options = EncodeOptions::new(private.clone()).password(PASSWORD);
+        let decode_args = format!("ruSt --password {PASSWORD}");
+        add("encrypted.png", "A message encrypted with a password", secret, &decode_args, options, secret.into())?;
+    }
+
+    let split: String = (1..=40).map(|line| format!("Line {line} of a payload too long for one chunk.\n")).collect();
+    let options = EncodeOptions::new(private).part_size(512);
+    add("split.png", "A long message split across several ruSt chunks", &split, "ruSt", options, split.clone().into())?;
+
+    let lsb = "Hidden in the lowest bit of each color sample; there is no extra chunk.";
+    let options = EncodeOptions::new(chunk_type("IDAT")).method(Method::Lsb);
+    add("lsb.png", "A message in the pixels themselves", lsb, "IDAT --method lsb", options, lsb.into())?;
+
+    Ok(examples)
+}
+
+/// An index of `examples` telling what each file hides and how to reveal it.
+pub fn readme(examples: &[Example]) -> String {
+    let mut readme = "pngme example gallery\n\nEach image hides a payload; the command after it reveals it.\n".to_string();
+    for example in examples {
+        readme.push_str(&format!("\n{}: {}\n", example.name, example.description));
+        readme.push_str(&format!("  pngme decode {} {}\n", example.name, example.decode_args));
+        for line in example.payload.lines().take(3) {
+            readme.push_str(&format!("  > {line}\n"));
+        }
+    }
+    readme
+}
+
+/// An 8-bit RGB gradient, with room in its pixels for the LSB example.
+fn image() -> Result<Png> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&SIZE.to_be_bytes());
+    header.extend_from_slice(&SIZE.to_be_bytes());
+    // Bit depth 8, truecolor, deflate, adaptive filtering, no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    let mut scanlines = Vec::new();
+    for y in 0..SIZE {
+        scanlines.push(0);
+        for x in 0..SIZE {
+            scanlines.extend([(x * 255 / SIZE) as u8, (y * 255 / SIZE) as u8, 160]);
+        }
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&scanlines)?;
+    Ok(Png::from_chunks(vec![
+        Chunk::new(chunk_type("IHDR"), header),
+        Chunk::new(chunk_type("IDAT"), encoder.finish()?),
+        Chunk::new(chunk_type("IEND"), Vec::new()),
+    ]))
+}
+
+fn chunk_type(chunk_type: &str) -> ChunkType {
+    ChunkType::from_str(chunk_type).expect("example chunk types are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{decode_from, DecodeOptions};
+
+    #[test]
+    fn test_examples_decode() {
+        let examples = generate().unwrap();
+        assert_eq!(examples.len(), if cfg!(feature = "crypto") { 5 } else { 4 });
+        for example in &examples {
+            let png = Png::try_from(example.bytes.as_slice()).unwrap();
+            let mut args = example.decode_args.split(' ');
+            let mut options = DecodeOptions::new(chunk_type(args.next().unwrap()));
+            if example.decode_args.ends_with("--method lsb") {
+                options = options.method(Method::Lsb);
+            }
+            #[cfg(feature = "crypto")]
+            if example.decode_args.contains("--password") {
+                options = options.password(PASSWORD);
+            }
+            let payload = decode_from(&png, &options).unwrap().unwrap();
+            let text = match example.name {
+                "compressed.png" => TextChunk::parse(&chunk_type("zTXt"), payload.as_bytes()).unwrap().text,
+                _ => String::from_utf8(payload.as_bytes().to_vec()).unwrap(),
+            };
+            assert_eq!(text, example.payload, "{}", example.name);
+        }
+        let split = examples.iter().find(|example| example.name == "split.png").unwrap();
+        let png = Png::try_from(split.bytes.as_slice()).unwrap();
+        assert!(png.chunks_by_type("ruSt").len() > 1);
+        assert!(readme(&examples).contains("pngme decode lsb.png IDAT --method lsb"));
+    }
+}
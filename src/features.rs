@@ -0,0 +1,85 @@
+//! The cargo features pngme can be built without, and the error for asking a build for one it
+//! lacks. Every feature-gated flag reports a missing feature through [`Feature::missing`], so the
+//! message and exit code are the same wherever it happens.
+
+use std::fmt::{self, Display};
+
+/// An optional cargo feature of pngme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Sqlite,
+    Json,
+    Crypto,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Sqlite, Feature::Json, Feature::Crypto];
+
+    /// The name of the feature in Cargo.toml.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Sqlite => "sqlite",
+            Feature::Json => "json",
+            Feature::Crypto => "crypto",
+        }
+    }
+
+    /// Whether this build has the feature.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Feature::Sqlite => cfg!(feature = "sqlite"),
+            Feature::Json => cfg!(feature = "json"),
+            Feature::Crypto => cfg!(feature = "crypto"),
+        }
+    }
+
+    /// The error for a build without this feature asked for `needed_for`: the flag that needs
+    /// it, such as `--password`, or what it is needed to do.
+    pub fn missing(self, needed_for: &'static str) -> FeatureError {
+        FeatureError::Disabled { feature: self, needed_for }
+    }
+
+    /// The features compiled into this build.
+    pub fn enabled() -> Vec<Feature> {
+        Feature::ALL.into_iter().filter(|feature| feature.is_enabled()).collect()
+    }
+}
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum FeatureError {
+    Disabled { feature: Feature, needed_for: &'static str },
+}
+
+impl std::error::Error for FeatureError {}
+
+impl Display for FeatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureError::Disabled { feature, needed_for } => write!(
+                f,
+                "pngme was built without the `{feature}` feature needed for {needed_for}; rebuild it with `--features {feature}`"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing() {
+        let error = Feature::Crypto.missing("--password");
+        assert_eq!(
+            error.to_string(),
+            "pngme was built without the `crypto` feature needed for --password; rebuild it with `--features crypto`"
+        );
+        assert_eq!(Feature::enabled().contains(&Feature::Json), cfg!(feature = "json"));
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::{self, Display};
+
+use crate::chunk_type::ChunkType;
+use crate::locate::{chunk_spans, span_at, ChunkStatus};
+
+/// Something wrong with the payloads of a file, as reported by `fsck`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A payload chunk whose bytes don't match its CRC.
+    Corrupt { chunk_type: [u8; 4], status: ChunkStatus },
+    /// A chunk the file can't be read past, so any payload after it is lost.
+    Unreadable { chunk_type: [u8; 4], status: ChunkStatus },
+    /// The file ends before its IEND chunk, so payloads may have been cut off.
+    MissingEnd,
+    /// A signed provenance claim that doesn't hold under the given key.
+    #[cfg(feature = "crypto")]
+    Claim(String),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Corrupt { chunk_type, status } => {
+                write!(f, "payload chunk {}: {status}", String::from_utf8_lossy(chunk_type))
+            }
+            Problem::Unreadable { chunk_type, status } => {
+                write!(f, "chunk {}: {status}, later payloads are unreadable", String::from_utf8_lossy(chunk_type))
+            }
+            Problem::MissingEnd => write!(f, "file ends before the IEND chunk, payloads may be cut off"),
+            #[cfg(feature = "crypto")]
+            Problem::Claim(reason) => write!(f, "provenance claim: {reason}"),
+        }
+    }
+}
+
+/// Lists the payload problems of the file `bytes` with the offset of the chunk each was found in.
+/// Payloads are the chunks of types the PNG spec doesn't define. Damaged standard chunks are only
+/// reported when they keep the rest of the file from being read.
+///
+/// Payloads appended after IEND that fail their CRC would otherwise pass for trailer data, so
+/// the start of the trailer is reported as well when it reads as a damaged chunk.
+pub fn check(bytes: &[u8]) -> Vec<(usize, Problem)> {
+    let (mut spans, trailer_start) = chunk_spans(bytes);
+    let trailing_chunk = span_at(bytes, trailer_start, spans.len())
+        .filter(|span| span.status != ChunkStatus::InvalidType);
+    spans.extend(trailing_chunk);
+    let stopped_early = spans.last().is_some_and(|span| span.status != ChunkStatus::Valid);
+    let ended = spans.iter().any(|span| &span.chunk_type == b"IEND");
+    let mut problems: Vec<(usize, Problem)> = spans
+        .into_iter()
+        .filter_map(|span| {
+            let (chunk_type, status) = (span.chunk_type, span.status);
+            let problem = match status {
+                ChunkStatus::Valid => return None,
+                ChunkStatus::InvalidType | ChunkStatus::Truncated => Problem::Unreadable { chunk_type, status },
+                ChunkStatus::CrcMismatch { .. } if is_payload(&chunk_type) => Problem::Corrupt { chunk_type, status },
+                ChunkStatus::CrcMismatch { .. } => return None,
+            };
+            Some((span.offset, problem))
+        })
+        .collect();
+    if !ended && !stopped_early {
+        problems.push((trailer_start, Problem::MissingEnd));
+    }
+    problems
+}
+
+fn is_payload(chunk_type: &[u8; 4]) -> bool {
+    ChunkType::try_from(*chunk_type).is_ok_and(|t| !t.is_standard())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunk = |t: &str, d: &str| Chunk::new(ChunkType::from_str(t).unwrap(), d.as_bytes().to_vec());
+        let mut png = Png::from_chunks(vec![chunk("IHDR", "header"), chunk("tEXt", "text"), chunk("ruSt", "secret"), chunk("IEND", "")]);
+        png.append_chunk(chunk("ruSt", "appended")).unwrap();
+        png.as_bytes()
+    }
+
+    #[test]
+    fn test_intact_file() {
+        assert!(check(&testing_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_payload() {
+        let mut bytes = testing_bytes();
+        // Damage the tEXt chunk, which isn't a payload, and the ruSt chunk after it.
+        bytes[8 + 18 + 8] ^= 0xff;
+        bytes[8 + 18 + 16 + 8] ^= 0xff;
+        let problems = check(&bytes);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0, 8 + 18 + 16);
+        assert!(matches!(problems[0].1, Problem::Corrupt { chunk_type, .. } if &chunk_type == b"ruSt"));
+    }
+
+    #[test]
+    fn test_corrupt_payload_after_end() {
+        let mut bytes = testing_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let problems = check(&bytes);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0, bytes.len() - 20);
+        assert!(matches!(problems[0].1, Problem::Corrupt { .. }));
+
+        bytes.extend_from_slice(b"plain trailer data");
+        bytes[last] ^= 0xff;
+        assert!(check(&bytes).is_empty());
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        let mut bytes = testing_bytes();
+        bytes.truncate(8 + 18 + 16 + 14);
+        let problems = check(&bytes);
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(problems[0].1, Problem::Unreadable { status: ChunkStatus::Truncated, .. }));
+
+        bytes.truncate(8 + 18 + 16 + 4);
+        assert_eq!(check(&bytes), vec![(8 + 18 + 16, Problem::MissingEnd)]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// Cells in the bar of each row of `print --graph`.
+pub const WIDTH: usize = 40;
+
+/// A run of bytes of a PNG file: the signature, a chunk with its length, type and CRC, or the
+/// trailer after the last chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub label: String,
+    pub offset: usize,
+    pub length: usize,
+    /// Whether decoders can skip the bytes without losing the image: ancillary chunks and the trailer.
+    pub skippable: bool,
+}
+
+/// The spans of `png` in file order. Together they cover the whole file.
+pub fn spans(png: &Png) -> Vec<Span> {
+    let mut spans = vec![Span { label: "PNG".to_string(), offset: 0, length: png.header().len(), skippable: false }];
+    for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+        spans.push(Span {
+            label: chunk.chunk_type().to_string(),
+            offset,
+            length: chunk.length() as usize + 12,
+            skippable: !chunk.chunk_type().is_critical(),
+        });
+    }
+    if !png.trailer().is_empty() {
+        spans.push(Span { label: "trailer".to_string(), offset: png.trailer_offset(), length: png.trailer().len(), skippable: true });
+    }
+    spans
+}
+
+/// Draws where `span` lies in a file of `total` bytes as a bar of `width` cells, each a
+/// `width`th of the file. Spans too small for a cell still get one, so every span is visible.
+/// Plain bars use ASCII only.
+pub fn bar(span: &Span, total: usize, width: usize, plain: bool) -> String {
+    let (filled, empty, edge) = if plain { ('#', '.', '|') } else { ('█', ' ', '│') };
+    let total = total.max(1);
+    let start = (span.offset * width / total).min(width - 1);
+    let end = ((span.offset + span.length) * width).div_ceil(total).clamp(start + 1, width);
+    let cells: String = (0..width).map(|cell| if (start..end).contains(&cell) { filled } else { empty }).collect();
+    format!("{edge}{cells}{edge}")
+}
+
+/// The headings `print --tree` groups chunks under, in the order it shows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// IHDR, and IEND closing the file.
+    Header,
+    /// PLTE and the chunks describing colors of the palette or the background.
+    Palette,
+    /// IDAT, and the frames of animated PNGs.
+    ImageData,
+    /// Text and the other standard ancillary chunks.
+    Metadata,
+    /// Private chunks and public ones pngme doesn't know.
+    Private,
+    Trailer,
+}
+
+impl Category {
+    pub fn of(chunk: &Chunk) -> Category {
+        let chunk_type = chunk.chunk_type();
+        match &chunk_type.bytes() {
+            b"IHDR" | b"IEND" => Category::Header,
+            b"PLTE" | b"tRNS" | b"bKGD" | b"hIST" | b"sPLT" => Category::Palette,
+            b"IDAT" | b"acTL" | b"fcTL" | b"fdAT" => Category::ImageData,
+            _ if chunk_type.is_standard() => Category::Metadata,
+            _ => Category::Private,
+        }
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Category::Header => write!(f, "Header"),
+            Category::Palette => write!(f, "Palette"),
+            Category::ImageData => write!(f, "Image Data"),
+            Category::Metadata => write!(f, "Text/Metadata"),
+            Category::Private => write!(f, "Private/Unknown"),
+            Category::Trailer => write!(f, "Trailer"),
+        }
+    }
+}
+
+/// The spans of `png` grouped by category, leaving out the signature. Groups come in the order
+/// of `Category` and chunks within a group in file order; empty groups are left out.
+pub fn groups(png: &Png) -> Vec<(Category, Vec<Span>)> {
+    let categories = png.chunks().iter().map(Category::of).chain(std::iter::once(Category::Trailer));
+    let mut groups: Vec<(Category, Vec<Span>)> = Vec::new();
+    for (category, span) in categories.zip(spans(png).into_iter().skip(1)) {
+        match groups.iter_mut().find(|(other, _)| *other == category) {
+            Some((_, spans)) => spans.push(span),
+            None => groups.push((category, vec![span])),
+        }
+    }
+    groups.sort_by_key(|(category, _)| *category);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, length: usize) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; length])
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("ruSt", 100), chunk("IEND", 0)]);
+        png.set_trailer(b"extra".to_vec()).unwrap();
+        let spans = spans(&png);
+        let labels: Vec<_> = spans.iter().map(|span| span.label.as_str()).collect();
+        assert_eq!(labels, ["PNG", "IHDR", "ruSt", "IEND", "trailer"]);
+        assert_eq!((spans[2].offset, spans[2].length, spans[2].skippable), (33, 112, true));
+        let last = spans.last().unwrap();
+        assert_eq!(last.offset + last.length, png.byte_len());
+    }
+
+    #[test]
+    fn test_groups() {
+        let chunks = ["IHDR", "ruSt", "tEXt", "PLTE", "IDAT", "IDAT", "IEND"];
+        let mut png = Png::from_chunks(chunks.iter().map(|chunk_type| chunk(chunk_type, 1)).collect());
+        png.set_trailer(b"extra".to_vec()).unwrap();
+        let groups = groups(&png);
+        let groups: Vec<(Category, Vec<&str>)> = groups
+            .iter()
+            .map(|(category, spans)| (*category, spans.iter().map(|span| span.label.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Category::Header, vec!["IHDR", "IEND"]),
+                (Category::Palette, vec!["PLTE"]),
+                (Category::ImageData, vec!["IDAT", "IDAT"]),
+                (Category::Metadata, vec!["tEXt"]),
+                (Category::Private, vec!["ruSt"]),
+                (Category::Trailer, vec!["trailer"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bar() {
+        let span = |offset, length| Span { label: String::new(), offset, length, skippable: false };
+        assert_eq!(bar(&span(0, 50), 100, 10, true), "|#####.....|");
+        assert_eq!(bar(&span(50, 50), 100, 10, true), "|.....#####|");
+        // A single byte still shows.
+        assert_eq!(bar(&span(99, 1), 100, 10, true), "|.........#|");
+        assert_eq!(bar(&span(0, 1), 100, 4, false), "│█   │");
+    }
+}
@@ -0,0 +1,130 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// TIFF tag pointing from the first EXIF directory to the GPS directory.
+const GPS_IFD_TAG: u16 = 0x8825;
+
+/// XMP properties carrying a location, as found in text chunks.
+const XMP_GPS_MARKERS: [&[u8]; 2] = [b"GPSLatitude", b"GPSLongitude"];
+
+/// Something a pre-commit check refuses to let into a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// An eXIf chunk holding camera metadata.
+    Exif,
+    /// A chunk holding a location, either as EXIF GPS data or as XMP properties.
+    Gps(ChunkType),
+    /// A private chunk that wasn't explicitly allowed.
+    PrivateChunk(ChunkType),
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Exif => write!(f, "eXIf chunk contains EXIF metadata"),
+            Violation::Gps(chunk_type) => write!(f, "{chunk_type} chunk contains GPS location data"),
+            Violation::PrivateChunk(chunk_type) => write!(f, "private chunk {chunk_type} is not allowed"),
+        }
+    }
+}
+
+/// Lists the violations in `png` with the offset of the chunk each was found in.
+/// Private chunks whose type matches a pattern in `allowed` are accepted.
+pub fn check(png: &Png, allowed: &[String]) -> Vec<(usize, Violation)> {
+    png.chunks()
+        .iter()
+        .zip(png.chunk_offsets())
+        .filter_map(|(chunk, offset)| check_chunk(chunk, allowed).map(|violation| (offset, violation)))
+        .collect()
+}
+
+fn check_chunk(chunk: &Chunk, allowed: &[String]) -> Option<Violation> {
+    let chunk_type = chunk.chunk_type();
+    match chunk_type.to_string().as_str() {
+        "eXIf" if has_gps_directory(chunk.data()) => Some(Violation::Gps(chunk_type.clone())),
+        "eXIf" => Some(Violation::Exif),
+        "tEXt" | "iTXt" if mentions_gps(chunk.data()) => Some(Violation::Gps(chunk_type.clone())),
+        _ if !chunk_type.is_public() && !allowed.iter().any(|pattern| chunk_type.matches(pattern)) => {
+            Some(Violation::PrivateChunk(chunk_type.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the first directory of the TIFF structure in `exif` links to a GPS directory.
+fn has_gps_directory(exif: &[u8]) -> bool {
+    let big_endian = match exif.get(..4) {
+        Some(b"MM\0*") => true,
+        Some(b"II*\0") => false,
+        _ => return false,
+    };
+    let read = |offset: usize, length: usize| -> Option<usize> {
+        let bytes = exif.get(offset..offset + length)?;
+        let fold = |value: usize, byte: &u8| value << 8 | *byte as usize;
+        Some(if big_endian { bytes.iter().fold(0, fold) } else { bytes.iter().rev().fold(0, fold) })
+    };
+    let Some(directory) = read(4, 4) else {
+        return false;
+    };
+    let count = read(directory, 2).unwrap_or(0);
+    (0..count)
+        .map_while(|i| read(directory + 2 + i * 12, 2))
+        .any(|tag| tag == GPS_IFD_TAG as usize)
+}
+
+fn mentions_gps(text: &[u8]) -> bool {
+    XMP_GPS_MARKERS
+        .iter()
+        .any(|marker| text.windows(marker.len()).any(|window| window == *marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_clean_png_passes() {
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("tEXt", b"Title\0cat"), chunk("IEND", b"")]);
+        assert!(check(&png, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        // Little-endian TIFF with one directory entry, the GPS pointer.
+        let mut gps_exif = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        gps_exif.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("eXIf", b"MM\0*\0\0\0\x08\0\0"),
+            chunk("eXIf", &gps_exif),
+            chunk("iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<exif:GPSLatitude>"),
+            chunk("ruSt", b"hidden"),
+            chunk("prVN", b"claim"),
+            chunk("IEND", b""),
+        ]);
+        let offsets = png.chunk_offsets();
+
+        let violations = check(&png, &["pr?N".to_string()]);
+        assert_eq!(
+            violations,
+            vec![
+                (offsets[1], Violation::Exif),
+                (offsets[2], Violation::Gps(ChunkType::from_str("eXIf").unwrap())),
+                (offsets[3], Violation::Gps(ChunkType::from_str("iTXt").unwrap())),
+                (offsets[4], Violation::PrivateChunk(ChunkType::from_str("ruSt").unwrap())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_exif_has_no_gps() {
+        assert!(!has_gps_directory(b"II*\0\xff\0\0\0"));
+        assert!(!has_gps_directory(b"II*"));
+    }
+}
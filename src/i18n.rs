@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+use crate::commands::CommandError;
+use crate::decode::DecodeError;
+use crate::encode::EncodeError;
+use crate::error::PngMeError;
+#[cfg(feature = "crypto")]
+use crate::crypto::CryptoError;
+use crate::keyword::KeywordError;
+use crate::patch::PatchError;
+use crate::payload::PayloadError;
+use crate::png::PngError;
+#[cfg(feature = "crypto")]
+use crate::provenance::ProvenanceError;
+use crate::query::QueryError;
+use crate::text_chunk::TextChunkError;
+use crate::features::FeatureError;
+use crate::lock::LockError;
+use crate::known_chunks::KnownChunkError;
+use crate::stego::StegoError;
+use crate::inflate::InflateError;
+use crate::encoding::EncodingError;
+#[cfg(feature = "crypto")]
+use crate::sign::SignError;
+#[cfg(feature = "crypto")]
+use crate::rekey::RekeyError;
+#[cfg(feature = "crypto")]
+use crate::seal::SealError;
+
+/// Catalogs compiled into the binary, keyed by language. Distributors add a translation by
+/// dropping a file into `locales/` and listing it here.
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.txt")),
+    ("de", include_str!("../locales/de.txt")),
+];
+
+/// The messages of one language. Ids missing from it are looked up in the English catalog.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parses a catalog made of `id = message` lines. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(source: &str) -> Self {
+        let messages = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(" = ").or_else(|| line.split_once('=')))
+            .map(|(id, message)| (id.trim().to_string(), message.trim().to_string()))
+            .collect();
+        Self { messages }
+    }
+
+    /// The built-in catalog for `language`, if there is one.
+    pub fn builtin(language: &str) -> Option<Self> {
+        CATALOGS.iter().find(|(l, _)| *l == language).map(|(_, source)| Self::parse(source))
+    }
+
+    fn get(&self, id: &str) -> Option<&str> {
+        self.messages.get(id).map(String::as_str)
+    }
+}
+
+/// The catalogs used for this process: the user's language and the English fallback.
+struct Locale {
+    selected: Catalog,
+    fallback: Catalog,
+}
+
+fn locale() -> &'static Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    LOCALE.get_or_init(|| Locale {
+        selected: Catalog::builtin(&language()).unwrap_or_default(),
+        fallback: Catalog::builtin("en").unwrap_or_default(),
+    })
+}
+
+/// The user's language from `PNGME_LANG`, or the usual POSIX locale variables,
+/// reduced to its language code, e.g. `de` for `de_DE.UTF-8`.
+pub(crate) fn language() -> String {
+    ["PNGME_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| value.split(['_', '.', '@', '-']).next().unwrap_or("").to_lowercase())
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Looks up message `id` and replaces each `{name}` in it with the matching argument.
+/// Unknown ids are returned as is, so a missing translation is visible but harmless.
+pub fn tr(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let locale = locale();
+    let template = locale.selected.get(id).or_else(|| locale.fallback.get(id)).unwrap_or(id);
+    substitute(template, args)
+}
+
+fn substitute(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |message, (name, value)| message.replace(&format!("{{{name}}}"), &value.to_string()))
+}
+
+/// Formats an integer with the locale's digit grouping, e.g. `1,234,567`.
+pub fn format_number(number: i64) -> String {
+    format_grouped(number.unsigned_abs(), number < 0, &tr("number-group-separator", &[]))
+}
+
+fn format_grouped(magnitude: u64, negative: bool, separator: &str) -> String {
+    let digits = magnitude.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    if negative { format!("-{grouped}") } else { grouped }
+}
+
+/// Formats `number` with `precision` decimals and the locale's decimal separator.
+pub fn format_decimal(number: f64, precision: usize) -> String {
+    format!("{number:.precision$}").replace('.', &tr("number-decimal-separator", &[]))
+}
+
+/// Formats a Unix timestamp as a UTC date and time in the locale's order.
+pub fn format_date(timestamp: u64) -> String {
+    format_date_with(&tr("date-format", &[]), timestamp)
+}
+
+fn format_date_with(pattern: &str, timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    let pad = |n: u64| format!("{n:02}");
+    substitute(
+        pattern,
+        &[
+            ("year", &year),
+            ("month", &pad(month)),
+            ("day", &pad(day)),
+            ("hour", &pad(seconds / 3600)),
+            ("minute", &pad(seconds / 60 % 60)),
+            ("second", &pad(seconds % 60)),
+        ],
+    )
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+/// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u64;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u64;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A stable, machine-readable code for `error`, made of the error's domain and variant,
+/// e.g. `png-invalid-header`. Codes don't change when messages are translated.
+pub fn error_code(error: &(dyn std::error::Error + 'static)) -> String {
+    type Is = fn(&(dyn std::error::Error + 'static)) -> bool;
+    let domains: &[(&str, Is)] = &[
+        ("png", |e| e.is::<PngError>()),
+        ("chunk", |e| e.is::<ChunkError>()),
+        ("chunk-type", |e| e.is::<ChunkTypeError>()),
+        ("command", |e| e.is::<CommandError>()),
+        ("decode", |e| e.is::<DecodeError>()),
+        ("encode", |e| e.is::<EncodeError>()),
+        ("encoding", |e| e.is::<EncodingError>()),
+        #[cfg(feature = "crypto")]
+        ("crypto", |e| e.is::<CryptoError>()),
+        ("keyword", |e| e.is::<KeywordError>()),
+        ("patch", |e| e.is::<PatchError>()),
+        ("payload", |e| e.is::<PayloadError>()),
+        #[cfg(feature = "crypto")]
+        ("provenance", |e| e.is::<ProvenanceError>()),
+        ("query", |e| e.is::<QueryError>()),
+        #[cfg(feature = "crypto")]
+        ("rekey", |e| e.is::<RekeyError>()),
+        #[cfg(feature = "crypto")]
+        ("seal", |e| e.is::<SealError>()),
+        #[cfg(feature = "crypto")]
+        ("sign", |e| e.is::<SignError>()),
+        ("text-chunk", |e| e.is::<TextChunkError>()),
+        ("known-chunk", |e| e.is::<KnownChunkError>()),
+        ("stego", |e| e.is::<StegoError>()),
+        ("inflate", |e| e.is::<InflateError>()),
+        ("feature", |e| e.is::<FeatureError>()),
+        ("lock", |e| e.is::<LockError>()),
+    ];
+    if let Some(error) = error.downcast_ref::<PngMeError>() {
+        return error_code(error.inner());
+    }
+    if error.is::<std::io::Error>() {
+        return "io".to_string();
+    }
+    let Some((domain, _)) = domains.iter().find(|(_, is)| is(error)) else {
+        return "other".to_string();
+    };
+    // The variant name is the leading identifier of the derived `Debug` output.
+    let debug = format!("{error:?}");
+    let variant: String = debug.chars().take_while(char::is_ascii_alphanumeric).collect();
+    let mut code = domain.to_string();
+    for c in variant.chars() {
+        if c.is_ascii_uppercase() {
+            code.push('-');
+        }
+        code.push(c.to_ascii_lowercase());
+    }
+    code
+}
+
+/// Describes `error` for the user as `[code] message`. The message is translated when the
+/// catalog has an `error-<code>` entry, and is the error's own text otherwise.
+pub fn describe_error(error: &(dyn std::error::Error + 'static)) -> String {
+    let code = error_code(error);
+    let id = format!("error-{code}");
+    let locale = locale();
+    match locale.selected.get(&id).or_else(|| locale.fallback.get(&id)) {
+        Some(message) => format!("[{code}] {message}"),
+        None => format!("[{code}] {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_catalogs_cover_english_ids() {
+        let english = Catalog::builtin("en").unwrap();
+        for (language, _) in CATALOGS {
+            let catalog = Catalog::builtin(language).unwrap();
+            for id in english.messages.keys() {
+                assert!(catalog.get(id).is_some(), "{language} catalog is missing {id}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let message = substitute("{valid} of {total} ok {Literal}", &[("valid", &3), ("total", &"4")]);
+        assert_eq!(message, "3 of 4 ok {Literal}");
+    }
+
+    #[test]
+    fn test_number_grouping() {
+        assert_eq!(format_grouped(0, false, ","), "0");
+        assert_eq!(format_grouped(999, false, ","), "999");
+        assert_eq!(format_grouped(1_234_567, true, "."), "-1.234.567");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date_with("{year}-{month}-{day} {hour}:{minute}:{second}", 0), "1970-01-01 00:00:00");
+        let german = Catalog::builtin("de").unwrap();
+        assert_eq!(format_date_with(german.get("date-format").unwrap(), 951_782_400), "29.02.2000 00:00:00 UTC");
+    }
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(error_code(&PngError::InvalidHeader), "png-invalid-header");
+        assert_eq!(error_code(&KeywordError::TooLong(80)), "keyword-too-long");
+        assert_eq!(error_code(&std::io::Error::other("x")), "io");
+        assert_eq!(error_code(&crate::inflate::InflateError::TooLarge { compressed: 1, limit: 2 }), "inflate-too-large");
+        assert!(describe_error(&PngError::SmallInput).starts_with("[png-small-input] "));
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::fs::{self, Metadata};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crc::CRC_64_XZ;
+use serde::{Deserialize, Serialize};
+
+use crate::png::{replace_file, WriteOptions};
+use crate::scan::ChunkRecord;
+use crate::Result;
+
+/// A cache of chunk tables built by `scan --index`, so unchanged files don't have to be
+/// parsed again. Chunk tables are keyed by a hash of the file contents, and each scanned
+/// path remembers the size and modification time the hash was computed for.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanIndex {
+    #[serde(with = "crate::os_path::keys")]
+    files: BTreeMap<PathBuf, FileEntry>,
+    tables: BTreeMap<String, Vec<ChunkRecord>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FileEntry {
+    size: u64,
+    modified: (u64, u32),
+    hash: String,
+}
+
+impl FileEntry {
+    fn is_current(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && Some(self.modified) == modified(metadata)
+    }
+}
+
+impl ScanIndex {
+    /// Loads the index stored at `path`, or an empty index if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Writes this index to `path`, dropping chunk tables no file refers to anymore.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        let files = &self.files;
+        self.tables.retain(|hash, _| files.values().any(|entry| &entry.hash == hash));
+        let bytes = serde_json::to_vec(self)?;
+        replace_file(path, &WriteOptions::default(), |writer| writer.write_all(&bytes))?;
+        Ok(())
+    }
+
+    /// Returns the cached chunk table of `file`, unless the file changed since it was indexed.
+    pub fn lookup(&self, file: &Path, metadata: &Metadata) -> Option<&[ChunkRecord]> {
+        let entry = self.files.get(file).filter(|entry| entry.is_current(metadata))?;
+        self.tables.get(&entry.hash).map(Vec::as_slice)
+    }
+
+    /// Records the chunk table of `file`, whose contents are `bytes`.
+    pub fn insert(&mut self, file: &Path, metadata: &Metadata, bytes: &[u8], table: Vec<ChunkRecord>) {
+        let Some(modified) = modified(metadata) else {
+            return;
+        };
+        let hash = format!("{:016x}", crc::Crc::<u64>::new(&CRC_64_XZ).checksum(bytes));
+        let entry = FileEntry { size: metadata.len(), modified, hash: hash.clone() };
+        self.files.insert(file.to_path_buf(), entry);
+        self.tables.insert(hash, table);
+    }
+}
+
+fn modified(metadata: &Metadata) -> Option<(u64, u32)> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn record(chunk_type: &str, length: u32) -> ChunkRecord {
+        ChunkRecord { chunk_type: ChunkType::from_str(chunk_type).unwrap(), length }
+    }
+
+    #[test]
+    fn test_index_roundtrip_and_invalidation() {
+        let dir = std::env::temp_dir().join(format!("pngme-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.png");
+        let index_path = dir.join("index.json");
+        fs::write(&file, b"first").unwrap();
+
+        let mut index = ScanIndex::load(&index_path).unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        assert!(index.lookup(&file, &metadata).is_none());
+        index.insert(&file, &metadata, b"first", vec![record("IHDR", 13), record("ruSt", 5)]);
+        index.save(&index_path).unwrap();
+
+        let index = ScanIndex::load(&index_path).unwrap();
+        assert_eq!(index.lookup(&file, &metadata).unwrap().len(), 2);
+
+        fs::write(&file, b"changed contents").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        assert!(index.lookup(&file, &metadata).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
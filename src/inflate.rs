@@ -0,0 +1,91 @@
+use std::fmt::{self, Display};
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+/// Default for how many times its compressed size inflated text may grow.
+pub const DEFAULT_MAX_RATIO: u64 = 100;
+
+/// Inflated text may always grow to this many bytes, whatever the ratio, so small chunks of
+/// very repetitive text aren't refused.
+pub const MIN_LIMIT: u64 = 1 << 20;
+
+/// Decompresses the zlib stream `compressed` of a zTXt or iTXt chunk, stopping as soon as the
+/// output grows past `max_ratio` times its size, or `MIN_LIMIT` if that is more, so a crafted
+/// stream can't expand into more memory than that.
+pub fn inflate(compressed: &[u8], max_ratio: u64) -> Result<Vec<u8>, InflateError> {
+    inflate_up_to(compressed, limit(compressed.len(), max_ratio))
+}
+
+/// The most bytes `length` compressed bytes may inflate to under `ratio`.
+fn limit(length: usize, ratio: u64) -> u64 {
+    (length as u64).saturating_mul(ratio).max(MIN_LIMIT)
+}
+
+/// Decompresses the zlib stream `compressed`, stopping as soon as the output passes `limit`
+/// bytes. For image data, whose size the IHDR chunk gives.
+pub fn inflate_up_to(compressed: &[u8], limit: u64) -> Result<Vec<u8>, InflateError> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut inflated)
+        .map_err(|_| InflateError::Corrupt)?;
+    if inflated.len() as u64 > limit {
+        return Err(InflateError::TooLarge { compressed: compressed.len(), limit });
+    }
+    Ok(inflated)
+}
+
+#[derive(Debug)]
+pub enum InflateError {
+    Corrupt,
+    TooLarge { compressed: usize, limit: u64 },
+}
+
+impl std::error::Error for InflateError {}
+
+impl Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InflateError::Corrupt => write!(f, "Compressed data can't be decompressed"),
+            InflateError::TooLarge { compressed, limit } => write!(
+                f,
+                "{compressed} compressed bytes inflate to more than {limit} bytes; raise --max-inflate-ratio if the file is trusted"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn deflate(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_inflate() {
+        assert_eq!(inflate(&deflate(b"hello"), DEFAULT_MAX_RATIO).unwrap(), b"hello");
+        assert!(matches!(inflate(b"not zlib", DEFAULT_MAX_RATIO), Err(InflateError::Corrupt)));
+    }
+
+    #[test]
+    fn test_limit() {
+        let compressed = deflate(&[0; 10_000]);
+        assert_eq!(inflate_up_to(&compressed, 10_000).unwrap().len(), 10_000);
+        let error = inflate_up_to(&compressed, 9_999).unwrap_err();
+        assert!(matches!(error, InflateError::TooLarge { limit: 9_999, .. }));
+        assert_eq!(limit(10, DEFAULT_MAX_RATIO), MIN_LIMIT);
+        assert_eq!(limit(1 << 20, 2), 2 << 20);
+
+        let zeros = deflate(&vec![0; 2 << 20]);
+        assert!(matches!(inflate(&zeros, 1), Err(InflateError::TooLarge { limit: MIN_LIMIT, .. })));
+        assert_eq!(inflate(&zeros, u64::MAX).unwrap().len(), 2 << 20);
+    }
+}
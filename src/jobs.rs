@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::Result;
+
+/// Runs `task` on every item of `items` on up to `jobs` threads (0 for one per core), and hands
+/// each result with its item's index to `done` on the calling thread as soon as it is ready.
+/// With one job items are processed in order; with more, results arrive as they finish.
+/// Threads take the next item as they finish, so a few large files spread evenly.
+/// Stops at the first error `done` returns.
+pub fn run<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    task: impl Fn(&T) -> R + Sync,
+    mut done: impl FnMut(usize, R) -> Result<()>,
+) -> Result<()> {
+    let jobs = match jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(items.len());
+    if jobs <= 1 {
+        for (index, item) in items.iter().enumerate() {
+            done(index, task(item))?;
+        }
+        return Ok(());
+    }
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (next, task) = (&next, &task);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if sender.send((index, task(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        // Returning drops the receiver, so the threads stop after their current item.
+        for (index, result) in receiver {
+            done(index, result)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_item_is_done_once() {
+        let items: Vec<usize> = (0..100).collect();
+        for jobs in [0, 1, 4] {
+            let mut results = vec![None; items.len()];
+            run(&items, jobs, |item| item * 2, |index, result| {
+                assert!(results[index].replace(result).is_none());
+                Ok(())
+            })
+            .unwrap();
+            assert!(results.iter().enumerate().all(|(index, result)| *result == Some(index * 2)));
+        }
+    }
+
+    #[test]
+    fn test_stops_at_first_error() {
+        let items: Vec<usize> = (0..1000).collect();
+        let mut seen = 0;
+        let result = run(&items, 4, |item| *item, |_, _| {
+            seen += 1;
+            if seen == 3 { Err("stop".into()) } else { Ok(()) }
+        });
+        assert!(result.is_err());
+        assert_eq!(seen, 3);
+    }
+}
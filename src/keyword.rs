@@ -0,0 +1,133 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Longest keyword a tEXt, zTXt or iTXt chunk may have, in bytes.
+pub const MAX_KEYWORD_LENGTH: usize = 79;
+
+/// Chunk types whose data starts with a keyword terminated by a null byte.
+pub const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+/// A keyword of a textual chunk, validated against the PNG spec: 1 to 79 bytes of printable
+/// Latin-1, without leading, trailing or consecutive spaces. Stored as Latin-1 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword(Vec<u8>);
+
+impl Keyword {
+    /// Validates `bytes` as a Latin-1 keyword.
+    pub fn from_latin1(bytes: &[u8]) -> Result<Self, KeywordError> {
+        if bytes.is_empty() {
+            return Err(KeywordError::Empty);
+        }
+        if bytes.len() > MAX_KEYWORD_LENGTH {
+            return Err(KeywordError::TooLong(bytes.len()));
+        }
+        if let Some(position) = bytes.iter().position(|&b| !is_printable_latin1(b)) {
+            return Err(KeywordError::InvalidCharacter { position, byte: bytes[position] });
+        }
+        if bytes[0] == b' ' {
+            return Err(KeywordError::LeadingSpace);
+        }
+        if bytes[bytes.len() - 1] == b' ' {
+            return Err(KeywordError::TrailingSpace);
+        }
+        if let Some(position) = bytes.windows(2).position(|pair| pair == b"  ") {
+            return Err(KeywordError::ConsecutiveSpaces(position));
+        }
+        Ok(Self(bytes.to_vec()))
+    }
+
+    /// The keyword as it is stored in a chunk.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Printable Latin-1 is the ASCII range 32-126 and 161-255; the non-breaking space is excluded too.
+fn is_printable_latin1(byte: u8) -> bool {
+    matches!(byte, 32..=126 | 161..=255)
+}
+
+impl FromStr for Keyword {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut bytes = Vec::with_capacity(s.len());
+        for (position, c) in s.chars().enumerate() {
+            let byte = u8::try_from(c as u32).map_err(|_| KeywordError::NotLatin1 { position, character: c })?;
+            bytes.push(byte);
+        }
+        Ok(Self::from_latin1(&bytes)?)
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|&b| write!(f, "{}", b as char))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeywordError {
+    Empty,
+    TooLong(usize),
+    LeadingSpace,
+    TrailingSpace,
+    ConsecutiveSpaces(usize),
+    InvalidCharacter { position: usize, byte: u8 },
+    NotLatin1 { position: usize, character: char },
+}
+
+impl std::error::Error for KeywordError {}
+
+impl Display for KeywordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeywordError::Empty => write!(f, "Keyword is empty, at least 1 byte is required"),
+            KeywordError::TooLong(length) => {
+                write!(f, "Keyword is {length} bytes long, at most {MAX_KEYWORD_LENGTH} are allowed")
+            }
+            KeywordError::LeadingSpace => write!(f, "Keyword starts with a space"),
+            KeywordError::TrailingSpace => write!(f, "Keyword ends with a space"),
+            KeywordError::ConsecutiveSpaces(position) => {
+                write!(f, "Keyword has consecutive spaces at byte {position}")
+            }
+            KeywordError::InvalidCharacter { position, byte } => {
+                write!(f, "Keyword has non-printable byte 0x{byte:02x} at byte {position}")
+            }
+            KeywordError::NotLatin1 { position, character } => {
+                write!(f, "Keyword has character {character:?} at position {position}, which is not Latin-1")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(keyword: &[u8]) -> KeywordError {
+        Keyword::from_latin1(keyword).unwrap_err()
+    }
+
+    #[test]
+    fn test_valid_keywords() {
+        assert!(Keyword::from_latin1(b"Title").is_ok());
+        assert!(Keyword::from_latin1(b"Creation Time").is_ok());
+        assert!(Keyword::from_latin1(&[b'k'; MAX_KEYWORD_LENGTH]).is_ok());
+        assert_eq!(Keyword::from_str("Légende").unwrap().as_bytes(), b"L\xe9gende");
+        assert_eq!(Keyword::from_str("Légende").unwrap().to_string(), "Légende");
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        assert_eq!(error(b""), KeywordError::Empty);
+        assert_eq!(error(&[b'k'; 80]), KeywordError::TooLong(80));
+        assert_eq!(error(b" Title"), KeywordError::LeadingSpace);
+        assert_eq!(error(b"Title "), KeywordError::TrailingSpace);
+        assert_eq!(error(b"Creation  Time"), KeywordError::ConsecutiveSpaces(8));
+        assert_eq!(error(b"Tab\there"), KeywordError::InvalidCharacter { position: 3, byte: b'\t' });
+        assert_eq!(error(b"nb\xa0sp"), KeywordError::InvalidCharacter { position: 2, byte: 0xa0 });
+        assert!(Keyword::from_str("Emoji \u{1f600}").is_err());
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::inflate::DEFAULT_MAX_RATIO;
+use crate::text_chunk::TextChunk;
+use crate::Result;
+
+/// How the pixels of an image are stored, from the IHDR chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(ColorType::Grayscale),
+            2 => Some(ColorType::Rgb),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayscaleAlpha),
+            6 => Some(ColorType::Rgba),
+            _ => None,
+        }
+    }
+
+    /// The bit depths the PNG spec allows for this color type.
+    pub fn bit_depths(self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGB with alpha",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The IHDR chunk: the image's size and pixel format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+}
+
+/// What a unit of the pHYs chunk is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Only the ratio of the two values is known, i.e. the pixel aspect ratio.
+    Unknown,
+    Metre,
+}
+
+/// The rendering intent of an sRGB chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+/// The last modification time of a tIME chunk, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60, for leap seconds.
+    pub second: u8,
+}
+
+/// A color given by bKGD or tRNS, in the format the IHDR color type calls for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Color {
+    PaletteIndex(u8),
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    /// The alpha of each palette entry, for tRNS with indexed color.
+    PaletteAlphas(Vec<u8>),
+}
+
+/// How an fcTL chunk shows one frame of an animated PNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    /// Position among the fcTL and fdAT chunks of the file, from 0.
+    pub sequence: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    /// The frame shows for `delay_numerator / delay_denominator` seconds; a denominator of 0 means 100.
+    pub delay_numerator: u16,
+    pub delay_denominator: u16,
+    pub dispose: Dispose,
+    pub blend: Blend,
+}
+
+impl FrameControl {
+    /// How long the frame shows, in seconds.
+    pub fn delay(&self) -> f64 {
+        let denominator = if self.delay_denominator == 0 { 100 } else { self.delay_denominator };
+        self.delay_numerator as f64 / denominator as f64
+    }
+}
+
+/// What happens to the area of a frame before the next one is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispose {
+    /// It is left as it is.
+    None,
+    /// It is cleared to fully transparent black.
+    Background,
+    /// It goes back to what it was before the frame.
+    Previous,
+}
+
+/// How a frame is drawn over what is already there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    /// It replaces it, alpha included.
+    Source,
+    /// It is alpha-blended over it.
+    Over,
+}
+
+/// A standard chunk decoded into what its fields mean.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KnownChunk {
+    Header(ImageHeader),
+    /// pHYs: the pixels per unit along x and y.
+    PhysicalSize { x: u32, y: u32, unit: Unit },
+    Time(Timestamp),
+    /// gAMA: the image gamma times 100000.
+    Gamma(u32),
+    Srgb(RenderingIntent),
+    Text(TextChunk),
+    Background(Color),
+    Transparency(Color),
+    /// acTL: the number of frames of an animated PNG and how often it plays, 0 meaning forever.
+    AnimationControl { frames: u32, plays: u32 },
+    FrameControl(FrameControl),
+    /// fdAT: pixel data of a frame after the first, `length` bytes after the sequence number.
+    FrameData { sequence: u32, length: usize },
+}
+
+/// The chunk types [`decode`] understands.
+pub const KNOWN_CHUNK_TYPES: [&str; 13] =
+    ["IHDR", "pHYs", "tIME", "gAMA", "sRGB", "tEXt", "zTXt", "iTXt", "bKGD", "tRNS", "acTL", "fcTL", "fdAT"];
+
+/// Decodes `chunk` if it is one of [`KNOWN_CHUNK_TYPES`], or returns `None` for other types.
+/// bKGD and tRNS depend on the image's color type; without `color_type` it is guessed from the
+/// chunk's length.
+pub fn decode(chunk: &Chunk, color_type: Option<ColorType>) -> Result<Option<KnownChunk>> {
+    decode_limited(chunk, color_type, DEFAULT_MAX_RATIO)
+}
+
+/// Same as `decode`, refusing compressed text that inflates to more than `max_ratio` times its
+/// size, see [`crate::inflate::inflate`].
+pub fn decode_limited(chunk: &Chunk, color_type: Option<ColorType>, max_ratio: u64) -> Result<Option<KnownChunk>> {
+    let chunk_type = chunk.chunk_type();
+    let data = chunk.data();
+    let wrong_length = |expected| KnownChunkError::WrongLength { chunk_type: chunk_type.clone(), expected, actual: data.len() };
+    let length = |expected| if data.len() == expected { Ok(()) } else { Err(wrong_length(expected)) };
+    let invalid = |field, value| KnownChunkError::InvalidValue { chunk_type: chunk_type.clone(), field, value };
+    let known = match &chunk_type.bytes() {
+        b"IHDR" => {
+            length(13)?;
+            let (width, height) = (u32_at(data, 0), u32_at(data, 4));
+            if width == 0 || height == 0 {
+                return Err(invalid("size", 0).into());
+            }
+            let color_type = ColorType::from_byte(data[9]).ok_or(invalid("color type", data[9].into()))?;
+            let bit_depth = data[8];
+            if !color_type.bit_depths().contains(&bit_depth) {
+                return Err(invalid("bit depth", bit_depth.into()).into());
+            }
+            let interlaced = match data[12] {
+                0 => false,
+                1 => true,
+                method => return Err(invalid("interlace method", method.into()).into()),
+            };
+            KnownChunk::Header(ImageHeader { width, height, bit_depth, color_type, interlaced })
+        }
+        b"pHYs" => {
+            length(9)?;
+            let unit = match data[8] {
+                0 => Unit::Unknown,
+                1 => Unit::Metre,
+                unit => return Err(invalid("unit", unit.into()).into()),
+            };
+            KnownChunk::PhysicalSize { x: u32_at(data, 0), y: u32_at(data, 4), unit }
+        }
+        b"tIME" => {
+            length(7)?;
+            let time = Timestamp {
+                year: u16_at(data, 0),
+                month: data[2],
+                day: data[3],
+                hour: data[4],
+                minute: data[5],
+                second: data[6],
+            };
+            let fields = [
+                ("month", time.month, 1..=12),
+                ("day", time.day, 1..=31),
+                ("hour", time.hour, 0..=23),
+                ("minute", time.minute, 0..=59),
+                ("second", time.second, 0..=60),
+            ];
+            if let Some((field, value, _)) = fields.into_iter().find(|(_, value, range)| !range.contains(value)) {
+                return Err(invalid(field, value.into()).into());
+            }
+            KnownChunk::Time(time)
+        }
+        b"gAMA" => {
+            length(4)?;
+            KnownChunk::Gamma(u32_at(data, 0))
+        }
+        b"sRGB" => {
+            length(1)?;
+            KnownChunk::Srgb(match data[0] {
+                0 => RenderingIntent::Perceptual,
+                1 => RenderingIntent::RelativeColorimetric,
+                2 => RenderingIntent::Saturation,
+                3 => RenderingIntent::AbsoluteColorimetric,
+                intent => return Err(invalid("rendering intent", intent.into()).into()),
+            })
+        }
+        b"tEXt" | b"zTXt" | b"iTXt" => KnownChunk::Text(TextChunk::parse_limited(chunk_type, data, max_ratio)?),
+        b"bKGD" => {
+            let color = match (color_type, data.len()) {
+                (Some(ColorType::Indexed), 1) | (None, 1) => Color::PaletteIndex(data[0]),
+                (Some(ColorType::Grayscale | ColorType::GrayscaleAlpha), 2) | (None, 2) => Color::Gray(u16_at(data, 0)),
+                (Some(ColorType::Rgb | ColorType::Rgba), 6) | (None, 6) => rgb(data),
+                (Some(ColorType::Indexed), _) => return Err(wrong_length(1).into()),
+                (Some(ColorType::Grayscale | ColorType::GrayscaleAlpha), _) => return Err(wrong_length(2).into()),
+                _ => return Err(wrong_length(6).into()),
+            };
+            KnownChunk::Background(color)
+        }
+        b"tRNS" => {
+            let color = match (color_type, data.len()) {
+                (Some(ColorType::Grayscale), 2) | (None, 2) => Color::Gray(u16_at(data, 0)),
+                (Some(ColorType::Rgb), 6) | (None, 6) => rgb(data),
+                (Some(ColorType::Indexed) | None, _) => Color::PaletteAlphas(data.to_vec()),
+                (Some(ColorType::Grayscale), _) => return Err(wrong_length(2).into()),
+                (Some(ColorType::Rgb), _) => return Err(wrong_length(6).into()),
+                (Some(color_type @ (ColorType::GrayscaleAlpha | ColorType::Rgba)), _) => {
+                    return Err(KnownChunkError::NotAllowed { chunk_type: chunk_type.clone(), color_type }.into())
+                }
+            };
+            KnownChunk::Transparency(color)
+        }
+        b"acTL" => {
+            length(8)?;
+            let frames = u32_at(data, 0);
+            if frames == 0 {
+                return Err(invalid("number of frames", 0).into());
+            }
+            KnownChunk::AnimationControl { frames, plays: u32_at(data, 4) }
+        }
+        b"fcTL" => {
+            length(26)?;
+            let (width, height) = (u32_at(data, 4), u32_at(data, 8));
+            if width == 0 || height == 0 {
+                return Err(invalid("size", 0).into());
+            }
+            let dispose = match data[24] {
+                0 => Dispose::None,
+                1 => Dispose::Background,
+                2 => Dispose::Previous,
+                op => return Err(invalid("dispose op", op.into()).into()),
+            };
+            let blend = match data[25] {
+                0 => Blend::Source,
+                1 => Blend::Over,
+                op => return Err(invalid("blend op", op.into()).into()),
+            };
+            KnownChunk::FrameControl(FrameControl {
+                sequence: u32_at(data, 0),
+                width,
+                height,
+                x_offset: u32_at(data, 12),
+                y_offset: u32_at(data, 16),
+                delay_numerator: u16_at(data, 20),
+                delay_denominator: u16_at(data, 22),
+                dispose,
+                blend,
+            })
+        }
+        b"fdAT" => {
+            if data.len() < 4 {
+                return Err(wrong_length(4).into());
+            }
+            KnownChunk::FrameData { sequence: u32_at(data, 0), length: data.len() - 4 }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(known))
+}
+
+impl Display for KnownChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KnownChunk::Header(header) => {
+                write!(f, "{} x {}, {}-bit {}", header.width, header.height, header.bit_depth, header.color_type)?;
+                write!(f, ", {}", if header.interlaced { "interlaced (Adam7)" } else { "not interlaced" })
+            }
+            KnownChunk::PhysicalSize { x, y, unit: Unit::Metre } => {
+                // The spec stores pixels per metre; DPI is what people ask for.
+                let dpi = |ppm: u32| (ppm as f64 * 0.0254).round();
+                write!(f, "{x} x {y} pixels per metre ({} x {} DPI)", dpi(*x), dpi(*y))
+            }
+            KnownChunk::PhysicalSize { x, y, unit: Unit::Unknown } => write!(f, "pixel aspect ratio {x}:{y}"),
+            KnownChunk::Time(time) => write!(
+                f,
+                "modified {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+                time.year, time.month, time.day, time.hour, time.minute, time.second
+            ),
+            KnownChunk::Gamma(gamma) => write!(f, "gamma {:.5}", *gamma as f64 / 100_000.0),
+            KnownChunk::Srgb(intent) => {
+                let intent = match intent {
+                    RenderingIntent::Perceptual => "perceptual",
+                    RenderingIntent::RelativeColorimetric => "relative colorimetric",
+                    RenderingIntent::Saturation => "saturation",
+                    RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+                };
+                write!(f, "sRGB, {intent} rendering intent")
+            }
+            KnownChunk::Text(text) => write!(f, "{text}"),
+            KnownChunk::Background(color) => write!(f, "background {color}"),
+            KnownChunk::Transparency(color) => write!(f, "transparent {color}"),
+            KnownChunk::AnimationControl { frames, plays: 0 } => write!(f, "{frames} frames, looping forever"),
+            KnownChunk::AnimationControl { frames, plays } => write!(f, "{frames} frames, played {plays} times"),
+            KnownChunk::FrameControl(frame) => {
+                let dispose = match frame.dispose {
+                    Dispose::None => "kept",
+                    Dispose::Background => "cleared",
+                    Dispose::Previous => "restored",
+                };
+                let blend = match frame.blend {
+                    Blend::Source => "replacing",
+                    Blend::Over => "blended over",
+                };
+                write!(
+                    f,
+                    "frame #{}: {} x {} at {}, {}, {:.3} s, {blend} the canvas, then {dispose}",
+                    frame.sequence, frame.width, frame.height, frame.x_offset, frame.y_offset, frame.delay()
+                )
+            }
+            KnownChunk::FrameData { sequence, length } => write!(f, "frame data #{sequence}, {length} bytes"),
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::PaletteIndex(index) => write!(f, "palette entry {index}"),
+            Color::Gray(gray) => write!(f, "gray {gray}"),
+            Color::Rgb(red, green, blue) => write!(f, "RGB {red}, {green}, {blue}"),
+            Color::PaletteAlphas(alphas) => write!(f, "alpha for {} palette entries", alphas.len()),
+        }
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+fn rgb(data: &[u8]) -> Color {
+    Color::Rgb(u16_at(data, 0), u16_at(data, 2), u16_at(data, 4))
+}
+
+#[derive(Debug)]
+pub enum KnownChunkError {
+    WrongLength { chunk_type: ChunkType, expected: usize, actual: usize },
+    InvalidValue { chunk_type: ChunkType, field: &'static str, value: u32 },
+    NotAllowed { chunk_type: ChunkType, color_type: ColorType },
+}
+
+impl std::error::Error for KnownChunkError {}
+
+impl Display for KnownChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KnownChunkError::WrongLength { chunk_type, expected, actual } => {
+                write!(f, "{chunk_type} chunk has {actual} bytes of data, expected {expected}")
+            }
+            KnownChunkError::InvalidValue { chunk_type, field, value } => {
+                write!(f, "{chunk_type} chunk has an invalid {field}: {value}")
+            }
+            KnownChunkError::NotAllowed { chunk_type, color_type } => {
+                write!(f, "{chunk_type} chunk is not allowed in {color_type} images")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn summary(chunk_type: &str, data: &[u8]) -> String {
+        decode(&chunk(chunk_type, data), None).unwrap().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_decode() {
+        let header = [0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 0];
+        assert_eq!(summary("IHDR", &header), "640 x 480, 8-bit RGB with alpha, not interlaced");
+        assert_eq!(summary("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]), "2835 x 2835 pixels per metre (72 x 72 DPI)");
+        assert_eq!(summary("tIME", &[7, 232, 5, 1, 12, 30, 0]), "modified 2024-05-01 12:30:00 UTC");
+        assert_eq!(summary("gAMA", &45455u32.to_be_bytes()), "gamma 0.45455");
+        assert_eq!(summary("sRGB", &[0]), "sRGB, perceptual rendering intent");
+        assert_eq!(summary("tEXt", b"Title\0cat"), "Title: cat");
+        assert_eq!(summary("bKGD", &[0, 255, 0, 255, 0, 255]), "background RGB 255, 255, 255");
+        assert_eq!(decode(&chunk("ruSt", b"secret"), None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_animation_chunks() {
+        assert_eq!(summary("acTL", &[0, 0, 0, 3, 0, 0, 0, 0]), "3 frames, looping forever");
+        let mut control = vec![0, 0, 0, 1, 0, 0, 0, 16, 0, 0, 0, 8, 0, 0, 0, 2, 0, 0, 0, 4];
+        control.extend([0, 1, 0, 10, 1, 1]);
+        assert_eq!(summary("fcTL", &control), "frame #1: 16 x 8 at 2, 4, 0.100 s, blended over the canvas, then cleared");
+        assert_eq!(summary("fdAT", &[0, 0, 0, 2, 120, 156]), "frame data #2, 2 bytes");
+        control[24] = 3;
+        let error = decode(&chunk("fcTL", &control), None).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(KnownChunkError::InvalidValue { field: "dispose op", value: 3, .. })));
+        assert!(decode(&chunk("fdAT", &[0, 0]), None).is_err());
+    }
+
+    #[test]
+    fn test_color_type() {
+        let transparency = decode(&chunk("tRNS", &[0, 7]), Some(ColorType::Indexed)).unwrap().unwrap();
+        assert_eq!(transparency, KnownChunk::Transparency(Color::PaletteAlphas(vec![0, 7])));
+        let transparency = decode(&chunk("tRNS", &[0, 7]), None).unwrap().unwrap();
+        assert_eq!(transparency, KnownChunk::Transparency(Color::Gray(7)));
+        assert!(decode(&chunk("tRNS", &[0, 7]), Some(ColorType::Rgba)).is_err());
+        assert!(decode(&chunk("bKGD", &[3]), Some(ColorType::Rgb)).is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let error = decode(&chunk("IHDR", &[0; 12]), None).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(KnownChunkError::WrongLength { actual: 12, .. })));
+        let header = [0, 0, 0, 1, 0, 0, 0, 1, 4, 2, 0, 0, 0];
+        let error = decode(&chunk("IHDR", &header), None).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(KnownChunkError::InvalidValue { field: "bit depth", value: 4, .. })));
+        let error = decode(&chunk("tIME", &[7, 232, 13, 1, 0, 0, 0]), None).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(KnownChunkError::InvalidValue { field: "month", .. })));
+    }
+}
@@ -0,0 +1,166 @@
+//! Putting the chunks of a PNG in the order the spec recommends.
+
+use crate::chunk::Chunk;
+use crate::chunk_order::{AFTER_PLTE, BEFORE_IDAT, BEFORE_PLTE};
+
+/// Where a chunk may go, from the start of the file to its end. Chunks in the same slot keep
+/// their order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+    Header,
+    BeforePalette,
+    Palette,
+    BeforeImage,
+    Image,
+    AfterImage,
+    End,
+}
+
+/// The slot the spec puts `chunk` in, or `None` if it may go anywhere between IHDR and IEND.
+fn required_slot(chunk: &Chunk) -> Option<Slot> {
+    let name = chunk.chunk_type().to_string();
+    let name = name.as_str();
+    Some(match name {
+        "IHDR" => Slot::Header,
+        "PLTE" => Slot::Palette,
+        "IDAT" => Slot::Image,
+        "IEND" => Slot::End,
+        _ if BEFORE_PLTE.contains(&name) => Slot::BeforePalette,
+        _ if AFTER_PLTE.contains(&name) || BEFORE_IDAT.contains(&name) => Slot::BeforeImage,
+        _ => return None,
+    })
+}
+
+/// Reorders `chunks` into the order the spec recommends: IHDR first, the chunks that must come
+/// before PLTE, PLTE, the chunks that must come before the image data, the IDAT chunks next to
+/// each other, and IEND last. Any other chunk stays after the chunk it followed, moving past the
+/// IDAT chunks if it sat among them and before IEND if it came after it. The sort is stable, so
+/// chunks already in order don't move.
+pub fn normalize(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut slotted: Vec<(Slot, Chunk)> = slots(&chunks).into_iter().zip(chunks).collect();
+    slotted.sort_by_key(|(slot, _)| *slot);
+    slotted.into_iter().map(|(_, chunk)| chunk).collect()
+}
+
+/// The slot of each of `chunks` as `normalize` sorts them.
+fn slots(chunks: &[Chunk]) -> Vec<Slot> {
+    let mut previous = Slot::Header;
+    chunks
+        .iter()
+        .map(|chunk| match required_slot(chunk) {
+            Some(slot) => {
+                previous = slot;
+                slot
+            }
+            None => match previous {
+                Slot::Header => Slot::BeforePalette,
+                Slot::Image | Slot::End => Slot::AfterImage,
+                slot => slot,
+            },
+        })
+        .collect()
+}
+
+/// Inserts `chunk` into `chunks` after the other chunks of the slot the spec puts it in,
+/// before the first chunk of a later slot. Chunks that may go anywhere go after the image
+/// data, just before IEND. Returns the index `chunk` was inserted at.
+pub fn insert(chunks: &mut Vec<Chunk>, chunk: Chunk) -> usize {
+    let slot = required_slot(&chunk).unwrap_or(Slot::AfterImage);
+    let index = slots(chunks).iter().position(|other| *other > slot).unwrap_or(chunks.len());
+    chunks.insert(index, chunk);
+    index
+}
+
+/// Joins every run of consecutive IDAT chunks into a single chunk. The image data is one zlib
+/// stream however it is split, so decoders see the same pixels.
+pub fn merge_idat(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut merged: Vec<Chunk> = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        match merged.last_mut() {
+            Some(last) if is_idat(last) && is_idat(&chunk) => {
+                let mut data = last.data().to_vec();
+                data.extend_from_slice(chunk.data());
+                *last = Chunk::new(chunk.chunk_type().clone(), data);
+            }
+            _ => merged.push(chunk),
+        }
+    }
+    merged
+}
+
+/// Splits the image data into IDAT chunks of `size` bytes, only the last of each run of
+/// consecutive IDAT chunks being shorter. `merge_idat` undoes it.
+pub fn split_idat(chunks: Vec<Chunk>, size: usize) -> Vec<Chunk> {
+    merge_idat(chunks)
+        .into_iter()
+        .flat_map(|chunk| match is_idat(&chunk) && chunk.data().len() > size {
+            true => chunk.data().chunks(size).map(|part| Chunk::new(chunk.chunk_type().clone(), part.to_vec())).collect(),
+            false => vec![chunk],
+        })
+        .collect()
+}
+
+fn is_idat(chunk: &Chunk) -> bool {
+    &chunk.chunk_type().bytes() == b"IDAT"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_order::check;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunks(names: &[&str]) -> Vec<Chunk> {
+        names.iter().map(|name| Chunk::new(ChunkType::from_str(name).unwrap(), name.as_bytes().to_vec())).collect()
+    }
+
+    fn names(chunks: &[Chunk]) -> Vec<String> {
+        chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let chunks = chunks(&["tRNS", "IHDR", "PLTE", "gAMA", "IDAT", "tEXt", "IDAT", "pHYs", "IEND", "ruSt"]);
+        let normalized = normalize(chunks);
+        assert_eq!(names(&normalized), ["IHDR", "gAMA", "PLTE", "tRNS", "pHYs", "IDAT", "IDAT", "tEXt", "ruSt", "IEND"]);
+        let types: Vec<ChunkType> = normalized.iter().map(|chunk| chunk.chunk_type().clone()).collect();
+        assert_eq!(check(&types), Vec::new());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut chunks = chunks(&["IHDR", "gAMA", "PLTE", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(insert(&mut chunks, Chunk::new(ChunkType::from_str("iCCP").unwrap(), Vec::new())), 2);
+        assert_eq!(insert(&mut chunks, Chunk::new(ChunkType::from_str("iTXt").unwrap(), Vec::new())), 7);
+        assert_eq!(insert(&mut chunks, Chunk::new(ChunkType::from_str("pHYs").unwrap(), Vec::new())), 4);
+        assert_eq!(names(&chunks), ["IHDR", "gAMA", "iCCP", "PLTE", "pHYs", "IDAT", "IDAT", "tEXt", "iTXt", "IEND"]);
+        let types: Vec<ChunkType> = chunks.iter().map(|chunk| chunk.chunk_type().clone()).collect();
+        assert_eq!(check(&types), Vec::new());
+
+        let mut chunks = Vec::new();
+        assert_eq!(insert(&mut chunks, Chunk::new(ChunkType::from_str("tEXt").unwrap(), Vec::new())), 0);
+    }
+
+    #[test]
+    fn test_ordered_chunks_stay_put() {
+        let order = ["IHDR", "ruSt", "sRGB", "PLTE", "tEXt", "IDAT", "zTXt", "IEND"];
+        assert_eq!(names(&normalize(chunks(&order))), order);
+    }
+
+    #[test]
+    fn test_merge_idat() {
+        let merged = merge_idat(chunks(&["IHDR", "IDAT", "IDAT", "tEXt", "IDAT", "IEND"]));
+        assert_eq!(names(&merged), ["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(merged[1].data(), b"IDATIDAT");
+    }
+
+    #[test]
+    fn test_split_idat() {
+        let split = split_idat(chunks(&["IHDR", "IDAT", "IDAT", "IDAT", "IEND"]), 5);
+        assert_eq!(names(&split), ["IHDR", "IDAT", "IDAT", "IDAT", "IEND"]);
+        let data: Vec<&[u8]> = split[1..4].iter().map(|chunk| chunk.data()).collect();
+        assert_eq!(data, [&b"IDATI"[..], b"DATID", b"AT"]);
+        assert_eq!(merge_idat(split)[1].data(), b"IDATIDATIDAT");
+    }
+}
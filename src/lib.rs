@@ -0,0 +1,107 @@
+//! Hide messages in PNG files.
+//!
+//! [`Png`], [`Chunk`] and [`ChunkType`] read, edit and write PNG files chunk by chunk.
+//! [`encode_into`] hides a [`Payload`] in a `Png` the same way the `encode` command does, and
+//! [`decode_from`] reads it back, asking a [`CredentialProvider`] for the password if it is encrypted.
+//! The functions in [`commands`] are the `pngme` subcommands, taking the same arguments as the CLI.
+
+mod analysis;
+mod appended;
+pub mod apng;
+pub mod args;
+mod capabilities;
+mod chunk_order;
+mod batch_output;
+mod benchmark;
+mod carve;
+pub mod chunk_type;
+pub mod chunk;
+pub mod commands;
+mod corpus;
+#[cfg(feature = "crypto")]
+mod crypto;
+mod decode;
+mod detect;
+mod diff;
+mod display;
+mod doctor;
+mod encode;
+mod encoding;
+mod examples;
+mod features;
+mod error;
+pub mod events;
+mod fsck;
+mod graph;
+mod hook;
+mod i18n;
+pub mod inflate;
+mod jobs;
+#[cfg(feature = "json")]
+mod index;
+mod keyword;
+mod layout;
+pub mod known_chunks;
+mod locate;
+mod lock;
+#[cfg(feature = "crypto")]
+mod manifest;
+pub mod output;
+#[cfg(feature = "crypto")]
+mod passphrase;
+#[cfg(any(feature = "json", feature = "sqlite"))]
+mod os_path;
+mod patch;
+pub mod parser;
+pub mod payload;
+pub mod png;
+pub mod progress;
+#[cfg(feature = "crypto")]
+mod provenance;
+mod query;
+#[cfg(feature = "crypto")]
+mod rekey;
+mod repair;
+mod rng;
+mod scan;
+#[cfg(feature = "crypto")]
+mod seal;
+mod structure;
+#[cfg(feature = "crypto")]
+mod sign;
+pub mod stego;
+mod testvec;
+mod text_chunk;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod undo;
+pub mod usage;
+
+pub use batch_output::RenameScheme;
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use analysis::{compression_ratio, entropy, printable_ratio, ByteStats};
+pub use detect::{detect, Suspicion};
+pub use decode::{
+    decode_file, decode_from, CredentialProvider, DecodeError, DecodeOptions, DecodedFile, Environment, KeyFile, Keyring, PayloadMeta, Prompt,
+    StaticPassword,
+};
+pub use encode::{encode_into, EncodeError, EncodeOptions, Position, ReplacePolicy};
+pub use encoding::{Encoding, EncodingError};
+pub use error::{ErrorKind, PngMeError};
+pub use events::{Event, EventSink};
+pub use features::{Feature, FeatureError};
+pub use i18n::describe_error;
+pub use keyword::{Keyword, KeywordError};
+pub use parser::Parser;
+pub use payload::{Envelope, Payload, RawChunkData};
+pub use scan::ChunkRecord;
+pub use stego::{Method, StegoError};
+pub use structure::{validate, validate_reader, Issue, ValidationReport};
+pub use png::Png;
+pub use text_chunk::{TextChunk, TextChunkError, TextKind};
+pub use known_chunks::{KnownChunk, KnownChunkError};
+
+//custom error and result type
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
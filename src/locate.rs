@@ -0,0 +1,178 @@
+use std::fmt::{self, Display};
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// Where a chunk sits in a file, read without requiring the file to be valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSpan {
+    pub index: usize,
+    pub offset: usize,
+    pub length: u32,
+    pub chunk_type: [u8; 4],
+    pub status: ChunkStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStatus {
+    Valid,
+    InvalidType,
+    CrcMismatch { stored: u32, computed: u32 },
+    /// The file ends before the chunk's declared length and CRC.
+    Truncated,
+}
+
+/// The field of a chunk a byte belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkPart {
+    Length,
+    Type,
+    /// The byte at the given position of the chunk's data.
+    Data(usize),
+    Crc,
+}
+
+/// What a byte offset of a file falls into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Signature,
+    Chunk(ChunkSpan, ChunkPart),
+    /// The byte at the given position of the data following the last chunk.
+    Trailer(usize),
+    PastEnd,
+}
+
+impl ChunkSpan {
+    fn end(&self) -> usize {
+        self.offset + self.length as usize + 12
+    }
+}
+
+/// Walks the chunks of `bytes` by their length fields, tolerating bad CRCs and invalid types.
+/// Walking stops at the first truncated chunk, at anything after `IEND` that isn't an intact chunk,
+/// or when fewer than 12 bytes are left.
+/// Returns the chunks and the offset where the trailer starts.
+pub fn chunk_spans(bytes: &[u8]) -> (Vec<ChunkSpan>, usize) {
+    let mut spans = Vec::new();
+    let mut seen_end = false;
+    let mut offset = Png::STANDARD_HEADER.len().min(bytes.len());
+    while let Some(span) = span_at(bytes, offset, spans.len()) {
+        // Like the parser, keep chunks after IEND only while they are intact.
+        if seen_end && span.status != ChunkStatus::Valid {
+            break;
+        }
+        seen_end |= &span.chunk_type == b"IEND";
+        let (end, status) = (span.end(), span.status);
+        spans.push(span);
+        if matches!(status, ChunkStatus::InvalidType | ChunkStatus::Truncated) {
+            break;
+        }
+        offset = end;
+    }
+    let trailer_start = spans.last().map_or(offset, |span| span.end().min(bytes.len()));
+    (spans, trailer_start)
+}
+
+/// Reads the chunk starting at `offset` of `bytes`, or `None` if fewer than 12 bytes are left.
+pub fn span_at(bytes: &[u8], offset: usize, index: usize) -> Option<ChunkSpan> {
+    if bytes.len().saturating_sub(offset) < 12 {
+        return None;
+    }
+    let field = |at: usize| [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+    let length = u32::from_be_bytes(field(offset));
+    let chunk_type = field(offset + 4);
+    let data_end = offset + 8 + length as usize;
+    let status = if !ChunkType::try_from(chunk_type).is_ok_and(|t| t.is_valid()) {
+        ChunkStatus::InvalidType
+    } else if bytes.len() < data_end + 4 {
+        ChunkStatus::Truncated
+    } else {
+        let stored = u32::from_be_bytes(field(data_end));
+        let computed = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&bytes[offset + 4..data_end]);
+        if stored == computed { ChunkStatus::Valid } else { ChunkStatus::CrcMismatch { stored, computed } }
+    };
+    Some(ChunkSpan { index, offset, length, chunk_type, status })
+}
+
+/// Finds what byte `offset` of `bytes` belongs to.
+pub fn locate(bytes: &[u8], offset: usize) -> Location {
+    if offset >= bytes.len() {
+        return Location::PastEnd;
+    }
+    if offset < Png::STANDARD_HEADER.len() {
+        return Location::Signature;
+    }
+    let (spans, trailer_start) = chunk_spans(bytes);
+    match spans.into_iter().find(|span| (span.offset..span.end()).contains(&offset)) {
+        Some(span) => {
+            let position = offset - span.offset;
+            let part = match position {
+                0..=3 => ChunkPart::Length,
+                4..=7 => ChunkPart::Type,
+                _ if position < 8 + span.length as usize => ChunkPart::Data(position - 8),
+                _ => ChunkPart::Crc,
+            };
+            Location::Chunk(span, part)
+        }
+        None => Location::Trailer(offset - trailer_start),
+    }
+}
+
+impl Display for ChunkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkStatus::Valid => write!(f, "valid"),
+            ChunkStatus::InvalidType => write!(f, "invalid chunk type"),
+            ChunkStatus::CrcMismatch { stored, computed } => {
+                write!(f, "CRC mismatch (stored {stored:08x}, computed {computed:08x})")
+            }
+            ChunkStatus::Truncated => write!(f, "truncated"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunk = |t: &str, d: &str| Chunk::new(ChunkType::from_str(t).unwrap(), d.as_bytes().to_vec());
+        let mut png = Png::from_chunks(vec![chunk("IHDR", "header"), chunk("ruSt", "secret"), chunk("IEND", "")]);
+        png.append_chunk(chunk("ruSt", "after end")).unwrap();
+        png.set_trailer(b"tail".to_vec()).unwrap();
+        png.as_bytes()
+    }
+
+    #[test]
+    fn test_locate() {
+        let bytes = testing_bytes();
+        assert_eq!(locate(&bytes, 3), Location::Signature);
+        let Location::Chunk(span, part) = locate(&bytes, 8 + 18 + 10) else {
+            panic!("expected a chunk");
+        };
+        assert_eq!((span.index, &span.chunk_type, span.status), (1, b"ruSt", ChunkStatus::Valid));
+        assert_eq!(part, ChunkPart::Data(2));
+        assert!(matches!(locate(&bytes, 8), Location::Chunk(_, ChunkPart::Length)));
+        assert!(matches!(locate(&bytes, 8 + 14), Location::Chunk(_, ChunkPart::Crc)));
+        assert!(matches!(locate(&bytes, bytes.len() - 5), Location::Chunk(ChunkSpan { index: 3, .. }, ChunkPart::Crc)));
+        assert_eq!(locate(&bytes, bytes.len() - 1), Location::Trailer(3));
+        assert_eq!(locate(&bytes, bytes.len()), Location::PastEnd);
+    }
+
+    #[test]
+    fn test_damaged_chunks() {
+        let mut bytes = testing_bytes();
+        bytes[8 + 18 + 10] ^= 0xff;
+        let (spans, _) = chunk_spans(&bytes);
+        assert!(matches!(spans[1].status, ChunkStatus::CrcMismatch { .. }));
+
+        bytes.truncate(8 + 18 + 12);
+        let (spans, trailer_start) = chunk_spans(&bytes);
+        assert_eq!(spans[1].status, ChunkStatus::Truncated);
+        assert_eq!(trailer_start, bytes.len());
+    }
+}
@@ -0,0 +1,134 @@
+//! The lock marker: a chunk asking pngme not to change the file holding it, so curated assets
+//! aren't re-encoded by accident. It guards against mistakes, not against anyone determined.
+
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::i18n::format_date;
+use crate::locate::{chunk_spans, ChunkStatus};
+use crate::png::Png;
+use crate::Result;
+
+/// Chunk type of the lock: ancillary, private and safe to copy, so files edited by other tools
+/// stay locked.
+pub const LOCK_CHUNK_TYPE: &str = "loCk";
+
+/// Who locked a file and when. Stored as the Unix time in 8 big-endian bytes, then the name in
+/// UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lock {
+    pub by: String,
+    pub at: u64,
+}
+
+impl Lock {
+    /// A lock taken by `by` now.
+    pub fn new(by: &str) -> Self {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Self { by: by.to_string(), at }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = self.at.to_be_bytes().to_vec();
+        data.extend_from_slice(self.by.as_bytes());
+        Ok(Chunk::new(ChunkType::from_str(LOCK_CHUNK_TYPE)?, data))
+    }
+
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let (at, by) = data.split_first_chunk::<8>().ok_or(LockError::Malformed)?;
+        let by = String::from_utf8(by.to_vec()).map_err(|_| LockError::Malformed)?;
+        Ok(Self { by, at: u64::from_be_bytes(*at) })
+    }
+}
+
+impl Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "locked by {} on {}", self.by, format_date(self.at))
+    }
+}
+
+/// Locks `png` with `lock`, replacing any earlier lock. The lock goes right after IHDR, so it is
+/// found without reading the whole file.
+pub fn lock(png: &mut Png, lock: &Lock) -> Result<()> {
+    unlock(png)?;
+    png.insert_chunk(1.min(png.chunks().len()), lock.to_chunk()?)?;
+    Ok(())
+}
+
+/// Removes the lock of `png`, returning it if there was one.
+pub fn unlock(png: &mut Png) -> Result<Option<Lock>> {
+    let removed = png.retain_chunks(|chunk| chunk.chunk_type().to_string() != LOCK_CHUNK_TYPE)?;
+    removed.first().map(|chunk| Lock::from_data(chunk.data())).transpose()
+}
+
+/// Fails if the PNG file `bytes`, which is about to be replaced, has a lock. Damaged files are
+/// checked as far as they can be read, so a lock doesn't keep them from being repaired.
+pub fn check_unlocked(path: &Path, bytes: &[u8]) -> Result<()> {
+    let (spans, _) = chunk_spans(bytes);
+    let Some(span) = spans.iter().find(|span| span.chunk_type == LOCK_CHUNK_TYPE.as_bytes()) else {
+        return Ok(());
+    };
+    let data = match span.status {
+        ChunkStatus::Valid | ChunkStatus::CrcMismatch { .. } => &bytes[span.offset + 8..span.offset + 8 + span.length as usize],
+        _ => return Ok(()),
+    };
+    let lock = Lock::from_data(data).unwrap_or_else(|_| Lock { by: "an unknown user".to_string(), at: 0 });
+    Err(Box::new(LockError::Locked { path: path.to_path_buf(), lock }))
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// A file is locked and `--unlock` wasn't given.
+    Locked { path: PathBuf, lock: Lock },
+    Malformed,
+}
+
+impl std::error::Error for LockError {}
+
+impl Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Locked { path, lock } => {
+                write!(f, "{} is {lock}; pass --unlock to change it anyway", path.display())
+            }
+            LockError::Malformed => write!(f, "The lock chunk must hold an 8-byte time followed by a UTF-8 name"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Png {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new());
+        Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")])
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let mut png = png();
+        let taken = Lock { by: "alice".to_string(), at: 1_700_000_000 };
+        lock(&mut png, &taken).unwrap();
+        lock(&mut png, &taken).unwrap();
+        assert_eq!(png.chunks_by_type(LOCK_CHUNK_TYPE).len(), 1);
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), LOCK_CHUNK_TYPE);
+        assert_eq!(Lock::from_data(png.chunks()[1].data()).unwrap(), taken);
+
+        let error = check_unlocked(Path::new("art.png"), &png.as_bytes()).unwrap_err();
+        assert!(error.to_string().starts_with("art.png is locked by alice on "));
+        assert_eq!(unlock(&mut png).unwrap(), Some(taken));
+        assert!(check_unlocked(Path::new("art.png"), &png.as_bytes()).is_ok());
+        assert_eq!(unlock(&mut png).unwrap(), None);
+    }
+
+    #[test]
+    fn test_malformed_lock() {
+        assert!(Lock::from_data(&[0; 7]).is_err());
+        assert_eq!(Lock::from_data(&[0; 8]).unwrap(), Lock { by: String::new(), at: 0 });
+    }
+}
@@ -1,25 +1,96 @@
-mod args;
-mod chunk_type;
-mod chunk;
-mod commands;
-mod png;
+use std::process::{self, ExitCode};
+use std::thread;
+use std::time::Duration;
 
-use clap::{Parser};
-use crate::args::{Arg,SubcommandType};
-use commands::{encode,decode,print,remove};
+use std::env;
+use std::path::PathBuf;
 
-//custom error and result type
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+use clap::{CommandFactory, FromArgMatches};
+use pngme_rs::{describe_error, PngMeError};
+use pngme_rs::args::{Arg,SubcommandType};
+use pngme_rs::commands::{CommandError,encode,decode,print,remove,extract,scrub,copy_chunks,normalize,rechunk,repair,lock,benchmark,trailer,scan,carve_stream,corpus_stats,chunk_type,list_types,diff,payload_diff,undo,fsck,verify,detect,hook,testvec,examples,which_chunk,stats,record_usage,capabilities,doctor,inspect};
+#[cfg(feature = "crypto")]
+use pngme_rs::commands::{provenance,rekey,seal};
+use pngme_rs::output;
+use pngme_rs::usage::BytesRead;
 
-fn main() -> Result<()> {
-    let args = Arg::parse();
+fn main() -> ExitCode {
+    let matches = Arg::command().get_matches();
+    let args = Arg::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let operation = matches.subcommand_name().unwrap_or_default().to_string();
+    let stats_file = args.stats_file.clone().or_else(|| env::var_os("PNGME_STATS_FILE").map(PathBuf::from));
+    output::set_plain(args.plain);
+    output::set_assume_yes(args.yes);
+    pngme_rs::progress::set_shown(match (args.progress, args.no_progress) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    });
+    if let Some(seconds) = args.timeout {
+        // The watchdog ends the process wherever the command is, even inside a long parse.
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs_f64(seconds));
+            pngme_rs::png::remove_temp_files();
+            let error = PngMeError::from(CommandError::TimedOut(seconds));
+            eprintln!("pngme: {}", describe_error(&error));
+            process::exit(error.kind().exit_code().into());
+        });
+    }
 
-    let _ = match args.subcommand {
-        SubcommandType::Encode(args) => encode(args),
-        SubcommandType::Decode(args) => decode(args),
-        SubcommandType::Remove(args) => remove(args),
-        SubcommandType::Print(args) => print(args),
+    let read = BytesRead::default();
+    let result = match args.subcommand {
+        SubcommandType::Encode(args) => encode(*args, &read),
+        SubcommandType::Decode(args) => decode(args, &read),
+        SubcommandType::Remove(args) => remove(args, &read),
+        SubcommandType::Extract(args) => extract(args, &read),
+        SubcommandType::Scrub(args) => scrub(args, &read),
+        SubcommandType::CopyChunks(args) => copy_chunks(args, &read),
+        SubcommandType::Normalize(args) => normalize(args, &read),
+        SubcommandType::Rechunk(args) => rechunk(args, &read),
+        SubcommandType::Repair(args) => repair(args, &read),
+        SubcommandType::Lock(args) => lock(args, &read),
+        SubcommandType::Print(args) => print(args, &read),
+        SubcommandType::Benchmark(args) => benchmark(args, &read),
+        SubcommandType::Trailer(args) => trailer(args, &read),
+        SubcommandType::Scan(args) => scan(args, &read),
+        SubcommandType::CarveStream(args) => carve_stream(args, &read),
+        SubcommandType::CorpusStats(args) => corpus_stats(args, &read),
+        SubcommandType::ListTypes(args) => list_types(args, &read),
+        SubcommandType::ChunkType(args) => chunk_type(args),
+        SubcommandType::Diff(args) => diff(args, &read),
+        SubcommandType::PayloadDiff(args) => payload_diff(args, &read),
+        SubcommandType::Undo(args) => undo(args),
+        SubcommandType::Verify(args) => verify(args, &read),
+        SubcommandType::Detect(args) => detect(args, &read),
+        #[cfg(feature = "crypto")]
+        SubcommandType::Provenance(args) => provenance(args, &read),
+        #[cfg(feature = "crypto")]
+        SubcommandType::Seal(args) => seal(args, &read),
+        #[cfg(feature = "crypto")]
+        SubcommandType::Rekey(args) => rekey(args, &read),
+        SubcommandType::Fsck(args) => fsck(args, &read),
+        SubcommandType::Hook(args) => hook(args, &read),
+        SubcommandType::Testvec(args) => testvec(args),
+        SubcommandType::Examples(args) => examples(args),
+        SubcommandType::WhichChunk(args) => which_chunk(args, &read),
+        SubcommandType::Stats(args) => stats(args, stats_file.clone()),
+        SubcommandType::Capabilities(args) => capabilities(args),
+        SubcommandType::Doctor(args) => doctor(args),
+        SubcommandType::Inspect(args) => inspect(args, &read),
     };
-    Ok(())
-}
\ No newline at end of file
+    // A stats file that can't be updated must not change the outcome of the command itself.
+    if let (Ok(()), Some(stats_file)) = (&result, stats_file) {
+        if operation != "stats" {
+            if let Err(error) = record_usage(&stats_file, &operation, &read) {
+                eprintln!("pngme: warning: {}", describe_error(&error));
+            }
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("pngme: {}", describe_error(&error));
+            ExitCode::from(error.kind().exit_code())
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::chunk_type::ChunkType;
+use crate::payload::Envelope;
+use crate::png::{replace_file, Png, WriteOptions};
+use crate::Result;
+
+/// A build manifest listing the payload each asset is expected to carry,
+/// written by `encode --manifest` and checked by `verify --manifest`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub assets: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    #[serde(with = "crate::os_path")]
+    pub path: PathBuf,
+    pub chunk_type: ChunkType,
+    pub length: u32,
+    pub sha256: String,
+}
+
+/// The outcome of checking one manifest entry against the file on disk.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EntryStatus {
+    /// A chunk of the expected type carries the expected payload.
+    Valid,
+    /// No chunk of the expected type exists.
+    MissingChunk,
+    /// Chunks of the expected type exist, but none carries the expected payload.
+    DigestMismatch,
+}
+
+impl Manifest {
+    /// Loads the manifest at `path`, or an empty manifest if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)?;
+        replace_file(path, &WriteOptions::default(), |writer| writer.write_all(&bytes))?;
+        Ok(())
+    }
+
+    /// Records that `path` carries `payload` in a chunk of type `chunk_type`,
+    /// replacing any earlier entry for the same asset and chunk type.
+    pub fn record(&mut self, path: &Path, chunk_type: &ChunkType, payload: &[u8]) {
+        self.assets.retain(|entry| !(entry.path == path && &entry.chunk_type == chunk_type));
+        self.assets.push(ManifestEntry {
+            path: path.to_path_buf(),
+            chunk_type: chunk_type.clone(),
+            length: payload.len() as u32,
+            sha256: sha256_hex(payload),
+        });
+    }
+}
+
+impl ManifestEntry {
+    /// Checks whether `png` carries the payload described by this entry.
+    pub fn check(&self, png: &Png) -> EntryStatus {
+        let mut candidates = png
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type() == &self.chunk_type)
+            .peekable();
+        if candidates.peek().is_none() {
+            return EntryStatus::MissingChunk;
+        }
+        // The entry holds the digest of the whole envelope, which may be split across chunks.
+        let joined = || Envelope::join(png.chunks().iter().filter(|c| c.chunk_type() == &self.chunk_type).map(|c| c.raw_data()));
+        if candidates.any(|chunk| sha256_hex(chunk.data()) == self.sha256)
+            || joined().is_ok_and(|envelope| sha256_hex(envelope.into_raw().as_bytes()) == self.sha256)
+        {
+            EntryStatus::Valid
+        } else {
+            EntryStatus::DigestMismatch
+        }
+    }
+}
+
+/// The SHA-256 digest of `data` as lowercase hex.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn png_with(chunk_type: &str, payload: &[u8]) -> Png {
+        Png::from_chunks(vec![Chunk::new(ChunkType::from_str(chunk_type).unwrap(), payload.to_vec())])
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_record_replaces_entry() {
+        let mut manifest = Manifest::default();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        manifest.record(Path::new("a.png"), &chunk_type, b"first");
+        manifest.record(Path::new("a.png"), &chunk_type, b"second");
+        manifest.record(Path::new("b.png"), &chunk_type, b"first");
+
+        assert_eq!(manifest.assets.len(), 2);
+        assert_eq!(manifest.assets[0].path, Path::new("a.png"));
+        assert_eq!(manifest.assets[0].sha256, sha256_hex(b"second"));
+    }
+
+    #[test]
+    fn test_check_entry() {
+        let mut manifest = Manifest::default();
+        manifest.record(Path::new("a.png"), &ChunkType::from_str("ruSt").unwrap(), b"payload");
+        let entry = &manifest.assets[0];
+
+        assert_eq!(entry.check(&png_with("ruSt", b"payload")), EntryStatus::Valid);
+        assert_eq!(entry.check(&png_with("ruSt", b"tampered")), EntryStatus::DigestMismatch);
+        assert_eq!(entry.check(&png_with("tEXt", b"payload")), EntryStatus::MissingChunk);
+    }
+}
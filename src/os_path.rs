@@ -0,0 +1,118 @@
+//! Paths in formats that only hold Unicode text, such as JSON, without losing the ones that
+//! aren't valid Unicode.
+
+use std::path::Path;
+#[cfg(feature = "json")]
+use std::path::PathBuf;
+
+#[cfg(feature = "json")]
+use base64ct::{Base64, Encoding};
+
+/// Starts a path that isn't valid Unicode, spelled as the base64 of its `raw_bytes`. No real path
+/// starts with it, since NUL can't occur in paths.
+#[cfg(feature = "json")]
+const RAW_MARKER: char = '\0';
+
+/// `path` as text: the path itself when it is valid Unicode, otherwise `RAW_MARKER` and the
+/// base64 of its raw bytes.
+#[cfg(feature = "json")]
+pub fn encode(path: &Path) -> String {
+    match path.to_str() {
+        Some(text) => text.to_string(),
+        None => format!("{RAW_MARKER}{}", Base64::encode_string(&raw_bytes(path))),
+    }
+}
+
+/// The path `text`, written by `encode`, stands for, or `None` if its raw bytes are damaged.
+#[cfg(feature = "json")]
+pub fn decode(text: &str) -> Option<PathBuf> {
+    match text.strip_prefix(RAW_MARKER) {
+        Some(encoded) => from_raw_bytes(Base64::decode_vec(encoded).ok()?),
+        None => Some(PathBuf::from(text)),
+    }
+}
+
+/// The bytes the platform names `path` with: the bytes themselves on Unix, and the UTF-16 code
+/// units in little-endian order on Windows.
+pub fn raw_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    return std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+    #[cfg(windows)]
+    return std::os::windows::ffi::OsStrExt::encode_wide(path.as_os_str()).flat_map(u16::to_le_bytes).collect();
+    #[cfg(not(any(unix, windows)))]
+    return path.to_string_lossy().into_owned().into_bytes();
+}
+
+#[cfg(feature = "json")]
+fn from_raw_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    #[cfg(unix)]
+    return Some(<std::ffi::OsString as std::os::unix::ffi::OsStringExt>::from_vec(bytes).into());
+    #[cfg(windows)]
+    {
+        if bytes.len() % 2 != 0 {
+            return None;
+        }
+        let wide: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        return Some(<std::ffi::OsString as std::os::windows::ffi::OsStringExt>::from_wide(&wide).into());
+    }
+    #[cfg(not(any(unix, windows)))]
+    return String::from_utf8(bytes).ok().map(PathBuf::from);
+}
+
+/// `#[serde(with = "crate::os_path")]` for a path field, stored as `encode` spells it.
+#[cfg(feature = "json")]
+pub fn serialize<S: serde::Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(path))
+}
+
+#[cfg(feature = "crypto")]
+pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+    decode(&text).ok_or_else(|| serde::de::Error::custom(format!("damaged path {text:?}")))
+}
+
+/// `#[serde(with = "crate::os_path::keys")]` for a map keyed by paths.
+#[cfg(feature = "json")]
+pub mod keys {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, V: Serialize>(map: &BTreeMap<PathBuf, V>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(path, value)| (super::encode(path), value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(deserializer: D) -> Result<BTreeMap<PathBuf, V>, D::Error> {
+        BTreeMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(text, value)| {
+                let path = super::decode(&text).ok_or_else(|| serde::de::Error::custom(format!("damaged path {text:?}")))?;
+                Ok((path, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_path_is_unchanged() {
+        let path = Path::new("images/straße.png");
+        assert_eq!(encode(path), "images/straße.png");
+        assert_eq!(decode("images/straße.png").unwrap(), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_unicode_path_roundtrips() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"images/caf\xe9.png"));
+        let encoded = encode(path);
+        assert!(encoded.starts_with(RAW_MARKER));
+        assert_eq!(decode(&encoded).unwrap(), path);
+        assert_eq!(decode("\0not base64!"), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::env;
+use std::fmt::Display;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::chunk::{Chunk, PayloadKind};
+use crate::events::{Event, EventSink};
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static DATA_ON_STDOUT: AtomicBool = AtomicBool::new(false);
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Switches to plain output for the rest of the process. Plain output is also used
+/// when the terminal declares itself dumb.
+pub fn set_plain(plain: bool) {
+    let dumb_terminal = env::var("TERM").is_ok_and(|term| term == "dumb");
+    PLAIN.store(plain || dumb_terminal, Ordering::Relaxed);
+}
+
+/// Whether output must avoid box drawing, color and animations and keep one record per line,
+/// so it works with screen readers and line-based log collectors.
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// A color for marking up output on a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+/// Whether stdout gets color: it must be a terminal, output not plain and `NO_COLOR` unset.
+pub fn uses_color() -> bool {
+    !is_plain() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
+}
+
+/// `text` in `color` when stdout gets color, as is otherwise.
+pub fn paint(text: impl Display, color: Color) -> String {
+    if !uses_color() {
+        return text.to_string();
+    }
+    let code = match color {
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+    };
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+/// Answers yes to every confirmation for the rest of the process.
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Shows `plan` on stderr and asks whether to carry it out. Goes ahead without asking after
+/// [`set_assume_yes`], or when stdin isn't a terminal, since nobody could answer.
+pub fn confirm(plan: &str) -> io::Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) || !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    eprint!("{plan}{} ", crate::i18n::tr("confirm-question", &[]));
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes" | "j" | "ja"))
+}
+
+/// Reserves stdout for file contents, sending status messages to stderr from now on.
+pub fn reserve_stdout() {
+    DATA_ON_STDOUT.store(true, Ordering::Relaxed);
+}
+
+/// Prints a status message on stdout, or on stderr once stdout carries file contents.
+pub fn status(message: impl Display) {
+    crate::progress::clear_line();
+    if DATA_ON_STDOUT.load(Ordering::Relaxed) {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Formats `chunk` as a block, or as a single line in plain mode.
+pub fn chunk(chunk: &Chunk) -> String {
+    if !is_plain() {
+        return chunk.to_string();
+    }
+    let empty = if chunk.payload_kind() == PayloadKind::Empty { " (empty payload)" } else { "" };
+    let mismatch = match chunk.stored_crc().filter(|_| !chunk.crc_ok()) {
+        Some(stored) => format!(" stored_crc={stored} crc_ok=false"),
+        None => String::new(),
+    };
+    format!("Chunk type={} length={} crc={}{mismatch}{empty}", chunk.chunk_type(), chunk.length(), chunk.crc())
+}
+
+/// Prints the events of a batch command as the CLI's log.
+pub struct EventLog {
+    warning_prefix: &'static str,
+    warnings_on_stdout: bool,
+}
+
+impl EventLog {
+    /// Prints found payloads as `path<TAB>index<TAB>type<TAB>length` lines on stdout,
+    /// and warnings on stderr.
+    pub fn new() -> Self {
+        Self { warning_prefix: "", warnings_on_stdout: false }
+    }
+
+    /// Prints warnings on stdout behind `prefix`, for commands whose report is the warnings.
+    pub fn report(prefix: &'static str) -> Self {
+        Self { warning_prefix: prefix, warnings_on_stdout: true }
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSink for EventLog {
+    fn emit(&mut self, event: Event) {
+        match event {
+            Event::PayloadFound { path, index, record } => {
+                println!("{}\t{index}\t{}\t{}", path.display(), record.chunk_type, record.length)
+            }
+            Event::Warning { path, message } if self.warnings_on_stdout => {
+                println!("{}{}: {message}", self.warning_prefix, path.display())
+            }
+            Event::Warning { path, message } => eprintln!("{}{}: {message}", self.warning_prefix, path.display()),
+            Event::FileStarted { .. } | Event::ChunkParsed { .. } | Event::FileFinished { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_plain_chunk_is_one_line() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), Vec::new());
+        set_plain(true);
+        let line = super::chunk(&chunk);
+        set_plain(false);
+        assert!(!line.contains('\n'));
+        assert!(line.ends_with("(empty payload)"));
+    }
+}
@@ -0,0 +1,212 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::chunk::{Chunk, ChunkError};
+use crate::png::{DuplicatePolicy, ParseOptions, Png, PngError};
+use crate::Result;
+
+/// What a [`Parser`] found in the data fed to it so far.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A complete chunk, in file order.
+    Chunk(Chunk),
+    /// Bytes following the last chunk. The trailer may arrive over several events.
+    Trailer(Bytes),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Signature,
+    Chunks,
+    /// After `IEND`, where intact chunks are still read but anything else starts the trailer.
+    AfterEnd,
+    Trailer,
+    Failed,
+}
+
+/// Parses a PNG that arrives in pieces of any size, e.g. from a network upload, applying the same
+/// rules as `Png::parse`. Each call to [`Parser::feed`] returns the chunks completed by that piece,
+/// so a file can be scanned while it is still being received. Only the chunk being received is
+/// held in memory.
+pub struct Parser {
+    options: ParseOptions,
+    buffer: BytesMut,
+    state: State,
+    seen_header: bool,
+}
+
+impl Parser {
+    pub fn new(options: ParseOptions) -> Self {
+        Self { options, buffer: BytesMut::new(), state: State::Signature, seen_header: false }
+    }
+
+    /// Adds the next piece of the file and returns the events it completes. After an error the
+    /// parser ignores further input.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Event>> {
+        if self.state == State::Failed {
+            return Ok(Vec::new());
+        }
+        self.buffer.extend_from_slice(data);
+        let mut events = Vec::new();
+        while let Some(event) = self.next_event().inspect_err(|_| self.state = State::Failed)? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Signals the end of the file and returns the remaining events. Fails if the file ends
+    /// inside its signature or inside a chunk before `IEND`.
+    pub fn finish(mut self) -> Result<Vec<Event>> {
+        let rest = self.buffer.split().freeze();
+        match self.state {
+            State::Signature => Err(Box::new(PngError::SmallInput)),
+            // Like `ChunkReader`, fewer bytes than a length field are a trailer, not a chunk.
+            State::Chunks if rest.len() >= 4 => Err(Box::new(ChunkError::SmallInput)),
+            _ if rest.is_empty() || self.state == State::Failed => Ok(Vec::new()),
+            _ => Ok(vec![Event::Trailer(rest)]),
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<Event>> {
+        match self.state {
+            State::Signature => {
+                if self.buffer.len() < Png::STANDARD_HEADER.len() {
+                    return Ok(None);
+                }
+                if self.buffer[..8] != Png::STANDARD_HEADER {
+                    return Err(Box::new(PngError::InvalidHeader));
+                }
+                let _ = self.buffer.split_to(8);
+                self.state = State::Chunks;
+                self.next_event()
+            }
+            State::Chunks | State::AfterEnd => {
+                let Some(bytes) = self.take_chunk_bytes() else {
+                    return Ok(None);
+                };
+                if self.state == State::AfterEnd {
+                    return self.chunk_after_end(bytes);
+                }
+                let chunk = match self.options.ignore_crc {
+                    true => Chunk::parse_lenient(bytes)?,
+                    false => Chunk::try_from(bytes)?,
+                };
+                match &chunk.chunk_type().bytes() {
+                    b"IHDR" if self.seen_header => match self.options.duplicates {
+                        DuplicatePolicy::Error => return Err(Box::new(PngError::DuplicateHeader)),
+                        DuplicatePolicy::KeepFirst => return self.next_event(),
+                        DuplicatePolicy::KeepAll => {}
+                    },
+                    b"IHDR" => self.seen_header = true,
+                    b"IEND" if self.options.duplicates == DuplicatePolicy::KeepFirst => self.state = State::Trailer,
+                    b"IEND" => self.state = State::AfterEnd,
+                    _ => {}
+                }
+                Ok(Some(Event::Chunk(chunk)))
+            }
+            State::Trailer if !self.buffer.is_empty() => Ok(Some(Event::Trailer(self.buffer.split().freeze()))),
+            State::Trailer | State::Failed => Ok(None),
+        }
+    }
+
+    /// Takes the next chunk's bytes off the buffer once all of them have arrived.
+    fn take_chunk_bytes(&mut self) -> Option<Bytes> {
+        if self.buffer.len() < 4 {
+            return None;
+        }
+        let length = u32::from_be_bytes([self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]]) as usize;
+        if self.buffer.len() < length + 12 {
+            return None;
+        }
+        Some(self.buffer.split_to(length + 12).freeze())
+    }
+
+    fn chunk_after_end(&mut self, bytes: Bytes) -> Result<Option<Event>> {
+        match Chunk::try_from(bytes.clone()) {
+            Ok(_) if self.options.duplicates == DuplicatePolicy::Error => Err(Box::new(PngError::ChunkAfterEnd)),
+            Ok(chunk) => Ok(Some(Event::Chunk(chunk))),
+            Err(_) => {
+                self.state = State::Trailer;
+                let mut trailer = BytesMut::from(bytes.as_ref());
+                trailer.extend_from_slice(&self.buffer.split());
+                Ok(Some(Event::Trailer(trailer.freeze())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn feed_in_pieces(bytes: &[u8], piece: usize, options: ParseOptions) -> Result<Vec<Event>> {
+        let mut parser = Parser::new(options);
+        let mut events = Vec::new();
+        for piece in bytes.chunks(piece) {
+            events.extend(parser.feed(piece)?);
+        }
+        events.extend(parser.finish()?);
+        Ok(events)
+    }
+
+    fn testing_png() -> Png {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", "header"), chunk("ruSt", "secret"), chunk("IEND", "")]);
+        png.append_chunk(chunk("ruSt", "appended")).unwrap();
+        png.set_trailer(Bytes::from_static(b"trailing data")).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_matches_png_parse() {
+        let png = testing_png();
+        let bytes = png.as_bytes();
+        for piece in [1, 5, 13, bytes.len()] {
+            let events = feed_in_pieces(&bytes, piece, ParseOptions::default()).unwrap();
+            let chunks: Vec<Vec<u8>> = events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Chunk(chunk) => Some(chunk.as_bytes()),
+                    Event::Trailer(_) => None,
+                })
+                .collect();
+            assert_eq!(chunks, png.chunks().iter().map(Chunk::as_bytes).collect::<Vec<_>>());
+            let trailer: Vec<u8> = events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Trailer(trailer) => Some(trailer.to_vec()),
+                    Event::Chunk(_) => None,
+                })
+                .flatten()
+                .collect();
+            assert_eq!(trailer, b"trailing data");
+        }
+    }
+
+    #[test]
+    fn test_chunks_arrive_when_complete() {
+        let bytes = testing_png().as_bytes();
+        let mut parser = Parser::new(ParseOptions::default());
+        assert!(parser.feed(&bytes[..8 + 12]).unwrap().is_empty());
+        let events = parser.feed(&bytes[8 + 12..8 + 18]).unwrap();
+        assert!(matches!(&events[..], [Event::Chunk(chunk)] if chunk.data() == b"header"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = testing_png().as_bytes();
+        let mut parser = Parser::new(ParseOptions::default());
+        assert!(parser.feed(b"not a png file").is_err());
+        assert!(parser.feed(&bytes).unwrap().is_empty());
+
+        let options = ParseOptions { duplicates: DuplicatePolicy::Error, ..ParseOptions::default() };
+        let error = feed_in_pieces(&bytes, 7, options).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(PngError::ChunkAfterEnd)));
+
+        assert!(feed_in_pieces(&bytes[..20], 3, ParseOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::crypto::CryptoError;
+use crate::Result;
+
+/// Symbols of generated passphrases: lowercase letters and digits without the easily confused
+/// `l`, `o`, `0` and `1`. 32 symbols give 5 bits each.
+const ALPHABET: &[u8; 32] = b"abcdefghijkmnpqrstuvwxyz23456789";
+const GROUPS: usize = 5;
+const GROUP_LENGTH: usize = 5;
+
+/// Passwords people pick most often, which guessing starts with.
+const COMMON: &[&str] = &[
+    "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111", "1234567", "dragon",
+    "123123", "baseball", "abc123", "football", "monkey", "letmein", "shadow", "master", "696969", "mustang",
+    "michael", "pussy", "superman", "1234567890", "iloveyou", "trustno1", "hunter2", "welcome", "admin", "secret",
+    "passw0rd", "qwertyuiop", "login", "starwars", "princess", "sunshine", "charlie", "changeme",
+];
+
+/// How hard a password is to guess, judged by [`estimate_bits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strength {
+    Weak,
+    Fair,
+    Strong,
+}
+
+impl Strength {
+    pub fn of(password: &str) -> Self {
+        match estimate_bits(password) {
+            bits if bits < 40.0 => Strength::Weak,
+            bits if bits < 64.0 => Strength::Fair,
+            _ => Strength::Strong,
+        }
+    }
+}
+
+/// Estimates the bits of entropy of `password` the way guessing tools see it: common passwords,
+/// optionally followed by digits, are guessed almost at once, and runs of a repeated character or
+/// of a sequence such as `abcd` or `1234` count little more than their first character.
+pub fn estimate_bits(password: &str) -> f64 {
+    let lowercase = password.to_lowercase();
+    let base = lowercase.trim_end_matches(|c: char| c.is_ascii_digit());
+    if COMMON.contains(&lowercase.as_str()) || COMMON.contains(&base) {
+        let suffix = lowercase.len() - base.len();
+        return 5.0 + suffix as f64 * 10f64.log2();
+    }
+
+    let has = |test: fn(&char) -> bool| password.chars().any(|c| test(&c));
+    let mut pool = 0;
+    if has(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if has(char::is_ascii_punctuation) || has(|c| *c == ' ') {
+        pool += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    let bits_per_symbol = (pool.max(1) as f64).log2();
+
+    let chars: Vec<char> = password.chars().collect();
+    let mut symbols = 0.0;
+    for (index, c) in chars.iter().enumerate() {
+        let continues_run = index > 0 && {
+            let step = *c as i64 - chars[index - 1] as i64;
+            step.abs() <= 1
+        };
+        symbols += if continues_run { 0.25 } else { 1.0 };
+    }
+    symbols * bits_per_symbol
+}
+
+/// Generates a random passphrase of 125 bits, e.g. `k3m9x-q2rtv-...`, grouped for reading aloud.
+pub fn generate() -> Result<String> {
+    let mut random = [0u8; GROUPS * GROUP_LENGTH];
+    getrandom::fill(&mut random).map_err(|_| CryptoError::Random)?;
+    let groups: Vec<String> = random
+        .chunks(GROUP_LENGTH)
+        .map(|group| group.iter().map(|byte| ALPHABET[(byte % 32) as usize] as char).collect())
+        .collect();
+    Ok(groups.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength() {
+        assert_eq!(Strength::of("password"), Strength::Weak);
+        assert_eq!(Strength::of("Password123"), Strength::Weak);
+        assert_eq!(Strength::of("aaaaaaaaaaaaaaaa"), Strength::Weak);
+        assert_eq!(Strength::of("abcdefghijklmnop"), Strength::Weak);
+        assert_eq!(Strength::of("sunflower42"), Strength::Fair);
+        assert_eq!(Strength::of("correct horse battery staple"), Strength::Strong);
+    }
+
+    #[test]
+    fn test_generate() {
+        let passphrase = generate().unwrap();
+        assert_eq!(passphrase.len(), GROUPS * GROUP_LENGTH + GROUPS - 1);
+        assert!(passphrase.bytes().all(|b| b == b'-' || ALPHABET.contains(&b)));
+        assert_ne!(passphrase, generate().unwrap());
+        assert_eq!(Strength::of(&passphrase), Strength::Strong);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::CrcPolicy;
+use crate::diff::{align, DiffOp};
+use crate::png::{Png, WriteOptions};
+use crate::{Error, Result};
+
+/// Replaces `old` with `new` at `offset` of the original file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// The byte-range edits turning one file into another. Offsets refer to the original file
+/// and edits are sorted by offset without overlapping.
+///
+/// The text form has one edit per line: the decimal offset, then the old and the new bytes
+/// in hex, with `-` standing for no bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patch {
+    pub edits: Vec<Edit>,
+}
+
+impl Patch {
+    /// Computes the edits turning `a` into `b`, replacing whole chunks and the trailer.
+    /// Both files are serialized with the CRCs they were read with.
+    pub fn between(a: &Png, b: &Png) -> Self {
+        let (a_offsets, b_offsets) = (a.chunk_offsets(), b.chunk_offsets());
+        let preserve = WriteOptions { crc: CrcPolicy::Preserve, ..WriteOptions::default() };
+        let (a_bytes, b_bytes) = (a.to_bytes(&preserve), b.to_bytes(&preserve));
+        let a_range = |i: usize| a_offsets[i]..a_offsets[i] + a.chunks()[i].length() as usize + 12;
+        let b_range = |j: usize| b_offsets[j]..b_offsets[j] + b.chunks()[j].length() as usize + 12;
+
+        let mut edits = Vec::new();
+        let mut pending: Option<Edit> = None;
+        let mut a_position = Png::STANDARD_HEADER.len();
+        for op in align(a.chunks(), b.chunks()) {
+            let edit = pending.get_or_insert_with(|| Edit { offset: a_position, old: Vec::new(), new: Vec::new() });
+            match op {
+                DiffOp::Removed(i) => {
+                    edit.old.extend_from_slice(&a_bytes[a_range(i)]);
+                    a_position = a_range(i).end;
+                }
+                DiffOp::Added(j) => edit.new.extend_from_slice(&b_bytes[b_range(j)]),
+                DiffOp::Same(i, _) => {
+                    edits.extend(pending.take().filter(|e| !e.old.is_empty() || !e.new.is_empty()));
+                    a_position = a_range(i).end;
+                }
+            }
+        }
+        if a.trailer() != b.trailer() {
+            let edit = pending.get_or_insert_with(|| Edit { offset: a_position, old: Vec::new(), new: Vec::new() });
+            edit.old.extend_from_slice(a.trailer());
+            edit.new.extend_from_slice(b.trailer());
+        }
+        edits.extend(pending.filter(|e| !e.old.is_empty() || !e.new.is_empty()));
+        Self { edits }
+    }
+
+    /// Computes a single edit turning `a` into `b`, covering everything between
+    /// their longest common prefix and suffix.
+    pub fn between_bytes(a: &[u8], b: &[u8]) -> Self {
+        let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        let max_suffix = a.len().min(b.len()) - prefix;
+        let suffix = a.iter().rev().zip(b.iter().rev()).take(max_suffix).take_while(|(x, y)| x == y).count();
+        if prefix + suffix == a.len() && prefix + suffix == b.len() {
+            return Self::default();
+        }
+        let edit = Edit {
+            offset: prefix,
+            old: a[prefix..a.len() - suffix].to_vec(),
+            new: b[prefix..b.len() - suffix].to_vec(),
+        };
+        Self { edits: vec![edit] }
+    }
+
+    /// Applies this patch to `input`, checking that every edit's old bytes are present.
+    pub fn apply(&self, input: &[u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len());
+        let mut position = 0;
+        for edit in &self.edits {
+            let end = edit.offset + edit.old.len();
+            if edit.offset < position || input.get(edit.offset..end) != Some(edit.old.as_slice()) {
+                return Err(Box::new(PatchError::Mismatch(edit.offset)));
+            }
+            output.extend_from_slice(&input[position..edit.offset]);
+            output.extend_from_slice(&edit.new);
+            position = end;
+        }
+        output.extend_from_slice(&input[position..]);
+        Ok(output)
+    }
+}
+
+impl Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for edit in &self.edits {
+            writeln!(f, "{} {} {}", edit.offset, hex_or_dash(&edit.old), hex_or_dash(&edit.new))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Patch {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut edits = Vec::new();
+        for (number, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [offset, old, new] = fields[..] else {
+                return Err(Box::new(PatchError::InvalidLine(number + 1)));
+            };
+            let invalid = |_| PatchError::InvalidLine(number + 1);
+            edits.push(Edit {
+                offset: offset.parse().map_err(|_| PatchError::InvalidLine(number + 1))?,
+                old: parse_hex_or_dash(old).map_err(invalid)?,
+                new: parse_hex_or_dash(new).map_err(invalid)?,
+            });
+        }
+        Ok(Self { edits })
+    }
+}
+
+fn hex_or_dash(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "-".to_string();
+    }
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_hex_or_dash(s: &str) -> std::result::Result<Vec<u8>, ()> {
+    if s == "-" {
+        return Ok(Vec::new());
+    }
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| ()))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    Mismatch(usize),
+    InvalidLine(usize),
+}
+
+impl std::error::Error for PatchError {}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Mismatch(offset) => write!(f, "File does not match the patch at offset {offset}"),
+            PatchError::InvalidLine(line) => write!(f, "Invalid patch line {line}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+
+    fn png(chunks: &[(&str, &str)]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(t, d)| Chunk::new(ChunkType::from_str(t).unwrap(), d.as_bytes().to_vec()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_patch_transforms_a_into_b() {
+        let a = png(&[("IHDR", "header"), ("tEXt", "old"), ("IDAT", "pixels"), ("IEND", "")]);
+        let mut b = png(&[("IHDR", "header"), ("tEXt", "new"), ("IDAT", "pixels"), ("IEND", ""), ("ruSt", "x")]);
+        b.set_trailer(b"trailing".to_vec()).unwrap();
+
+        let patch = Patch::between(&a, &b);
+        assert_eq!(patch.edits.len(), 2);
+        assert_eq!(patch.edits[0].offset, a.chunk_offsets()[1]);
+        assert_eq!(patch.apply(&a.as_bytes()).unwrap(), b.as_bytes());
+    }
+
+    #[test]
+    fn test_identical_files_have_empty_patch() {
+        let a = png(&[("IHDR", "header"), ("IEND", "")]);
+        assert!(Patch::between(&a, &a).edits.is_empty());
+    }
+
+    #[test]
+    fn test_patch_text_roundtrip() {
+        let a = png(&[("IHDR", "header"), ("IEND", "")]);
+        let b = png(&[("IHDR", "header"), ("ruSt", "hi"), ("IEND", "")]);
+        let patch = Patch::between(&a, &b);
+
+        let text = patch.to_string();
+        assert!(text.starts_with(&format!("{} - ", a.chunk_offsets()[1])));
+        assert_eq!(Patch::from_str(&text).unwrap(), patch);
+        assert!(Patch::from_str("12 zz -").is_err());
+    }
+
+    #[test]
+    fn test_between_bytes() {
+        let a = b"header-old-footer";
+        let b = b"header-brand-new-footer";
+        let patch = Patch::between_bytes(a, b);
+        assert_eq!(patch.edits, vec![Edit { offset: 7, old: b"old".to_vec(), new: b"brand-new".to_vec() }]);
+        assert_eq!(patch.apply(a).unwrap(), b);
+        assert_eq!(Patch::between_bytes(b, a).apply(b).unwrap(), a);
+        assert!(Patch::between_bytes(a, a).edits.is_empty());
+        assert_eq!(Patch::between_bytes(b"aaa", b"aaaa").apply(b"aaa").unwrap(), b"aaaa");
+    }
+
+    #[test]
+    fn test_apply_rejects_other_file() {
+        let a = png(&[("IHDR", "header"), ("tEXt", "old"), ("IEND", "")]);
+        let b = png(&[("IHDR", "header"), ("IEND", "")]);
+        let patch = Patch::between(&a, &b);
+        assert!(patch.apply(&b.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::chunk::PayloadKind;
+use crate::Result;
+
+/// Marks chunk data holding one part of an envelope split across several chunks.
+pub const PART_MAGIC: &[u8; 4] = b"PMSP";
+
+/// Bytes in front of each part's data: the magic, then the envelope's id, the part's
+/// index and the number of parts, each a big-endian u32.
+pub const PART_HEADER_LENGTH: usize = 16;
+
+/// Marks an envelope signed by [`Envelope::sign`]: the magic, then an HMAC-SHA256 tag of the
+/// envelope, then the envelope itself.
+pub const SIGNED_MAGIC: &[u8; 4] = b"PMSG";
+
+/// Bytes in front of a signed envelope: the magic and the 32-byte tag.
+const SIGNATURE_LENGTH: usize = 4 + 32;
+
+/// Marks a plain envelope whose payload starts with one of the magics, so the payload isn't
+/// taken for a signed, split or encrypted envelope. [`Envelope::open`] drops it again.
+pub const ESCAPED_MAGIC: &[u8; 4] = b"PMSE";
+
+/// The magics a plain payload can't start with unescaped. `PMEC` marks encrypted payloads, but
+/// `crate::crypto` is only built with the `crypto` feature.
+const MAGICS: [&[u8; 4]; 4] = [PART_MAGIC, SIGNED_MAGIC, ESCAPED_MAGIC, b"PMEC"];
+
+/// Envelopes larger than this are split into parts by default, since some decoders
+/// struggle with huge chunks.
+pub const DEFAULT_PART_SIZE: usize = 1 << 20;
+
+/// The bytes of a chunk's data field, exactly as they appear in the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawChunkData(Bytes);
+
+impl RawChunkData {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl<T: Into<Bytes>> From<T> for RawChunkData {
+    fn from(data: T) -> Self {
+        Self(data.into())
+    }
+}
+
+/// The bytes a user hides in, or gets back from, a PNG file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Payload(Vec<u8>);
+
+impl Payload {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Classifies this payload for display.
+    pub fn kind(&self) -> PayloadKind {
+        PayloadKind::of(&self.0)
+    }
+}
+
+/// A payload as stored in a chunk: either the payload's bytes as they are, or the payload
+/// encrypted by [`Envelope::encrypt`]. Payloads only reach chunk data through an envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope(RawChunkData);
+
+impl Envelope {
+    /// Stores `payload` as it is, behind [`ESCAPED_MAGIC`] if it starts with a magic.
+    pub fn plain(payload: Payload) -> Self {
+        let bytes = payload.into_vec();
+        if !MAGICS.iter().any(|magic| bytes.starts_with(*magic)) {
+            return Self(RawChunkData::from(bytes));
+        }
+        let mut escaped = Vec::with_capacity(ESCAPED_MAGIC.len() + bytes.len());
+        escaped.extend_from_slice(ESCAPED_MAGIC);
+        escaped.extend(bytes);
+        Self(RawChunkData::from(escaped))
+    }
+
+    /// Encrypts `payload` under `password`.
+    #[cfg(feature = "crypto")]
+    pub fn encrypt(payload: &Payload, password: &str) -> Result<Self> {
+        Ok(Self(RawChunkData::from(crate::crypto::encrypt(payload.as_bytes(), password)?)))
+    }
+
+    /// Reads the data of a chunk as an envelope.
+    pub fn from_raw(data: RawChunkData) -> Self {
+        Self(data)
+    }
+
+    /// The chunk data holding this envelope.
+    pub fn into_raw(self) -> RawChunkData {
+        self.0
+    }
+
+    /// Whether this envelope holds an encrypted payload.
+    #[cfg(feature = "crypto")]
+    pub fn is_encrypted(&self) -> bool {
+        crate::crypto::is_encrypted(&self.0.as_bytes()[self.signature_length()..])
+    }
+
+    /// Adds an HMAC-SHA256 tag of the envelope under `key`, so changes to it can be detected.
+    /// An encrypted envelope is signed as it is stored, after encryption.
+    #[cfg(feature = "crypto")]
+    pub fn sign(self, key: &[u8]) -> Self {
+        let bytes = self.0.as_bytes();
+        let mut signed = Vec::with_capacity(SIGNATURE_LENGTH + bytes.len());
+        signed.extend_from_slice(SIGNED_MAGIC);
+        signed.extend(crate::sign::sign(key, bytes));
+        signed.extend_from_slice(bytes);
+        Self(RawChunkData::from(signed))
+    }
+
+    /// Whether this envelope carries a tag added by [`Envelope::sign`].
+    pub fn is_signed(&self) -> bool {
+        self.signature_length() > 0
+    }
+
+    /// Checks the tag of this envelope under `key` and returns the envelope without it.
+    #[cfg(feature = "crypto")]
+    pub fn verify(self, key: &[u8]) -> Result<Self> {
+        use crate::sign::SignError;
+        if !self.is_signed() {
+            return Err(Box::new(SignError::Unsigned));
+        }
+        let (tag, signed) = self.0.as_bytes()[SIGNED_MAGIC.len()..].split_at(SIGNATURE_LENGTH - SIGNED_MAGIC.len());
+        if !crate::sign::verify(key, signed, tag) {
+            return Err(Box::new(SignError::Mismatch));
+        }
+        Ok(self.unsigned())
+    }
+
+    /// This envelope without its tag, if it has one. The tag is dropped unchecked.
+    pub fn unsigned(self) -> Self {
+        let length = self.signature_length();
+        Self(RawChunkData(self.0.into_bytes().slice(length..)))
+    }
+
+    fn signature_length(&self) -> usize {
+        let bytes = self.0.as_bytes();
+        match bytes.len() >= SIGNATURE_LENGTH && bytes.starts_with(SIGNED_MAGIC) {
+            true => SIGNATURE_LENGTH,
+            false => 0,
+        }
+    }
+
+    /// Returns the payload, decrypting it with `password` if one is given. A tag is dropped
+    /// unchecked; use [`Envelope::verify`] first to check it.
+    pub fn open(self, password: Option<&str>) -> Result<Payload> {
+        let envelope = self.unsigned();
+        match password {
+            #[cfg(feature = "crypto")]
+            Some(password) => Ok(Payload::new(crate::crypto::decrypt(envelope.0.as_bytes(), password)?)),
+            #[cfg(not(feature = "crypto"))]
+            Some(_) => Err(crate::features::Feature::Crypto.missing("decrypting payloads").into()),
+            None => {
+                let bytes = envelope.0.as_bytes();
+                Ok(Payload::new(bytes.strip_prefix(ESCAPED_MAGIC).unwrap_or(bytes).to_vec()))
+            }
+        }
+    }
+}
+
+/// One part of a split envelope, read from a chunk's data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    /// Identifies the envelope the part belongs to. It is the CRC of the whole envelope.
+    pub id: u32,
+    pub index: u32,
+    pub count: u32,
+    pub data: Bytes,
+}
+
+impl Part {
+    /// Reads `data` as a part, or returns `None` if it holds a whole envelope.
+    pub fn parse(data: &RawChunkData) -> Option<Self> {
+        let bytes = data.as_bytes();
+        if bytes.len() < PART_HEADER_LENGTH || !bytes.starts_with(PART_MAGIC) {
+            return None;
+        }
+        let field = |at: usize| u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        let (id, index, count) = (field(4), field(8), field(12));
+        if index >= count {
+            return None;
+        }
+        Some(Self { id, index, count, data: data.clone().into_bytes().slice(PART_HEADER_LENGTH..) })
+    }
+}
+
+impl Envelope {
+    /// Splits this envelope into the data of consecutive chunks, each holding at most
+    /// `part_size` bytes of it. An envelope that fits is returned whole, without a part header.
+    pub fn split(self, part_size: usize) -> Vec<RawChunkData> {
+        let bytes = self.0.into_bytes();
+        let part_size = part_size.max(1);
+        if bytes.len() <= part_size {
+            return vec![RawChunkData(bytes)];
+        }
+        let id = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&bytes);
+        let count = bytes.len().div_ceil(part_size) as u32;
+        bytes
+            .chunks(part_size)
+            .enumerate()
+            .map(|(index, data)| {
+                let mut part = Vec::with_capacity(PART_HEADER_LENGTH + data.len());
+                part.extend_from_slice(PART_MAGIC);
+                for field in [id, index as u32, count] {
+                    part.extend_from_slice(&field.to_be_bytes());
+                }
+                part.extend_from_slice(data);
+                RawChunkData::from(part)
+            })
+            .collect()
+    }
+
+    /// Reassembles the envelope stored in `chunks`, the data of chunks of one type in file order.
+    /// A whole envelope is returned as is. Otherwise every part of the envelope the first part
+    /// belongs to must be present; parts of other envelopes are ignored.
+    pub fn join(chunks: impl IntoIterator<Item = RawChunkData>) -> Result<Self> {
+        let mut chunks = chunks.into_iter();
+        let first = chunks.next().ok_or(PayloadError::MissingParts { found: 0, expected: 1 })?;
+        let Some(first_part) = Part::parse(&first) else {
+            return Ok(Self(first));
+        };
+        let (id, count) = (first_part.id, first_part.count);
+        let mut parts = BTreeMap::from([(first_part.index, first_part.data)]);
+        for part in chunks.filter_map(|data| Part::parse(&data)) {
+            if part.id == id && part.count == count {
+                parts.entry(part.index).or_insert(part.data);
+            }
+        }
+        if parts.len() != count as usize {
+            return Err(Box::new(PayloadError::MissingParts { found: parts.len(), expected: count as usize }));
+        }
+        let joined: Vec<u8> = parts.into_values().flat_map(|data| data.to_vec()).collect();
+        if Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&joined) != id {
+            return Err(Box::new(PayloadError::CorruptParts));
+        }
+        Ok(Self(RawChunkData::from(joined)))
+    }
+}
+
+#[derive(Debug)]
+pub enum PayloadError {
+    MissingParts { found: usize, expected: usize },
+    CorruptParts,
+}
+
+impl std::error::Error for PayloadError {}
+
+impl std::fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::MissingParts { found, expected } => {
+                write!(f, "Payload is split into {expected} chunks but only {found} were found")
+            }
+            PayloadError::CorruptParts => write!(f, "Parts of the split payload don't match its checksum"),
+        }
+    }
+}
+
+impl From<Envelope> for RawChunkData {
+    fn from(envelope: Envelope) -> Self {
+        envelope.into_raw()
+    }
+}
+
+impl From<String> for Payload {
+    fn from(message: String) -> Self {
+        Self(message.into_bytes())
+    }
+}
+
+impl From<Vec<u8>> for Payload {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_envelope() {
+        let raw = RawChunkData::from(Envelope::plain(Payload::from("message".to_string())));
+        assert_eq!(raw.as_bytes(), b"message");
+        let payload = Envelope::from_raw(raw).open(None).unwrap();
+        assert_eq!(payload.as_bytes(), b"message");
+        assert_eq!(payload.kind(), PayloadKind::Text);
+    }
+
+    #[test]
+    fn test_magic_prefixed_plain_envelope() {
+        let mut signed_like = SIGNED_MAGIC.to_vec();
+        signed_like.extend([7; 40]);
+        let mut part_like = PART_MAGIC.to_vec();
+        part_like.extend([0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 42]);
+        for bytes in [signed_like, part_like, b"PMSEscaped".to_vec(), b"PMEC\x01".to_vec()] {
+            let envelope = Envelope::plain(Payload::new(bytes.clone()));
+            assert!(!envelope.is_signed());
+            let raw = envelope.into_raw();
+            assert!(raw.as_bytes().starts_with(ESCAPED_MAGIC));
+            assert!(Part::parse(&raw).is_none());
+            assert_eq!(Envelope::join([raw]).unwrap().open(None).unwrap().as_bytes(), bytes);
+        }
+        // Split parts of an escaped envelope still join back to it.
+        let mut long = PART_MAGIC.to_vec();
+        long.extend([1; 100]);
+        let parts = Envelope::plain(Payload::new(long.clone())).split(30);
+        assert_eq!(Envelope::join(parts).unwrap().open(None).unwrap().as_bytes(), long);
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let parts = Envelope::plain(Payload::new(payload.clone())).split(300);
+        assert_eq!(parts.len(), 4);
+        assert!(parts.iter().all(|part| part.as_bytes().len() <= 300 + PART_HEADER_LENGTH));
+        let part = Part::parse(&parts[3]).unwrap();
+        assert_eq!((part.index, part.count, part.data.len()), (3, 4, 100));
+
+        // Parts are found by index, wherever they are, and other chunks of the type are skipped.
+        let mut shuffled = vec![RawChunkData::from(b"unrelated".to_vec())];
+        shuffled.extend(parts.iter().rev().cloned());
+        shuffled.rotate_left(1);
+        let joined = Envelope::join(shuffled).unwrap().open(None).unwrap();
+        assert_eq!(joined.as_bytes(), payload);
+
+        let error = Envelope::join(parts[..3].to_vec()).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(PayloadError::MissingParts { found: 3, expected: 4 })));
+    }
+
+    #[test]
+    fn test_small_envelope_is_not_split() {
+        let parts = Envelope::plain(Payload::from("short".to_string())).split(DEFAULT_PART_SIZE);
+        assert_eq!(parts, vec![RawChunkData::from(b"short".to_vec())]);
+        assert!(Part::parse(&parts[0]).is_none());
+        assert_eq!(Envelope::join(parts).unwrap().into_raw().as_bytes(), b"short");
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_encrypted_envelope() {
+        let payload = Payload::from(b"\x00binary".to_vec());
+        let envelope = Envelope::encrypt(&payload, "hunter2").unwrap();
+        assert!(envelope.is_encrypted());
+        let raw = envelope.into_raw();
+        assert_ne!(raw.as_bytes(), payload.as_bytes());
+        assert_eq!(Envelope::from_raw(raw).open(Some("hunter2")).unwrap(), payload);
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_signed_envelope() {
+        let payload = Payload::from("watermark".to_string());
+        let signed = Envelope::encrypt(&payload, "hunter2").unwrap().sign(b"key");
+        assert!(signed.is_signed() && signed.is_encrypted());
+        assert_eq!(signed.clone().open(Some("hunter2")).unwrap(), payload);
+        let verified = signed.clone().verify(b"key").unwrap();
+        assert!(!verified.is_signed());
+        assert_eq!(verified.open(Some("hunter2")).unwrap(), payload);
+
+        let error = signed.clone().verify(b"other key").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(crate::sign::SignError::Mismatch)));
+        let mut tampered = signed.into_raw().as_bytes().to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(Envelope::from_raw(tampered.into()).verify(b"key").is_err());
+        let error = Envelope::plain(payload).verify(b"key").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(crate::sign::SignError::Unsigned)));
+    }
+}
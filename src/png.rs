@@ -1,10 +1,86 @@
 use std::fmt::Display;
-use std::io::Read;
-use crate::chunk::{Chunk};
-use crate::{Error,Result};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use bytes::Bytes;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use crate::chunk::{Chunk, ChunkError, CrcPolicy};
+use crate::error::PngMeError;
+
+type Result<T> = std::result::Result<T, PngMeError>;
+
+/// How the parser treats a repeated `IHDR` chunk and chunks found after the first `IEND`.
+/// Bytes after `IEND` that do not form a valid chunk are always kept as the trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Fail parsing.
+    Error,
+    /// Drop the repeated `IHDR` and treat everything after the first `IEND` as the trailer.
+    KeepFirst,
+    /// Keep every chunk as it appears in the file.
+    #[default]
+    KeepAll,
+}
+
+impl DuplicatePolicy {
+    /// The names accepted by `from_str`.
+    pub const NAMES: [&'static str; 3] = ["error", "keep-first", "keep-all"];
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(DuplicatePolicy::Error),
+            "keep-first" => Ok(DuplicatePolicy::KeepFirst),
+            "keep-all" => Ok(DuplicatePolicy::KeepAll),
+            _ => Err(format!("Unknown policy '{s}', expected one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Options controlling how a `Png` is parsed from bytes.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub duplicates: DuplicatePolicy,
+    /// Threads verifying chunk CRCs. 0 picks one per core once the file holds at least
+    /// `PARALLEL_CRC_THRESHOLD` bytes of chunk data, and a single thread below that.
+    pub crc_threads: usize,
+    /// Keep chunks whose CRC doesn't match instead of failing, so damaged files can still be
+    /// inspected. `Chunk::crc_ok` tells those chunks apart.
+    pub ignore_crc: bool,
+}
+
+/// Chunk data size from which verifying CRCs on several threads pays off.
+pub const PARALLEL_CRC_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Options controlling how a `Png` is written as bytes.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub crc: CrcPolicy,
+    /// Unix permissions for the saved file, e.g. `0o600`. Without them new files follow the umask
+    /// and existing files keep theirs.
+    pub mode: Option<u32>,
+    /// Replace existing files through a renamed temporary file. Turned off, they are written over
+    /// directly, for filesystems where renaming over a file doesn't work, or loses its ownership
+    /// or links.
+    pub atomic: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self { crc: CrcPolicy::default(), mode: None, atomic: true }
+    }
+}
 
 pub struct Png{
-    chunks:Vec<Chunk>
+    chunks:Vec<Chunk>,
+    trailer:Bytes,
+    /// Set by `open_readonly`; every method changing or saving this `Png` then fails.
+    read_only:bool,
 }
 
 impl Png {
@@ -15,24 +91,98 @@ impl Png {
    /// Creates a `Png` from a list of chunks using the correct header
    #[allow(dead_code)]
    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-       Self { chunks }
+       Self { chunks, trailer: Bytes::new(), read_only: false }
+   }
+
+   /// Reads and parses the PNG file at `path` with default options. The returned `Png`
+   /// can't be changed or saved: those methods fail with `PngError::ReadOnly`.
+   pub fn open_readonly(path: impl AsRef<Path>) -> Result<Self> {
+       let mut png = Png::try_from(Bytes::from(fs::read(path)?))?;
+       png.read_only = true;
+       Ok(png)
+   }
+
+   /// Whether this `Png` was opened with `open_readonly`.
+   pub fn is_read_only(&self) -> bool {
+       self.read_only
+   }
+
+   fn ensure_writable(&self) -> Result<()> {
+       if self.read_only {
+           return Err(PngError::ReadOnly.into());
+       }
+       Ok(())
    }
 
 
    /// Appends a chunk to the end of this `Png` file's `Chunk` list.
-   pub fn append_chunk(&mut self, chunk: Chunk) {
-       self.chunks.push(chunk)
+   pub fn append_chunk(&mut self, chunk: Chunk) -> Result<()> {
+       self.ensure_writable()?;
+       self.chunks.push(chunk);
+       Ok(())
+   }
+
+   /// Inserts a chunk at `index` in this `Png` file's `Chunk` list, shifting the chunks after it.
+   /// `index` may be the number of chunks, which appends.
+   pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+       self.ensure_writable()?;
+       if index > self.chunks.len() {
+           return Err(PngError::IndexOutOfRange { index, chunks: self.chunks.len() }.into());
+       }
+       self.chunks.insert(index, chunk);
+       Ok(())
    }
 
    /// Searches for a `Chunk` with the specified `chunk_type` and removes the first
    /// matching `Chunk` from this `Png` list of chunks.
    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+       self.ensure_writable()?;
        let index_to_remove = self.chunks.iter().position(|e| e.chunk_type().to_string() == chunk_type);
        if let Some(index) = index_to_remove{
            return Ok(self.chunks.remove(index));
        }
-       Err(Box::new(PngError::UnknownChunkType))
+       Err(PngError::UnknownChunkType.into())
+
+   }
 
+   /// Removes every `Chunk` of type `chunk_type` and returns them in their original order.
+   /// Fails if there is none.
+   pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Result<Vec<Chunk>> {
+       let removed = self.retain_chunks(|chunk| chunk.chunk_type().to_string() != chunk_type)?;
+       if removed.is_empty() {
+           return Err(PngError::UnknownChunkType.into());
+       }
+       Ok(removed)
+   }
+
+   /// Removes the `Chunk` at `index` in this `Png` list of chunks.
+   pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+       self.ensure_writable()?;
+       if index >= self.chunks.len() {
+           return Err(PngError::IndexOutOfRange { index, chunks: self.chunks.len() }.into());
+       }
+       Ok(self.chunks.remove(index))
+   }
+
+   /// Replaces every `Chunk` of type `chunk_type` with `chunks`, placed where the first of them
+   /// was. Fails if there is none.
+   pub fn replace_chunks(&mut self, chunk_type: &str, chunks: Vec<Chunk>) -> Result<Vec<Chunk>> {
+       self.ensure_writable()?;
+       let Some(index) = self.chunks.iter().position(|chunk| chunk.chunk_type().to_string() == chunk_type) else {
+           return Err(PngError::UnknownChunkType.into());
+       };
+       let removed = self.retain_chunks(|chunk| chunk.chunk_type().to_string() != chunk_type)?;
+       self.chunks.splice(index..index, chunks);
+       Ok(removed)
+   }
+
+   /// Removes every `Chunk` for which `keep` returns false and returns the removed
+   /// chunks in their original order.
+   pub fn retain_chunks(&mut self, mut keep: impl FnMut(&Chunk) -> bool) -> Result<Vec<Chunk>> {
+       self.ensure_writable()?;
+       let (kept, removed) = std::mem::take(&mut self.chunks).into_iter().partition(|chunk| keep(chunk));
+       self.chunks = kept;
+       Ok(removed)
    }
 
    /// The header of this PNG.
@@ -53,61 +203,449 @@ impl Png {
             .find(|&e| e.chunk_type().to_string() == chunk_type)
    }
 
+   /// Every `Chunk` with the specified `chunk_type`, in file order.
+   pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        self.chunks
+            .iter()
+            .filter(|&e| e.chunk_type().to_string() == chunk_type)
+            .collect()
+   }
+
+   /// Whether this is an animated PNG, with an acTL chunk before its image data.
+   pub fn is_apng(&self) -> bool {
+       crate::apng::is_apng(self)
+   }
+
+   /// The frames of this animated PNG in file order, or none if it isn't one.
+   pub fn frames(&self) -> Result<Vec<crate::apng::Frame>> {
+       crate::apng::frames(self).map_err(PngMeError::from)
+   }
+
+   /// The byte offset of each chunk from the start of the file.
+   pub fn chunk_offsets(&self) -> Vec<usize> {
+       let mut offset = Png::STANDARD_HEADER.len();
+       self.chunks
+           .iter()
+           .map(|chunk| {
+               let start = offset;
+               offset += chunk.length() as usize + 12;
+               start
+           })
+           .collect()
+   }
+
+   /// The byte offset at which the trailer starts, which is also the end of the last chunk.
+   pub fn trailer_offset(&self) -> usize {
+       Png::STANDARD_HEADER.len() + self.chunks.iter().map(|chunk| chunk.length() as usize + 12).sum::<usize>()
+   }
+
+   /// The bytes following the final `IEND` chunk that are not part of any chunk.
+   pub fn trailer(&self) -> &[u8] {
+       &self.trailer
+   }
+
+   /// Replaces the bytes written after the final chunk of this `Png`.
+   pub fn set_trailer(&mut self, trailer: impl Into<Bytes>) -> Result<()> {
+       self.ensure_writable()?;
+       self.trailer = trailer.into();
+       Ok(())
+   }
+
+   /// Removes the trailing bytes of this `Png` and returns them.
+   pub fn strip_trailer(&mut self) -> Result<Bytes> {
+       self.ensure_writable()?;
+       Ok(std::mem::take(&mut self.trailer))
+   }
+
+   /// Parses a `Png` from `value` according to `options`.
+   /// The parsed chunks share `value`'s buffer instead of copying their data.
+   pub fn parse(value: Bytes, options: &ParseOptions) -> Result<Self> {
+
+        if value.len() < 12 {
+            return Err(PngError::SmallInput.into());
+        }
+
+        if value[..8] != Png::STANDARD_HEADER{
+            return Err(PngError::InvalidHeader.into());
+        }
+
+        let mut chunks = Vec::new();
+        let mut unverified = 0;
+        let mut seen_header = false;
+        let mut seen_end = false;
+        let mut offset = 8;
+        while value.len() - offset >= 4 {
+            if seen_end && options.duplicates == DuplicatePolicy::KeepFirst {
+                break;
+            }
+            let length_bytes = [value[offset], value[offset + 1], value[offset + 2], value[offset + 3]];
+            let end = (offset + 12 + u32::from_be_bytes(length_bytes) as usize).min(value.len());
+            // Up to IEND, CRCs are verified together once every chunk is found.
+            let parsed = if seen_end {
+                Chunk::try_from(value.slice(offset..end))
+            } else {
+                Chunk::parse_unchecked(value.slice(offset..end)).map(|(chunk, _)| chunk)
+            };
+            let chunk = match parsed {
+                Ok(chunk) => chunk,
+                // Whatever follows IEND and is not a chunk belongs to the trailer.
+                Err(_) if seen_end => break,
+                Err(e) => return Err(e),
+            };
+            offset = end;
+
+            if seen_end && options.duplicates == DuplicatePolicy::Error {
+                return Err(PngError::ChunkAfterEnd.into());
+            }
+            match &chunk.chunk_type().bytes() {
+                b"IHDR" if seen_header => match options.duplicates {
+                    DuplicatePolicy::Error => return Err(PngError::DuplicateHeader.into()),
+                    DuplicatePolicy::KeepFirst => continue,
+                    DuplicatePolicy::KeepAll => {}
+                },
+                b"IHDR" => seen_header = true,
+                b"IEND" => seen_end = true,
+                _ => {}
+            }
+            chunks.push(chunk);
+            if !seen_end {
+                unverified = chunks.len();
+            }
+        }
+        if !options.ignore_crc {
+            verify_crcs(&chunks[..unverified], options.crc_threads)?;
+        }
+        let trailer = value.slice(offset..);
+        Ok(Self { chunks, trailer, read_only: false })
+   }
+
+   /// Parses a `Png` from `bytes`, keeping chunks whose CRC doesn't match instead of failing.
+   /// `Chunk::crc_ok` is false for those chunks.
+   pub fn from_bytes_lenient(bytes: &[u8]) -> Result<Self> {
+       Png::parse(Bytes::copy_from_slice(bytes), &ParseOptions { ignore_crc: true, ..Default::default() })
+   }
+
    /// Returns this `Png` as a byte sequence.
-   /// These bytes will contain the header followed by the bytes of all of the chunks.
+   /// These bytes will contain the header followed by the bytes of all of the chunks
+   /// and the trailer, if any.
    pub fn as_bytes(&self) -> Vec<u8> {
-    
-      let header_bytes = self.header().iter().copied().collect::<Vec<u8>>();
+      self.to_bytes(&WriteOptions::default())
+   }
 
-      let chunks_bytes = self.chunks
-        .iter()
-        .map(|e| e.as_bytes().into_iter())
-        .flatten()
-        .collect::<Vec<u8>>();
+   /// Same as `as_bytes`, with chunks serialized according to `options`.
+   pub fn to_bytes(&self, options: &WriteOptions) -> Vec<u8> {
+      let mut bytes = Vec::with_capacity(self.byte_len());
+      self.write_to(&mut bytes, options).expect("writing to a Vec cannot fail");
+      bytes
+   }
 
-    header_bytes.into_iter().chain(chunks_bytes).collect()
+   /// The number of bytes `to_bytes` returns, computed without serializing.
+   pub fn byte_len(&self) -> usize {
+      self.trailer_offset() + self.trailer.len()
+   }
+
+   /// Writes this `Png` to `writer` one chunk at a time, as `to_bytes` would serialize it.
+   pub fn write_to(&self, mut writer: impl Write, options: &WriteOptions) -> io::Result<()> {
+      writer.write_all(self.header())?;
+      for chunk in &self.chunks {
+          chunk.write_to(&mut writer, options.crc)?;
+      }
+      writer.write_all(&self.trailer)?;
+      writer.flush()
+   }
+
+   /// Parses a `Png` from `reader` according to `options`, reading one chunk at a time
+   /// instead of loading the whole input first. CRCs are verified as chunks are read.
+   pub fn from_reader(reader: impl Read, options: &ParseOptions) -> Result<Self> {
+      let mut chunk_reader = ChunkReader::new(reader, options.clone())?;
+      let chunks = chunk_reader.by_ref().collect::<Result<Vec<Chunk>>>()?;
+      let trailer = chunk_reader.into_trailer();
+      Ok(Self { chunks, trailer, read_only: false })
+   }
+
+   /// Writes this `Png` to the file at `path`, replacing its contents.
+   pub fn save(&self, path: impl AsRef<Path>, options: &WriteOptions) -> Result<()> {
+      self.ensure_writable()?;
+      replace_file(path.as_ref(), options, |writer| self.write_to(writer, options))?;
+      Ok(())
    }
 }
 
+/// Reads the chunks of a PNG from a stream one at a time, applying the same rules as `Png::parse`,
+/// so a caller looking for one chunk never holds more than that chunk in memory.
+/// Once the iterator is exhausted, `trailer` holds the bytes following the last chunk.
+pub struct ChunkReader<R> {
+    reader: R,
+    options: ParseOptions,
+    seen_header: bool,
+    /// Everything after the first `IEND`, which is read at once to tell chunks from trailer.
+    after_end: Option<Bytes>,
+    trailer: Bytes,
+    done: bool,
+}
 
-impl TryFrom<&[u8]> for Png{
-    type Error = Error;
-    fn try_from(value: &[u8]) ->Result<Self> {
+impl<R: Read> ChunkReader<R> {
+    /// Checks the PNG signature at the start of `reader`.
+    pub fn new(mut reader: R, options: ParseOptions) -> Result<Self> {
+        let mut header = [0; 8];
+        if read_up_to(&mut reader, &mut header)? < header.len() {
+            return Err(PngError::SmallInput.into());
+        }
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidHeader.into());
+        }
+        Ok(Self { reader, options, seen_header: false, after_end: None, trailer: Bytes::new(), done: false })
+    }
 
-        if value.len() < 12 {
-            return Err(Box::new(PngError::SmallInput));
+    /// The bytes after the last chunk. Only complete once every chunk has been read.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    pub fn into_trailer(self) -> Bytes {
+        self.trailer
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Chunk>> {
+        if let Some(rest) = self.after_end.as_mut() {
+            return Self::next_chunk_after_end(rest, &mut self.trailer, &self.options);
+        }
+        let mut header = [0; 8];
+        let read = read_up_to(&mut self.reader, &mut header)?;
+        if read < 4 {
+            self.trailer = Bytes::copy_from_slice(&header[..read]);
+            return Ok(None);
+        }
+        if read < header.len() {
+            return Err(ChunkError::SmallInput.into());
+        }
+        let chunk = match self.options.ignore_crc {
+            true => Chunk::read_from_lenient(header, &mut self.reader)?,
+            false => Chunk::read_from(header, &mut self.reader)?,
+        };
+        match &chunk.chunk_type().bytes() {
+            b"IHDR" if self.seen_header => match self.options.duplicates {
+                DuplicatePolicy::Error => return Err(PngError::DuplicateHeader.into()),
+                DuplicatePolicy::KeepFirst => return self.next_chunk(),
+                DuplicatePolicy::KeepAll => {}
+            },
+            b"IHDR" => self.seen_header = true,
+            b"IEND" => {
+                let mut rest = Vec::new();
+                self.reader.read_to_end(&mut rest)?;
+                if self.options.duplicates == DuplicatePolicy::KeepFirst {
+                    self.trailer = Bytes::from(rest);
+                    self.after_end = Some(Bytes::new());
+                } else {
+                    self.after_end = Some(Bytes::from(rest));
+                }
+            }
+            _ => {}
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Takes the next intact chunk off `rest`, or moves `rest` to `trailer` once it holds none.
+    fn next_chunk_after_end(rest: &mut Bytes, trailer: &mut Bytes, options: &ParseOptions) -> Result<Option<Chunk>> {
+        if rest.len() < 4 {
+            // With `KeepFirst`, `rest` is empty and the trailer was already taken at IEND.
+            if !rest.is_empty() {
+                *trailer = std::mem::take(rest);
+            }
+            return Ok(None);
         }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let end = (12 + length).min(rest.len());
+        let Ok(chunk) = Chunk::try_from(rest.slice(..end)) else {
+            *trailer = std::mem::take(rest);
+            return Ok(None);
+        };
+        if options.duplicates == DuplicatePolicy::Error {
+            return Err(PngError::ChunkAfterEnd.into());
+        }
+        *rest = rest.slice(end..);
+        Ok(Some(chunk))
+    }
+}
 
-        let mut reader = std::io::BufReader::new(value);
-        let mut header_buffer:[u8;8] = [0,0,0,0,0,0,0,0];
-        reader.read_exact(&mut header_buffer)?;
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
 
-        let mut chunks = Vec::new();
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_chunk().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
 
-        if header_buffer!=Png::STANDARD_HEADER{
-            return Err(Box::new(PngError::InvalidHeader));
+/// Creates or truncates the file at `path`. With `mode`, a new file is created with it, so
+/// sensitive contents are never readable with wider permissions, and an existing file whose
+/// permissions differ is changed to it before anything is written. `mode` is ignored on
+/// platforms without Unix permissions.
+pub fn create_file(path: &Path, mode: Option<u32>) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(mode);
+        let file = options.open(path)?;
+        // The mode only applies to a new file, and the umask may narrow it further.
+        if file.metadata()?.permissions().mode() & 0o7777 != mode {
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
         }
-        let mut length_buffer:[u8;4] = [0,0,0,0];
-        while let Ok(()) = reader.read_exact(&mut length_buffer){
+        return Ok(file);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    options.open(path)
+}
 
-            let mut chunk = Vec::new();
-            length_buffer.iter().for_each(|e| chunk.push(*e));
+/// Writes the file at `path` with `write` and flushes it to disk. An existing file is replaced
+/// through a temporary file in the same directory that is synced and renamed over it, so a crash
+/// mid-write leaves either the old or the new contents, never a mix. The temporary file is only
+/// readable by its owner until it is complete, then takes `mode`, or the permissions of the file
+/// it replaces. A new file is created directly, with `mode` or following the umask, and so is an
+/// existing one without `atomic`. Only the `mode` and `atomic` of `options` are used.
+pub fn replace_file(path: &Path, options: &WriteOptions, write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+   let mode = options.mode;
+   let existing = match fs::metadata(path) {
+      Ok(metadata) if metadata.is_file() && options.atomic => metadata,
+      _ => {
+         let mut writer = BufWriter::new(create_file(path, mode)?);
+         write(&mut writer)?;
+         return writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all();
+      }
+   };
+   // Replace the file a symlink points to, not the symlink.
+   let path = fs::canonicalize(path)?;
+   let temp = temp_path(&path);
+   let mut options = fs::OpenOptions::new();
+   options.write(true).create_new(true);
+   #[cfg(unix)]
+   std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+   let mut writer = BufWriter::new(options.open(&temp)?);
+   TEMP_FILES.lock().expect("no panics while locked").push(temp.clone());
+   let written = write(&mut writer).and_then(|()| {
+      let file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+      let permissions = match mode {
+         #[cfg(unix)]
+         Some(mode) => std::os::unix::fs::PermissionsExt::from_mode(mode),
+         _ => existing.permissions(),
+      };
+      file.set_permissions(permissions)?;
+      file.sync_all()
+   });
+   let result = written.and_then(|()| fs::rename(&temp, &path));
+   if result.is_err() {
+      let _ = fs::remove_file(&temp);
+   }
+   TEMP_FILES.lock().expect("no panics while locked").retain(|other| *other != temp);
+   result?;
+   sync_directory(&path)
+}
 
-            let mut chunk_type_buffer:[u8;4] = [0,0,0,0];
-            reader.read_exact(&mut chunk_type_buffer)?;
-            chunk_type_buffer.iter().for_each(|e| chunk.push(*e));
-            
-            let mut chunk_buffer = vec![0;u32::from_be_bytes(length_buffer) as usize];
-            reader.read_exact(&mut chunk_buffer)?;
-            chunk_buffer.iter().for_each(|e| chunk.push(*e));
+/// Flushes the directory entry of `path` after a rename, so the rename itself survives a crash.
+/// Only Unix can open directories for this; elsewhere it does nothing.
+fn sync_directory(path: &Path) -> io::Result<()> {
+   #[cfg(unix)]
+   if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+      File::open(directory)?.sync_all()?;
+   }
+   #[cfg(not(unix))]
+   let _ = path;
+   Ok(())
+}
 
-            let mut crc_buffer:[u8;4] = [0,0,0,0];
-            reader.read_exact(&mut crc_buffer)?;
-            crc_buffer.iter().for_each(|e| chunk.push(*e));
+/// Temporary files `replace_file` is writing.
+static TEMP_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
 
-            chunks.push(Chunk::try_from(chunk.as_slice())?);
+/// Removes the temporary files of writes still in progress, for a process about to exit
+/// without finishing them. The files they were replacing are left as they were.
+pub fn remove_temp_files() {
+   let files = TEMP_FILES.lock().map(|mut files| std::mem::take(&mut *files)).unwrap_or_default();
+   for file in files {
+      let _ = fs::remove_file(file);
+   }
+}
+
+/// A hidden file next to `path` to write its new contents to.
+fn temp_path(path: &Path) -> PathBuf {
+   let name = path.file_name().unwrap_or_default().to_string_lossy();
+   path.with_file_name(format!(".{name}.pngme-{}.tmp", std::process::id()))
+}
+
+/// Fills as much of `buffer` as `reader` has left, returning how many bytes were read.
+pub(crate) fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
         }
-        Ok(Self { chunks })
+    }
+    Ok(filled)
+}
+
+
+/// Checks the stored CRC of every chunk in `chunks`, on up to `threads` threads (0 for automatic).
+/// Threads take the next unchecked chunk as they finish, so a few large chunks spread evenly.
+fn verify_crcs(chunks: &[Chunk], threads: usize) -> Result<()> {
+    let is_intact = |chunk: &Chunk| chunk.stored_crc().is_none_or(|crc| crc == chunk.crc());
+    let threads = match threads {
+        0 if chunks.iter().map(|c| c.length() as usize).sum::<usize>() >= PARALLEL_CRC_THRESHOLD => {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+        0 => 1,
+        n => n,
+    }
+    .min(chunks.len());
+    if threads <= 1 {
+        if !chunks.iter().all(is_intact) {
+            return Err(ChunkError::InvalidCrc.into());
+        }
+        return Ok(());
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let Some(chunk) = chunks.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    if !is_intact(chunk) {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    if failed.into_inner() {
+        return Err(ChunkError::InvalidCrc.into());
+    }
+    Ok(())
+}
+
+impl TryFrom<&[u8]> for Png{
+    type Error = PngMeError;
+    fn try_from(value: &[u8]) ->Result<Self> {
+        Png::try_from(Bytes::copy_from_slice(value))
+    }
+}
+
+impl TryFrom<Bytes> for Png{
+    type Error = PngMeError;
+    /// Parses a `Png` whose chunks share `value`'s buffer instead of copying their data.
+    fn try_from(value: Bytes) ->Result<Self> {
+        Png::parse(value, &ParseOptions::default())
     }
 }
 
@@ -122,23 +660,22 @@ impl Display for Png{
 
 
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum PngError {
+    #[error("Invalid header")]
     InvalidHeader,
+    #[error("Input size is very small")]
     SmallInput,
+    #[error("Unknown chunk type")]
     UnknownChunkType,
-}
-
-impl std::error::Error for PngError {}
-
-impl std::fmt::Display for PngError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            PngError::InvalidHeader => write!(f, "Invalid header"),
-            PngError::SmallInput => write!(f, "Input size is very small"),
-            PngError::UnknownChunkType => write!(f, "Unknown chunk type"),
-        }
-    }
+    #[error("Found more than one IHDR chunk")]
+    DuplicateHeader,
+    #[error("Found chunks after the IEND chunk")]
+    ChunkAfterEnd,
+    #[error("PNG was opened read-only and can't be changed or saved")]
+    ReadOnly,
+    #[error("Chunk index {index} is past the end of the {chunks} chunks")]
+    IndexOutOfRange { index: usize, chunks: usize },
 }
 
 #[cfg(test)]
@@ -146,9 +683,10 @@ mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
     use crate::chunk::Chunk;
-    use std::str::FromStr;
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -240,6 +778,132 @@ mod tests {
     }
 
 
+    fn duplicated_bytes() -> Vec<u8> {
+        let chunks = vec![
+            chunk_from_strings("IHDR", "first header").unwrap(),
+            chunk_from_strings("IHDR", "second header").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+            chunk_from_strings("ruSt", "after end").unwrap(),
+        ];
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    fn parse_with(bytes: Vec<u8>, duplicates: DuplicatePolicy) -> Result<Png> {
+        Png::parse(Bytes::from(bytes), &ParseOptions { duplicates, ..Default::default() })
+    }
+
+    #[test]
+    fn test_duplicates_keep_all() {
+        let png = parse_with(duplicated_bytes(), DuplicatePolicy::KeepAll).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_duplicates_keep_first() {
+        let png = parse_with(duplicated_bytes(), DuplicatePolicy::KeepFirst).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IEND"]);
+        assert_eq!(&png.chunks()[0].data_as_string().unwrap(), "first header");
+    }
+
+    #[test]
+    fn test_duplicates_error() {
+        assert!(parse_with(duplicated_bytes(), DuplicatePolicy::Error).is_err());
+
+        let chunks = vec![
+            chunk_from_strings("IEND", "").unwrap(),
+            chunk_from_strings("ruSt", "after end").unwrap(),
+        ];
+        assert!(parse_with(Png::from_chunks(chunks).as_bytes(), DuplicatePolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let png = testing_png();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets, vec![8, 8 + 12 + 20, 8 + 12 + 20 + 12 + 18]);
+        assert_eq!(png.trailer_offset(), png.as_bytes().len());
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(&chunk_from_strings("IEND", "").unwrap().as_bytes());
+        bytes.extend_from_slice(b"trailing data");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(png.trailer(), b"trailing data");
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_keep_first_moves_chunks_after_end_to_trailer() {
+        let png = parse_with(duplicated_bytes(), DuplicatePolicy::KeepFirst).unwrap();
+        let expected = chunk_from_strings("ruSt", "after end").unwrap().as_bytes();
+        assert_eq!(png.trailer(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_set_and_strip_trailer() {
+        let mut png = testing_png();
+        png.set_trailer(b"hidden".to_vec()).unwrap();
+        assert!(png.as_bytes().ends_with(b"hidden"));
+        assert_eq!(png.strip_trailer().unwrap().as_ref(), b"hidden");
+        assert!(png.trailer().is_empty());
+    }
+
+    #[test]
+    fn test_zero_length_chunks() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("ruSt", "").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let parsed = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        assert_eq!(parsed.chunks().len(), 3);
+        assert_eq!(parsed.chunk_by_type("ruSt").unwrap().length(), 0);
+        assert!(parsed.trailer().is_empty());
+    }
+
+    #[test]
+    fn test_parallel_crc_verification() {
+        let mut chunks = vec![chunk_from_strings("IHDR", "header").unwrap()];
+        chunks.extend((0..16).map(|i| Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![i; 64 * 1024])));
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let mut bytes = Png::from_chunks(chunks).as_bytes();
+        let options = ParseOptions { crc_threads: 4, ..Default::default() };
+
+        assert_eq!(Png::parse(Bytes::from(bytes.clone()), &options).unwrap().chunks().len(), 18);
+        let last_idat_crc = bytes.len() - 12 - 1;
+        bytes[last_idat_crc] ^= 0xff;
+        assert!(Png::parse(Bytes::from(bytes), &options).is_err());
+    }
+
+    #[test]
+    fn test_lenient_parsing() {
+        let chunks = vec![chunk_from_strings("IHDR", "header").unwrap(), chunk_from_strings("ruSt", "data").unwrap(), chunk_from_strings("IEND", "").unwrap()];
+        let mut bytes = Png::from_chunks(chunks).as_bytes();
+        let ru_st_crc = 8 + 18 + 16 - 1;
+        bytes[ru_st_crc] ^= 0xff;
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        let png = Png::from_bytes_lenient(&bytes).unwrap();
+        let crc_ok: Vec<bool> = png.chunks().iter().map(Chunk::crc_ok).collect();
+        assert_eq!(crc_ok, [true, false, true]);
+        let options = ParseOptions { ignore_crc: true, ..Default::default() };
+        let streamed = Png::from_reader(bytes.as_slice(), &options).unwrap();
+        assert!(!streamed.chunk_by_type("ruSt").unwrap().crc_ok());
+        assert!(Png::from_reader(bytes.as_slice(), &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_policy_from_str() {
+        assert_eq!(DuplicatePolicy::from_str("keep-first").unwrap(), DuplicatePolicy::KeepFirst);
+        assert!(DuplicatePolicy::from_str("first").is_err());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -259,7 +923,7 @@ mod tests {
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
@@ -268,18 +932,221 @@ mod tests {
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         png.remove_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        let error = png.remove_chunk_at(2).unwrap_err();
+        assert!(matches!(error, PngMeError::Png(PngError::IndexOutOfRange { index: 2, chunks: 2 })));
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        png.insert_chunk(4, chunk_from_strings("TeSt", "Last").unwrap()).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "TeSt", "miDl", "LASt", "TeSt"]);
+        let error = png.insert_chunk(6, chunk_from_strings("TeSt", "").unwrap()).unwrap_err();
+        assert!(matches!(error, PngMeError::Png(PngError::IndexOutOfRange { index: 6, chunks: 5 })));
+    }
+
+    #[test]
+    fn test_replace_chunks() {
+        let mut png = testing_png();
+        let replacement = vec![chunk_from_strings("miDl", "one").unwrap(), chunk_from_strings("miDl", "two").unwrap()];
+        let removed = png.replace_chunks("miDl", replacement).unwrap();
+        assert_eq!(removed.len(), 1);
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "miDl", "LASt"]);
+        assert!(png.replace_chunks("TeSt", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_retain_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        let removed = png.retain_chunks(|chunk| chunk.chunk_type().to_string() != "TeSt").unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type("TeSt").is_none());
+    }
+
+    #[test]
+    fn test_open_readonly() {
+        let path = std::env::temp_dir().join(format!("pngme-readonly-{}.png", std::process::id()));
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+        let mut png = Png::open_readonly(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(png.is_read_only());
+        assert_eq!(png.chunks().len(), 3);
+        let is_read_only = |e: PngMeError| matches!(e, PngMeError::Png(PngError::ReadOnly));
+        assert!(is_read_only(png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap_err()));
+        assert!(is_read_only(png.remove_chunk("FrSt").unwrap_err()));
+        assert!(is_read_only(png.set_trailer(b"hidden".to_vec()).unwrap_err()));
+        assert!(is_read_only(png.save(&path, &WriteOptions::default()).unwrap_err()));
+        assert_eq!(png.chunks().len(), 3);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap()).unwrap();
+        let duplicates = png.chunks_by_type("miDl");
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[1].data_as_string().unwrap(), "I am a duplicate");
+
+        let removed = png.remove_all_chunks("miDl").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(png.chunks_by_type("miDl").is_empty());
+        assert!(png.remove_all_chunks("miDl").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_with_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("pngme-mode-{}.png", std::process::id()));
+        fs::write(&path, b"old contents").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        testing_png().save(&path, &WriteOptions { mode: Some(0o600), ..WriteOptions::default() }).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let directory = std::env::temp_dir().join(format!("pngme-replace-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("image.png");
+        fs::write(&path, b"old contents").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        replace_file(&path, &WriteOptions::default(), |writer| writer.write_all(b"new contents")).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new contents");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        // A failed write leaves the file as it was and no temporary file behind.
+        let failed = replace_file(&path, &WriteOptions::default(), |writer| {
+            writer.write_all(b"partial")?;
+            Err(io::Error::other("disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"new contents");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let directory = std::env::temp_dir().join(format!("pngme-create-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("secret.bin");
+        create_file(&path, Some(0o600)).unwrap().write_all(b"secret").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        // An existing file takes the mode too, and is truncated.
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        create_file(&path, Some(0o640)).unwrap().write_all(b"new").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_file_in_place() {
+        let directory = std::env::temp_dir().join(format!("pngme-in-place-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("image.png");
+        let link = directory.join("link.png");
+        fs::write(&path, b"old contents").unwrap();
+        fs::hard_link(&path, &link).unwrap();
+
+        // Written over directly, the file keeps its other links.
+        let direct = WriteOptions { atomic: false, ..WriteOptions::default() };
+        replace_file(&path, &direct, |writer| writer.write_all(b"new contents")).unwrap();
+        assert_eq!(fs::read(&link).unwrap(), b"new contents");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+        // Renamed over, it gets a new inode and the link keeps the old contents.
+        replace_file(&path, &WriteOptions::default(), |writer| writer.write_all(b"newer contents")).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"newer contents");
+        assert_eq!(fs::read(&link).unwrap(), b"new contents");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_chunks_share_input_buffer() {
+        let input = Bytes::copy_from_slice(&PNG_FILE[..]);
+        let png = Png::try_from(input.clone()).unwrap();
+        let input_range = input.as_ptr_range();
+        for chunk in png.chunks() {
+            assert!(input_range.contains(&chunk.data_bytes().as_ptr()) || chunk.length() == 0);
+        }
+    }
+
+    #[test]
+    fn test_from_reader_matches_parse() {
+        let mut with_trailer = duplicated_bytes();
+        with_trailer.extend_from_slice(b"trailing data");
+        for bytes in [PNG_FILE.to_vec(), duplicated_bytes(), with_trailer] {
+            for duplicates in [DuplicatePolicy::KeepAll, DuplicatePolicy::KeepFirst, DuplicatePolicy::Error] {
+                let options = ParseOptions { duplicates, ..Default::default() };
+                let parsed = Png::parse(Bytes::from(bytes.clone()), &options);
+                let streamed = Png::from_reader(bytes.as_slice(), &options);
+                match (parsed, streamed) {
+                    (Ok(parsed), Ok(streamed)) => assert_eq!(parsed.as_bytes(), streamed.as_bytes()),
+                    (parsed, streamed) => assert!(parsed.is_err() && streamed.is_err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_reader_stops_early() {
+        let bytes = duplicated_bytes();
+        let mut reader = ChunkReader::new(bytes.as_slice(), ParseOptions::default()).unwrap();
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.data(), b"first header");
+        assert_eq!(reader.count(), 3);
+    }
+
+    #[test]
+    fn test_from_reader_rejects_bad_input() {
+        assert!(Png::from_reader(&b"not a png"[..], &ParseOptions::default()).is_err());
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[40] ^= 0xff;
+        assert!(Png::from_reader(bytes.as_slice(), &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut written = Vec::new();
+        png.write_to(&mut written, &WriteOptions::default()).unwrap();
+        assert_eq!(written, PNG_FILE.to_vec());
+        assert_eq!(png.byte_len(), PNG_FILE.len());
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
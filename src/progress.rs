@@ -0,0 +1,207 @@
+//! Progress bars for reading large files and working through batches, drawn on stderr so they
+//! never mix with the data written to stdout.
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::i18n::{format_decimal, format_number};
+
+const AUTO: u8 = 0;
+const ALWAYS: u8 = 1;
+const NEVER: u8 = 2;
+
+static MODE: AtomicU8 = AtomicU8::new(AUTO);
+/// Whether a bar is on screen. Only one is drawn at a time, so the bar of a large file read
+/// during a batch gives way to the batch's.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Files at least this large show a bar while they are read.
+pub const LARGE_FILE: u64 = 16 << 20;
+
+const WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shows progress bars for the rest of the process if `show` is `Some(true)`, never if it is
+/// `Some(false)`, and only when stdout and stderr are terminals if it is `None`.
+pub fn set_shown(show: Option<bool>) {
+    let mode = match show {
+        None => AUTO,
+        Some(true) => ALWAYS,
+        Some(false) => NEVER,
+    };
+    MODE.store(mode, Ordering::Relaxed);
+}
+
+/// Whether progress bars are drawn. Plain output never has them, since they redraw a line.
+pub fn is_shown() -> bool {
+    if crate::output::is_plain() {
+        return false;
+    }
+    match MODE.load(Ordering::Relaxed) {
+        ALWAYS => true,
+        NEVER => false,
+        _ => io::stdout().is_terminal() && io::stderr().is_terminal(),
+    }
+}
+
+/// What a bar counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Bytes,
+    Files,
+}
+
+/// A progress bar on stderr, erased when dropped. Does nothing when bars aren't shown, while
+/// another bar is, or for a batch of a single file, which the bar of that file covers.
+#[derive(Debug)]
+pub struct Progress {
+    label: String,
+    unit: Unit,
+    total: u64,
+    done: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    shown: bool,
+}
+
+impl Progress {
+    pub fn new(label: impl Display, unit: Unit, total: u64) -> Self {
+        Self {
+            label: label.to_string(),
+            unit,
+            total,
+            done: 0,
+            started: Instant::now(),
+            drawn: None,
+            shown: is_shown() && !(unit == Unit::Files && total <= 1) && !ACTIVE.swap(true, Ordering::Relaxed),
+        }
+    }
+
+    /// Counts `amount` more done, redrawing the bar at most ten times a second. The bar is
+    /// erased once everything is done.
+    pub fn advance(&mut self, amount: u64) {
+        self.done = self.done.saturating_add(amount).min(self.total);
+        if !self.shown {
+            return;
+        }
+        if self.done == self.total {
+            self.finish();
+        } else if self.drawn.is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL) {
+            self.draw();
+        }
+    }
+
+    /// Erases the bar for good, letting another one be drawn.
+    fn finish(&mut self) {
+        if self.shown {
+            self.clear();
+            self.shown = false;
+            ACTIVE.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Prints `line` on stdout without leaving the bar in the middle of it.
+    pub fn println(&mut self, line: impl Display) {
+        self.clear();
+        println!("{line}");
+        if self.shown && self.drawn.is_some() {
+            self.draw();
+        }
+    }
+
+    /// The bar as it is drawn, e.g. `image.png [#####     ]  50% 8.0 MiB/16.0 MiB 4.0 MiB/s`.
+    fn render(&self, elapsed: Duration) -> String {
+        let filled = match self.total {
+            0 => WIDTH,
+            total => (self.done as f64 / total as f64 * WIDTH as f64) as usize,
+        };
+        let percent = match self.total {
+            0 => 100,
+            total => self.done * 100 / total,
+        };
+        let rate = self.done as f64 / elapsed.as_secs_f64().max(0.001);
+        let (done, total, rate) = match self.unit {
+            Unit::Bytes => (format_mib(self.done as f64), format_mib(self.total as f64), format!("{}/s", format_mib(rate))),
+            Unit::Files => (
+                format_number(self.done as i64),
+                format!("{} files", format_number(self.total as i64)),
+                format!("{} files/s", format_decimal(rate, 1)),
+            ),
+        };
+        format!("{} [{}{}] {percent:>3}% {done}/{total} {rate}", self.label, "#".repeat(filled), " ".repeat(WIDTH - filled))
+    }
+
+    fn draw(&mut self) {
+        let line = self.render(self.started.elapsed());
+        let mut stderr = io::stderr().lock();
+        // A bar that can't be drawn isn't worth failing the command for.
+        let _ = write!(stderr, "\r{line}\x1b[K").and_then(|_| stderr.flush());
+        self.drawn = Some(Instant::now());
+    }
+
+    fn clear(&self) {
+        if self.shown && self.drawn.is_some() {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[K").and_then(|_| stderr.flush());
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Erases the bar on screen, if any, so a message can be printed in its place. The bar is drawn
+/// again the next time it advances.
+pub fn clear_line() {
+    if ACTIVE.load(Ordering::Relaxed) {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K").and_then(|_| stderr.flush());
+    }
+}
+
+fn format_mib(bytes: f64) -> String {
+    format!("{} MiB", format_decimal(bytes / (1 << 20) as f64, 1))
+}
+
+/// A reader advancing a [`Progress`] in bytes as it is read.
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: Progress,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, progress: Progress) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut progress = Progress::new("image.png", Unit::Bytes, 16 << 20);
+        progress.done = 8 << 20;
+        assert_eq!(
+            progress.render(Duration::from_secs(2)),
+            format!("image.png [{}{}]  50% 8.0 MiB/16.0 MiB 4.0 MiB/s", "#".repeat(15), " ".repeat(15))
+        );
+        let mut progress = Progress::new("verify", Unit::Files, 4);
+        progress.done = 4;
+        assert!(progress.render(Duration::from_secs(2)).ends_with("] 100% 4/4 files 2.0 files/s"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::manifest::sha256_hex;
+use crate::png::Png;
+use crate::sign;
+use crate::Result;
+
+/// Chunk type holding the signed claim: ancillary, private and unsafe to copy,
+/// since the claim no longer holds once the image is edited.
+pub const CLAIM_CHUNK_TYPE: &str = "prVN";
+
+/// A statement about who produced an image and from what.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claim {
+    pub creator: String,
+    pub toolchain: String,
+    pub timestamp: u64,
+    /// SHA-256 of the image without its claim chunk.
+    pub content_sha256: String,
+    /// SHA-256 of the file this image was derived from, if any.
+    pub parent_sha256: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedClaim {
+    claim: Claim,
+    signature: String,
+}
+
+impl Claim {
+    /// Creates a claim by `creator` for the current contents of `png`.
+    pub fn new(png: &Png, creator: &str, parent: Option<&[u8]>) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Self {
+            creator: creator.to_string(),
+            toolchain: format!("pngme {}", env!("CARGO_PKG_VERSION")),
+            timestamp,
+            content_sha256: content_digest(png),
+            parent_sha256: parent.map(sha256_hex),
+        }
+    }
+}
+
+/// Signs `claim` with `key` and stores it in `png`, replacing any earlier claim.
+pub fn embed(png: &mut Png, claim: Claim, key: &[u8]) -> Result<()> {
+    let signature = sign::sign(key, &serde_json::to_vec(&claim)?);
+    let signed = SignedClaim { claim, signature: signature.iter().map(|b| format!("{b:02x}")).collect() };
+    while png.remove_chunk(CLAIM_CHUNK_TYPE).is_ok() {}
+    png.append_chunk(Chunk::new(ChunkType::from_str(CLAIM_CHUNK_TYPE)?, serde_json::to_vec(&signed)?))?;
+    Ok(())
+}
+
+/// Returns the claim stored in `png` after checking its signature under `key`
+/// and that the image has not changed since it was signed.
+pub fn verify(png: &Png, key: &[u8]) -> Result<Claim> {
+    let chunk = png.chunk_by_type(CLAIM_CHUNK_TYPE).ok_or(ProvenanceError::MissingClaim)?;
+    let signed: SignedClaim = serde_json::from_slice(chunk.data())?;
+    let signature = parse_hex(&signed.signature).ok_or(ProvenanceError::InvalidSignature)?;
+    if !sign::verify(key, &serde_json::to_vec(&signed.claim)?, &signature) {
+        return Err(Box::new(ProvenanceError::InvalidSignature));
+    }
+    if signed.claim.content_sha256 != content_digest(png) {
+        return Err(Box::new(ProvenanceError::ContentChanged));
+    }
+    Ok(signed.claim)
+}
+
+/// The digest of `png` with every claim chunk left out.
+fn content_digest(png: &Png) -> String {
+    let chunks = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() != CLAIM_CHUNK_TYPE)
+        .cloned()
+        .collect();
+    let mut content = Png::from_chunks(chunks);
+    content.set_trailer(png.trailer().to_vec()).expect("a new Png is writable");
+    sha256_hex(&content.as_bytes())
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+#[derive(Debug)]
+pub enum ProvenanceError {
+    MissingClaim,
+    InvalidSignature,
+    ContentChanged,
+}
+
+impl std::error::Error for ProvenanceError {}
+
+impl std::fmt::Display for ProvenanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProvenanceError::MissingClaim => write!(f, "No provenance claim found"),
+            ProvenanceError::InvalidSignature => write!(f, "Provenance claim signature is invalid"),
+            ProvenanceError::ContentChanged => write!(f, "Image was modified after the claim was signed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ])
+    }
+
+    fn sign_as(png: &mut Png, creator: &str) {
+        let claim = Claim::new(png, creator, None);
+        embed(png, claim, b"key").unwrap();
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut png = testing_png();
+        let claim = Claim::new(&png, "alice", Some(b"parent file"));
+        embed(&mut png, claim.clone(), b"key").unwrap();
+
+        let verified = verify(&png, b"key").unwrap();
+        assert_eq!(verified, claim);
+        assert_eq!(verified.parent_sha256, Some(sha256_hex(b"parent file")));
+    }
+
+    #[test]
+    fn test_resigning_replaces_claim() {
+        let mut png = testing_png();
+        sign_as(&mut png, "alice");
+        sign_as(&mut png, "bob");
+
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(verify(&png, b"key").unwrap().creator, "bob");
+    }
+
+    #[test]
+    fn test_verify_failures() {
+        let mut png = testing_png();
+        assert!(verify(&png, b"key").is_err());
+
+        sign_as(&mut png, "alice");
+        assert!(verify(&png, b"wrong key").is_err());
+
+        png.append_chunk(Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"edited".to_vec())).unwrap();
+        assert!(verify(&png, b"key").is_err());
+    }
+}
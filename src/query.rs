@@ -0,0 +1,434 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+
+/// A filter over the chunks of a PNG, parsed from expressions such as
+/// `type !in standard && length > 4096`.
+///
+/// Grammar:
+/// ```text
+/// expr       := and ( "||" and )*
+/// and        := unary ( "&&" unary )*
+/// unary      := "!" unary | "(" expr ")" | flag | comparison
+/// flag       := critical | ancillary | public | private | safe | standard
+/// comparison := field op value | field ["!"] "in" set
+/// field      := type | length | index
+/// op         := == | != | < | <= | > | >= | ~
+/// set        := standard | "[" value ( "," value )* "]"
+/// ```
+///
+/// `~` and `in` take chunk type patterns for `type`, such as `type ~ ??Xt`; see [`ChunkType::matches`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Flag(Flag),
+    Compare(Field, Op, Value),
+    In(Field, Set),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+    Critical,
+    Ancillary,
+    Public,
+    Private,
+    SafeToCopy,
+    Standard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Type,
+    Length,
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The chunk type matches a pattern.
+    Like,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Set {
+    Standard,
+    List(Vec<Value>),
+}
+
+/// The properties of a chunk a `Query` is evaluated against.
+pub struct ChunkContext<'a> {
+    pub chunk_type: &'a ChunkType,
+    pub length: u32,
+    pub index: usize,
+}
+
+impl Query {
+    /// Returns true if the chunk described by `context` satisfies this query.
+    pub fn matches(&self, context: &ChunkContext) -> bool {
+        match self {
+            Query::And(lhs, rhs) => lhs.matches(context) && rhs.matches(context),
+            Query::Or(lhs, rhs) => lhs.matches(context) || rhs.matches(context),
+            Query::Not(query) => !query.matches(context),
+            Query::Flag(flag) => flag.matches(context),
+            Query::Compare(Field::Type, Op::Like, Value::Text(pattern)) => context.chunk_type.matches(pattern),
+            Query::Compare(field, op, value) => op.holds(field.value(context).compare(value)),
+            Query::In(field, set) => {
+                let actual = field.value(context);
+                match set {
+                    Set::Standard => context.chunk_type.is_standard(),
+                    Set::List(values) => values.iter().any(|value| match (field, value) {
+                        (Field::Type, Value::Text(pattern)) => context.chunk_type.matches(pattern),
+                        _ => actual.compare(value) == Some(std::cmp::Ordering::Equal),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl Flag {
+    fn matches(&self, context: &ChunkContext) -> bool {
+        let chunk_type = context.chunk_type;
+        match self {
+            Flag::Critical => chunk_type.is_critical(),
+            Flag::Ancillary => !chunk_type.is_critical(),
+            Flag::Public => chunk_type.is_public(),
+            Flag::Private => !chunk_type.is_public(),
+            Flag::SafeToCopy => chunk_type.is_safe_to_copy(),
+            Flag::Standard => chunk_type.is_standard(),
+        }
+    }
+}
+
+impl Field {
+    fn value(&self, context: &ChunkContext) -> Value {
+        match self {
+            Field::Type => Value::Text(context.chunk_type.to_string()),
+            Field::Length => Value::Number(context.length as u64),
+            Field::Index => Value::Number(context.index as u64),
+        }
+    }
+}
+
+impl Op {
+    fn holds(&self, ordering: Option<std::cmp::Ordering>) -> bool {
+        use std::cmp::Ordering::*;
+        match (self, ordering) {
+            (_, None) | (Op::Like, _) => false,
+            (Op::Eq, Some(o)) => o == Equal,
+            (Op::Ne, Some(o)) => o != Equal,
+            (Op::Lt, Some(o)) => o == Less,
+            (Op::Le, Some(o)) => o != Greater,
+            (Op::Gt, Some(o)) => o == Greater,
+            (Op::Ge, Some(o)) => o != Less,
+        }
+    }
+}
+
+impl Value {
+    /// Values of different kinds never compare, so `type > 5` matches nothing.
+    fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let query = parser.expr()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(Box::new(QueryError::UnexpectedToken(token.to_string()))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(u64),
+    Text(String),
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::Number(number) => write!(f, "{number}"),
+            Token::Text(text) => write!(f, "'{text}'"),
+            Token::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 15] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "~", "!", "(", ")", "[", "]", ","];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if rest.starts_with(['\'', '"']) {
+            let quote = rest.chars().next().unwrap();
+            let end = rest[1..]
+                .find(quote)
+                .ok_or_else(|| Box::new(QueryError::UnterminatedString))?;
+            tokens.push(Token::Text(rest[1..=end].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '?' | '^')))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(Box::new(QueryError::UnexpectedToken(rest.chars().next().unwrap().to_string())));
+            }
+            let word = &rest[..end];
+            match word.parse::<u64>() {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_) => tokens.push(Token::Word(word.to_string())),
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token.ok_or_else(|| Box::new(QueryError::UnexpectedEnd) as Error)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(Box::new(QueryError::UnexpectedToken(token.to_string()))),
+            None => Err(Box::new(QueryError::UnexpectedEnd)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Query> {
+        let mut query = self.and()?;
+        while self.eat("||") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query> {
+        let mut query = self.unary()?;
+        while self.eat("&&") {
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<Query> {
+        if self.eat("!") {
+            return Ok(Query::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let query = self.expr()?;
+            self.expect(")")?;
+            return Ok(query);
+        }
+        let word = match self.next()? {
+            Token::Word(word) => word,
+            token => return Err(Box::new(QueryError::UnexpectedToken(token.to_string()))),
+        };
+        let field = match word.as_str() {
+            "critical" => return Ok(Query::Flag(Flag::Critical)),
+            "ancillary" => return Ok(Query::Flag(Flag::Ancillary)),
+            "public" => return Ok(Query::Flag(Flag::Public)),
+            "private" => return Ok(Query::Flag(Flag::Private)),
+            "safe" => return Ok(Query::Flag(Flag::SafeToCopy)),
+            "standard" => return Ok(Query::Flag(Flag::Standard)),
+            "type" => Field::Type,
+            "length" => Field::Length,
+            "index" => Field::Index,
+            _ => return Err(Box::new(QueryError::UnknownField(word))),
+        };
+        self.comparison(field)
+    }
+
+    fn comparison(&mut self, field: Field) -> Result<Query> {
+        let negated = self.eat("!");
+        if self.peek() == Some(&Token::Word("in".to_string())) {
+            self.position += 1;
+            let query = Query::In(field, self.set(field)?);
+            return Ok(if negated { Query::Not(Box::new(query)) } else { query });
+        }
+        if negated {
+            return Err(Box::new(QueryError::UnexpectedToken("!".to_string())));
+        }
+        let op = match self.next()? {
+            Token::Symbol("==") => Op::Eq,
+            Token::Symbol("!=") => Op::Ne,
+            Token::Symbol("<") => Op::Lt,
+            Token::Symbol("<=") => Op::Le,
+            Token::Symbol(">") => Op::Gt,
+            Token::Symbol(">=") => Op::Ge,
+            Token::Symbol("~") => Op::Like,
+            token => return Err(Box::new(QueryError::UnexpectedToken(token.to_string()))),
+        };
+        Ok(Query::Compare(field, op, self.value()?))
+    }
+
+    fn set(&mut self, field: Field) -> Result<Set> {
+        if self.peek() == Some(&Token::Word("standard".to_string())) {
+            self.position += 1;
+            // Only chunk types are standard or not; `length in standard` would test the type.
+            if field != Field::Type {
+                return Err(Box::new(QueryError::StandardNeedsType));
+            }
+            return Ok(Set::Standard);
+        }
+        self.expect("[")?;
+        let mut values = vec![self.value()?];
+        while self.eat(",") {
+            values.push(self.value()?);
+        }
+        self.expect("]")?;
+        Ok(Set::List(values))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.next()? {
+            Token::Number(number) => Ok(Value::Number(number)),
+            Token::Word(text) | Token::Text(text) => Ok(Value::Text(text)),
+            token => Err(Box::new(QueryError::UnexpectedToken(token.to_string()))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnterminatedString,
+    UnknownField(String),
+    StandardNeedsType,
+}
+
+impl std::error::Error for QueryError {}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::UnexpectedToken(token) => write!(f, "Unexpected '{token}' in query"),
+            QueryError::UnexpectedEnd => write!(f, "Query ended unexpectedly"),
+            QueryError::UnterminatedString => write!(f, "Unterminated string in query"),
+            QueryError::UnknownField(field) => write!(f, "Unknown field '{field}' in query"),
+            QueryError::StandardNeedsType => write!(f, "Only 'type' can be tested with 'in standard'"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, chunk_type: &str, length: u32, index: usize) -> bool {
+        let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+        let query = Query::from_str(query).unwrap();
+        query.matches(&ChunkContext { chunk_type: &chunk_type, length, index })
+    }
+
+    #[test]
+    fn test_compare_fields() {
+        assert!(matches("type == ruSt", "ruSt", 0, 0));
+        assert!(matches("type != 'tEXt'", "ruSt", 0, 0));
+        assert!(matches("length > 4096", "ruSt", 5000, 0));
+        assert!(!matches("length > 4096", "ruSt", 4096, 0));
+        assert!(matches("index <= 2", "ruSt", 0, 2));
+    }
+
+    #[test]
+    fn test_set_membership() {
+        assert!(matches("type !in standard", "ruSt", 0, 0));
+        assert!(!matches("type !in standard", "tEXt", 0, 0));
+        assert!(matches("type in [tEXt, zTXt, iTXt]", "zTXt", 0, 0));
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let query = "type !in standard && length > 4096";
+        assert!(matches(query, "ruSt", 5000, 0));
+        assert!(!matches(query, "ruSt", 10, 0));
+        assert!(matches("private || (critical && !standard)", "RuSt", 0, 0));
+        assert!(!matches("!ancillary", "ruSt", 0, 0));
+        assert!(matches("safe && private", "ruSt", 0, 0));
+    }
+
+    #[test]
+    fn test_type_patterns() {
+        assert!(matches("type ~ ??Xt", "zTXt", 0, 0));
+        assert!(!matches("type ~ ??Xt", "ruSt", 0, 0));
+        assert!(matches("type in [??Xt, ru?t]", "ruSt", 0, 0));
+        assert!(matches("type ~ '_???'", "ruSt", 0, 0));
+        assert!(!matches("length ~ ????", "ruSt", 0, 0));
+    }
+
+    #[test]
+    fn test_type_mismatch_never_matches() {
+        assert!(!matches("type > 5", "ruSt", 0, 0));
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        assert!(Query::from_str("size > 5").is_err());
+        assert!(Query::from_str("length >").is_err());
+        assert!(Query::from_str("type == 'ruSt").is_err());
+        assert!(Query::from_str("(critical").is_err());
+        assert!(Query::from_str("critical public").is_err());
+        assert!(Query::from_str("length ! 5").is_err());
+        assert!(Query::from_str("length in standard").is_err());
+        assert!(Query::from_str("index !in standard").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Re-encrypting the payloads hidden in a PNG under a new password.
+
+use std::fmt::{self, Display};
+
+use crate::chunk_type::ChunkType;
+use crate::encode::{encode_into, EncodeOptions, Position, ReplacePolicy};
+use crate::payload::{Envelope, Part, RawChunkData, DEFAULT_PART_SIZE};
+use crate::png::Png;
+use crate::stego::Method;
+use crate::Result;
+
+/// Where a payload `rekey` re-encrypted is hidden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Chunks(ChunkType),
+    /// A record of this type after IEND.
+    Trailer(ChunkType),
+    Pixels,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Chunks(chunk_type) => write!(f, "{chunk_type} chunks"),
+            Location::Trailer(chunk_type) => write!(f, "{chunk_type} record after IEND"),
+            Location::Pixels => write!(f, "the pixels"),
+        }
+    }
+}
+
+/// Re-encrypts every encrypted payload in `png` from the password `old` to `new`: those in the
+/// chunks of each type, in the records after IEND and in the pixels. Returns where they were.
+/// Chunks keep their place in the file. Fails on the first payload `old` doesn't open, or that is
+/// signed, since its tag would no longer hold; `png` is then left half done and should be dropped.
+pub fn rekey(png: &mut Png, old: &str, new: &str) -> Result<Vec<Location>> {
+    let mut rekeyed = Vec::new();
+    let mut types: Vec<ChunkType> = Vec::new();
+    for chunk in png.chunks() {
+        if !types.contains(chunk.chunk_type()) {
+            types.push(chunk.chunk_type().clone());
+        }
+    }
+    for chunk_type in types {
+        let chunks: Vec<RawChunkData> =
+            png.chunks().iter().filter(|chunk| chunk.chunk_type() == &chunk_type).map(|chunk| chunk.raw_data()).collect();
+        // A split payload is split the same way again: every part but the last is full.
+        let part_size = Part::parse(&chunks[0]).map_or(DEFAULT_PART_SIZE, |part| part.data.len());
+        let envelope = Envelope::join(chunks)?;
+        let first = png.chunks().iter().position(|chunk| chunk.chunk_type() == &chunk_type).expect("a chunk of the type");
+        let options = EncodeOptions::new(chunk_type.clone()).position(Position::Index(first)).part_size(part_size);
+        reencrypt(png, envelope, Location::Chunks(chunk_type), options, old, new, &mut rekeyed)?;
+    }
+    for chunk_type in crate::appended::types(png.trailer()) {
+        let envelope = Envelope::from_raw(crate::appended::find(png.trailer(), &chunk_type).expect("a record of the type"));
+        let options = EncodeOptions::new(chunk_type.clone()).method(Method::Trailer);
+        reencrypt(png, envelope, Location::Trailer(chunk_type), options, old, new, &mut rekeyed)?;
+    }
+    // Images whose pixels can't carry a payload have none there.
+    if let Ok(Some(data)) = crate::stego::extract(png) {
+        let options = EncodeOptions::new(ChunkType::try_from(*b"IDAT")?).method(Method::Lsb);
+        reencrypt(png, Envelope::from_raw(RawChunkData::from(data)), Location::Pixels, options, old, new, &mut rekeyed)?;
+    }
+    Ok(rekeyed)
+}
+
+/// Stores the payload in `envelope` with `options` and the password `new`, if `old` encrypts it,
+/// and adds `location` to `rekeyed`.
+fn reencrypt(
+    png: &mut Png,
+    envelope: Envelope,
+    location: Location,
+    options: EncodeOptions,
+    old: &str,
+    new: &str,
+    rekeyed: &mut Vec<Location>,
+) -> Result<()> {
+    if !envelope.is_encrypted() {
+        return Ok(());
+    }
+    if envelope.is_signed() {
+        return Err(Box::new(RekeyError::Signed(location)));
+    }
+    let payload = envelope.open(Some(old))?;
+    encode_into(png, payload, &options.password(new).replace(ReplacePolicy::Replace))?;
+    rekeyed.push(location);
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum RekeyError {
+    Signed(Location),
+}
+
+impl std::error::Error for RekeyError {}
+
+impl Display for RekeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RekeyError::Signed(location) => {
+                write!(f, "The payload in {location} is signed, and a new password would break its tag")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::decode::{decode_from, DecodeOptions};
+    use crate::payload::Payload;
+    use std::str::FromStr;
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    #[test]
+    fn test_rekey() {
+        let mut png = Png::from_chunks(vec![Chunk::new(chunk_type("IHDR"), vec![0; 13]), Chunk::new(chunk_type("IEND"), Vec::new())]);
+        let encode = |png: &mut Png, options: EncodeOptions| encode_into(png, Payload::from("secret".to_string()), &options).unwrap();
+        encode(&mut png, EncodeOptions::new(chunk_type("ruSt")).password("old").position(Position::BeforeIend).part_size(20));
+        encode(&mut png, EncodeOptions::new(chunk_type("plAn")).position(Position::BeforeIend));
+        encode(&mut png, EncodeOptions::new(chunk_type("apPd")).password("old").method(Method::Trailer));
+        let types: Vec<ChunkType> = png.chunks().iter().map(|chunk| chunk.chunk_type().clone()).collect();
+
+        let error = rekey(&mut Png::try_from(png.as_bytes().as_slice()).unwrap(), "wrong", "new");
+        assert!(error.is_err());
+        let rekeyed = rekey(&mut png, "old", "new").unwrap();
+        assert_eq!(rekeyed, [Location::Chunks(chunk_type("ruSt")), Location::Trailer(chunk_type("apPd"))]);
+        // The split payload keeps its place, and the plain one is left alone.
+        assert_eq!(png.chunks().iter().map(|chunk| chunk.chunk_type().clone()).collect::<Vec<_>>(), types);
+        for name in ["ruSt", "apPd"] {
+            let old = DecodeOptions::new(chunk_type(name)).password("old");
+            assert!(decode_from(&png, &old).is_err());
+            let payload = decode_from(&png, &DecodeOptions::new(chunk_type(name)).password("new")).unwrap().unwrap();
+            assert_eq!(payload.as_bytes(), b"secret");
+        }
+        let plain = decode_from(&png, &DecodeOptions::new(chunk_type("plAn"))).unwrap().unwrap();
+        assert_eq!(plain.as_bytes(), b"secret");
+    }
+
+    #[test]
+    fn test_rekey_signed() {
+        let mut png = Png::from_chunks(Vec::new());
+        let options = EncodeOptions::new(chunk_type("ruSt")).password("old").sign_key(b"key".to_vec());
+        encode_into(&mut png, Payload::from("secret".to_string()), &options).unwrap();
+        let error = rekey(&mut png, "old", "new").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(RekeyError::Signed(Location::Chunks(_)))));
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{self, Display};
+
+use bytes::Bytes;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::locate::{span_at, ChunkStatus};
+use crate::png::{Png, PngError};
+use crate::Result;
+
+/// What `repair` may change besides wrong CRCs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// Append an IEND chunk if the file has none.
+    pub add_end: bool,
+    /// Keep the bytes after the last readable chunk instead of removing them.
+    pub keep_trailer: bool,
+}
+
+/// A change `repair` made, with the byte offset in the original file it applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    Crc { chunk_type: [u8; 4], stored: u32, computed: u32 },
+    /// A chunk cut off by the end of the file, or with an invalid type. Nothing after it is read.
+    Unreadable { chunk_type: [u8; 4], status: ChunkStatus },
+    AddedEnd,
+    RemovedTrailer { length: usize },
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |chunk_type: &[u8; 4]| String::from_utf8_lossy(chunk_type).into_owned();
+        match self {
+            Fix::Crc { chunk_type, stored, computed } => {
+                write!(f, "rewrote the CRC of {} from {stored:08x} to {computed:08x}", name(chunk_type))
+            }
+            Fix::Unreadable { chunk_type, status } => write!(f, "dropped chunk {}: {status}", name(chunk_type)),
+            Fix::AddedEnd => write!(f, "added the missing IEND chunk"),
+            Fix::RemovedTrailer { length } => write!(f, "removed {length} bytes after the last chunk"),
+        }
+    }
+}
+
+/// Rebuilds the PNG file `bytes` from every chunk that can be read, recomputing wrong CRCs.
+/// Reading stops at the first chunk that is cut off or has an invalid type. Chunks after IEND
+/// are kept as long as they can be read; only the bytes after the last readable chunk are
+/// removed, unless `options` keeps them, and a missing IEND chunk is added if `options` asks
+/// for it. Returns the repaired PNG and the fixes in file order, each with its offset. Fails
+/// only if `bytes` doesn't start with the PNG signature.
+pub fn repair(bytes: &[u8], options: &RepairOptions) -> Result<(Png, Vec<(usize, Fix)>)> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        return Err(Box::new(PngError::InvalidHeader));
+    }
+    let bytes = Bytes::copy_from_slice(bytes);
+    let mut chunks = Vec::new();
+    let mut fixes = Vec::new();
+    let mut seen_end = false;
+    let mut offset = Png::STANDARD_HEADER.len();
+    // Unlike `chunk_spans`, carry on past IEND through chunks with a wrong CRC, since those are
+    // repaired like any other.
+    while let Some(span) = span_at(&bytes, offset, chunks.len()) {
+        let chunk_type = span.chunk_type;
+        match span.status {
+            ChunkStatus::Valid => {}
+            ChunkStatus::CrcMismatch { stored, computed } => fixes.push((span.offset, Fix::Crc { chunk_type, stored, computed })),
+            // What follows IEND without being a chunk is trailing data, not a broken chunk.
+            _ if seen_end => break,
+            status => {
+                fixes.push((span.offset, Fix::Unreadable { chunk_type, status }));
+                break;
+            }
+        }
+        seen_end |= &chunk_type == b"IEND";
+        offset = span.offset + span.length as usize + 12;
+        chunks.push(Chunk::parse_lenient(bytes.slice(span.offset..offset))?);
+    }
+    let readable_end = Png::STANDARD_HEADER.len() + chunks.iter().map(|chunk| chunk.length() as usize + 12).sum::<usize>();
+    let has_end = chunks.iter().any(|chunk| &chunk.chunk_type().bytes() == b"IEND");
+    if !has_end && options.add_end {
+        chunks.push(Chunk::new(ChunkType::try_from(*b"IEND")?, Vec::new()));
+        fixes.push((readable_end, Fix::AddedEnd));
+    }
+    let mut png = Png::from_chunks(chunks);
+    let rest = bytes.slice(readable_end.min(bytes.len())..);
+    if options.keep_trailer {
+        png.set_trailer(rest)?;
+    } else if !rest.is_empty() {
+        fixes.push((bytes.len() - rest.len(), Fix::RemovedTrailer { length: rest.len() }));
+    }
+    Ok((png, fixes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn file(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for chunk in chunks {
+            bytes.extend(chunk.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_repair_crc_and_trailer() {
+        let mut bytes = file(&[chunk("IHDR", &[0; 13]), chunk("ruSt", b"secret"), chunk("IEND", b"")]);
+        let damaged_crc = 8 + 25 + 18 - 1;
+        bytes[damaged_crc] ^= 0xff;
+        bytes.extend_from_slice(b"junk");
+        let (png, fixes) = repair(&bytes, &RepairOptions::default()).unwrap();
+        assert!(matches!(fixes[0], (33, Fix::Crc { chunk_type: [b'r', b'u', b'S', b't'], .. })));
+        assert_eq!(fixes[1], (bytes.len() - 4, Fix::RemovedTrailer { length: 4 }));
+        assert_eq!(png.chunks().len(), 3);
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+
+        let (png, fixes) = repair(&bytes, &RepairOptions { keep_trailer: true, ..Default::default() }).unwrap();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(png.trailer(), b"junk");
+    }
+
+    #[test]
+    fn test_repair_keeps_chunks_after_end() {
+        let mut bytes = file(&[chunk("IHDR", &[0; 13]), chunk("IEND", b""), chunk("ruSt", b"hello"), chunk("ruSt", b"again")]);
+        let damaged_crc = bytes.len() - 1;
+        bytes[damaged_crc] ^= 0xff;
+        bytes.extend_from_slice(b"junk that is no chunk");
+        let (png, fixes) = repair(&bytes, &RepairOptions::default()).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IEND", "ruSt", "ruSt"]);
+        assert_eq!(png.chunks()[3].data(), b"again");
+        assert!(matches!(fixes[0], (_, Fix::Crc { chunk_type: [b'r', b'u', b'S', b't'], .. })));
+        assert_eq!(fixes[1].1, Fix::RemovedTrailer { length: 21 });
+        assert_eq!(fixes.len(), 2);
+    }
+
+    #[test]
+    fn test_repair_truncated_file() {
+        let bytes = file(&[chunk("IHDR", &[0; 13]), chunk("IDAT", b"pixels")]);
+        let cut = &bytes[..bytes.len() - 3];
+        let (png, fixes) = repair(cut, &RepairOptions { add_end: true, ..Default::default() }).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IEND"]);
+        assert!(matches!(fixes[0].1, Fix::Unreadable { status: ChunkStatus::Truncated, .. }));
+        assert_eq!(fixes[1], (33, Fix::AddedEnd));
+        assert!(matches!(fixes[2].1, Fix::RemovedTrailer { length: 15 }));
+        assert!(repair(b"not a png", &RepairOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::query::{ChunkContext, Query};
+use crate::rng::SplitMix64;
+use crate::Result;
+
+/// The type and length of a chunk, which is all a scan needs to know about it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkRecord {
+    pub chunk_type: ChunkType,
+    pub length: u32,
+}
+
+/// Lists the type and length of every chunk in `png`.
+pub fn chunk_table(png: &Png) -> Vec<ChunkRecord> {
+    png.chunks()
+        .iter()
+        .map(|chunk| ChunkRecord { chunk_type: chunk.chunk_type().clone(), length: chunk.length() })
+        .collect()
+}
+
+/// Lists the files to process for `path`. A file is returned as is, while a directory
+/// yields the PNG files it contains, descending into subdirectories when `recursive` is set.
+pub fn collect_files(path: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            if recursive {
+                files.extend(collect_files(&entry, recursive)?);
+            }
+        } else if is_png_path(&entry) {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` names several files for a batch: a directory, or a glob pattern such as
+/// `images/*.png` that the shell left unexpanded.
+pub fn is_batch_path(path: &Path) -> bool {
+    path.is_dir() || is_glob(path)
+}
+
+/// Lists the files to process for `path` like [`collect_files`], also expanding glob patterns.
+/// Directories matched by a pattern are treated like a directory given directly.
+pub fn expand_path(path: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    if !is_glob(path) {
+        return collect_files(path, recursive);
+    }
+    let pattern = path.to_str().expect("glob patterns are valid UTF-8");
+    let mut files = Vec::new();
+    for entry in glob::glob(pattern)? {
+        files.extend(collect_files(&entry?, recursive)?);
+    }
+    Ok(files)
+}
+
+fn is_glob(path: &Path) -> bool {
+    !path.exists() && path.to_str().is_some_and(|path| path.contains(['*', '?', '[']))
+}
+
+fn is_png_path(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.eq_ignore_ascii_case("png"))
+        .unwrap_or(false)
+}
+
+/// Picks `count` of `files` at random, keeping their order. The same `seed` always picks the
+/// same files, so a sample can be scanned again. All files are kept if there are no more than
+/// `count`.
+pub fn sample(files: Vec<PathBuf>, count: usize, seed: u64) -> Vec<PathBuf> {
+    if files.len() <= count {
+        return files;
+    }
+    let mut rng = SplitMix64(seed);
+    let mut indices: Vec<usize> = (0..files.len()).collect();
+    for picked in 0..count {
+        let swap = picked + rng.below((files.len() - picked) as u64) as usize;
+        indices.swap(picked, swap);
+    }
+    let mut picked = indices[..count].to_vec();
+    picked.sort_unstable();
+    let mut files = files.into_iter().map(Some).collect::<Vec<_>>();
+    picked.into_iter().map(|index| files[index].take().expect("indices are distinct")).collect()
+}
+
+/// Returns the index and record of every chunk in `table` matching `query`, or every chunk if there is no query.
+pub fn matching_chunks<'a>(table: &'a [ChunkRecord], query: Option<&Query>) -> Vec<(usize, &'a ChunkRecord)> {
+    table
+        .iter()
+        .enumerate()
+        .filter(|(index, record)| {
+            query.is_none_or(|q| {
+                q.matches(&ChunkContext { chunk_type: &record.chunk_type, length: record.length, index: *index })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_matching_chunks() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0; 100]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ]);
+        let query = Query::from_str("type !in standard").unwrap();
+
+        let table = chunk_table(&png);
+        let matches = matching_chunks(&table, Some(&query));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, 1);
+        assert_eq!(matching_chunks(&table, None).len(), 3);
+    }
+
+    #[test]
+    fn test_sample() {
+        let files: Vec<PathBuf> = (0..100).map(|n| PathBuf::from(format!("{n:03}.png"))).collect();
+        let picked = sample(files.clone(), 10, 7);
+        assert_eq!(picked.len(), 10);
+        assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(picked, sample(files.clone(), 10, 7));
+        assert_ne!(picked, sample(files.clone(), 10, 8));
+        assert_eq!(sample(files.clone(), 500, 7), files);
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let dir = std::env::temp_dir().join(format!("pngme-scan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.png"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        fs::write(dir.join("nested").join("b.PNG"), b"").unwrap();
+
+        assert_eq!(collect_files(&dir, false).unwrap(), vec![dir.join("a.png")]);
+        assert_eq!(collect_files(&dir, true).unwrap(), vec![dir.join("a.png"), dir.join("nested").join("b.PNG")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let dir = std::env::temp_dir().join(format!("pngme-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.png"), b"").unwrap();
+        fs::write(dir.join("b.png"), b"").unwrap();
+        fs::write(dir.join("nested").join("c.png"), b"").unwrap();
+
+        assert!(is_batch_path(&dir) && is_batch_path(&dir.join("*.png")));
+        assert!(!is_batch_path(&dir.join("a.png")));
+        assert_eq!(expand_path(&dir.join("?.png"), false).unwrap(), vec![dir.join("a.png"), dir.join("b.png")]);
+        assert_eq!(expand_path(&dir.join("n*"), true).unwrap(), vec![dir.join("nested").join("c.png")]);
+        assert!(expand_path(&dir.join("*.gif"), true).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
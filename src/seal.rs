@@ -0,0 +1,174 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::diff::{align_by, DiffOp};
+use crate::encode::Position;
+use crate::png::Png;
+use crate::sign::{self, TAG_LENGTH};
+use crate::Result;
+
+/// Chunk type of the seal: ancillary, private and unsafe to copy, since the tags no longer
+/// hold once the image is edited.
+pub const SEAL_CHUNK_TYPE: &str = "hmIX";
+
+/// Bytes of one entry of the seal: the chunk type, then the tag of the chunk.
+const ENTRY_LENGTH: usize = 4 + TAG_LENGTH;
+
+/// A chunk that differs from when the file was sealed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The chunk at `index` has the type it was sealed with but different data.
+    Modified { index: usize, chunk_type: ChunkType },
+    /// The chunk at `index` wasn't there when the file was sealed.
+    Added { index: usize, chunk_type: ChunkType },
+    /// A chunk of this type was sealed but is gone.
+    Removed { chunk_type: ChunkType },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Modified { index, chunk_type } => write!(f, "{chunk_type}@{index} modified"),
+            Change::Added { index, chunk_type } => write!(f, "{chunk_type}@{index} added"),
+            Change::Removed { chunk_type } => write!(f, "{chunk_type} removed"),
+        }
+    }
+}
+
+/// Stores the HMAC-SHA256 of every chunk of `png` under `key` in a seal chunk before IEND,
+/// replacing any earlier seal. The seal itself is signed too, so its list can't be edited.
+pub fn seal(png: &mut Png, key: &[u8]) -> Result<()> {
+    png.retain_chunks(|chunk| chunk.chunk_type().to_string() != SEAL_CHUNK_TYPE)?;
+    let mut data = Vec::with_capacity((png.chunks().len() + 1) * ENTRY_LENGTH);
+    for (chunk_type, tag) in entries(png.chunks().iter(), key) {
+        data.extend(chunk_type);
+        data.extend(tag);
+    }
+    let tag = sign::sign(key, &data);
+    data.extend(tag);
+    let index = Position::BeforeIend.index(png.chunks());
+    png.insert_chunk(index, Chunk::new(ChunkType::from_str(SEAL_CHUNK_TYPE)?, data))?;
+    Ok(())
+}
+
+/// Lists the chunks of `png` that changed since it was sealed under `key`, by comparing the tag
+/// of each chunk with the seal. Only the chunks are hashed, so this is fast even for large files.
+pub fn check(png: &Png, key: &[u8]) -> Result<Vec<Change>> {
+    let chunk = png.chunk_by_type(SEAL_CHUNK_TYPE).ok_or(SealError::Missing)?;
+    let data = chunk.data();
+    if data.len() < TAG_LENGTH || !(data.len() - TAG_LENGTH).is_multiple_of(ENTRY_LENGTH) {
+        return Err(Box::new(SealError::Invalid));
+    }
+    let (list, tag) = data.split_at(data.len() - TAG_LENGTH);
+    if !sign::verify(key, list, tag) {
+        return Err(Box::new(SealError::Invalid));
+    }
+    let sealed: Vec<([u8; 4], [u8; TAG_LENGTH])> = list
+        .chunks_exact(ENTRY_LENGTH)
+        .map(|entry| (entry[..4].try_into().expect("4 bytes"), entry[4..].try_into().expect("a tag")))
+        .collect();
+    // The chunks that were sealed, with their index in the file.
+    let chunks: Vec<(usize, &Chunk)> =
+        png.chunks().iter().enumerate().filter(|(_, chunk)| chunk.chunk_type().to_string() != SEAL_CHUNK_TYPE).collect();
+    let current = entries(chunks.iter().map(|(_, chunk)| *chunk), key);
+    let change = |new: usize, modified: bool| {
+        let (index, chunk) = chunks[new];
+        let chunk_type = chunk.chunk_type().clone();
+        if modified { Change::Modified { index, chunk_type } } else { Change::Added { index, chunk_type } }
+    };
+
+    let mut changes = Vec::new();
+    let mut removed: Vec<usize> = Vec::new();
+    let mut added: Vec<usize> = Vec::new();
+    // Every run of differences between two unchanged chunks is a hunk. A replaced chunk shows
+    // up in a hunk as a removal and an addition of the same type.
+    let ops = align_by(&sealed, &current, |a, b| a == b);
+    for op in ops.into_iter().chain([DiffOp::Same(sealed.len(), current.len())]) {
+        match op {
+            DiffOp::Removed(old) => removed.push(old),
+            DiffOp::Added(new) => added.push(new),
+            DiffOp::Same(..) => {
+                for new in added.drain(..) {
+                    let replaced = removed.iter().position(|&old| sealed[old].0 == current[new].0);
+                    changes.push(change(new, replaced.map(|position| removed.remove(position)).is_some()));
+                }
+                for old in removed.drain(..) {
+                    changes.push(Change::Removed { chunk_type: ChunkType::try_from(sealed[old].0)? });
+                }
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// The type and tag of every chunk in `chunks` but the seal.
+fn entries<'a>(chunks: impl Iterator<Item = &'a Chunk>, key: &[u8]) -> Vec<([u8; 4], [u8; TAG_LENGTH])> {
+    chunks
+        .filter(|chunk| chunk.chunk_type().to_string() != SEAL_CHUNK_TYPE)
+        .map(|chunk| {
+            let chunk_type = chunk.chunk_type().bytes();
+            // The type is signed with the data, so renaming a chunk is caught too.
+            let tag = sign::sign(key, &[&chunk_type[..], chunk.data()].concat());
+            (chunk_type, tag)
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum SealError {
+    Missing,
+    Invalid,
+}
+
+impl std::error::Error for SealError {}
+
+impl Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::Missing => write!(f, "File is not sealed, there is no {SEAL_CHUNK_TYPE} chunk"),
+            SealError::Invalid => write!(f, "Seal doesn't match the key, or the seal itself was changed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn sealed_png() -> Png {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", "header"), chunk("tEXt", "Title\0cat"), chunk("IEND", "")]);
+        seal(&mut png, b"key").unwrap();
+        png
+    }
+
+    #[test]
+    fn test_seal() {
+        let mut png = sealed_png();
+        assert_eq!(png.chunks()[2].chunk_type().to_string(), SEAL_CHUNK_TYPE);
+        assert_eq!(check(&png, b"key").unwrap(), Vec::new());
+        // Sealing again replaces the seal.
+        seal(&mut png, b"key").unwrap();
+        assert_eq!(png.chunks_by_type(SEAL_CHUNK_TYPE).len(), 1);
+        let error = check(&png, b"other key").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(SealError::Invalid)));
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut png = sealed_png();
+        png.replace_chunks("tEXt", vec![chunk("tEXt", "Title\0dog")]).unwrap();
+        png.insert_chunk(1, chunk("ruSt", "hidden")).unwrap();
+        png.remove_chunk("IEND").unwrap();
+        let changes = check(&png, b"key").unwrap();
+        assert_eq!(changes.iter().map(ToString::to_string).collect::<Vec<_>>(), ["ruSt@1 added", "tEXt@2 modified", "IEND removed"]);
+
+        let error = check(&Png::from_chunks(Vec::new()), b"key").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(SealError::Missing)));
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+use crate::Result;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length in bytes of an HMAC-SHA256 tag.
+pub const TAG_LENGTH: usize = 32;
+
+/// Computes the HMAC-SHA256 tag of `data` under `key`.
+pub fn sign(key: &[u8], data: &[u8]) -> [u8; TAG_LENGTH] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Checks `tag` against the HMAC-SHA256 of `data` under `key` in constant time.
+pub fn verify(key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.verify_slice(tag).is_ok()
+}
+
+/// Reads a signing key from `path`. The whole file is the key.
+pub fn load_key(path: &Path) -> Result<Vec<u8>> {
+    let key = fs::read(path)?;
+    if key.is_empty() {
+        return Err(Box::new(SignError::EmptyKey));
+    }
+    Ok(key)
+}
+
+#[derive(Debug)]
+pub enum SignError {
+    EmptyKey,
+    Unsigned,
+    Mismatch,
+}
+
+impl std::error::Error for SignError {}
+
+impl std::fmt::Display for SignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignError::EmptyKey => write!(f, "Signing key file is empty"),
+            SignError::Unsigned => write!(f, "Payload is not signed"),
+            SignError::Mismatch => write!(f, "Payload signature doesn't match, it was changed or signed with another key"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_known_vector() {
+        // RFC 4231, test case 2
+        let tag = sign(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = tag.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(hex, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_verify() {
+        let tag = sign(b"key", b"data");
+        assert!(verify(b"key", b"data", &tag));
+        assert!(!verify(b"other key", b"data", &tag));
+        assert!(!verify(b"key", b"tampered", &tag));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+
+use crate::os_path;
+use crate::scan::ChunkRecord;
+use crate::Result;
+
+const SCHEMA: &str = "
+    CREATE TABLE files (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL,
+        error TEXT
+    );
+    CREATE TABLE chunks (
+        file_id INTEGER NOT NULL REFERENCES files(id),
+        chunk_index INTEGER NOT NULL,
+        chunk_type TEXT NOT NULL,
+        length INTEGER NOT NULL,
+        critical INTEGER NOT NULL,
+        public INTEGER NOT NULL,
+        standard INTEGER NOT NULL
+    );
+    CREATE INDEX chunks_by_type ON chunks(chunk_type);
+";
+
+/// Writes scan results into an SQLite database with a `files` and a `chunks` table.
+pub struct SqliteExport {
+    connection: Connection,
+}
+
+impl SqliteExport {
+    /// Creates a new database at `path`, replacing any existing file.
+    pub fn create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch("BEGIN")?;
+        Ok(Self { connection })
+    }
+
+    /// Records a scanned file and the chunks of it that matched, returning the file's id.
+    pub fn add_file(&mut self, path: &Path, chunks: &[(usize, &ChunkRecord)]) -> Result<i64> {
+        self.connection.execute(
+            "INSERT INTO files (path, error) VALUES (?1, NULL)",
+            params![path_value(path)],
+        )?;
+        let file_id = self.connection.last_insert_rowid();
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO chunks (file_id, chunk_index, chunk_type, length, critical, public, standard)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for (index, record) in chunks {
+            let chunk_type = &record.chunk_type;
+            statement.execute(params![
+                file_id,
+                *index as i64,
+                chunk_type.to_string(),
+                record.length,
+                chunk_type.is_critical(),
+                chunk_type.is_public(),
+                chunk_type.is_standard(),
+            ])?;
+        }
+        Ok(file_id)
+    }
+
+    /// Records a file that could not be scanned.
+    pub fn add_error(&mut self, path: &Path, error: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO files (path, error) VALUES (?1, ?2)",
+            params![path_value(path), error],
+        )?;
+        Ok(())
+    }
+
+    /// Commits everything written so far.
+    pub fn finish(self) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+/// `path` as text, or as a blob of its raw bytes if it isn't valid Unicode, so no path is mangled.
+fn path_value(path: &Path) -> Value {
+    match path.to_str() {
+        Some(text) => Value::Text(text.to_string()),
+        None => Value::Blob(os_path::raw_bytes(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_export() {
+        let path = std::env::temp_dir().join(format!("pngme-export-{}.db", std::process::id()));
+        let record = ChunkRecord { chunk_type: ChunkType::from_str("ruSt").unwrap(), length: 42 };
+
+        let mut export = SqliteExport::create(&path).unwrap();
+        export.add_file(Path::new("a.png"), &[(4, &record)]).unwrap();
+        export.add_error(Path::new("bad.png"), "Invalid header").unwrap();
+        export.finish().unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let (path_column, chunk_type, length): (String, String, u32) = connection
+            .query_row(
+                "SELECT files.path, chunk_type, length FROM chunks JOIN files ON files.id = chunks.file_id",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((path_column.as_str(), chunk_type.as_str(), length), ("a.png", "ruSt", 42));
+        let errors: u32 = connection
+            .query_row("SELECT COUNT(*) FROM files WHERE error IS NOT NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(errors, 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
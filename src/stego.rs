@@ -0,0 +1,343 @@
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::inflate::inflate_up_to;
+use crate::known_chunks::{self, ColorType, ImageHeader, KnownChunk};
+use crate::png::Png;
+use crate::Result;
+
+/// Starts the data hidden in the pixels, so images without any are told apart from noise.
+const MAGIC: &[u8; 4] = b"PMLB";
+
+/// Bytes in front of the hidden data: the magic, then its length as a big-endian u32.
+const FRAME_HEADER_LENGTH: usize = 8;
+
+/// The largest IDAT chunk written back, the size most encoders use.
+const IDAT_SIZE: usize = 64 * 1024;
+
+/// Where [`crate::encode_into`] hides a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Method {
+    /// In a chunk of its own.
+    #[default]
+    Chunk,
+    /// In the least significant bit of each color sample of the IDAT pixel data. The image
+    /// looks the same, and there is no extra chunk to spot.
+    Lsb,
+    /// In a record after the IEND chunk, which hosting services that strip unknown chunks
+    /// often keep.
+    Trailer,
+}
+
+impl Method {
+    /// The names accepted by `from_str`.
+    pub const NAMES: [&'static str; 3] = ["chunk", "lsb", "trailer"];
+}
+
+impl FromStr for Method {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "chunk" => Ok(Method::Chunk),
+            "lsb" => Ok(Method::Lsb),
+            "trailer" => Ok(Method::Trailer),
+            _ => Err(format!("Unknown method '{s}', expected one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// The decompressed, unfiltered pixels of an image, and the filter each scanline had.
+struct Pixels {
+    header: ImageHeader,
+    filters: Vec<u8>,
+    rows: Vec<u8>,
+}
+
+impl Pixels {
+    fn read(png: &Png) -> Result<Self> {
+        let header = match png.chunks().first().map(|chunk| known_chunks::decode(chunk, None)) {
+            Some(Ok(Some(KnownChunk::Header(header)))) => header,
+            Some(Err(e)) => return Err(e),
+            _ => return Err(Box::new(StegoError::MissingHeader)),
+        };
+        if header.color_type == ColorType::Indexed {
+            return Err(Box::new(StegoError::Unsupported("indexed color")));
+        }
+        if header.bit_depth < 8 {
+            return Err(Box::new(StegoError::Unsupported("bit depths below 8")));
+        }
+        if header.interlaced {
+            return Err(Box::new(StegoError::Unsupported("interlaced images")));
+        }
+
+        // Each filtered row is a filter byte followed by the row's bytes, which bounds how far the
+        // image data may inflate.
+        let size = (header.width as usize)
+            .checked_mul(bytes_per_pixel(&header))
+            .and_then(|row| row.checked_add(1))
+            .and_then(|line| line.checked_mul(header.height as usize))
+            .ok_or(StegoError::Unsupported("images this large"))?;
+
+        let mut compressed = Vec::new();
+        for chunk in png.chunks_by_type("IDAT") {
+            compressed.extend_from_slice(chunk.data());
+        }
+        let filtered = match inflate_up_to(&compressed, size as u64) {
+            Err(_) => return Err(Box::new(StegoError::CorruptImage)),
+            Ok(filtered) if filtered.len() < size => return Err(Box::new(StegoError::CorruptImage)),
+            Ok(filtered) => filtered,
+        };
+
+        let (row_length, height) = (row_length(&header), header.height as usize);
+        let bpp = bytes_per_pixel(&header);
+        let mut filters = Vec::with_capacity(height);
+        let mut rows = vec![0; height * row_length];
+        for (y, line) in filtered.chunks_exact(row_length + 1).take(height).enumerate() {
+            let (filter, data) = (line[0], &line[1..]);
+            filters.push(filter);
+            let (done, rest) = rows.split_at_mut(y * row_length);
+            let previous = y.checked_sub(1).map(|previous| &done[previous * row_length..]);
+            let row = &mut rest[..row_length];
+            for x in 0..row_length {
+                let a = if x >= bpp { row[x - bpp] } else { 0 };
+                let b = previous.map_or(0, |previous| previous[x]);
+                let c = if x >= bpp { previous.map_or(0, |previous| previous[x - bpp]) } else { 0 };
+                row[x] = data[x].wrapping_add(predict(filter, a, b, c)?);
+            }
+        }
+        Ok(Self { header, filters, rows })
+    }
+
+    /// Filters the rows again with the filter each had, compresses them and puts them in place
+    /// of the IDAT chunks of `png`.
+    fn write(&self, png: &mut Png) -> Result<()> {
+        let (row_length, bpp) = (row_length(&self.header), bytes_per_pixel(&self.header));
+        let mut filtered = Vec::with_capacity(self.rows.len() + self.filters.len());
+        for (y, row) in self.rows.chunks_exact(row_length).enumerate() {
+            let filter = self.filters[y];
+            let previous = (y > 0).then(|| &self.rows[(y - 1) * row_length..y * row_length]);
+            filtered.push(filter);
+            for x in 0..row_length {
+                let a = if x >= bpp { row[x - bpp] } else { 0 };
+                let b = previous.map_or(0, |previous| previous[x]);
+                let c = if x >= bpp { previous.map_or(0, |previous| previous[x - bpp]) } else { 0 };
+                filtered.push(row[x].wrapping_sub(predict(filter, a, b, c)?));
+            }
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&filtered)?;
+        let compressed = encoder.finish()?;
+        let idat = ChunkType::from_str("IDAT").expect("IDAT is a valid chunk type");
+        let chunks = compressed.chunks(IDAT_SIZE).map(|data| Chunk::new(idat.clone(), data.to_vec())).collect();
+        png.replace_chunks("IDAT", chunks)?;
+        Ok(())
+    }
+
+    /// The indexes in `rows` of the bytes carrying hidden bits: the low byte of every color
+    /// sample. Alpha is left alone, since changing it shows on fully transparent pixels.
+    fn carriers(&self) -> impl Iterator<Item = usize> {
+        let sample = self.header.bit_depth as usize / 8;
+        let channels = channels(self.header.color_type);
+        let colors = if matches!(self.header.color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) {
+            channels - 1
+        } else {
+            channels
+        };
+        let pixels = self.rows.len() / (sample * channels);
+        (0..pixels).flat_map(move |pixel| (0..colors).map(move |color| (pixel * channels + color) * sample + sample - 1))
+    }
+
+    fn capacity(&self) -> usize {
+        (self.carriers().count() / 8).saturating_sub(FRAME_HEADER_LENGTH)
+    }
+}
+
+/// The number of bytes [`embed`] can hide in `png`.
+pub fn capacity(png: &Png) -> Result<usize> {
+    Ok(Pixels::read(png)?.capacity())
+}
+
+/// Hides `data` in the pixels of `png`, replacing anything hidden there before.
+pub fn embed(png: &mut Png, data: &[u8]) -> Result<()> {
+    let mut pixels = Pixels::read(png)?;
+    let capacity = pixels.capacity();
+    if data.len() > capacity {
+        return Err(Box::new(StegoError::TooLarge { length: data.len(), capacity }));
+    }
+    let mut frame = MAGIC.to_vec();
+    frame.extend((data.len() as u32).to_be_bytes());
+    frame.extend_from_slice(data);
+    let bits = frame.iter().flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+    let carriers: Vec<usize> = pixels.carriers().collect();
+    for (index, bit) in carriers.into_iter().zip(bits) {
+        pixels.rows[index] = (pixels.rows[index] & !1) | bit;
+    }
+    pixels.write(png)
+}
+
+/// Reads the data [`embed`] hid in the pixels of `png`, or `None` if there is none.
+pub fn extract(png: &Png) -> Result<Option<Vec<u8>>> {
+    let pixels = Pixels::read(png)?;
+    let bits: Vec<u8> = pixels.carriers().map(|index| pixels.rows[index] & 1).collect();
+    let mut bytes: Vec<u8> = bits.chunks_exact(8).map(|bits| bits.iter().fold(0, |byte, bit| (byte << 1) | bit)).collect();
+    if bytes.len() < FRAME_HEADER_LENGTH || &bytes[..4] != MAGIC {
+        return Ok(None);
+    }
+    let length = u32::from_be_bytes(bytes[4..8].try_into().expect("4 bytes")) as usize;
+    if length > bytes.len() - FRAME_HEADER_LENGTH {
+        return Err(Box::new(StegoError::CorruptImage));
+    }
+    bytes.truncate(FRAME_HEADER_LENGTH + length);
+    Ok(Some(bytes.split_off(FRAME_HEADER_LENGTH)))
+}
+
+fn channels(color_type: ColorType) -> usize {
+    match color_type {
+        ColorType::Grayscale | ColorType::Indexed => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+    }
+}
+
+fn bytes_per_pixel(header: &ImageHeader) -> usize {
+    channels(header.color_type) * header.bit_depth as usize / 8
+}
+
+fn row_length(header: &ImageHeader) -> usize {
+    header.width as usize * bytes_per_pixel(header)
+}
+
+/// The value scanline filter `filter` predicts from the bytes to the left (`a`), above (`b`)
+/// and above left (`c`).
+fn predict(filter: u8, a: u8, b: u8, c: u8) -> Result<u8> {
+    Ok(match filter {
+        0 => 0,
+        1 => a,
+        2 => b,
+        3 => ((a as u16 + b as u16) / 2) as u8,
+        4 => {
+            let p = a as i16 + b as i16 - c as i16;
+            let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+            if pa <= pb && pa <= pc {
+                a
+            } else if pb <= pc {
+                b
+            } else {
+                c
+            }
+        }
+        _ => return Err(Box::new(StegoError::CorruptImage)),
+    })
+}
+
+#[derive(Debug)]
+pub enum StegoError {
+    /// `--method lsb` was given another chunk type than IDAT.
+    NotIdat(ChunkType),
+    MissingHeader,
+    Unsupported(&'static str),
+    CorruptImage,
+    TooLarge { length: usize, capacity: usize },
+}
+
+impl std::error::Error for StegoError {}
+
+impl Display for StegoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StegoError::NotIdat(chunk_type) => {
+                write!(f, "Payloads hidden in pixels live in the IDAT chunks, not {chunk_type}; pass IDAT as the chunk type")
+            }
+            StegoError::MissingHeader => write!(f, "The image has no valid IHDR chunk to read its pixel format from"),
+            StegoError::Unsupported(what) => write!(f, "Hiding data in the pixels of {what} is not supported"),
+            StegoError::CorruptImage => write!(f, "The image data in the IDAT chunks can't be decoded"),
+            StegoError::TooLarge { length, capacity } => {
+                write!(f, "Payload of {length} bytes doesn't fit in the pixels, which hold {capacity} bytes")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` x `height` image whose rows use every filter type in turn.
+    fn image(width: u32, height: u32, color_type: u8, bit_depth: u8) -> Png {
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend(height.to_be_bytes());
+        header.extend([bit_depth, color_type, 0, 0, 0]);
+        let bpp = [1, 0, 3, 0, 2, 0, 4][color_type as usize] * bit_depth as usize / 8;
+        let mut raw = Vec::new();
+        for y in 0..height as usize {
+            raw.push((y % 5) as u8);
+            raw.extend((0..width as usize * bpp).map(|x| (x * 7 + y * 13) as u8));
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let chunk = |chunk_type, data| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data);
+        Png::from_chunks(vec![chunk("IHDR", header), chunk("IDAT", encoder.finish().unwrap()), chunk("IEND", Vec::new())])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (color_type, bit_depth) in [(2, 8), (6, 8), (0, 16), (4, 8)] {
+            let mut png = image(16, 16, color_type, bit_depth);
+            let before = Pixels::read(&png).unwrap();
+            assert_eq!(extract(&png).unwrap(), None);
+            embed(&mut png, b"hidden in plain sight").unwrap();
+            assert_eq!(extract(&png).unwrap().unwrap(), b"hidden in plain sight");
+
+            // Only the lowest bit of color samples changes.
+            let after = Pixels::read(&png).unwrap();
+            assert_eq!(after.filters, before.filters);
+            for (index, (old, new)) in before.rows.iter().zip(&after.rows).enumerate() {
+                assert!(old ^ new <= 1, "byte {index} changed by more than its lowest bit");
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut png = image(4, 4, 6, 8);
+        // 16 pixels with 3 color samples each hold 6 bytes, 8 of them taken by the frame header.
+        assert_eq!(capacity(&png).unwrap(), 0);
+        let error = embed(&mut png, b"x").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(StegoError::TooLarge { length: 1, capacity: 0 })));
+        assert_eq!(capacity(&image(32, 32, 2, 8)).unwrap(), 32 * 32 * 3 / 8 - 8);
+    }
+
+    #[test]
+    fn test_image_data_size() {
+        // A flat image inflates far past any ratio, up to the size its header gives.
+        let chunk = |chunk_type, data| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data);
+        let (width, height) = (1024u32, 1024u32);
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend(height.to_be_bytes());
+        header.extend([8, 0, 0, 0, 0]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![0; (width as usize + 1) * height as usize]).unwrap();
+        let flat = encoder.finish().unwrap();
+        let mut png = Png::from_chunks(vec![chunk("IHDR", header.clone()), chunk("IDAT", flat.clone()), chunk("IEND", Vec::new())]);
+        embed(&mut png, b"flat").unwrap();
+        assert_eq!(extract(&png).unwrap().unwrap(), b"flat");
+
+        // Data inflating past that size is refused.
+        header[4..8].copy_from_slice(&(height - 1).to_be_bytes());
+        let png = Png::from_chunks(vec![chunk("IHDR", header), chunk("IDAT", flat), chunk("IEND", Vec::new())]);
+        assert!(matches!(capacity(&png).unwrap_err().downcast_ref(), Some(StegoError::CorruptImage)));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let error = capacity(&image(4, 4, 3, 8)).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(StegoError::Unsupported("indexed color"))));
+    }
+}
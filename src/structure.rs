@@ -0,0 +1,270 @@
+use std::fmt::{self, Display};
+use std::io::{self, Read};
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::capabilities::MAX_CHUNK_LENGTH;
+use crate::chunk_type::ChunkType;
+use crate::png::{read_up_to, Png};
+
+/// A way the file breaks the structure the PNG spec requires, as reported by `verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    BadSignature,
+    InvalidType { chunk_type: [u8; 4] },
+    /// The file ends before the chunk's declared length and CRC.
+    Truncated { chunk_type: [u8; 4], length: u32 },
+    CrcMismatch { chunk_type: [u8; 4], stored: u32, computed: u32 },
+    LengthTooLarge { chunk_type: [u8; 4], length: u32 },
+    /// A chunk whose length the spec fixes, such as IHDR or IEND, has another length.
+    WrongLength { chunk_type: [u8; 4], length: u32, expected: u32 },
+    IhdrNotFirst { chunk_type: [u8; 4] },
+    DuplicateIhdr,
+    ChunkAfterEnd { chunk_type: [u8; 4] },
+    MissingEnd,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |chunk_type: &[u8; 4]| String::from_utf8_lossy(chunk_type).into_owned();
+        match self {
+            Issue::BadSignature => write!(f, "not a PNG signature"),
+            Issue::InvalidType { chunk_type } => {
+                write!(f, "invalid chunk type {:?}, the rest of the file can't be read", name(chunk_type))
+            }
+            Issue::Truncated { chunk_type, length } => {
+                write!(f, "chunk {} of length {length} is cut off by the end of the file", name(chunk_type))
+            }
+            Issue::CrcMismatch { chunk_type, stored, computed } => {
+                write!(f, "chunk {}: stored CRC {stored:#010x}, computed {computed:#010x}", name(chunk_type))
+            }
+            Issue::LengthTooLarge { chunk_type, length } => {
+                write!(f, "chunk {}: length {length} exceeds the maximum of {MAX_CHUNK_LENGTH}", name(chunk_type))
+            }
+            Issue::WrongLength { chunk_type, length, expected } => {
+                write!(f, "chunk {}: length {length}, expected {expected}", name(chunk_type))
+            }
+            Issue::IhdrNotFirst { chunk_type } => write!(f, "first chunk is {}, not IHDR", name(chunk_type)),
+            Issue::DuplicateIhdr => write!(f, "IHDR appears more than once"),
+            Issue::ChunkAfterEnd { chunk_type } => write!(f, "chunk {} after IEND", name(chunk_type)),
+            Issue::MissingEnd => write!(f, "file ends without an IEND chunk"),
+        }
+    }
+}
+
+/// Lists every structural issue of the file `bytes` with the offset it was found at, walking past
+/// bad CRCs and lengths instead of stopping at the first problem. Only an invalid chunk type or a
+/// truncated chunk end the walk, since the chunks after them can't be found.
+pub fn check(bytes: &[u8]) -> Vec<(usize, Issue)> {
+    validate(bytes).issues.into_iter().map(|(offset, issue)| (offset as usize, issue)).collect()
+}
+
+/// What `validate` found out about a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Every structural issue with the byte offset it was found at, in file order.
+    pub issues: Vec<(u64, Issue)>,
+    /// Chunks walked, including damaged ones.
+    pub chunks: usize,
+    /// Bytes after the last chunk.
+    pub trailer_length: u64,
+}
+
+impl ValidationReport {
+    /// Whether the file is a structurally valid PNG.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks the structure and CRCs of the PNG file `bytes` without building a `Png`, for services
+/// that only need to accept or reject uploads. See `validate_reader`.
+pub fn validate(bytes: &[u8]) -> ValidationReport {
+    validate_reader(bytes).expect("reading from a slice cannot fail")
+}
+
+/// Checks the structure and CRCs of the PNG file read from `reader` the way `check` does, in
+/// one pass and without holding any chunk data: CRCs are computed through a fixed-size
+/// buffer, so memory use doesn't depend on the input, whatever lengths it declares. Fails
+/// only if `reader` does.
+pub fn validate_reader(mut reader: impl Read) -> io::Result<ValidationReport> {
+    let mut report = ValidationReport { issues: Vec::new(), chunks: 0, trailer_length: 0 };
+    let mut signature = [0; 8];
+    let mut offset = read_up_to(&mut reader, &mut signature)? as u64;
+    if signature != Png::STANDARD_HEADER {
+        report.issues.push((0, Issue::BadSignature));
+    }
+    let (mut seen_header, mut seen_end) = (false, false);
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    // The offset the trailer starts at once the walk ends, and how many of its bytes were read.
+    let (trailer_start, mut trailer_read) = loop {
+        let mut header = [0; 8];
+        let read = read_up_to(&mut reader, &mut header)?;
+        if read < header.len() {
+            break (offset, read as u64);
+        }
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = [header[4], header[5], header[6], header[7]];
+        if !ChunkType::try_from(chunk_type).is_ok_and(|t| t.is_valid()) {
+            // Like a span, a chunk needs at least 12 bytes to be told from the trailer.
+            let read = read_up_to(&mut reader, &mut [0; 4])? as u64;
+            if read < 4 || seen_end {
+                break (offset, 8 + read);
+            }
+            report.issues.push((offset, Issue::InvalidType { chunk_type }));
+            report.chunks += 1;
+            break (offset, 12);
+        }
+        let mut digest = crc.digest();
+        digest.update(&chunk_type);
+        let mut data_read = 0;
+        let mut data = (&mut reader).take(length as u64);
+        loop {
+            let read = data.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            digest.update(&buffer[..read]);
+            data_read += read as u64;
+        }
+        let mut stored = [0; 4];
+        let crc_read = read_up_to(&mut reader, &mut stored)? as u64;
+        let chunk_read = 8 + data_read + crc_read;
+        if data_read < length as u64 || crc_read < 4 {
+            if chunk_read < 12 || seen_end {
+                break (offset, chunk_read);
+            }
+            report.issues.push((offset, Issue::Truncated { chunk_type, length }));
+            report.chunks += 1;
+            break (offset + chunk_read, 0);
+        }
+        let (stored, computed) = (u32::from_be_bytes(stored), digest.finalize());
+        // Like the parser, keep chunks after IEND only while they are intact.
+        if seen_end && stored != computed {
+            break (offset, chunk_read);
+        }
+        let mut found = |issue| report.issues.push((offset, issue));
+        if stored != computed {
+            found(Issue::CrcMismatch { chunk_type, stored, computed });
+        }
+        if length > MAX_CHUNK_LENGTH {
+            found(Issue::LengthTooLarge { chunk_type, length });
+        }
+        let expected = match &chunk_type {
+            b"IHDR" => Some(13),
+            b"IEND" => Some(0),
+            _ => None,
+        };
+        if let Some(expected) = expected.filter(|&expected| expected != length) {
+            found(Issue::WrongLength { chunk_type, length, expected });
+        }
+        if report.chunks == 0 && &chunk_type != b"IHDR" {
+            found(Issue::IhdrNotFirst { chunk_type });
+        }
+        if &chunk_type == b"IHDR" && seen_header {
+            found(Issue::DuplicateIhdr);
+        }
+        seen_header |= &chunk_type == b"IHDR";
+        if seen_end {
+            found(Issue::ChunkAfterEnd { chunk_type });
+        }
+        seen_end |= &chunk_type == b"IEND";
+        report.chunks += 1;
+        offset += chunk_read;
+    };
+    trailer_read += io::copy(&mut reader, &mut io::sink())?;
+    report.trailer_length = trailer_read;
+    let stopped_early = report.issues.last().is_some_and(|(_, issue)| matches!(issue, Issue::InvalidType { .. } | Issue::Truncated { .. }));
+    if !seen_end && !stopped_early {
+        report.issues.push((trailer_start, Issue::MissingEnd));
+    }
+    Ok(report)
+}
+
+/// Bytes of chunk data `validate_reader` reads at a time.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, length: usize) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; length])
+    }
+
+    #[test]
+    fn test_valid_file() {
+        let png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 5), chunk("IEND", 0)]);
+        assert!(check(&png.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_reports_every_issue() {
+        let png = Png::from_chunks(vec![chunk("tEXt", 4), chunk("IHDR", 12), chunk("IHDR", 13), chunk("IEND", 0), chunk("ruSt", 2)]);
+        let mut bytes = png.as_bytes();
+        bytes[0] = 0;
+        // Damage the CRC of the tEXt chunk; the walk goes on past it.
+        bytes[8 + 8 + 4] ^= 0xff;
+        let issues: Vec<Issue> = check(&bytes).into_iter().map(|(_, issue)| issue).collect();
+        assert_eq!(issues.len(), 6);
+        assert_eq!(issues[0], Issue::BadSignature);
+        assert!(matches!(issues[1], Issue::CrcMismatch { chunk_type, .. } if &chunk_type == b"tEXt"));
+        assert_eq!(issues[2], Issue::IhdrNotFirst { chunk_type: *b"tEXt" });
+        assert_eq!(issues[3], Issue::WrongLength { chunk_type: *b"IHDR", length: 12, expected: 13 });
+        assert_eq!(issues[4], Issue::DuplicateIhdr);
+        assert_eq!(issues[5], Issue::ChunkAfterEnd { chunk_type: *b"ruSt" });
+    }
+
+    #[test]
+    fn test_truncated_and_unterminated() {
+        let bytes = Png::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 5)]).as_bytes();
+        assert_eq!(check(&bytes), vec![(bytes.len(), Issue::MissingEnd)]);
+
+        let issues = check(&bytes[..bytes.len() - 2]);
+        assert_eq!(issues, vec![(8 + 25, Issue::Truncated { chunk_type: *b"IDAT", length: 5 })]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 100_000), chunk("IEND", 0)]);
+        png.set_trailer(b"extra".to_vec()).unwrap();
+        let bytes = png.as_bytes();
+        let report = validate(&bytes);
+        assert!(report.is_valid());
+        assert_eq!((report.chunks, report.trailer_length), (3, 5));
+
+        // A declared length far past the end is reported, not allocated.
+        let mut huge = bytes[..8 + 25].to_vec();
+        huge.extend_from_slice(&[0x7f, 0xff, 0xff, 0xff, b'I', b'D', b'A', b'T', 1, 2, 3, 4]);
+        let issues = validate(&huge).issues;
+        assert_eq!(issues, vec![(33, Issue::Truncated { chunk_type: *b"IDAT", length: 0x7fff_ffff })]);
+    }
+
+    #[test]
+    fn test_validate_never_panics() {
+        let bytes = Png::from_chunks(vec![chunk("IHDR", 13), chunk("tEXt", 7), chunk("IEND", 0)]).as_bytes();
+        let mut state: u32 = 0x2545_f491;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize
+        };
+        for _ in 0..2000 {
+            let mut mutated = bytes[..random() % (bytes.len() + 1)].to_vec();
+            for _ in 0..random() % 4 {
+                if !mutated.is_empty() {
+                    let at = random() % mutated.len();
+                    mutated[at] = random() as u8;
+                }
+            }
+            let report = validate(&mutated);
+            assert_eq!(report.is_valid(), Png::try_from(mutated.as_slice()).is_ok() && report.issues.is_empty());
+            assert_eq!(validate_reader(io::Cursor::new(&mutated)).unwrap(), report);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::keyword::MAX_KEYWORD_LENGTH;
+use crate::png::Png;
+use crate::rng::SplitMix64;
+
+/// A generated file and the edge case it exercises.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// Generates the test-vector suite. The same seed always produces the same files.
+pub fn generate(seed: u64) -> Vec<TestVector> {
+    let mut rng = SplitMix64(seed);
+    let image = Image::random(&mut rng);
+
+    let mut vectors = Vec::new();
+    let mut add = |name, description, bytes| vectors.push(TestVector { name, description, bytes });
+
+    add("minimal.png", "Valid grayscale image with only IHDR, IDAT and IEND", image.png(Vec::new()).as_bytes());
+
+    let empty = vec![
+        chunk(&private_type(&mut rng), Vec::new()),
+        chunk("IDAT", Vec::new()),
+        chunk("tEXt", Vec::new()),
+    ];
+    add("zero_length_chunks.png", "Zero-length private, IDAT and tEXt chunks", image.png(empty).as_bytes());
+
+    let keyword: String = (0..MAX_KEYWORD_LENGTH).map(|_| rng.letter()).collect();
+    let mut text = keyword.into_bytes();
+    text.push(0);
+    text.extend((0..rng.below(64)).map(|_| rng.letter() as u8));
+    add("max_keyword.png", "tEXt chunk with a 79-byte keyword", image.png(vec![chunk("tEXt", text)]).as_bytes());
+
+    let time = |rng: &mut SplitMix64| chunk("tIME", (0..7).map(|_| rng.below(28) as u8 + 1).collect::<Vec<u8>>());
+    let times = vec![time(&mut rng), time(&mut rng)];
+    add("duplicate_ancillary.png", "Two tIME chunks where at most one is allowed", image.png(times).as_bytes());
+
+    let mut headers = image.png(Vec::new()).chunks().to_vec();
+    headers.insert(1, Image::random(&mut rng).header());
+    add("duplicate_header.png", "Two IHDR chunks with different dimensions", Png::from_chunks(headers).as_bytes());
+
+    let mut ends = image.png(Vec::new()).chunks().to_vec();
+    ends.push(chunk("IEND", Vec::new()));
+    add("duplicate_end.png", "Two IEND chunks", Png::from_chunks(ends).as_bytes());
+
+    let mut huge = image.png(Vec::new()).as_bytes();
+    let end = huge.len() - 12;
+    huge.truncate(end);
+    huge.extend_from_slice(&0x7fff_ffffu32.to_be_bytes());
+    huge.extend_from_slice(private_type(&mut rng).as_bytes());
+    huge.extend((0..16).map(|_| rng.next() as u8));
+    add("huge_declared_length.png", "Chunk declaring 2^31-1 data bytes in a short file", huge);
+
+    let plain = image.png(Vec::new());
+    let mut bad_crc = plain.as_bytes();
+    let idat_end = plain.chunk_offsets()[1] + plain.chunks()[1].length() as usize + 12;
+    bad_crc[idat_end - 1] ^= 0xff;
+    add("bad_crc.png", "IDAT chunk with a corrupted CRC", bad_crc);
+
+    let mut trailer = image.png(Vec::new());
+    trailer.set_trailer((0..rng.below(256) + 1).map(|_| rng.next() as u8).collect::<Vec<u8>>()).expect("a new Png is writable");
+    add("trailer.png", "Random bytes after IEND", trailer.as_bytes());
+
+    vectors
+}
+
+/// A small 8-bit grayscale image with random dimensions and pixels.
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn random(rng: &mut SplitMix64) -> Self {
+        let (width, height) = (rng.below(16) as u32 + 1, rng.below(16) as u32 + 1);
+        let pixels = (0..width * height).map(|_| rng.next() as u8).collect();
+        Self { width, height, pixels }
+    }
+
+    fn header(&self) -> Chunk {
+        let mut data = Vec::with_capacity(13);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        // Bit depth 8, grayscale, deflate, adaptive filtering, no interlacing.
+        data.extend_from_slice(&[8, 0, 0, 0, 0]);
+        chunk("IHDR", data)
+    }
+
+    /// The image with `extra` chunks placed between IHDR and IDAT.
+    fn png(&self, extra: Vec<Chunk>) -> Png {
+        let mut scanlines = Vec::with_capacity(self.pixels.len() + self.height as usize);
+        for row in self.pixels.chunks(self.width as usize) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        let mut chunks = vec![self.header()];
+        chunks.extend(extra);
+        chunks.push(chunk("IDAT", zlib_stored(&scanlines)));
+        chunks.push(chunk("IEND", Vec::new()));
+        Png::from_chunks(chunks)
+    }
+}
+
+/// Wraps `data` in a zlib stream made of a single uncompressed deflate block.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let length = data.len() as u16;
+    let mut stream = vec![0x78, 0x01, 0x01];
+    stream.extend_from_slice(&length.to_le_bytes());
+    stream.extend_from_slice(&(!length).to_le_bytes());
+    stream.extend_from_slice(data);
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    stream.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    stream
+}
+
+fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+}
+
+/// A random ancillary, private, safe-to-copy chunk type.
+fn private_type(rng: &mut SplitMix64) -> String {
+    let first = rng.letter().to_ascii_lowercase();
+    let fourth = rng.letter().to_ascii_lowercase();
+    format!("{first}{}{}{fourth}", rng.letter().to_ascii_lowercase(), rng.letter().to_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::{DuplicatePolicy, ParseOptions};
+    use bytes::Bytes;
+
+    fn vector(vectors: &[TestVector], name: &str) -> Bytes {
+        Bytes::from(vectors.iter().find(|v| v.name == name).unwrap().bytes.clone())
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        assert_eq!(generate(7), generate(7));
+        assert_ne!(generate(7), generate(8));
+    }
+
+    #[test]
+    fn test_vectors_exercise_their_edge_case() {
+        let vectors = generate(42);
+        let parse = |name| Png::parse(vector(&vectors, name), &ParseOptions::default());
+
+        assert_eq!(parse("minimal.png").unwrap().chunks().len(), 3);
+        let empty = parse("zero_length_chunks.png").unwrap();
+        assert_eq!(empty.chunks().iter().filter(|c| c.length() == 0).count(), 4);
+        let text = parse("max_keyword.png").unwrap();
+        let keyword = text.chunk_by_type("tEXt").unwrap().data().split(|&b| b == 0).next().unwrap().len();
+        assert_eq!(keyword, MAX_KEYWORD_LENGTH);
+        let strict = ParseOptions { duplicates: DuplicatePolicy::Error, ..Default::default() };
+        assert!(Png::parse(vector(&vectors, "duplicate_header.png"), &strict).is_err());
+        assert!(Png::parse(vector(&vectors, "duplicate_end.png"), &strict).is_err());
+        assert!(parse("huge_declared_length.png").is_err());
+        assert!(parse("bad_crc.png").is_err());
+        assert!(!parse("trailer.png").unwrap().trailer().is_empty());
+    }
+}
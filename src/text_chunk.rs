@@ -0,0 +1,262 @@
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::inflate::{InflateError, DEFAULT_MAX_RATIO};
+use crate::keyword::Keyword;
+use crate::Result;
+
+/// The only compression method the PNG spec defines for zTXt and iTXt: zlib deflate.
+const DEFLATE: u8 = 0;
+
+/// How the text of a [`TextChunk`] is stored, which decides its chunk type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextKind {
+    /// tEXt: uncompressed Latin-1 text.
+    Plain,
+    /// zTXt: compressed Latin-1 text.
+    Compressed,
+    /// iTXt: UTF-8 text, optionally compressed, with the language it is written in (e.g. `en-GB`,
+    /// may be empty) and the keyword translated into that language.
+    International { compressed: bool, language: String, translated_keyword: String },
+}
+
+impl TextKind {
+    pub fn chunk_type(&self) -> ChunkType {
+        let name = match self {
+            TextKind::Plain => "tEXt",
+            TextKind::Compressed => "zTXt",
+            TextKind::International { .. } => "iTXt",
+        };
+        ChunkType::from_str(name).expect("text chunk types are valid")
+    }
+}
+
+/// A keyword/text pair of a tEXt, zTXt or iTXt chunk, the chunks the PNG spec sets aside for
+/// text such as a title or comment, and which other PNG tools show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: Keyword,
+    pub text: String,
+    pub kind: TextKind,
+}
+
+impl TextChunk {
+    /// A tEXt chunk holding `text` under `keyword`.
+    pub fn new(keyword: Keyword, text: impl Into<String>) -> Self {
+        Self { keyword, text: text.into(), kind: TextKind::Plain }
+    }
+
+    pub fn kind(mut self, kind: TextKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Encodes this text as the data of its chunk type. Fails for tEXt and zTXt if the text
+    /// isn't Latin-1, which only iTXt can go beyond.
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = self.keyword.as_bytes().to_vec();
+        data.push(0);
+        match &self.kind {
+            TextKind::Plain => data.extend(latin1(&self.text)?),
+            TextKind::Compressed => {
+                data.push(DEFLATE);
+                data.extend(deflate(&latin1(&self.text)?)?);
+            }
+            TextKind::International { compressed, language, translated_keyword } => {
+                data.extend([u8::from(*compressed), DEFLATE]);
+                data.extend(language.as_bytes());
+                data.push(0);
+                data.extend(translated_keyword.as_bytes());
+                data.push(0);
+                match compressed {
+                    true => data.extend(deflate(self.text.as_bytes())?),
+                    false => data.extend(self.text.as_bytes()),
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Ok(Chunk::new(self.kind.chunk_type(), self.to_data()?))
+    }
+
+    /// Parses the data of a chunk of type `chunk_type`, which must be tEXt, zTXt or iTXt.
+    pub fn parse(chunk_type: &ChunkType, data: &[u8]) -> Result<Self> {
+        Self::parse_limited(chunk_type, data, DEFAULT_MAX_RATIO)
+    }
+
+    /// Same as `parse`, refusing compressed text that inflates to more than `max_ratio` times
+    /// its size, see [`crate::inflate::inflate`].
+    pub fn parse_limited(chunk_type: &ChunkType, data: &[u8], max_ratio: u64) -> Result<Self> {
+        let (keyword, rest) = split_at_null(data)?;
+        let keyword = Keyword::from_latin1(keyword)?;
+        let (text, kind) = match &chunk_type.bytes() {
+            b"tEXt" => (from_latin1(rest), TextKind::Plain),
+            b"zTXt" => {
+                let (&method, compressed) = rest.split_first().ok_or(TextChunkError::Truncated)?;
+                check_method(method)?;
+                (from_latin1(&inflate(compressed, max_ratio)?), TextKind::Compressed)
+            }
+            b"iTXt" => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err(Box::new(TextChunkError::Truncated));
+                };
+                let compressed = *flag != 0;
+                if compressed {
+                    check_method(*method)?;
+                }
+                let (language, rest) = split_at_null(rest)?;
+                let (translated_keyword, text) = split_at_null(rest)?;
+                let text = if compressed { inflate(text, max_ratio)? } else { text.to_vec() };
+                let kind = TextKind::International {
+                    compressed,
+                    language: utf8(language)?,
+                    translated_keyword: utf8(translated_keyword)?,
+                };
+                (utf8(&text)?, kind)
+            }
+            _ => return Err(Box::new(TextChunkError::NotTextChunk(chunk_type.clone()))),
+        };
+        Ok(Self { keyword, text, kind })
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        Self::parse(chunk.chunk_type(), chunk.data())
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TextKind::International { language, .. } if !language.is_empty() => {
+                write!(f, "{} [{language}]: {}", self.keyword, self.text)
+            }
+            _ => write!(f, "{}: {}", self.keyword, self.text),
+        }
+    }
+}
+
+fn split_at_null(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let position = bytes.iter().position(|&b| b == 0).ok_or(TextChunkError::MissingSeparator)?;
+    Ok((&bytes[..position], &bytes[position + 1..]))
+}
+
+fn check_method(method: u8) -> Result<()> {
+    match method {
+        DEFLATE => Ok(()),
+        _ => Err(Box::new(TextChunkError::UnknownCompression(method))),
+    }
+}
+
+fn latin1(text: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .enumerate()
+        .map(|(position, c)| u8::try_from(c as u32).map_err(|_| TextChunkError::NotLatin1 { position, character: c }.into()))
+        .collect()
+}
+
+fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn utf8(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| TextChunkError::InvalidUtf8.into())
+}
+
+fn deflate(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+fn inflate(bytes: &[u8], max_ratio: u64) -> Result<Vec<u8>> {
+    match crate::inflate::inflate(bytes, max_ratio) {
+        Err(InflateError::Corrupt) => Err(TextChunkError::CorruptCompression.into()),
+        inflated => Ok(inflated?),
+    }
+}
+
+#[derive(Debug)]
+pub enum TextChunkError {
+    NotTextChunk(ChunkType),
+    MissingSeparator,
+    Truncated,
+    UnknownCompression(u8),
+    CorruptCompression,
+    NotLatin1 { position: usize, character: char },
+    InvalidUtf8,
+}
+
+impl std::error::Error for TextChunkError {}
+
+impl Display for TextChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextChunkError::NotTextChunk(chunk_type) => write!(f, "{chunk_type} is not a tEXt, zTXt or iTXt chunk"),
+            TextChunkError::MissingSeparator => write!(f, "Text chunk is missing a null separator"),
+            TextChunkError::Truncated => write!(f, "Text chunk ends before its header does"),
+            TextChunkError::UnknownCompression(method) => write!(f, "Unknown text compression method {method}"),
+            TextChunkError::CorruptCompression => write!(f, "Compressed text can't be decompressed"),
+            TextChunkError::NotLatin1 { position, character } => {
+                write!(f, "Text has character {character:?} at position {position}, which is not Latin-1; use iTXt")
+            }
+            TextChunkError::InvalidUtf8 => write!(f, "iTXt chunk text is not valid UTF-8"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(keyword: &str) -> Keyword {
+        Keyword::from_str(keyword).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let international = TextKind::International {
+            compressed: true,
+            language: "de".to_string(),
+            translated_keyword: "Kommentar".to_string(),
+        };
+        for (kind, text) in [
+            (TextKind::Plain, "hello"),
+            (TextKind::Compressed, "hello hello hello hello"),
+            (international, "Grüße, ünïcödé ✓"),
+        ] {
+            let text = TextChunk::new(keyword("Comment"), text).kind(kind);
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(TextChunk::from_chunk(&chunk).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_spec_layout() {
+        let chunk = TextChunk::new(keyword("Title"), "café").to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Title\0caf\xe9");
+
+        let kind = TextKind::International { compressed: false, language: "en".into(), translated_keyword: String::new() };
+        let data = TextChunk::new(keyword("Title"), "x").kind(kind).to_data().unwrap();
+        assert_eq!(data, b"Title\0\0\0en\0\0x");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = TextChunk::new(keyword("Title"), "✓").to_data().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(TextChunkError::NotLatin1 { position: 0, .. })));
+        let chunk_type = |name| ChunkType::from_str(name).unwrap();
+        assert!(TextChunk::parse(&chunk_type("ruSt"), b"Title\0x").is_err());
+        let error = TextChunk::parse(&chunk_type("zTXt"), b"Title\0\0not zlib").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(TextChunkError::CorruptCompression)));
+        assert!(TextChunk::parse(&chunk_type("tEXt"), b"no separator").is_err());
+    }
+}
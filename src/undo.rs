@@ -0,0 +1,67 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::patch::Patch;
+use crate::png::{replace_file, WriteOptions};
+use crate::Result;
+
+/// The undo record of `file` is stored next to it as `<file>.undo`.
+pub fn undo_path(file: &Path) -> PathBuf {
+    let mut name = OsString::from(file.as_os_str());
+    name.push(".undo");
+    PathBuf::from(name)
+}
+
+/// Records how to turn `new` back into `old`, the previous contents of `file`.
+pub fn record(file: &Path, old: &[u8], new: &[u8]) -> Result<()> {
+    let patch = Patch::between_bytes(new, old);
+    replace_file(&undo_path(file), &WriteOptions::default(), |writer| writer.write_all(patch.to_string().as_bytes()))?;
+    Ok(())
+}
+
+/// Reverts the last recorded modification of `file` and removes its undo record.
+/// Fails without touching the file if it changed since the record was written.
+pub fn revert(file: &Path) -> Result<()> {
+    let record_path = undo_path(file);
+    let patch = Patch::from_str(&fs::read_to_string(&record_path)?)?;
+    let restored = patch.apply(&fs::read(file)?)?;
+    replace_file(file, &WriteOptions::default(), |writer| writer.write_all(&restored))?;
+    fs::remove_file(record_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_revert() {
+        let file = std::env::temp_dir().join(format!("pngme-undo-{}.png", std::process::id()));
+        fs::write(&file, b"modified contents").unwrap();
+        record(&file, b"original contents", b"modified contents").unwrap();
+
+        revert(&file).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"original contents");
+        assert!(!undo_path(&file).exists());
+        assert!(revert(&file).is_err());
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_revert_refuses_changed_file() {
+        let file = std::env::temp_dir().join(format!("pngme-undo-changed-{}.png", std::process::id()));
+        fs::write(&file, b"modified contents").unwrap();
+        record(&file, b"original contents", b"modified contents").unwrap();
+
+        fs::write(&file, b"edited by someone else").unwrap();
+        assert!(revert(&file).is_err());
+        assert_eq!(fs::read(&file).unwrap(), b"edited by someone else");
+
+        fs::remove_file(&file).unwrap();
+        fs::remove_file(undo_path(&file)).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+//! Local usage statistics, kept only when `--stats-file` asks for them and never sent anywhere.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "json")]
+use std::collections::BTreeMap;
+#[cfg(feature = "json")]
+use std::fmt::{self, Display};
+#[cfg(feature = "json")]
+use std::io::{ErrorKind, Write};
+#[cfg(feature = "json")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "json")]
+use crate::i18n::format_number;
+#[cfg(feature = "json")]
+use crate::png::{replace_file, WriteOptions};
+#[cfg(feature = "json")]
+use crate::Result;
+
+/// Counts the bytes a run reads from its input files, across the threads of a batch.
+#[derive(Debug, Default)]
+pub struct BytesRead(AtomicU64);
+
+impl BytesRead {
+    /// Counts `bytes` more read.
+    pub fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Where `stats --self` looks when no stats file is given: `pngme/usage.json` in the user's
+/// cache directory.
+#[cfg(feature = "json")]
+pub fn default_path() -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache.join("pngme").join("usage.json"))
+}
+
+/// What the runs recorded in a stats file added up to.
+#[cfg(feature = "json")]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// How many times each command ran successfully.
+    pub operations: BTreeMap<String, u64>,
+    /// Bytes read from input files by those runs.
+    pub bytes_processed: u64,
+}
+
+#[cfg(feature = "json")]
+impl UsageStats {
+    /// Loads the stats stored at `path`, or empty stats if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Writes these stats to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = serde_json::to_vec_pretty(self)?;
+        replace_file(path, &WriteOptions::default(), |writer| writer.write_all(&bytes))?;
+        Ok(())
+    }
+
+    /// Counts one more run of `operation`, which read `bytes` from its input files.
+    pub fn add(&mut self, operation: &str, bytes: u64) {
+        *self.operations.entry(operation.to_string()).or_default() += 1;
+        self.bytes_processed = self.bytes_processed.saturating_add(bytes);
+    }
+}
+
+#[cfg(feature = "json")]
+impl Display for UsageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let runs: u64 = self.operations.values().sum();
+        writeln!(f, "Runs           : {}", format_number(runs as i64))?;
+        writeln!(f, "Bytes processed: {}", format_number(self.bytes_processed as i64))?;
+        let mut operations: Vec<_> = self.operations.iter().collect();
+        operations.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (operation, count) in operations {
+            writeln!(f, "  {operation:<16} {:>10}", format_number(*count as i64))?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds this run of `operation`, which read `bytes` from its input files, to the stats file at
+/// `path`.
+#[cfg(feature = "json")]
+pub fn record(path: &Path, operation: &str, bytes: u64) -> Result<()> {
+    let mut stats = UsageStats::load(path)?;
+    stats.add(operation, bytes);
+    stats.save(path)
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let dir = std::env::temp_dir().join(format!("pngme-usage-{}", std::process::id()));
+        let path = dir.join("nested").join("usage.json");
+        let mut stats = UsageStats::load(&path).unwrap();
+        assert_eq!(stats, UsageStats::default());
+        stats.add("print", 100);
+        stats.add("encode", 20);
+        stats.add("print", 5);
+        stats.save(&path).unwrap();
+        let loaded = UsageStats::load(&path).unwrap();
+        assert_eq!(loaded.operations["print"], 2);
+        assert_eq!(loaded.bytes_processed, 125);
+        assert!(loaded.to_string().contains("Runs           : 3"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}